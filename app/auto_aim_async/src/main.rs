@@ -1,17 +1,28 @@
 extern crate ndarray as nd;
 extern crate rerun as rr;
 
-use crate::rbt_threads::{infer, post_process, pre_process};
+use crate::rbt_threads::{
+    SolveStage, control_process, estimate_process, infer, lightbar_process, post_process,
+    pre_process,
+};
 use auto_aim_rust::rbt_infra::rbt_log;
 use lib as auto_aim_rust;
+use lib::rbt_base::rbt_geometry::rbt_tf::RbtTfTree;
 use lib::rbt_infra::rbt_err::RbtResult;
 use lib::rbt_infra::rbt_global::GENERIC_RBT_CFG;
 use lib::rbt_infra::rbt_queue_async::RbtSPSCQueueAsync;
+use lib::rbt_mod::rbt_detector::DetectorKind;
+use lib::rbt_mod::rbt_detector::rbt_backend::build_session;
 use lib::rbt_mod::rbt_detector::rbt_frame::RbtFrame;
-use ort::execution_providers;
-use ort::session::Session;
-use std::sync::Arc;
-use tracing::info;
+use lib::rbt_mod::rbt_detector::rbt_lightbar::LightBarDetector;
+use lib::rbt_mod::rbt_detector::rbt_yolo::YoloDecoder;
+use lib::rbt_mod::rbt_solver::RbtSolvedResults;
+use lib::rbt_mod::rbt_source::source_from_cfg;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tokio::time::Instant;
+use tracing::{error, info};
 
 pub mod rbt_threads;
 
@@ -24,31 +35,58 @@ async fn main() -> RbtResult<()> {
 
     let pre_infer_queue = Arc::new(RbtSPSCQueueAsync::<RbtFrame>::new(1));
     let infer_post_queue = Arc::new(RbtSPSCQueueAsync::<RbtFrame>::new(1));
+    // 解算结果队列，由检测阶段中的解算阶段写入
+    let solved_queue = Arc::new(RbtSPSCQueueAsync::<(RbtSolvedResults, Instant)>::new(1));
+    // 估计器发布的最新目标，控制线程每个周期读取
+    let (target_tx, target_rx) = watch::channel(None);
+    // 估计器发布的装甲板半径和跟踪装甲板 yaw，解算阶段作为先验
+    let (prior_tx, prior_rx) = watch::channel(HashMap::new());
+    // 坐标变换树，云台关节状态由控制线程写入，解算阶段按图像时刻查询
+    let tf_tree = Arc::new(Mutex::new(RbtTfTree::new(
+        &GENERIC_RBT_CFG.read().unwrap().tf_cfg,
+    )));
 
-    // build orrtruntime session
-    let session = Session::builder()?
-        .with_execution_providers([execution_providers::OpenVINOExecutionProvider::default()
-            .with_device_type("GPU")
-            .build()
-            .error_on_failure()])?
-        .with_optimization_level(ort::session::builder::GraphOptimizationLevel::Level3)?
-        .with_inter_threads(8)?
-        .commit_from_file(
-            GENERIC_RBT_CFG
-                .read()
-                .unwrap()
-                .detector_cfg
-                .armor_detect_model_path
-                .as_str(),
-        )?;
+    let detector_cfg = GENERIC_RBT_CFG.read().unwrap().detector_cfg.clone();
+    // build orrtruntime session，推理后端由 detector_cfg/ort_ep 选择
+    // 标签表来自模型元数据或 detector_cfg/armor_label_path，并与模型输出形状核对
+    // 选择灯条检测或模型加载失败时，使用传统视觉灯条检测
+    let yolo = match detector_cfg.detector_kind {
+        DetectorKind::Yolo => build_session(&detector_cfg)
+            .and_then(|session| {
+                let decoder = YoloDecoder::from_session(&detector_cfg, &session)?;
+                Ok((session, decoder))
+            })
+            .inspect_err(|err| error!("{}, fall back to light bar detector", err))
+            .ok(),
+        DetectorKind::LightBar => None,
+    };
 
     // let session = Arc::new(Mutex::new(session));
-    let pre_task_handler = pre_process(pre_infer_queue.clone());
-    let infer_task_handler = infer(pre_infer_queue, session, infer_post_queue.clone());
-    let post_task_handler = post_process(infer_post_queue);
+    // 图像源由 source_cfg 配置
+    let source = source_from_cfg(&GENERIC_RBT_CFG.read().unwrap().source_cfg)?;
+    let solve_stage = SolveStage::new(tf_tree.clone(), solved_queue.clone(), prior_rx);
+    let detect_task_handlers = match yolo {
+        Some((session, decoder)) => vec![
+            pre_process(pre_infer_queue.clone(), source),
+            infer(pre_infer_queue, session, infer_post_queue.clone()),
+            post_process(infer_post_queue, decoder, solve_stage),
+        ],
+        None => vec![lightbar_process(
+            source,
+            LightBarDetector::new(GENERIC_RBT_CFG.read().unwrap().lightbar_cfg.clone()),
+            solve_stage,
+        )],
+    };
+    let estimate_task_handler = estimate_process(solved_queue.clone(), target_tx, prior_tx);
+    let control_task_handler = control_process(target_rx, tf_tree);
 
     let tim = std::time::Instant::now();
-    let (_, _, _) = tokio::join!(pre_task_handler, infer_task_handler, post_task_handler);
+    for handler in detect_task_handlers {
+        let _ = handler.await;
+    }
+    // 估计线程可能阻塞在空队列上，推入一帧空结果唤醒它退出
+    solved_queue.force_push((RbtSolvedResults::default(), Instant::now()));
+    let (_, _) = tokio::join!(estimate_task_handler, control_task_handler);
     tokio::time::sleep(tokio::time::Duration::from_millis(100)).await; // wait for post process to finish
     info!("multi_thread_pipeline finished in {:?}", tim.elapsed());
 
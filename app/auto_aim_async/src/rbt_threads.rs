@@ -1,76 +1,187 @@
+use image::GenericImageView;
 use ort::inputs;
 use ort::value::TensorRef;
-use std::sync::Arc;
-use std::time::Duration;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
 use tokio::task::JoinHandle;
+use tokio::time::Instant;
 use tracing::{error, info, warn};
 
 // use crate::rbt_cfg::{self, DetectorConfig, RbtCfg};
 // use lib::rbt_mod::rbt_armor::ArmorKeyPoints;
-use lib::rbt_mod::rbt_solver::RbtSolvedResults;
+use lib::rbt_mod::rbt_solver::{EnemyPrior, RbtSolvedResults, enemys_solver};
 use lib::{
-    rbt_base::rbt_geometry::rbt_point2::RbtImgPoint2,
+    rbt_base::rbt_geometry::rbt_tf::RbtTfTree,
     rbt_infra::{
+        rbt_err::RbtResult,
         rbt_global::{FAILED_COUNT, GENERIC_RBT_CFG, IS_RUNNING},
         rbt_queue_async::RbtSPSCQueueAsync,
     },
     rbt_mod::{
-        rbt_armor::detected_armor::DetectedArmor,
+        rbt_armor::{ArmorId, detected_armor::DetectedArmor},
+        rbt_comm::rbt_comm_device::{RbtComm, RbtCommDevice},
+        rbt_controller::RbtController,
         rbt_detector::{
-            BBox,
             rbt_frame::{RbtFrame, RbtFrameStage},
-            rbt_yolo::{YOLO_LABEL_TABLE, letterbox, nms},
+            rbt_lightbar::LightBarDetector,
+            rbt_refine::{CornerRefiner, brightness_image},
+            rbt_yolo::{Nms, YoloDecoder, letterbox},
         },
-        rbt_estimator::RbtHandlerPoll,
+        rbt_estimator::{AimTarget, RbtHandlerPoll, rbt_enemy_dynamic_model::EnemyId},
+        rbt_source::FrameSource,
     },
 };
 
-/// 图像预处理阶段：读取图像并通过通道发送到下一阶段。
+/// 解算阶段：在检测阶段的阻塞任务中调用，以图像采集时刻的坐标变换把一帧的检测结果
+/// 解算为各敌方单位的位置，连同该时刻推入解算结果队列，供估计阶段使用
+///
+/// 装甲板半径和跟踪装甲板 yaw 的先验来自估计阶段发布的最新状态，解算前外推到图像采集时刻
+#[derive(Clone)]
+pub struct SolveStage {
+    tf_tree: Arc<Mutex<RbtTfTree>>,
+    solved_queue: Arc<RbtSPSCQueueAsync<(RbtSolvedResults, Instant)>>,
+    prior_rx: watch::Receiver<HashMap<EnemyId, EnemyPrior>>,
+}
+
+impl SolveStage {
+    pub fn new(
+        tf_tree: Arc<Mutex<RbtTfTree>>,
+        solved_queue: Arc<RbtSPSCQueueAsync<(RbtSolvedResults, Instant)>>,
+        prior_rx: watch::Receiver<HashMap<EnemyId, EnemyPrior>>,
+    ) -> Self {
+        Self {
+            tf_tree,
+            solved_queue,
+            prior_rx,
+        }
+    }
+
+    /// 解算 `time` 时刻图像中的装甲板，返回解算出的敌方单位数
+    ///
+    /// 没有检测到装甲板时同样推入一帧空结果，估计器据此进入丢失状态
+    pub fn solve(
+        &self,
+        armors: HashMap<ArmorId, Vec<DetectedArmor>>,
+        time: Instant,
+    ) -> RbtResult<usize> {
+        let (cam_cfg, solver_cfg, refine_cfg) = {
+            let cfg = GENERIC_RBT_CFG.read().unwrap();
+            (
+                cfg.cam_cfg.clone(),
+                cfg.solver_cfg.clone(),
+                cfg.pose_refine_cfg.clone(),
+            )
+        };
+        // 按图像采集时刻插值云台姿态；图像晚于最新的下位机数据时使用最新姿态，没有下位机时按零处理
+        let tf = {
+            let tf_tree = self.tf_tree.lock().unwrap();
+            tf_tree
+                .at(time)
+                .or_else(|| tf_tree.latest())
+                .unwrap_or_else(|| tf_tree.static_tf().clone())
+        };
+        // 先复制先验，避免解算期间持有 watch 的读锁阻塞估计阶段
+        let priors = self
+            .prior_rx
+            .borrow()
+            .iter()
+            .map(|(enemy_id, prior)| (*enemy_id, prior.predict(time)))
+            .collect();
+        let enemys = enemys_solver(
+            armors,
+            &cam_cfg,
+            &tf,
+            &solver_cfg,
+            &refine_cfg,
+            &priors,
+            &rr::RecordingStream::disabled(),
+        )?;
+        let solved = enemys.values().filter(|enemy| enemy.is_some()).count();
+        self.solved_queue.force_push((enemys, time));
+        Ok(solved)
+    }
+}
+
+/// 图像预处理阶段：从图像源读取图像并通过通道发送到下一阶段。
 /// 此函数负责读取图像、调整图像大小、转换为归一化格式，并为推理阶段准备数据。
-pub fn pre_process(queue: Arc<RbtSPSCQueueAsync<RbtFrame>>) -> JoinHandle<()> {
+/// 图像源耗尽时停止整条流水线。
+pub fn pre_process(
+    queue: Arc<RbtSPSCQueueAsync<RbtFrame>>,
+    mut source: Box<dyn FrameSource>,
+) -> JoinHandle<()> {
     tokio::spawn(async move {
-        for frame_id in 1..=1000 {
-            let mut rbt_frame = RbtFrame::new();
-            // 在阻塞线程中执行图像处理操作，以避免阻塞异步运行时
+        let (camera_size, (width, height)) = {
+            let cfg = GENERIC_RBT_CFG.read().unwrap();
+            (
+                cfg.detector_cfg.camera_img_size(),
+                cfg.detector_cfg.infer_img_size(),
+            )
+        };
+        loop {
+            // 角点优化需要原图亮度，只在开启时计算
+            let refine = GENERIC_RBT_CFG.read().unwrap().corner_refine_cfg.enable;
+            // 在阻塞线程中执行图像读取和处理操作，以避免阻塞异步运行时
             let result = tokio::task::spawn_blocking(move || {
-                // 从磁盘加载图像
-                let resized_img =
-                    image::open("../../../imgs/test_resize.jpg").expect("无法打开图像");
-
-                // 创建一个 4D 数组以存储处理后的图像数据。
-                let mut input_array = nd::Array4::zeros((1, 3, 384, 640));
-                letterbox(&mut input_array, &resized_img);
+                let frame = source.next_frame().map(|frame| {
+                    frame.map(|frame| {
+                        let mut rbt_frame = RbtFrame::new(width, height);
+                        rbt_frame.set_id(frame.id());
+                        rbt_frame.set_time(frame.time_stamp());
+                        let img = frame.into_image();
+                        if img.dimensions() != camera_size {
+                            warn!(
+                                "预处理阶段：图像尺寸 {:?} 与配置的相机分辨率 {:?} 不符",
+                                img.dimensions(),
+                                camera_size
+                            );
+                        }
 
-                rbt_frame.pre_data().assign(&input_array);
-                rbt_frame.set_id(frame_id);
-                rbt_frame.set_state(RbtFrameStage::Pre);
-                rbt_frame
+                        // 直接 letterbox 到帧的模型输入张量中
+                        let lb = letterbox(&mut rbt_frame.pre_data(), &img);
+                        rbt_frame.set_letterbox(lb);
+                        if refine {
+                            rbt_frame.set_brightness(brightness_image(&img));
+                        }
+                        rbt_frame.set_state(RbtFrameStage::Pre);
+                        rbt_frame
+                    })
+                });
+                (source, frame)
             })
             .await;
 
             // 处理阻塞任务的结果。
-            if let Ok(frame) = result {
-                // 通过通道将处理后的帧发送到下一阶段
-                let id = frame.id(); // 获取帧 ID，用于日志记录
-                info!(
-                    "预处理阶段：图像 {} 处理完成，耗时 {:?}",
-                    id,
-                    frame.time_used()
-                );
-                let _ = queue.force_push(frame);
-                if id == 1000 {
-                    IS_RUNNING.store(false, std::sync::atomic::Ordering::SeqCst);
+            match result {
+                Ok((source_return, Ok(Some(frame)))) => {
+                    source = source_return;
+                    // 通过通道将处理后的帧发送到下一阶段
                     info!(
-                        "Failed count: {}",
-                        FAILED_COUNT.load(std::sync::atomic::Ordering::SeqCst)
+                        "预处理阶段：图像 {} 处理完成，耗时 {:?}",
+                        frame.id(),
+                        frame.time_used()
                     );
-                    info!("预处理阶段：已处理完所有图像，停止处理");
+                    queue.force_push(frame);
+                }
+                Ok((_, Ok(None))) => {
+                    info!("预处理阶段：图像源已耗尽，停止处理");
+                    break;
+                }
+                Ok((_, Err(err))) => {
+                    error!("预处理阶段：读取图像失败: {}", err);
+                    break;
+                }
+                Err(err) => {
+                    error!("预处理阶段：处理任务异常: {}", err);
                     break;
                 }
-            } else {
-                error!("预处理阶段：图像 {} 处理失败", frame_id);
             }
         }
+        IS_RUNNING.store(false, std::sync::atomic::Ordering::SeqCst);
+        info!(
+            "Failed count: {}",
+            FAILED_COUNT.load(std::sync::atomic::Ordering::SeqCst)
+        );
     })
 }
 
@@ -107,20 +218,19 @@ pub fn infer(
                             .try_extract_array::<f32>()
                             .unwrap()
                             .t()
-                            .into_owned()
                             .as_standard_layout()
-                            .into_shape_with_order((5040, 48, 1)) // 重塑输出形状，基于先验的模型尺寸
-                            .expect("Failed to reshape output")
-                            .to_owned()
+                            .into_owned() // [anchor_count, row_len, 1]，形状已在启动时与标签表核对
+                            .into_dimensionality::<nd::Ix3>()
+                            .expect("Model output should be 3-dimensional")
                     };
-                    frame.infer_data().assign(&output_array);
+                    frame.set_infer_data(output_array);
                     (session, frame) // 返回会话和处理后的帧
                 })
                 .await;
 
                 // 处理推理结果
                 if let Ok((session_return, output)) = output_result {
-                    let _ = infer_post_queue.force_push(output); // 将推理结果发送到后处理阶段
+                    infer_post_queue.force_push(output); // 将推理结果发送到后处理阶段
                     session = session_return; // 确保会话在闭包外部可用
                 } else {
                     warn!("infer: Failed to process frame ID: {}", id);
@@ -131,15 +241,21 @@ pub fn infer(
     })
 }
 
-/// 后处理阶段：接收推理结果，执行目标检测框处理，并提取装甲板信息
-pub fn post_process(frame: Arc<RbtSPSCQueueAsync<RbtFrame>>) -> JoinHandle<()> {
+/// 后处理阶段：接收推理结果，执行目标检测框处理，提取装甲板信息并交给解算阶段
+///
+/// `decoder` 在启动时根据模型构建，NMS 参数随配置热更新
+pub fn post_process(
+    frame: Arc<RbtSPSCQueueAsync<RbtFrame>>,
+    mut decoder: YoloDecoder,
+    solve_stage: SolveStage,
+) -> JoinHandle<()> {
     tokio::spawn(async move {
         loop {
             if IS_RUNNING.load(std::sync::atomic::Ordering::SeqCst) == false {
                 info!("post_process: Stopping processing as IS_RUNNING is false");
                 break;
             }
-            let detector_cfg = GENERIC_RBT_CFG.read().unwrap().detector_cfg.clone();
+            decoder.set_nms(Nms::from_cfg(&GENERIC_RBT_CFG.read().unwrap().detector_cfg));
             if let Some(mut frame) = frame.pop().await {
                 let time_used = frame.time_used(); // 获取处理时间
                 info!(
@@ -150,100 +266,39 @@ pub fn post_process(frame: Arc<RbtSPSCQueueAsync<RbtFrame>>) -> JoinHandle<()> {
                 frame.set_state(RbtFrameStage::Post); // 更新状态为后处理
                 let id = frame.id(); // 获取帧 ID，用于日志记录
                 // 在阻塞线程中执行后处理操作
+                let decoder = decoder.clone();
+                let solve_stage = solve_stage.clone();
+                let refine_cfg = GENERIC_RBT_CFG.read().unwrap().corner_refine_cfg.clone();
                 let result = tokio::task::spawn_blocking(move || {
+                    let lb = *frame.letterbox();
+                    let self_faction = GENERIC_RBT_CFG.read().unwrap().game_cfg.self_fraction();
                     let binding = frame.infer_data();
                     let output = binding.slice(nd::s![.., .., 0]);
 
-                    let mut boxes = Vec::new(); // 存储目标检测框
-
-                    // 遍历每一行输出，提取目标检测框信息
-                    for (idx, row) in output.axis_iter(nd::Axis(0)).enumerate() {
-                        let row: Vec<_> = row.iter().copied().collect();
-                        let (class_id, prob) = row[4..40]
-                            .iter()
-                            .enumerate()
-                            .map(|(index, value)| (index, *value))
-                            .reduce(|accum, row| if row.1 > accum.1 { row } else { accum })
-                            .unwrap();
-
-                        // 如果置信度低于阈值，跳过该检测框
-                        if prob < detector_cfg.confidence_threshold {
-                            continue;
-                        }
-
-                        let xc = row[0]; // 中心点 x 坐标
-                        let yc = row[1]; // 中心点 y 坐标
-                        let w = row[2]; // 检测框宽度
-                        let h = row[3]; // 检测框高度
-
-                        let half_w = w / 2.0;
-                        let half_h = h / 2.0;
-
-                        boxes.push((
-                            BBox::new(xc - half_w, yc - half_h, xc + half_w, yc + half_h),
-                            class_id,
-                            prob,
-                            idx,
-                        ));
-                    }
-
-                    // 非极大值抑制：去除重叠的检测框，保留最优框
-                    let result = nms(boxes);
-
-                    let mut id = 0usize;
-                    // 收集装甲板信息
-                    let mut armors = Vec::<DetectedArmor>::with_capacity(result.len());
-                    for (_, class_id, _, idx) in result {
-                        //     let armor = ArmorKeyPoints::new(
-                        //         ImgCoord::from_f32(output[[idx, 0]], output[[idx, 1]]), // 中心点坐标
-                        //         ImgCoord::from_f32(output[[idx, 40]], output[[idx, 41]]), // 特征点 1
-                        //         ImgCoord::from_f32(output[[idx, 42]], output[[idx, 43]]), // 特征点 2
-                        //         ImgCoord::from_f32(output[[idx, 44]], output[[idx, 45]]), // 特征点 3
-                        //         ImgCoord::from_f32(output[[idx, 46]], output[[idx, 47]]), // 特征点 4
-                        //     );
-                        //     armors.push(armor); // 添加到装甲板列表
-                        let armor_label = &YOLO_LABEL_TABLE[class_id];
-                        if armor_label.color()
-                            == &GENERIC_RBT_CFG
-                                .read()
-                                .unwrap()
-                                .game_cfg
-                                .self_fraction()
-                                .unwrap()
-                        {
-                            continue;
+                    // 解码、非极大值抑制，并将关键点还原到相机原图像素坐标
+                    let armors = decoder.decode(output, &lb, self_faction.as_ref());
+                    // 角点亚像素优化，预处理阶段未保存亮度图时跳过
+                    let armors = armors.map(|mut armors| {
+                        if let (true, Some(brightness)) = (refine_cfg.enable, frame.brightness()) {
+                            CornerRefiner::new(refine_cfg).refine_all(brightness, &mut armors);
                         }
-                        // let armor_id = armor_label.id().clone();
-
-                        let armor = DetectedArmor::new(
-                            RbtImgPoint2::new_screen_pixel(output[[idx, 0]], output[[idx, 1]]),
-                            RbtImgPoint2::new_screen_pixel(output[[idx, 40]], output[[idx, 41]]),
-                            RbtImgPoint2::new_screen_pixel(output[[idx, 42]], output[[idx, 43]]),
-                            RbtImgPoint2::new_screen_pixel(output[[idx, 44]], output[[idx, 45]]),
-                            RbtImgPoint2::new_screen_pixel(output[[idx, 46]], output[[idx, 47]]),
-                            id,
-                        );
+                        armors
+                    });
+                    // 解算并推入解算结果队列
+                    let solved = armors.and_then(|armors| solve_stage.solve(armors, frame.time()));
 
-                        id += 1;
-                        armors.push(armor);
-                    }
-
-                    (frame, armors) // 返回装甲板信息
+                    (frame, solved) // 返回解算出的敌方单位数
                 })
                 .await;
 
-                if let Ok((_frame, _armors)) = result {
-                    // 处理装甲板信息
-                    // for armor in armors {
-                    //     info!("Detected armor: {:?}", armor);
-                    // }
-                    // 将处理后的帧发送到下一阶段或存储
-                    // 这里可以添加代码将处理后的帧存储或发送到其他组件
-                    let time_used = _frame.time_used(); // 获取处理时间
+                if let Ok((frame, Ok(solved))) = result {
+                    let time_used = frame.time_used(); // 获取处理时间
                     info!(
-                        "post_process: Frame ID {} processed successfully, time used: {:?}",
-                        id, time_used
+                        "post_process: Frame ID {} solved {} enemys, time used: {:?}",
+                        id, solved, time_used
                     );
+                } else if let Ok((_, Err(err))) = result {
+                    error!("post_process: Failed to process frame ID {}: {}", id, err);
                 } else {
                     warn!("post_process: Failed to process frame ID: {}", id);
                 }
@@ -255,17 +310,140 @@ pub fn post_process(frame: Arc<RbtSPSCQueueAsync<RbtFrame>>) -> JoinHandle<()> {
     })
 }
 
-/// 500Hz 频率通讯
-pub async fn estimate_process() -> JoinHandle<()> {
+/// 灯条检测阶段：不使用神经网络时代替预处理、推理、后处理三个阶段
+///
+/// 直接从图像源读取图像做传统视觉灯条检测，结果与后处理阶段一样交给解算阶段；
+/// 灯条参数和敌方颜色随配置热更新；图像源耗尽时停止整条流水线
+pub fn lightbar_process(
+    mut source: Box<dyn FrameSource>,
+    mut detector: LightBarDetector,
+    solve_stage: SolveStage,
+) -> JoinHandle<()> {
     tokio::spawn(async move {
-        let mut ticker = tokio::time::interval(Duration::from_millis(2));
         loop {
-            ticker.tick().await;
-            let enemys = RbtSolvedResults::default();
-            let mut estimator_poll = RbtHandlerPoll::new();
-            estimator_poll
-                .update(&GENERIC_RBT_CFG.read().unwrap().estimator_cfg, enemys)
-                .await;
+            if !IS_RUNNING.load(std::sync::atomic::Ordering::SeqCst) {
+                info!("lightbar_process: Stopping processing as IS_RUNNING is false");
+                break;
+            }
+            let (lightbar_cfg, refine_cfg, enemy) = {
+                let cfg = GENERIC_RBT_CFG.read().unwrap();
+                (
+                    cfg.lightbar_cfg.clone(),
+                    cfg.corner_refine_cfg.clone(),
+                    cfg.game_cfg.enemy_fraction(),
+                )
+            };
+            let Some(enemy) = enemy else {
+                error!("lightbar_process: game_cfg/enemy_fraction must be R or B");
+                break;
+            };
+            detector.set_cfg(lightbar_cfg);
+            let solve_stage = solve_stage.clone();
+
+            let result = tokio::task::spawn_blocking(move || {
+                let armors = source.next_frame().map(|frame| {
+                    frame.map(|frame| {
+                        let id = frame.id();
+                        let tim = std::time::Instant::now();
+                        let mut armors = detector.detect(frame.image(), &enemy);
+                        if refine_cfg.enable {
+                            CornerRefiner::new(refine_cfg)
+                                .refine_all(&brightness_image(frame.image()), &mut armors);
+                        }
+                        let detected = armors.values().map(Vec::len).sum::<usize>();
+                        // 解算并推入解算结果队列
+                        let solved = solve_stage.solve(armors, frame.time_stamp());
+                        (id, detected, solved, tim.elapsed())
+                    })
+                });
+                (source, detector, armors)
+            })
+            .await;
+
+            match result {
+                Ok((source_return, detector_return, Ok(Some((id, detected, solved, elapsed))))) => {
+                    source = source_return;
+                    detector = detector_return;
+                    match solved {
+                        Ok(solved) => info!(
+                            "lightbar_process: Frame ID {} detected {} armors, solved {} enemys in {:?}",
+                            id, detected, solved, elapsed
+                        ),
+                        Err(err) => {
+                            error!("lightbar_process: Failed to solve frame ID {}: {}", id, err)
+                        }
+                    }
+                }
+                Ok((_, _, Ok(None))) => {
+                    info!("lightbar_process: 图像源已耗尽，停止处理");
+                    break;
+                }
+                Ok((_, _, Err(err))) => {
+                    error!("lightbar_process: 读取图像失败: {}", err);
+                    break;
+                }
+                Err(err) => {
+                    error!("lightbar_process: 处理任务异常: {}", err);
+                    break;
+                }
+            }
+        }
+        IS_RUNNING.store(false, std::sync::atomic::Ordering::SeqCst);
+    })
+}
+
+/// 估计阶段：接收每一帧的解算结果，更新所有估计器，并向控制线程发布最新的击打目标，
+/// 向解算阶段发布各估计器的先验
+///
+/// 估计器池在整个任务生命周期内只创建一次
+pub fn estimate_process(
+    solved_queue: Arc<RbtSPSCQueueAsync<(RbtSolvedResults, Instant)>>,
+    target_tx: watch::Sender<Option<AimTarget>>,
+    prior_tx: watch::Sender<HashMap<EnemyId, EnemyPrior>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut estimator_poll = RbtHandlerPoll::new();
+        loop {
+            if !IS_RUNNING.load(std::sync::atomic::Ordering::SeqCst) {
+                info!("estimate_process: Stopping processing as IS_RUNNING is false");
+                break;
+            }
+            if let Some((enemys, time_stamp)) = solved_queue.pop().await {
+                let estimator_cfg = GENERIC_RBT_CFG.read().unwrap().estimator_cfg.clone();
+                estimator_poll.update(&estimator_cfg, &enemys, time_stamp);
+                // 控制线程只关心最新目标，旧值直接覆盖
+                target_tx.send_replace(estimator_poll.aim_target());
+                prior_tx.send_replace(estimator_poll.enemy_priors());
+            }
+        }
+    })
+}
+
+/// 控制阶段：500Hz 频率预测瞄准点并与下位机通讯
+///
+/// 收到的传感器帧写入 `tf_tree`，解算阶段据此查询图像采集时刻的坐标变换
+pub fn control_process(
+    target_rx: watch::Receiver<Option<AimTarget>>,
+    tf_tree: Arc<Mutex<RbtTfTree>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let (comm_cfg, control_cfg, bullet_speed) = {
+            let cfg = GENERIC_RBT_CFG.read().unwrap();
+            (
+                cfg.comm_cfg.clone(),
+                cfg.control_cfg.clone(),
+                cfg.general_cfg.bullet_speed,
+            )
+        };
+        let mut comm = RbtCommDevice::from_cfg(&comm_cfg);
+        if let Err(err) = comm.open().await {
+            error!("control_process: Failed to open comm device: {}", err);
+            return;
+        }
+        let mut controller = RbtController::new(&control_cfg, bullet_speed).with_tf_tree(tf_tree);
+        if let Err(err) = controller.run(&mut comm, target_rx).await {
+            error!("control_process: {}", err);
         }
+        let _ = comm.close().await;
     })
 }
@@ -0,0 +1,502 @@
+//! 棋盘格内角点检测
+//!
+//! 1. 高斯模糊后计算 Hessian，棋盘格角点是鞍点，取响应 fxy² - fxx·fyy 的局部极大作为候选，
+//!    再要求候选周围一圈的亮度恰好明暗交替四次，排除棋盘格边缘的 T 形交点
+//! 2. 从强响应的候选出发，按相邻格点的步长逐格生长网格，行列数与棋盘格一致才算检测成功
+//! 3. 与 OpenCV `cornerSubPix` 相同，利用窗口内梯度与角点连线正交的性质迭代求亚像素位置
+
+use image::GrayImage;
+use std::collections::{HashMap, HashSet, VecDeque};
+use tracing::debug;
+
+/// 计算鞍点响应前的模糊尺度
+const RESPONSE_SIGMA: f32 = 2.0;
+/// 亚像素优化前的模糊尺度
+const REFINE_SIGMA: f32 = 1.0;
+/// 非极大值抑制半径 px
+const NMS_RADIUS: i64 = 4;
+/// 低于最大响应该比例的候选被丢弃
+const RESPONSE_RATIO: f32 = 0.1;
+/// 检查明暗交替时采样圆的半径 px 与采样点数
+const RING_RADIUS: f64 = 5.0;
+const RING_SAMPLES: usize = 32;
+/// 采样圆上最亮与最暗的最小差值
+const RING_MIN_CONTRAST: f64 = 20.0;
+/// 最多尝试多少个候选作为生长起点
+const MAX_SEEDS: usize = 30;
+/// 生长时与预测位置的最大偏差，相对于格点步长
+const MATCH_TOLERANCE: f64 = 0.35;
+/// 亚像素优化的最大迭代次数与收敛阈值 px
+const REFINE_MAX_ITERS: usize = 30;
+const REFINE_EPS: f64 = 1e-3;
+
+/// 棋盘格内角点的列数与行数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PatternSize {
+    pub cols: usize,
+    pub rows: usize,
+}
+
+impl PatternSize {
+    pub fn len(&self) -> usize {
+        self.cols * self.rows
+    }
+}
+
+/// 检测棋盘格内角点，按行优先返回 `rows × cols` 个像素坐标
+///
+/// 第一行沿图像 x 方向排列，首个角点靠近图像左上角；未找到完整棋盘格时返回 None
+pub fn find_chessboard_corners(
+    img: &GrayImage,
+    pattern: PatternSize,
+) -> Option<Vec<na::Point2<f64>>> {
+    if pattern.cols < 2 || pattern.rows < 2 {
+        return None;
+    }
+    let gray = FloatImage::from_gray(img);
+    let smooth = gray.gaussian_blur(REFINE_SIGMA);
+    let points = saddle_candidates(&gray.gaussian_blur(RESPONSE_SIGMA))
+        .into_iter()
+        .map(|(p, _)| p)
+        .filter(|p| is_x_corner(&smooth, *p))
+        .collect::<Vec<_>>();
+    if points.len() < pattern.len() {
+        debug!("鞍点候选只有 {} 个", points.len());
+        return None;
+    }
+    let grid =
+        (0..points.len().min(MAX_SEEDS)).find_map(|seed| grow_grid(&points, seed, pattern))?;
+
+    let corners = row_major(grid, pattern)
+        .into_iter()
+        .map(|p| points[p])
+        .collect::<Vec<_>>();
+    let corners = normalize_orientation(corners, pattern);
+    Some(
+        corners
+            .iter()
+            .enumerate()
+            .map(|(idx, p)| {
+                let half_win = (0.4 * neighbor_spacing(&corners, idx, pattern)).clamp(2.0, 10.0);
+                refine_corner(&smooth, *p, half_win as i64).unwrap_or(*p)
+            })
+            .collect(),
+    )
+}
+
+/// 行优先网格中某个角点到相邻角点的最短距离
+fn neighbor_spacing(corners: &[na::Point2<f64>], idx: usize, pattern: PatternSize) -> f64 {
+    let (row, col) = (idx / pattern.cols, idx % pattern.cols);
+    [(0, 1), (0, -1), (1, 0), (-1, 0)]
+        .into_iter()
+        .filter_map(|(dr, dc)| {
+            let r = row.checked_add_signed(dr).filter(|r| *r < pattern.rows)?;
+            let c = col.checked_add_signed(dc).filter(|c| *c < pattern.cols)?;
+            Some(na::distance(&corners[idx], &corners[r * pattern.cols + c]))
+        })
+        .fold(f64::MAX, f64::min)
+}
+
+/// 单通道浮点图像，越界访问按边缘像素延拓
+struct FloatImage {
+    width: usize,
+    height: usize,
+    data: Vec<f32>,
+}
+
+impl FloatImage {
+    fn from_gray(img: &GrayImage) -> Self {
+        Self {
+            width: img.width() as usize,
+            height: img.height() as usize,
+            data: img.as_raw().iter().map(|v| *v as f32).collect(),
+        }
+    }
+
+    fn at(&self, x: i64, y: i64) -> f32 {
+        let x = x.clamp(0, self.width as i64 - 1) as usize;
+        let y = y.clamp(0, self.height as i64 - 1) as usize;
+        self.data[y * self.width + x]
+    }
+
+    /// 双线性插值
+    fn sample(&self, x: f64, y: f64) -> f64 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = self.at(x0, y0) as f64 * (1.0 - fx) + self.at(x0 + 1, y0) as f64 * fx;
+        let bottom = self.at(x0, y0 + 1) as f64 * (1.0 - fx) + self.at(x0 + 1, y0 + 1) as f64 * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+
+    /// 可分离高斯模糊，核半径取 3σ
+    fn gaussian_blur(&self, sigma: f32) -> Self {
+        let radius = (3.0 * sigma).ceil() as i64;
+        let kernel = (-radius..=radius)
+            .map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp())
+            .collect::<Vec<_>>();
+        let norm = kernel.iter().sum::<f32>();
+        let convolve = |src: &FloatImage, horizontal: bool| {
+            let mut data = vec![0.0; src.data.len()];
+            for y in 0..src.height as i64 {
+                for x in 0..src.width as i64 {
+                    let sum = kernel
+                        .iter()
+                        .zip(-radius..=radius)
+                        .map(|(k, i)| {
+                            let v = if horizontal {
+                                src.at(x + i, y)
+                            } else {
+                                src.at(x, y + i)
+                            };
+                            k * v
+                        })
+                        .sum::<f32>();
+                    data[y as usize * src.width + x as usize] = sum / norm;
+                }
+            }
+            FloatImage {
+                width: src.width,
+                height: src.height,
+                data,
+            }
+        };
+        convolve(&convolve(self, true), false)
+    }
+}
+
+/// 鞍点候选及其响应，按响应从大到小排序
+fn saddle_candidates(img: &FloatImage) -> Vec<(na::Point2<f64>, f32)> {
+    let (width, height) = (img.width as i64, img.height as i64);
+    let mut response = vec![0.0f32; img.data.len()];
+    for y in 1..height - 1 {
+        for x in 1..width - 1 {
+            let center = img.at(x, y);
+            let fxx = img.at(x + 1, y) - 2.0 * center + img.at(x - 1, y);
+            let fyy = img.at(x, y + 1) - 2.0 * center + img.at(x, y - 1);
+            let fxy = (img.at(x + 1, y + 1) - img.at(x + 1, y - 1) - img.at(x - 1, y + 1)
+                + img.at(x - 1, y - 1))
+                / 4.0;
+            response[(y * width + x) as usize] = (fxy * fxy - fxx * fyy).max(0.0);
+        }
+    }
+    let max_response = response.iter().copied().fold(0.0f32, f32::max);
+    if max_response <= 0.0 {
+        return Vec::new();
+    }
+    let threshold = max_response * RESPONSE_RATIO;
+    let r = |x: i64, y: i64| response[(y * width + x) as usize];
+
+    let margin = NMS_RADIUS + 1;
+    let mut candidates = Vec::new();
+    for y in margin..height - margin {
+        for x in margin..width - margin {
+            let value = r(x, y);
+            if value < threshold {
+                continue;
+            }
+            // 响应相同时保留扫描顺序靠前的点
+            let is_max = (-NMS_RADIUS..=NMS_RADIUS).all(|dy| {
+                (-NMS_RADIUS..=NMS_RADIUS).all(|dx| {
+                    let other = r(x + dx, y + dy);
+                    other < value || (other == value && (dy, dx) >= (0, 0))
+                })
+            });
+            if !is_max {
+                continue;
+            }
+            // 抛物线拟合响应峰值得到初始亚像素位置
+            let offset = |prev: f32, next: f32| {
+                let denom = prev - 2.0 * value + next;
+                if denom < 0.0 {
+                    (0.5 * (prev - next) / denom).clamp(-0.5, 0.5) as f64
+                } else {
+                    0.0
+                }
+            };
+            let point = na::Point2::new(
+                x as f64 + offset(r(x - 1, y), r(x + 1, y)),
+                y as f64 + offset(r(x, y - 1), r(x, y + 1)),
+            );
+            candidates.push((point, value));
+        }
+    }
+    candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+    candidates
+}
+
+/// 候选周围一圈的亮度按中值二值化后恰好交替四次，即两明两暗的 X 形角点
+fn is_x_corner(img: &FloatImage, p: na::Point2<f64>) -> bool {
+    let ring = (0..RING_SAMPLES)
+        .map(|i| {
+            let angle = i as f64 * std::f64::consts::TAU / RING_SAMPLES as f64;
+            img.sample(
+                p.x + RING_RADIUS * angle.cos(),
+                p.y + RING_RADIUS * angle.sin(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let (min, max) = ring
+        .iter()
+        .fold((f64::MAX, f64::MIN), |(lo, hi), v| (lo.min(*v), hi.max(*v)));
+    if max - min < RING_MIN_CONTRAST {
+        return false;
+    }
+    let mid = (min + max) / 2.0;
+    let transitions = (0..RING_SAMPLES)
+        .filter(|i| (ring[*i] > mid) != (ring[(i + 1) % RING_SAMPLES] > mid))
+        .count();
+    transitions == 4
+}
+
+/// 从 `seed` 出发逐格生长网格，返回格点坐标到候选下标的映射，尺寸不符时返回 None
+fn grow_grid(
+    points: &[na::Point2<f64>],
+    seed: usize,
+    pattern: PatternSize,
+) -> Option<HashMap<(i64, i64), usize>> {
+    let origin = points[seed];
+    let nearest = |predicted: na::Point2<f64>, radius: f64, used: &HashSet<usize>| {
+        points
+            .iter()
+            .enumerate()
+            .filter(|(idx, _)| !used.contains(idx))
+            .map(|(idx, p)| (idx, na::distance(p, &predicted)))
+            .filter(|(_, d)| *d < radius)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(idx, _)| idx)
+    };
+
+    // 最近邻确定第一个轴，与之不平行的最近邻确定第二个轴
+    let mut by_distance = (0..points.len())
+        .filter(|idx| *idx != seed)
+        .map(|idx| (idx, points[idx] - origin))
+        .collect::<Vec<_>>();
+    by_distance.sort_by(|a, b| a.1.norm().total_cmp(&b.1.norm()));
+    let (first, axis_u) = *by_distance.first()?;
+    let (second, axis_v) = *by_distance.iter().skip(1).find(|(_, d)| {
+        (d.dot(&axis_u) / (d.norm() * axis_u.norm())).abs() < 0.5
+            && (0.5..2.0).contains(&(d.norm() / axis_u.norm()))
+    })?;
+
+    let mut cells = HashMap::from([((0, 0), seed), ((1, 0), first), ((0, 1), second)]);
+    let mut used = HashSet::from([seed, first, second]);
+    let mut queue = VecDeque::from([(0, 0), (1, 0), (0, 1)]);
+    let max_cells = pattern.len();
+    while let Some((i, j)) = queue.pop_front() {
+        let here = points[cells[&(i, j)]];
+        for (di, dj) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+            let target = (i + di, j + dj);
+            if cells.contains_key(&target) {
+                continue;
+            }
+            // 优先用反方向的格点外推，其次借用相邻行列同方向的步长，最后退回初始轴
+            let step = cells
+                .get(&(i - di, j - dj))
+                .map(|prev| here - points[*prev])
+                .or_else(|| {
+                    [(dj, di), (-dj, -di)].into_iter().find_map(|(si, sj)| {
+                        let from = cells.get(&(i + si, j + sj))?;
+                        let to = cells.get(&(i + si + di, j + sj + dj))?;
+                        Some(points[*to] - points[*from])
+                    })
+                })
+                .unwrap_or_else(|| (di as f64) * axis_u + (dj as f64) * axis_v);
+            if let Some(found) = nearest(here + step, MATCH_TOLERANCE * step.norm(), &used) {
+                cells.insert(target, found);
+                used.insert(found);
+                queue.push_back(target);
+                if cells.len() > max_cells {
+                    return None;
+                }
+            }
+        }
+    }
+
+    let (min_i, max_i) = cells
+        .keys()
+        .map(|k| k.0)
+        .fold((i64::MAX, i64::MIN), |(lo, hi), v| (lo.min(v), hi.max(v)));
+    let (min_j, max_j) = cells
+        .keys()
+        .map(|k| k.1)
+        .fold((i64::MAX, i64::MIN), |(lo, hi), v| (lo.min(v), hi.max(v)));
+    let size = ((max_i - min_i + 1) as usize, (max_j - min_j + 1) as usize);
+    let fits = size == (pattern.cols, pattern.rows) || size == (pattern.rows, pattern.cols);
+    if !fits || cells.len() != pattern.len() {
+        return None;
+    }
+    Some(
+        cells
+            .into_iter()
+            .map(|((i, j), idx)| ((i - min_i, j - min_j), idx))
+            .collect(),
+    )
+}
+
+/// 将生长出的网格整理为行优先的候选下标，网格生长方向与棋盘格行列可能互换
+fn row_major(cells: HashMap<(i64, i64), usize>, pattern: PatternSize) -> Vec<usize> {
+    let cols = cells.keys().map(|k| k.0).max().unwrap_or(0) as usize + 1;
+    let at = |row: usize, col: usize| {
+        if cols == pattern.cols {
+            cells[&(col as i64, row as i64)]
+        } else {
+            cells[&(row as i64, col as i64)]
+        }
+    };
+    (0..pattern.len())
+        .map(|idx| at(idx / pattern.cols, idx % pattern.cols))
+        .collect()
+}
+
+/// 统一角点顺序：列方向到行方向为顺时针（图像 y 轴向下），首个角点靠近左上角
+fn normalize_orientation(
+    corners: Vec<na::Point2<f64>>,
+    pattern: PatternSize,
+) -> Vec<na::Point2<f64>> {
+    let idx = |row: usize, col: usize| row * pattern.cols + col;
+    let along_col = corners[idx(0, 1)] - corners[idx(0, 0)];
+    let along_row = corners[idx(1, 0)] - corners[idx(0, 0)];
+    let mirrored = along_col.perp(&along_row) < 0.0;
+    let remap = |row: usize, col: usize| {
+        if mirrored {
+            corners[idx(row, pattern.cols - 1 - col)]
+        } else {
+            corners[idx(row, col)]
+        }
+    };
+    let mut oriented = (0..pattern.len())
+        .map(|i| remap(i / pattern.cols, i % pattern.cols))
+        .collect::<Vec<_>>();
+    let (first, last) = (oriented[0], oriented[pattern.len() - 1]);
+    if first.x + first.y > last.x + last.y {
+        oriented.reverse();
+    }
+    oriented
+}
+
+/// 亚像素角点：窗口内每个像素的梯度都应与其到角点的连线正交，求加权最小二乘解并迭代
+fn refine_corner(
+    img: &FloatImage,
+    init: na::Point2<f64>,
+    half_win: i64,
+) -> Option<na::Point2<f64>> {
+    let sigma = half_win as f64 / 2.0;
+    let mut corner = init;
+    for _ in 0..REFINE_MAX_ITERS {
+        let mut a = na::Matrix2::zeros();
+        let mut b = na::Vector2::zeros();
+        for dy in -half_win..=half_win {
+            for dx in -half_win..=half_win {
+                let (x, y) = (corner.x + dx as f64, corner.y + dy as f64);
+                let gradient = na::Vector2::new(
+                    img.sample(x + 1.0, y) - img.sample(x - 1.0, y),
+                    img.sample(x, y + 1.0) - img.sample(x, y - 1.0),
+                ) / 2.0;
+                let weight = (-((dx * dx + dy * dy) as f64) / (2.0 * sigma * sigma)).exp();
+                let gg = weight * gradient * gradient.transpose();
+                a += gg;
+                b += gg * na::Vector2::new(x, y);
+            }
+        }
+        let next = na::Point2::from(a.try_inverse()? * b);
+        let shift = na::distance(&next, &corner);
+        corner = next;
+        if shift < REFINE_EPS {
+            break;
+        }
+    }
+    (na::distance(&corner, &init) <= half_win as f64).then_some(corner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Luma;
+
+    const PATTERN: PatternSize = PatternSize { cols: 9, rows: 6 };
+    const SQUARE: f64 = 30.0;
+
+    /// 棋盘格平面 (mm，原点在第一个内角点) 到像素的单应性矩阵
+    fn board_homography(roll_deg: f64) -> na::Matrix3<f64> {
+        let k = na::Matrix3::new(900.0, 0.0, 320.0, 0.0, 900.0, 240.0, 0.0, 0.0, 1.0);
+        let rotation = na::Rotation3::from_euler_angles(0.35, -0.25, roll_deg.to_radians());
+        let center = na::Vector3::new(4.0 * SQUARE, 2.5 * SQUARE, 0.0);
+        let t = na::Vector3::new(0.0, 0.0, 650.0) - rotation * center;
+        let r = rotation.matrix();
+        k * na::Matrix3::from_columns(&[r.column(0).into_owned(), r.column(1).into_owned(), t])
+    }
+
+    /// 4x4 超采样渲染，棋盘格外留一格白边，再往外为灰色背景，叠加确定性噪声
+    ///
+    /// 测试以 debug 编译运行，逐采样点的矩阵运算太慢，这里手动展开
+    fn render(h: &na::Matrix3<f64>) -> GrayImage {
+        let m = h.try_inverse().unwrap().transpose();
+        let m = m.as_slice();
+        let (cols, rows) = (PATTERN.cols as i64, PATTERN.rows as i64);
+        let mut seed = 12345u32;
+        GrayImage::from_fn(640, 480, |x, y| {
+            let mut sum = 0.0;
+            for s in 0..16 {
+                let u = x as f64 + ((s % 4) as f64 + 0.5) / 4.0 - 0.5;
+                let v = y as f64 + ((s / 4) as f64 + 0.5) / 4.0 - 0.5;
+                let w = m[6] * u + m[7] * v + m[8];
+                let i = ((m[0] * u + m[1] * v + m[2]) / w / SQUARE).floor() as i64;
+                let j = ((m[3] * u + m[4] * v + m[5]) / w / SQUARE).floor() as i64;
+                let inside = (-1..cols).contains(&i) && (-1..rows).contains(&j);
+                let margin = (-2..=cols).contains(&i) && (-2..=rows).contains(&j);
+                sum += match (inside, margin) {
+                    (true, _) if (i + j).rem_euclid(2) == 0 => 30.0,
+                    (_, true) => 220.0,
+                    _ => 120.0,
+                };
+            }
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            let noise = (seed >> 24) as f64 / 255.0 * 16.0 - 8.0;
+            Luma([(sum / 16.0 + noise).clamp(0.0, 255.0) as u8])
+        })
+    }
+
+    fn truth(h: &na::Matrix3<f64>) -> Vec<na::Point2<f64>> {
+        (0..PATTERN.len())
+            .map(|idx| {
+                let p = na::Point2::new(
+                    (idx % PATTERN.cols) as f64 * SQUARE,
+                    (idx / PATTERN.cols) as f64 * SQUARE,
+                );
+                na::Point2::from_homogeneous(h * p.to_homogeneous()).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_find_synthetic_chessboard() {
+        let h = board_homography(5.0);
+        let corners = find_chessboard_corners(&render(&h), PATTERN).unwrap();
+        let errors = corners
+            .iter()
+            .zip(truth(&h))
+            .map(|(p, t)| na::distance(p, &t))
+            .collect::<Vec<_>>();
+        let mean = errors.iter().sum::<f64>() / errors.len() as f64;
+        let max = errors.iter().copied().fold(0.0, f64::max);
+        assert!(mean < 0.05 && max < 0.15, "mean {mean}, max {max}");
+    }
+
+    #[test]
+    fn test_orientation_and_pattern_size() {
+        // 棋盘格转过 180° 后角点顺序仍从左上角开始
+        let h = board_homography(180.0);
+        let corners = find_chessboard_corners(&render(&h), PATTERN).unwrap();
+        let mut expected = truth(&h);
+        expected.reverse();
+        assert!(na::distance(&corners[0], &expected[0]) < 0.5);
+        assert!(na::distance(&corners[PATTERN.len() - 1], &expected[PATTERN.len() - 1]) < 0.5);
+        assert!(corners[1].x > corners[0].x && corners[PATTERN.cols].y > corners[0].y);
+
+        let img = render(&board_homography(5.0));
+        assert!(find_chessboard_corners(&img, PatternSize { cols: 8, rows: 6 }).is_none());
+        assert!(find_chessboard_corners(&img, PatternSize { cols: 6, rows: 9 }).is_some());
+        assert!(find_chessboard_corners(&GrayImage::new(64, 64), PATTERN).is_none());
+    }
+}
@@ -0,0 +1,266 @@
+//! 相机内参标定
+//!
+//! 用法: `cam_calib <图片目录> <列数>x<行数> <格子边长 mm> [--model brown_conrady|fisheye|none] [--output <路径>]`
+//!
+//! 列数、行数为棋盘格内角点数，例如 10x7 个格子的棋盘格填 `9x6`。目前只支持普通棋盘格，不支持 ChArUco。
+//! 对目录下每张图片检测棋盘格角点，用张正友标定法求内参和畸变系数，打印重投影均方根误差，
+//! 并输出可直接粘贴到 `cfg/rbt_cfg.toml` 的 `[cam_cfg]` 配置块，指定 `--output` 时同时写入文件。
+
+extern crate nalgebra as na;
+
+use lib::rbt_base::rbt_geometry::rbt_distortion::RbtDistortion;
+use lib::rbt_infra::rbt_err::{RbtError, RbtResult};
+use std::path::{Path, PathBuf};
+use tracing::{info, warn};
+
+use crate::corners::{PatternSize, find_chessboard_corners};
+use crate::zhang::{Calibration, calibrate};
+
+mod corners;
+mod zhang;
+
+/// 单张图重投影误差超过全局 RMS 的该倍数时提示检查
+const VIEW_RMS_WARN_RATIO: f64 = 2.0;
+
+const USAGE: &str = "Usage: cam_calib <image_dir> <cols>x<rows> <square_mm> \
+                     [--model brown_conrady|fisheye|none] [--output cam_cfg.toml]";
+
+struct Args {
+    image_dir: PathBuf,
+    pattern: PatternSize,
+    square_size: f64,
+    model: RbtDistortion,
+    output: Option<PathBuf>,
+}
+
+fn parse_args(args: &[String]) -> RbtResult<Args> {
+    let usage = || RbtError::StringError(USAGE.to_string());
+    let mut positional = Vec::new();
+    let mut model = RbtDistortion::BrownConrady { coeffs: [0.0; 5] };
+    let mut output = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--model" => {
+                model = match iter.next().map(String::as_str) {
+                    Some("brown_conrady") => RbtDistortion::BrownConrady { coeffs: [0.0; 5] },
+                    Some("fisheye") => RbtDistortion::Fisheye { coeffs: [0.0; 4] },
+                    Some("none") => RbtDistortion::None,
+                    _ => return Err(usage()),
+                }
+            }
+            "--output" => output = Some(PathBuf::from(iter.next().ok_or_else(usage)?)),
+            _ => positional.push(arg),
+        }
+    }
+    let [image_dir, pattern, square_size] = positional[..] else {
+        return Err(usage());
+    };
+    let (cols, rows) = pattern.split_once('x').ok_or_else(usage)?;
+    let pattern = PatternSize {
+        cols: cols.parse().map_err(|_| usage())?,
+        rows: rows.parse().map_err(|_| usage())?,
+    };
+    let square_size = square_size.parse::<f64>().map_err(|_| usage())?;
+    if pattern.cols < 2 || pattern.rows < 2 || square_size <= 0.0 {
+        return Err(usage());
+    }
+    Ok(Args {
+        image_dir: PathBuf::from(image_dir),
+        pattern,
+        square_size,
+        model,
+        output,
+    })
+}
+
+/// 目录下的图片，按文件名排序
+fn image_paths(dir: &Path) -> RbtResult<Vec<PathBuf>> {
+    let mut paths = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| {
+                    ["png", "jpg", "jpeg", "bmp"].contains(&ext.to_ascii_lowercase().as_str())
+                })
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+    Ok(paths)
+}
+
+/// 棋盘格坐标系下的角点，行优先，与 `find_chessboard_corners` 的顺序一致
+fn object_points(pattern: PatternSize, square_size: f64) -> Vec<na::Point2<f64>> {
+    (0..pattern.len())
+        .map(|idx| {
+            na::Point2::new(
+                (idx % pattern.cols) as f64 * square_size,
+                (idx / pattern.cols) as f64 * square_size,
+            )
+        })
+        .collect()
+}
+
+/// 生成 `cfg/rbt_cfg.toml` 中的 `[cam_cfg]` 配置块
+fn cam_cfg_toml(calibration: &Calibration) -> String {
+    let k = &calibration.cam_k;
+    let cam_k = [
+        k[(0, 0)],
+        k[(0, 1)],
+        k[(0, 2)],
+        k[(1, 0)],
+        k[(1, 1)],
+        k[(1, 2)],
+        k[(2, 0)],
+        k[(2, 1)],
+        k[(2, 2)],
+    ];
+    let list = |values: &[f64]| {
+        values
+            .iter()
+            .map(|v| format!("{v:?}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let distortion = match &calibration.distortion {
+        RbtDistortion::None => "{ model = \"none\" }".to_string(),
+        RbtDistortion::BrownConrady { coeffs } => {
+            format!(
+                "{{ model = \"brown_conrady\", coeffs = [{}] }}",
+                list(coeffs)
+            )
+        }
+        RbtDistortion::Fisheye { coeffs } => {
+            format!("{{ model = \"fisheye\", coeffs = [{}] }}", list(coeffs))
+        }
+    };
+    format!(
+        "[cam_cfg]\n# cam_calib 标定结果，重投影均方根误差 {:.4} px\ncam_k = [{}]\ndistortion = {}\n",
+        calibration.rms,
+        list(&cam_k),
+        distortion
+    )
+}
+
+fn main() -> RbtResult<()> {
+    tracing_subscriber::fmt().init();
+
+    let args = parse_args(&std::env::args().skip(1).collect::<Vec<_>>())?;
+    let paths = image_paths(&args.image_dir)?;
+    info!(
+        "{} images found in {}",
+        paths.len(),
+        args.image_dir.display()
+    );
+
+    let mut image_size = None;
+    let mut views = Vec::new();
+    for path in &paths {
+        let img = image::open(path)?.to_luma8();
+        let size = img.dimensions();
+        if *image_size.get_or_insert(size) != size {
+            warn!("{} 尺寸 {:?} 与首张图片不同，跳过", path.display(), size);
+            continue;
+        }
+        match find_chessboard_corners(&img, args.pattern) {
+            Some(corners) => {
+                info!("{}: 找到棋盘格", path.display());
+                views.push((path, corners));
+            }
+            None => warn!("{}: 未找到完整棋盘格", path.display()),
+        }
+    }
+    let image_size = image_size.ok_or(RbtError::StringError(format!(
+        "No images found in {}",
+        args.image_dir.display()
+    )))?;
+
+    let corners = views
+        .iter()
+        .map(|(_, corners)| corners.clone())
+        .collect::<Vec<_>>();
+    let calibration = calibrate(
+        &object_points(args.pattern, args.square_size),
+        &corners,
+        image_size,
+        &args.model,
+    )?;
+    for (((path, _), rms), pose) in views
+        .iter()
+        .zip(&calibration.view_rms)
+        .zip(&calibration.poses)
+    {
+        let distance = pose.translation.vector.norm();
+        if *rms > VIEW_RMS_WARN_RATIO * calibration.rms {
+            warn!(
+                "{}: 距离 {:.0} mm，重投影误差 {:.4} px 偏大，建议检查或剔除",
+                path.display(),
+                distance,
+                rms
+            );
+        } else {
+            info!(
+                "{}: 距离 {:.0} mm，重投影误差 {:.4} px",
+                path.display(),
+                distance,
+                rms
+            );
+        }
+    }
+    info!(
+        "{} / {} 张图片参与标定，重投影均方根误差 {:.4} px",
+        views.len(),
+        paths.len(),
+        calibration.rms
+    );
+
+    let cfg = cam_cfg_toml(&calibration);
+    println!("{cfg}");
+    if let Some(output) = &args.output {
+        std::fs::write(output, &cfg)?;
+        info!("[cam_cfg] written to {}", output.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lib::rbt_infra::rbt_cfg::CamCfg;
+
+    #[test]
+    fn test_parse_args() {
+        let to_args = |s: &str| s.split_whitespace().map(String::from).collect::<Vec<_>>();
+        let args = parse_args(&to_args("imgs 9x6 25 --model fisheye --output out.toml")).unwrap();
+        assert_eq!(args.pattern, PatternSize { cols: 9, rows: 6 });
+        assert_eq!(args.square_size, 25.0);
+        assert!(matches!(args.model, RbtDistortion::Fisheye { .. }));
+        assert_eq!(args.output, Some(PathBuf::from("out.toml")));
+        let args = parse_args(&to_args("imgs 11x8 20.5")).unwrap();
+        assert!(matches!(args.model, RbtDistortion::BrownConrady { .. }));
+        assert!(parse_args(&to_args("imgs 9-6 25")).is_err());
+        assert!(parse_args(&to_args("imgs 9x6")).is_err());
+        assert!(parse_args(&to_args("imgs 9x6 25 --model kannala")).is_err());
+    }
+
+    #[test]
+    fn test_cam_cfg_toml_round_trip() {
+        #[derive(serde::Deserialize)]
+        struct Cfg {
+            cam_cfg: CamCfg,
+        }
+        let calibration = Calibration {
+            cam_k: na::Matrix3::new(1234.5, 0.0, 640.25, 0.0, 1236.0, 511.75, 0.0, 0.0, 1.0),
+            distortion: RbtDistortion::BrownConrady {
+                coeffs: [-0.31, 0.12, 1e-4, -2e-4, -0.015],
+            },
+            rms: 0.2,
+            view_rms: vec![],
+            poses: vec![],
+        };
+        let cfg = toml::from_str::<Cfg>(&cam_cfg_toml(&calibration)).unwrap();
+        assert_eq!(cfg.cam_cfg.cam_k(), calibration.cam_k);
+        assert_eq!(cfg.cam_cfg.distortion(), &calibration.distortion);
+    }
+}
@@ -0,0 +1,495 @@
+//! 张正友平面标定法
+//!
+//! 1. 每张图求棋盘格平面到图像的单应性矩阵，复用 `rbt_ippe::find_homography`
+//! 2. 假设像素无倾斜，由各单应性矩阵对 B = K⁻ᵀK⁻¹ 的线性约束闭式求出内参初值
+//! 3. 由 K⁻¹H 分解出每张图的外参初值
+//! 4. 以上述结果为初值，LM 联合优化内参、畸变系数和全部外参，最小化重投影误差
+
+use lib::rbt_base::rbt_algorithm::rbt_ippe::find_homography;
+use lib::rbt_base::rbt_geometry::rbt_distortion::{RbtDistortion, project_point};
+use lib::rbt_infra::rbt_err::{RbtError, RbtResult};
+use tracing::debug;
+
+/// LM 最大迭代次数
+const MAX_ITERS: usize = 100;
+/// 代价相对下降量小于该值时认为收敛
+const COST_TOLERANCE: f64 = 1e-12;
+/// 投影失败（点在相机后方）时的残差，使该步被拒绝
+const INVALID_RESIDUAL: f64 = 1e6;
+/// 内参 fx fy cx cy 的个数
+const INTRINSIC_PARAMS: usize = 4;
+/// 每张图外参的个数：旋转向量 + 平移
+const POSE_PARAMS: usize = 6;
+
+/// 标定结果
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    pub cam_k: na::Matrix3<f64>,
+    pub distortion: RbtDistortion,
+    /// 全部角点的重投影均方根误差 px，与 OpenCV `calibrateCamera` 的返回值含义相同
+    pub rms: f64,
+    /// 每张图的重投影均方根误差 px
+    pub view_rms: Vec<f64>,
+    /// 每张图中棋盘格坐标系到相机坐标系的变换
+    pub poses: Vec<na::Isometry3<f64>>,
+}
+
+/// 标定相机内参
+///
+/// `object_points` 为棋盘格平面上的角点坐标 (Z = 0)，`views` 为每张图中与之一一对应的像素坐标，
+/// `model` 只决定畸变模型，其系数被忽略，传 `RbtDistortion::None` 时只标定针孔内参
+pub fn calibrate(
+    object_points: &[na::Point2<f64>],
+    views: &[Vec<na::Point2<f64>>],
+    image_size: (u32, u32),
+    model: &RbtDistortion,
+) -> RbtResult<Calibration> {
+    if views.len() < 3 {
+        return Err(RbtError::StringError(format!(
+            "At least 3 views are required, got {}",
+            views.len()
+        )));
+    }
+    if views.iter().any(|view| view.len() != object_points.len()) {
+        return Err(RbtError::StringError(
+            "Every view must have the same number of points as the pattern".to_string(),
+        ));
+    }
+    let homographies = views
+        .iter()
+        .map(|view| find_homography(object_points, view))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(RbtError::StringError(
+            "Failed to find homography".to_string(),
+        ))?;
+    let cam_k = initial_intrinsics(&homographies, image_size).ok_or(RbtError::StringError(
+        "Degenerate views for calibration".to_string(),
+    ))?;
+    debug!("闭式解内参初值: {}", cam_k);
+    let poses = homographies
+        .iter()
+        .map(|h| initial_pose(&cam_k, h))
+        .collect::<Option<Vec<_>>>()
+        .ok_or(RbtError::StringError(
+            "Failed to decompose homography".to_string(),
+        ))?;
+    let distortion =
+        model
+            .with_coeffs(&vec![0.0; model.coeffs().len()])
+            .ok_or(RbtError::StringError(
+                "Invalid distortion model".to_string(),
+            ))?;
+
+    let problem = Problem {
+        object_points,
+        views,
+        distortion,
+    };
+    let params = problem.optimize(problem.pack(&cam_k, &distortion, &poses));
+    let (cam_k, distortion, poses) = problem.unpack(&params);
+
+    let residuals = problem.residuals(&params);
+    let view_len = 2 * object_points.len();
+    let view_rms = residuals
+        .as_slice()
+        .chunks(view_len)
+        .map(|chunk| (chunk.iter().map(|r| r * r).sum::<f64>() / object_points.len() as f64).sqrt())
+        .collect::<Vec<_>>();
+    let rms = (residuals.norm_squared() / (object_points.len() * views.len()) as f64).sqrt();
+    Ok(Calibration {
+        cam_k,
+        distortion,
+        rms,
+        view_rms,
+        poses,
+    })
+}
+
+/// 闭式求解无倾斜的内参
+///
+/// 单应性矩阵先左乘图像尺寸的归一化矩阵 N 改善数值条件，解出 N·K 后再还原
+fn initial_intrinsics(
+    homographies: &[na::Matrix3<f64>],
+    (width, height): (u32, u32),
+) -> Option<na::Matrix3<f64>> {
+    let (w, h) = (width as f64, height as f64);
+    let norm = na::Matrix3::new(2.0 / w, 0.0, -1.0, 0.0, 2.0 / h, -1.0, 0.0, 0.0, 1.0);
+    // vᵢⱼ 使 hᵢᵀ B hⱼ = vᵢⱼᵀ b，b = [B11, B12, B22, B13, B23, B33]
+    let v = |hm: &na::Matrix3<f64>, i: usize, j: usize| {
+        let (a, b) = (hm.column(i), hm.column(j));
+        na::SVector::<f64, 6>::from([
+            a[0] * b[0],
+            a[0] * b[1] + a[1] * b[0],
+            a[1] * b[1],
+            a[2] * b[0] + a[0] * b[2],
+            a[2] * b[1] + a[1] * b[2],
+            a[2] * b[2],
+        ])
+    };
+    let mut vtv = na::SMatrix::<f64, 6, 6>::zeros();
+    for hm in homographies {
+        let hm = norm * hm;
+        let hm = hm / hm.norm();
+        for row in [v(&hm, 0, 1), v(&hm, 0, 0) - v(&hm, 1, 1)] {
+            vtv += row * row.transpose();
+        }
+    }
+    // 无倾斜约束 B12 = 0
+    let skew = na::SVector::<f64, 6>::from([0.0, 1.0, 0.0, 0.0, 0.0, 0.0]);
+    vtv += skew * skew.transpose();
+
+    let eigen = na::SymmetricEigen::new(vtv);
+    let (min_idx, _) = eigen
+        .eigenvalues
+        .iter()
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(b.1))?;
+    let mut b = eigen.eigenvectors.column(min_idx).into_owned();
+    if b[0] < 0.0 {
+        b = -b;
+    }
+    let [b11, b12, b22, b13, b23, b33] = [b[0], b[1], b[2], b[3], b[4], b[5]];
+    let denom = b11 * b22 - b12 * b12;
+    if b11 <= 0.0 || denom <= 0.0 {
+        return None;
+    }
+    let v0 = (b12 * b13 - b11 * b23) / denom;
+    let lambda = b33 - (b13 * b13 + v0 * (b12 * b13 - b11 * b23)) / b11;
+    if lambda <= 0.0 {
+        return None;
+    }
+    let alpha = (lambda / b11).sqrt();
+    let beta = (lambda * b11 / denom).sqrt();
+    let gamma = -b12 * alpha * alpha * beta / lambda;
+    let u0 = gamma * v0 / beta - b13 * alpha * alpha / lambda;
+    let norm_k = na::Matrix3::new(alpha, 0.0, u0, 0.0, beta, v0, 0.0, 0.0, 1.0);
+    Some(norm.try_inverse()? * norm_k)
+}
+
+/// 由 K⁻¹H = λ[r1 r2 t] 分解外参，旋转矩阵投影到最近的正交阵
+fn initial_pose(cam_k: &na::Matrix3<f64>, hm: &na::Matrix3<f64>) -> Option<na::Isometry3<f64>> {
+    let a = cam_k.try_inverse()? * hm;
+    let mut scale = a.column(0).norm().recip();
+    // 棋盘格必须在相机前方
+    if a[(2, 2)] * scale < 0.0 {
+        scale = -scale;
+    }
+    let r1 = a.column(0) * scale;
+    let r2 = a.column(1) * scale;
+    let t = a.column(2) * scale;
+    let r = na::Matrix3::from_columns(&[r1, r2, r1.cross(&r2)]);
+    let svd = r.svd(true, true);
+    let mut rotation = svd.u? * svd.v_t?;
+    if rotation.determinant() < 0.0 {
+        rotation = -rotation;
+    }
+    Some(na::Isometry3::from_parts(
+        na::Translation3::from(t),
+        na::UnitQuaternion::from_matrix(&rotation),
+    ))
+}
+
+/// 重投影误差最小化问题
+///
+/// 参数排列为 [fx, fy, cx, cy, 畸变系数..., 每张图的 (旋转向量, 平移)...]
+struct Problem<'a> {
+    object_points: &'a [na::Point2<f64>],
+    views: &'a [Vec<na::Point2<f64>>],
+    /// 畸变模型，系数在优化中被替换
+    distortion: RbtDistortion,
+}
+
+impl Problem<'_> {
+    fn distortion_len(&self) -> usize {
+        self.distortion.coeffs().len()
+    }
+
+    fn camera_len(&self) -> usize {
+        INTRINSIC_PARAMS + self.distortion_len()
+    }
+
+    fn pack(
+        &self,
+        cam_k: &na::Matrix3<f64>,
+        distortion: &RbtDistortion,
+        poses: &[na::Isometry3<f64>],
+    ) -> na::DVector<f64> {
+        let mut params = vec![cam_k[(0, 0)], cam_k[(1, 1)], cam_k[(0, 2)], cam_k[(1, 2)]];
+        params.extend_from_slice(distortion.coeffs());
+        for pose in poses {
+            params.extend(pose.rotation.scaled_axis().iter());
+            params.extend(pose.translation.vector.iter());
+        }
+        na::DVector::from_vec(params)
+    }
+
+    fn unpack(
+        &self,
+        params: &na::DVector<f64>,
+    ) -> (na::Matrix3<f64>, RbtDistortion, Vec<na::Isometry3<f64>>) {
+        let (cam_k, distortion) = self.camera(params.as_slice());
+        let poses = params.as_slice()[self.camera_len()..]
+            .chunks(POSE_PARAMS)
+            .map(pose_from)
+            .collect();
+        (cam_k, distortion, poses)
+    }
+
+    fn camera(&self, params: &[f64]) -> (na::Matrix3<f64>, RbtDistortion) {
+        let cam_k = na::Matrix3::new(
+            params[0], 0.0, params[2], 0.0, params[1], params[3], 0.0, 0.0, 1.0,
+        );
+        let distortion = self
+            .distortion
+            .with_coeffs(&params[INTRINSIC_PARAMS..self.camera_len()])
+            .unwrap_or(self.distortion);
+        (cam_k, distortion)
+    }
+
+    /// 单张图的残差 (u, v) 交错排列
+    fn view_residuals(
+        &self,
+        camera: &(na::Matrix3<f64>, RbtDistortion),
+        pose: &[f64],
+        view: &[na::Point2<f64>],
+        out: &mut [f64],
+    ) {
+        let pose = pose_from(pose);
+        for ((p, uv), r) in self.object_points.iter().zip(view).zip(out.chunks_mut(2)) {
+            let pc = pose * na::Point3::new(p.x, p.y, 0.0);
+            match project_point(&pc, &camera.0, &camera.1) {
+                Some(proj) => {
+                    r[0] = proj.x - uv.x;
+                    r[1] = proj.y - uv.y;
+                }
+                None => r.fill(INVALID_RESIDUAL),
+            }
+        }
+    }
+
+    fn residuals(&self, params: &na::DVector<f64>) -> na::DVector<f64> {
+        let params = params.as_slice();
+        let camera = self.camera(params);
+        let view_len = 2 * self.object_points.len();
+        let mut residuals = na::DVector::zeros(view_len * self.views.len());
+        for (i, view) in self.views.iter().enumerate() {
+            let pose = &params[self.camera_len() + i * POSE_PARAMS..][..POSE_PARAMS];
+            let out = &mut residuals.as_mut_slice()[i * view_len..][..view_len];
+            self.view_residuals(&camera, pose, view, out);
+        }
+        residuals
+    }
+
+    /// 中心差分数值雅可比，外参只影响所在图的残差，只重算对应的块
+    fn jacobian(&self, params: &na::DVector<f64>) -> na::DMatrix<f64> {
+        let view_len = 2 * self.object_points.len();
+        let mut jacobian = na::DMatrix::zeros(view_len * self.views.len(), params.len());
+        let step = |value: f64| 1e-6 * value.abs().max(1.0);
+        for col in 0..self.camera_len() {
+            let h = step(params[col]);
+            let (mut plus, mut minus) = (params.clone(), params.clone());
+            plus[col] += h;
+            minus[col] -= h;
+            let diff = (self.residuals(&plus) - self.residuals(&minus)) / (2.0 * h);
+            jacobian.set_column(col, &diff);
+        }
+        let camera = self.camera(params.as_slice());
+        let (mut plus, mut minus) = (vec![0.0; view_len], vec![0.0; view_len]);
+        for (i, view) in self.views.iter().enumerate() {
+            let offset = self.camera_len() + i * POSE_PARAMS;
+            for k in 0..POSE_PARAMS {
+                let mut pose = params.as_slice()[offset..][..POSE_PARAMS].to_vec();
+                let h = step(pose[k]);
+                pose[k] += h;
+                self.view_residuals(&camera, &pose, view, &mut plus);
+                pose[k] -= 2.0 * h;
+                self.view_residuals(&camera, &pose, view, &mut minus);
+                for row in 0..view_len {
+                    jacobian[(i * view_len + row, offset + k)] =
+                        (plus[row] - minus[row]) / (2.0 * h);
+                }
+            }
+        }
+        jacobian
+    }
+
+    /// Levenberg-Marquardt，阻尼项按 JᵀJ 对角线缩放
+    fn optimize(&self, mut params: na::DVector<f64>) -> na::DVector<f64> {
+        let mut cost = self.residuals(&params).norm_squared();
+        let mut lambda = 1e-3;
+        for iter in 0..MAX_ITERS {
+            let jacobian = self.jacobian(&params);
+            let residuals = self.residuals(&params);
+            let jtj = jacobian.transpose() * &jacobian;
+            let gradient = jacobian.transpose() * residuals;
+            let mut improved = false;
+            while lambda < 1e12 {
+                let mut damped = jtj.clone();
+                for i in 0..damped.nrows() {
+                    damped[(i, i)] += lambda * jtj[(i, i)].max(1e-12);
+                }
+                let Some(step) = damped.cholesky().map(|c| c.solve(&-&gradient)) else {
+                    lambda *= 10.0;
+                    continue;
+                };
+                let candidate = &params + step;
+                let candidate_cost = self.residuals(&candidate).norm_squared();
+                if candidate_cost < cost {
+                    let reduction = (cost - candidate_cost) / cost.max(f64::MIN_POSITIVE);
+                    params = candidate;
+                    cost = candidate_cost;
+                    lambda = (lambda / 10.0).max(1e-12);
+                    improved = reduction > COST_TOLERANCE;
+                    break;
+                }
+                lambda *= 10.0;
+            }
+            if !improved {
+                debug!("LM 在第 {} 次迭代收敛, 代价 {:.6e}", iter, cost);
+                break;
+            }
+        }
+        params
+    }
+}
+
+fn pose_from(params: &[f64]) -> na::Isometry3<f64> {
+    na::Isometry3::new(
+        na::Vector3::new(params[3], params[4], params[5]),
+        na::Vector3::new(params[0], params[1], params[2]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLS: usize = 9;
+    const ROWS: usize = 6;
+    const SQUARE: f64 = 25.0;
+    const IMAGE_SIZE: (u32, u32) = (1280, 1024);
+
+    fn cam_k() -> na::Matrix3<f64> {
+        na::Matrix3::new(1000.0, 0.0, 640.0, 0.0, 1005.0, 512.0, 0.0, 0.0, 1.0)
+    }
+
+    fn object_points() -> Vec<na::Point2<f64>> {
+        (0..COLS * ROWS)
+            .map(|i| na::Point2::new((i % COLS) as f64 * SQUARE, (i / COLS) as f64 * SQUARE))
+            .collect()
+    }
+
+    /// 棋盘格以不同倾角和位置出现在画面各处
+    fn synthetic_views(distortion: &RbtDistortion, noise_px: f64) -> Vec<Vec<na::Point2<f64>>> {
+        let center = na::Vector3::new(4.0 * SQUARE, 2.5 * SQUARE, 0.0);
+        let mut seed = 7u32;
+        let mut noise = move || {
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            ((seed >> 8) as f64 / (1u32 << 24) as f64 * 2.0 - 1.0) * noise_px
+        };
+        (0..10)
+            .map(|i| {
+                let angle = i as f64 * 0.7;
+                let rotation = na::Rotation3::from_euler_angles(
+                    0.45 * angle.cos(),
+                    0.4 * angle.sin(),
+                    0.2 * (i as f64 - 5.0) / 5.0,
+                );
+                let shift = na::Vector3::new(
+                    120.0 * (1.3 * angle).sin(),
+                    90.0 * (0.9 * angle).cos(),
+                    550.0 + 25.0 * i as f64,
+                );
+                let pose = na::Isometry3::from_parts(
+                    na::Translation3::from(shift - rotation * center),
+                    na::UnitQuaternion::from_rotation_matrix(&rotation),
+                );
+                object_points()
+                    .iter()
+                    .map(|p| {
+                        let pc = pose * na::Point3::new(p.x, p.y, 0.0);
+                        let uv = project_point(&pc, &cam_k(), distortion).unwrap();
+                        assert!(uv.x > 0.0 && uv.x < 1280.0 && uv.y > 0.0 && uv.y < 1024.0);
+                        uv + na::Vector2::new(noise(), noise())
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn coeffs_error(a: &RbtDistortion, b: &RbtDistortion) -> f64 {
+        a.coeffs()
+            .iter()
+            .zip(b.coeffs())
+            .map(|(x, y)| (x - y).abs())
+            .fold(0.0, f64::max)
+    }
+
+    #[test]
+    fn test_calibrate_brown_conrady() {
+        let truth = RbtDistortion::BrownConrady {
+            coeffs: [-0.25, 0.09, 0.0006, -0.0004, -0.01],
+        };
+        let views = synthetic_views(&truth, 0.0);
+        let calibration = calibrate(&object_points(), &views, IMAGE_SIZE, &truth).unwrap();
+        assert!(calibration.rms < 1e-6, "{}", calibration.rms);
+        assert!(
+            (calibration.cam_k - cam_k()).abs().max() < 1e-3,
+            "{}",
+            calibration.cam_k
+        );
+        assert!(coeffs_error(&calibration.distortion, &truth) < 1e-6);
+        assert_eq!(calibration.view_rms.len(), views.len());
+        assert!((calibration.poses[0].translation.vector.norm() - 550.0).abs() < 100.0);
+    }
+
+    #[test]
+    fn test_calibrate_with_noise() {
+        let truth = RbtDistortion::BrownConrady {
+            coeffs: [-0.25, 0.09, 0.0006, -0.0004, -0.01],
+        };
+        // u、v 各加 ±0.2 px 均匀噪声，每个角点的 RMS 约为 0.2·√(2/3) ≈ 0.163 px
+        let views = synthetic_views(&truth, 0.2);
+        let calibration = calibrate(&object_points(), &views, IMAGE_SIZE, &truth).unwrap();
+        assert!(
+            (calibration.rms - 0.163).abs() < 0.02,
+            "{}",
+            calibration.rms
+        );
+        let k = calibration.cam_k;
+        assert!((k[(0, 0)] / 1000.0 - 1.0).abs() < 5e-3, "{k}");
+        assert!((k[(1, 1)] / 1005.0 - 1.0).abs() < 5e-3, "{k}");
+        // 主点与切向畸变相关，10 张图时约束较弱，允许数个像素的偏差
+        assert!(
+            (k[(0, 2)] - 640.0).abs() < 6.0 && (k[(1, 2)] - 512.0).abs() < 6.0,
+            "{k}"
+        );
+    }
+
+    #[test]
+    fn test_calibrate_fisheye_and_pinhole() {
+        let fisheye = RbtDistortion::Fisheye {
+            coeffs: [0.05, -0.01, 0.003, -0.0005],
+        };
+        let views = synthetic_views(&fisheye, 0.0);
+        let calibration = calibrate(&object_points(), &views, IMAGE_SIZE, &fisheye).unwrap();
+        assert!(calibration.rms < 1e-6, "{}", calibration.rms);
+        assert!((calibration.cam_k - cam_k()).abs().max() < 1e-3);
+        assert!(coeffs_error(&calibration.distortion, &fisheye) < 1e-6);
+
+        let views = synthetic_views(&RbtDistortion::None, 0.0);
+        let calibration =
+            calibrate(&object_points(), &views, IMAGE_SIZE, &RbtDistortion::None).unwrap();
+        assert!(calibration.rms < 1e-6);
+        assert!((calibration.cam_k - cam_k()).abs().max() < 1e-3);
+        assert!(
+            calibrate(
+                &object_points(),
+                &views[..2],
+                IMAGE_SIZE,
+                &RbtDistortion::None
+            )
+            .is_err()
+        );
+    }
+}
@@ -2,37 +2,64 @@ mod serial_test;
 mod udp_test;
 mod usb_test;
 
-/// 用于测试电控通讯
-use lib::rbt_mod::rbt_comm::rbt_comm_frame;
+/// 虚拟下位机，用于在没有电控硬件时测试通讯与自瞄闭环
+///
+/// 用法:
+/// - `test_serial pty [秒]`: 创建伪终端并打印从端路径，将 comm_cfg/serial_port 指向该路径
+/// - `test_serial udp <本机地址> <上位机地址> [秒]`: 对应 comm_cfg/udp_remote_addr 与 udp_local_addr
+///
+/// 运行结束后打印跟踪误差，未收到任何控制帧时返回错误，便于在 CI 中回归
+use lib::rbt_infra::rbt_err::{RbtError, RbtResult};
+use lib::rbt_mod::rbt_comm::rbt_comm_sim::SimReport;
+use tokio::time::Duration;
 use tracing::info;
 
+/// 默认运行时长
+const DEFAULT_DURATION_S: u64 = 10;
+
+fn parse_duration(arg: Option<&String>) -> RbtResult<Duration> {
+    match arg {
+        Some(secs) => secs
+            .parse::<u64>()
+            .map(Duration::from_secs)
+            .map_err(|e| RbtError::StringError(format!("Invalid duration {secs}: {e}"))),
+        None => Ok(Duration::from_secs(DEFAULT_DURATION_S)),
+    }
+}
+
 #[tokio::main]
-async fn main() {
+async fn main() -> RbtResult<()> {
     tracing_subscriber::fmt()
         .with_max_level(tracing::Level::DEBUG)
         .init();
 
-    // 接收线程
-    let sens_handle = tokio::spawn(async move {
-        // 2ms 定时器
-        let mut ticker = tokio::time::interval(tokio::time::Duration::from_millis(10));
-        loop {
-            ticker.tick().await;
-            info!("received");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let report: SimReport = match args.first().map(String::as_str) {
+        Some("pty") => serial_test::run(parse_duration(args.get(1))?).await?,
+        Some("udp") if args.len() >= 3 => {
+            udp_test::run(&args[1], &args[2], parse_duration(args.get(3))?).await?
         }
-    });
-
-    // 发送线程
-    let ctrl_handle = tokio::spawn(async move {
-        // 10ms 定时器
-        let mut ticker = tokio::time::interval(tokio::time::Duration::from_millis(10));
-        loop {
-            ticker.tick().await;
-            info!("sended");
+        _ => {
+            return Err(RbtError::StringError(
+                "Usage: test_serial pty [secs] | test_serial udp <local> <remote> [secs]"
+                    .to_string(),
+            ));
         }
-    });
+    };
 
-    tokio::join!(sens_handle, ctrl_handle,);
+    info!(
+        "sens: {}, ctrl: {} (lost {}, corrupted {}), tracking rms {:.3} deg, max {:.3} deg",
+        report.sens_frames,
+        report.ctrl_frames,
+        report.ctrl_lost_frames,
+        report.ctrl_decoder.corrupted_frames,
+        report.tracking_rms,
+        report.tracking_max,
+    );
+    if report.ctrl_frames == 0 {
+        return Err(RbtError::StringError("No CtrlData received from host".to_string()));
+    }
 
     info!("main task exiting");
+    Ok(())
 }
@@ -1 +1,21 @@
+/// 通过伪终端运行虚拟下位机
+use lib::rbt_infra::rbt_err::RbtResult;
+use lib::rbt_mod::rbt_comm::rbt_comm_frame::SelfFraction;
+use lib::rbt_mod::rbt_comm::rbt_comm_sim::{McuSim, SimReport, SimScript};
+use tokio::time::Duration;
+use tokio_serial::{SerialPort, SerialStream};
+use tracing::info;
 
+pub async fn run(duration: Duration) -> RbtResult<SimReport> {
+    let (mcu, slave) = SerialStream::pair().map_err(std::io::Error::from)?;
+    // 从端需要保持打开，否则上位机连接前主端读取会返回 EIO
+    let slave_name = slave.name().unwrap_or_default();
+    info!("Virtual MCU listening on {}", slave_name);
+
+    let mut sim = McuSim::new(SimScript::default_script(), SelfFraction::Red);
+    let report = sim
+        .run_stream(mcu, Duration::from_millis(1), duration)
+        .await;
+    drop(slave);
+    report
+}
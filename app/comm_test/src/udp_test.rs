@@ -1 +1,17 @@
+/// 通过 UDP 运行虚拟下位机
+use lib::rbt_infra::rbt_err::RbtResult;
+use lib::rbt_mod::rbt_comm::rbt_comm_frame::SelfFraction;
+use lib::rbt_mod::rbt_comm::rbt_comm_sim::{McuSim, SimReport, SimScript};
+use tokio::net::UdpSocket;
+use tokio::time::Duration;
+use tracing::info;
 
+pub async fn run(local: &str, remote: &str, duration: Duration) -> RbtResult<SimReport> {
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(remote).await?;
+    info!("Virtual MCU bound to {} and sending to {}", local, remote);
+
+    let mut sim = McuSim::new(SimScript::default_script(), SelfFraction::Red);
+    sim.run_udp(&socket, Duration::from_millis(1), duration)
+        .await
+}
@@ -0,0 +1,144 @@
+//! YOLO-pose 格式的标注数据集
+//!
+//! ```text
+//! dataset/
+//! ├── images/   xxx.jpg / xxx.png
+//! └── labels/   xxx.txt，与图片同名，没有标注文件的图片视为负样本
+//! ```
+//! 标注每行一个装甲板，坐标均按图像宽高归一化：
+//! `class xc yc w h x1 y1 [v1] x2 y2 [v2] x3 y3 [v3] x4 y4 [v4]`，
+//! 四个关键点依次为左上、左下、右下、右上，与模型输出一致，可见性标志不使用。
+
+use std::path::{Path, PathBuf};
+
+use lib::rbt_infra::rbt_err::{RbtError, RbtResult};
+use lib::rbt_mod::rbt_detector::rbt_yolo::BBox;
+
+const IMAGE_EXTENSIONS: [&str; 4] = ["jpg", "jpeg", "png", "bmp"];
+
+/// 一个标注的装甲板，坐标为原图像素
+#[derive(Debug, Clone)]
+pub struct GroundTruth {
+    pub class_id: usize,
+    pub bbox: BBox,
+    /// 左上、左下、右下、右上
+    pub corners: [na::Point2<f64>; 4],
+}
+
+/// 数据集中的一张图片及其标注文件
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub image: PathBuf,
+    pub label: PathBuf,
+}
+
+impl Sample {
+    /// 读取标注，`size` 为图片宽高；标注文件不存在时返回空
+    pub fn ground_truths(&self, size: (u32, u32)) -> RbtResult<Vec<GroundTruth>> {
+        if !self.label.exists() {
+            return Ok(Vec::new());
+        }
+        parse_labels(&std::fs::read_to_string(&self.label)?, size)
+            .map_err(|err| RbtError::StringError(format!("{}: {}", self.label.display(), err)))
+    }
+}
+
+/// 列出 `dir/images` 下的所有图片，按文件名排序
+pub fn samples(dir: &Path) -> RbtResult<Vec<Sample>> {
+    let image_dir = dir.join("images");
+    let label_dir = dir.join("labels");
+    let mut samples = std::fs::read_dir(&image_dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+        })
+        .map(|image| Sample {
+            label: label_dir.join(image.with_extension("txt").file_name().unwrap_or_default()),
+            image,
+        })
+        .collect::<Vec<_>>();
+    if samples.is_empty() {
+        return Err(RbtError::StringError(format!(
+            "no image found in {}",
+            image_dir.display()
+        )));
+    }
+    samples.sort_by(|a, b| a.image.cmp(&b.image));
+    Ok(samples)
+}
+
+/// 解析一个标注文件
+pub fn parse_labels(text: &str, (width, height): (u32, u32)) -> RbtResult<Vec<GroundTruth>> {
+    let (w, h) = (width as f64, height as f64);
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(idx, line)| {
+            let invalid = |msg: &str| RbtError::StringError(format!("line {}: {}", idx + 1, msg));
+            let mut fields = line.split_whitespace();
+            let class_id = fields
+                .next()
+                .and_then(|c| c.parse::<usize>().ok())
+                .ok_or_else(|| invalid("invalid class id"))?;
+            let values = fields
+                .map(|v| v.parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| invalid("invalid number"))?;
+            // 检测框 4 个值，关键点每个 2 或 3 个值
+            let stride = match values.len() {
+                12 => 2,
+                16 => 3,
+                n => return Err(invalid(&format!("expected 12 or 16 values, got {}", n))),
+            };
+            let (xc, yc, bw, bh) = (values[0] * w, values[1] * h, values[2] * w, values[3] * h);
+            let corners = std::array::from_fn(|i| {
+                let k = 4 + i * stride;
+                na::Point2::new(values[k] * w, values[k + 1] * h)
+            });
+            Ok(GroundTruth {
+                class_id,
+                bbox: BBox::new(
+                    (xc - bw / 2.0) as f32,
+                    (yc - bh / 2.0) as f32,
+                    (xc + bw / 2.0) as f32,
+                    (yc + bh / 2.0) as f32,
+                ),
+                corners,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lib::rbt_mod::rbt_detector::rbt_yolo::iou;
+
+    #[test]
+    fn test_parse_labels() {
+        let labels = parse_labels(
+            "1 0.5 0.5 0.25 0.5 0.4 0.3 0.4 0.7 0.6 0.7 0.6 0.3\n\
+             \n\
+             20 0.25 0.25 0.1 0.1 0.2 0.2 2 0.2 0.3 2 0.3 0.3 1 0.3 0.2 2\n",
+            (1280, 720),
+        )
+        .unwrap();
+        assert_eq!(labels.len(), 2);
+        assert_eq!(labels[0].class_id, 1);
+        assert!(iou(&labels[0].bbox, &BBox::new(480.0, 180.0, 800.0, 540.0)) > 0.999);
+        assert!(na::distance(&labels[0].corners[0], &na::Point2::new(512.0, 216.0)) < 1e-9);
+        assert!(na::distance(&labels[0].corners[2], &na::Point2::new(768.0, 504.0)) < 1e-9);
+        // 带可见性标志的格式
+        assert_eq!(labels[1].class_id, 20);
+        assert!(na::distance(&labels[1].corners[3], &na::Point2::new(384.0, 144.0)) < 1e-9);
+    }
+
+    #[test]
+    fn test_reject_invalid_labels() {
+        assert!(parse_labels("1 0.5 0.5 0.25 0.5", (640, 480)).is_err());
+        assert!(parse_labels("x 0.5 0.5 0.25 0.5 0 0 0 0 0 0 0 0", (640, 480)).is_err());
+        assert!(parse_labels("1 0.5 0.5 0.25 0.5 0 0 0 0 0 0 0 nan?", (640, 480)).is_err());
+    }
+}
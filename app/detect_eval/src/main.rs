@@ -0,0 +1,122 @@
+//! 检测效果离线评估
+//!
+//! 用法: `detect_eval <数据集目录> [报告路径]`，报告默认写到 `eval_report.json`
+//!
+//! 按 `cfg/rbt_cfg.toml` 的 `detector_cfg` 构建推理会话和解码器，对 YOLO-pose 格式数据集中的每张图片
+//! 运行检测，统计每类精确率 / 召回率、mAP@0.5、关键点像素误差以及 PnP 距离误差，
+//! 更换模型或修改后处理参数前后各跑一次，对比两份报告即可。
+
+extern crate nalgebra as na;
+extern crate ndarray as nd;
+
+use image::GenericImageView;
+use ort::inputs;
+use ort::value::TensorRef;
+use std::path::PathBuf;
+use tracing::info;
+
+use lib::rbt_base::rbt_algorithm::rbt_ippe::ArmorPnpSolver;
+use lib::rbt_infra::rbt_err::{RbtError, RbtResult};
+use lib::rbt_infra::rbt_global::GENERIC_RBT_CFG;
+use lib::rbt_mod::rbt_detector::rbt_backend::build_session;
+use lib::rbt_mod::rbt_detector::rbt_yolo::{Nms, YoloDecoder, letterbox};
+
+use crate::metrics::{Evaluator, Prediction};
+
+mod dataset;
+mod metrics;
+
+/// 评估时的 NMS 置信度阈值，保留低置信度检测以得到完整的 PR 曲线
+const EVAL_SCORE_THRESHOLD: f32 = 0.01;
+
+fn main() -> RbtResult<()> {
+    tracing_subscriber::fmt().init();
+
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    let Some(dataset_dir) = args.first().map(PathBuf::from) else {
+        return Err(RbtError::StringError(
+            "Usage: detect_eval <dataset_dir> [report.json]".to_string(),
+        ));
+    };
+    let report_path = args
+        .get(1)
+        .map_or_else(|| PathBuf::from("eval_report.json"), PathBuf::from);
+
+    let (detector_cfg, cam_k, distortion) = {
+        let cfg = GENERIC_RBT_CFG.read().unwrap();
+        (
+            cfg.detector_cfg.clone(),
+            cfg.cam_cfg.cam_k(),
+            *cfg.cam_cfg.distortion(),
+        )
+    };
+    let mut session = build_session(&detector_cfg)?;
+    let mut decoder = YoloDecoder::from_session(&detector_cfg, &session)?;
+    decoder.set_nms(Nms::from_cfg(&detector_cfg).with_score_threshold(EVAL_SCORE_THRESHOLD));
+    let class_names = decoder
+        .labels()
+        .iter()
+        .map(|label| format!("{}_{}", label.color(), label.id()))
+        .collect::<Vec<_>>();
+
+    // PnP 只用于比较检测角点和标注角点解出的距离，两者使用同一个求解器
+    let pnp_solver = ArmorPnpSolver::new()
+        .ok_or(RbtError::StringError(
+            "Failed to create ArmorPnpSolver Instant".to_string(),
+        ))?
+        .with_distortion(distortion);
+    let distance = |corners: &[na::Point2<f64>; 4]| {
+        pnp_solver
+            .solve(corners, &cam_k)
+            .map(|pose| pose.translation.vector.norm())
+    };
+
+    let samples = dataset::samples(&dataset_dir)?;
+    info!(
+        "{} images found in {}",
+        samples.len(),
+        dataset_dir.display()
+    );
+    let mut evaluator = Evaluator::new(decoder.labels().len(), detector_cfg.confidence_threshold);
+    let (width, height) = detector_cfg.infer_img_size();
+    let mut input = nd::Array4::<f32>::zeros((1, 3, height as usize, width as usize));
+    for sample in &samples {
+        let img = image::open(&sample.image)?;
+        let ground_truths = sample.ground_truths(img.dimensions())?;
+
+        let lb = letterbox(&mut input, &img);
+        let outputs = session.run(inputs![TensorRef::from_array_view(&input)?])?;
+        let output = outputs["output0"]
+            .try_extract_array::<f32>()?
+            .t()
+            .into_owned();
+        let predictions = decoder
+            .detections(output.slice(nd::s![.., .., 0]), &lb)?
+            .iter()
+            .map(Prediction::from)
+            .collect::<Vec<_>>();
+        evaluator.add_image(&ground_truths, &predictions, distance);
+    }
+
+    let report = evaluator.report(&class_names);
+    for class in &report.classes {
+        info!(
+            "{:<14} gt {:>4}  P {:.3}  R {:.3}  AP50 {:.3}",
+            class.name, class.ground_truths, class.precision, class.recall, class.ap50
+        );
+    }
+    info!(
+        "mAP@0.5 {:.4}, keypoint error mean {:.2} px / p95 {:.2} px, PnP distance error mean {:.1} mm / p95 {:.1} mm",
+        report.map50,
+        report.keypoint_error_px.mean,
+        report.keypoint_error_px.p95,
+        report.pnp_distance_error_mm.mean,
+        report.pnp_distance_error_mm.p95
+    );
+
+    let json = serde_json::to_string_pretty(&report)
+        .map_err(|err| RbtError::StringError(format!("Failed to serialize report: {err}")))?;
+    std::fs::write(&report_path, json)?;
+    info!("Report written to {}", report_path.display());
+    Ok(())
+}
@@ -0,0 +1,328 @@
+//! 检测指标统计
+//!
+//! - 每类精确率 / 召回率：只统计置信度不低于 `detector_cfg/confidence_threshold` 的检测，即实际运行时的工作点
+//! - mAP@0.5：所有检测按置信度排序，全点插值计算每类 AP 后取平均，只统计有标注的类别
+//! - 关键点误差：工作点下匹配成功的检测，四个角点到标注的像素距离
+//! - PnP 距离误差：同一块装甲板分别用检测角点和标注角点解 PnP，两者距离之差
+
+use serde::Serialize;
+
+use crate::dataset::GroundTruth;
+use lib::rbt_mod::rbt_detector::rbt_yolo::{BBox, YoloDetection, iou};
+
+/// 检测框与标注匹配的 IoU 阈值
+pub const IOU_THRESHOLD: f32 = 0.5;
+
+/// 一个检测结果，坐标为原图像素
+#[derive(Debug, Clone)]
+pub struct Prediction {
+    pub class_id: usize,
+    pub score: f32,
+    pub bbox: BBox,
+    /// 左上、左下、右下、右上
+    pub corners: [na::Point2<f64>; 4],
+}
+
+impl From<&YoloDetection> for Prediction {
+    fn from(detection: &YoloDetection) -> Self {
+        let [_, lt, lb, rb, rt] = detection.keypoints;
+        Self {
+            class_id: detection.class_id,
+            score: detection.score,
+            bbox: detection.bbox,
+            corners: [lt, lb, rb, rt].map(|p| *p),
+        }
+    }
+}
+
+/// 误差分布
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+pub struct ErrorStats {
+    pub count: usize,
+    pub mean: f64,
+    pub median: f64,
+    pub p95: f64,
+    pub max: f64,
+}
+
+impl ErrorStats {
+    pub fn from_samples(samples: &[f64]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let percentile = |p: f64| sorted[((sorted.len() - 1) as f64 * p).round() as usize];
+        Self {
+            count: sorted.len(),
+            mean: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            median: percentile(0.5),
+            p95: percentile(0.95),
+            max: sorted[sorted.len() - 1],
+        }
+    }
+}
+
+/// 单个类别的统计结果
+#[derive(Serialize, Debug, Clone)]
+pub struct ClassReport {
+    pub class_id: usize,
+    pub name: String,
+    pub ground_truths: usize,
+    pub true_positives: usize,
+    pub false_positives: usize,
+    pub precision: f64,
+    pub recall: f64,
+    pub ap50: f64,
+}
+
+/// 评估报告，以 JSON 格式写出用于不同模型或参数之间的对比
+#[derive(Serialize, Debug, Clone)]
+pub struct EvalReport {
+    pub images: usize,
+    pub iou_threshold: f32,
+    pub confidence_threshold: f32,
+    pub map50: f64,
+    pub classes: Vec<ClassReport>,
+    pub keypoint_error_px: ErrorStats,
+    pub pnp_distance_error_mm: ErrorStats,
+}
+
+/// 逐张图片累计匹配结果
+pub struct Evaluator {
+    confidence_threshold: f32,
+    images: usize,
+    ground_truths: Vec<usize>,
+    records: Vec<Vec<(f32, bool)>>, // 每类所有检测的 (置信度, 是否匹配成功)
+    keypoint_errors: Vec<f64>,
+    distance_errors: Vec<f64>,
+}
+
+impl Evaluator {
+    pub fn new(class_count: usize, confidence_threshold: f32) -> Self {
+        Self {
+            confidence_threshold,
+            images: 0,
+            ground_truths: vec![0; class_count],
+            records: vec![Vec::new(); class_count],
+            keypoint_errors: Vec::new(),
+            distance_errors: Vec::new(),
+        }
+    }
+
+    /// 累计一张图片的结果
+    ///
+    /// 检测按置信度从高到低依次与同类别、IoU 最大且未被匹配的标注配对；
+    /// `distance` 根据四个角点解 PnP 返回目标距离，解算失败时返回 None
+    pub fn add_image(
+        &mut self,
+        ground_truths: &[GroundTruth],
+        predictions: &[Prediction],
+        distance: impl Fn(&[na::Point2<f64>; 4]) -> Option<f64>,
+    ) {
+        self.images += 1;
+        for gt in ground_truths {
+            if let Some(count) = self.ground_truths.get_mut(gt.class_id) {
+                *count += 1;
+            }
+        }
+
+        let mut order = (0..predictions.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| predictions[b].score.total_cmp(&predictions[a].score));
+        let mut matched = vec![false; ground_truths.len()];
+        for pred in order.into_iter().map(|i| &predictions[i]) {
+            let Some(records) = self.records.get_mut(pred.class_id) else {
+                continue;
+            };
+            let best = ground_truths
+                .iter()
+                .enumerate()
+                .filter(|(i, gt)| !matched[*i] && gt.class_id == pred.class_id)
+                .map(|(i, gt)| (i, iou(&pred.bbox, &gt.bbox)))
+                .filter(|(_, overlap)| *overlap >= IOU_THRESHOLD)
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+            records.push((pred.score, best.is_some()));
+
+            let Some((gt_idx, _)) = best else {
+                continue;
+            };
+            matched[gt_idx] = true;
+            if pred.score < self.confidence_threshold {
+                continue;
+            }
+            let gt = &ground_truths[gt_idx];
+            self.keypoint_errors.extend(
+                pred.corners
+                    .iter()
+                    .zip(&gt.corners)
+                    .map(|(p, g)| na::distance(p, g)),
+            );
+            if let (Some(pred_dist), Some(gt_dist)) =
+                (distance(&pred.corners), distance(&gt.corners))
+            {
+                self.distance_errors.push((pred_dist - gt_dist).abs());
+            }
+        }
+    }
+
+    /// 生成报告，`class_names` 与类别序号一一对应
+    pub fn report(&self, class_names: &[String]) -> EvalReport {
+        let classes = (0..self.ground_truths.len())
+            .filter(|&c| self.ground_truths[c] > 0 || !self.records[c].is_empty())
+            .map(|c| {
+                let gt = self.ground_truths[c];
+                let hits = self.records[c]
+                    .iter()
+                    .filter(|(score, _)| *score >= self.confidence_threshold)
+                    .map(|(_, hit)| *hit)
+                    .collect::<Vec<_>>();
+                let tp = hits.iter().filter(|hit| **hit).count();
+                let fp = hits.len() - tp;
+                ClassReport {
+                    class_id: c,
+                    name: class_names.get(c).cloned().unwrap_or_else(|| c.to_string()),
+                    ground_truths: gt,
+                    true_positives: tp,
+                    false_positives: fp,
+                    precision: ratio(tp, tp + fp),
+                    recall: ratio(tp, gt),
+                    ap50: average_precision(&self.records[c], gt),
+                }
+            })
+            .collect::<Vec<_>>();
+        let with_gt = classes.iter().filter(|c| c.ground_truths > 0);
+        let map50 = with_gt.clone().map(|c| c.ap50).sum::<f64>() / with_gt.count().max(1) as f64;
+        EvalReport {
+            images: self.images,
+            iou_threshold: IOU_THRESHOLD,
+            confidence_threshold: self.confidence_threshold,
+            map50,
+            classes,
+            keypoint_error_px: ErrorStats::from_samples(&self.keypoint_errors),
+            pnp_distance_error_mm: ErrorStats::from_samples(&self.distance_errors),
+        }
+    }
+}
+
+fn ratio(num: usize, den: usize) -> f64 {
+    if den == 0 {
+        0.0
+    } else {
+        num as f64 / den as f64
+    }
+}
+
+/// 全点插值 AP：精确率取右侧最大值形成单调包络后，对召回率积分
+fn average_precision(records: &[(f32, bool)], ground_truths: usize) -> f64 {
+    if ground_truths == 0 {
+        return 0.0;
+    }
+    let mut sorted = records.to_vec();
+    sorted.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let (mut tp, mut fp) = (0, 0);
+    let curve = sorted
+        .iter()
+        .map(|(_, hit)| {
+            if *hit {
+                tp += 1;
+            } else {
+                fp += 1;
+            }
+            (ratio(tp, ground_truths), ratio(tp, tp + fp))
+        })
+        .collect::<Vec<_>>();
+
+    let mut ap = 0.0;
+    let mut envelope = 0.0f64;
+    let mut next_recall = curve.last().map_or(0.0, |p| p.0);
+    for (recall, precision) in curve.iter().rev() {
+        ap += (next_recall - recall) * envelope;
+        envelope = envelope.max(*precision);
+        next_recall = *recall;
+    }
+    ap + next_recall * envelope
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn armor(class_id: usize, x: f32, score: f32) -> Prediction {
+        let corners = [(x, 0.0), (x, 20.0), (x + 50.0, 20.0), (x + 50.0, 0.0)]
+            .map(|(x, y): (f32, f32)| na::Point2::new(x as f64, y as f64));
+        Prediction {
+            class_id,
+            score,
+            bbox: BBox::new(x, 0.0, x + 50.0, 20.0),
+            corners,
+        }
+    }
+
+    fn gt(class_id: usize, x: f32) -> GroundTruth {
+        let pred = armor(class_id, x, 1.0);
+        GroundTruth {
+            class_id,
+            bbox: pred.bbox,
+            corners: pred.corners,
+        }
+    }
+
+    #[test]
+    fn test_average_precision() {
+        // 3 个标注：TP, FP, TP，第三个标注漏检
+        let records = [(0.9, true), (0.8, false), (0.7, true)];
+        // 召回率 1/3 处精确率 1，2/3 处精确率 2/3
+        let expected = 1.0 / 3.0 + (1.0 / 3.0) * (2.0 / 3.0);
+        assert!((average_precision(&records, 3) - expected).abs() < 1e-9);
+        assert_eq!(average_precision(&[], 3), 0.0);
+        assert_eq!(average_precision(&[(0.9, true)], 1), 1.0);
+    }
+
+    #[test]
+    fn test_evaluator() {
+        let mut evaluator = Evaluator::new(3, 0.5);
+        // 图 1：类别 0 命中并偏移 2 像素，类别 1 重复检测一次，类别 2 误检
+        evaluator.add_image(
+            &[gt(0, 0.0), gt(1, 100.0)],
+            &[
+                armor(0, 2.0, 0.9),
+                armor(1, 100.0, 0.8),
+                armor(1, 101.0, 0.6),
+                armor(2, 300.0, 0.7),
+            ],
+            |corners| Some(corners[0].x),
+        );
+        // 图 2：类别 0 漏检，低置信度的命中不计入工作点
+        evaluator.add_image(&[gt(0, 0.0)], &[armor(0, 0.0, 0.3)], |_| None);
+
+        let report = evaluator.report(&["B_Hero1".into(), "B_Engineer2".into()]);
+        assert_eq!(report.images, 2);
+        assert_eq!(report.classes.len(), 3);
+        let c0 = &report.classes[0];
+        assert_eq!((c0.name.as_str(), c0.ground_truths), ("B_Hero1", 2));
+        assert_eq!((c0.true_positives, c0.false_positives), (1, 0));
+        assert_eq!((c0.precision, c0.recall), (1.0, 0.5));
+        assert!((c0.ap50 - 1.0).abs() < 1e-9);
+        let c1 = &report.classes[1];
+        assert_eq!((c1.true_positives, c1.false_positives), (1, 1));
+        assert_eq!(c1.ap50, 1.0);
+        let c2 = &report.classes[2];
+        assert_eq!(
+            (c2.name.as_str(), c2.ground_truths, c2.false_positives),
+            ("2", 0, 1)
+        );
+        // mAP 只统计有标注的类别
+        assert!((report.map50 - 1.0).abs() < 1e-9);
+
+        // 工作点下命中的两块装甲板：一块每个角点偏 2 像素，一块无误差
+        assert_eq!(report.keypoint_error_px.count, 8);
+        assert!((report.keypoint_error_px.mean - 1.0).abs() < 1e-9);
+        assert_eq!(report.keypoint_error_px.max, 2.0);
+        assert_eq!(report.pnp_distance_error_mm.count, 2);
+        assert_eq!(report.pnp_distance_error_mm.max, 2.0);
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["classes"][0]["name"], "B_Hero1");
+        assert!(json["keypoint_error_px"]["p95"].is_number());
+    }
+}
@@ -1,18 +1,319 @@
+//! IPPE 装甲板 PnP 基准
+//!
+//! 装甲板法线上仰 15°，在不同距离和朝向下投影角点并加入固定种子的像素噪声，在这些合成数据上：
+//! 1. 对比 IPPE 所用单应性矩阵的两种求法：装甲板所用的伪逆近似 H = P·W⁺ 与扇叶所用的归一化 DLT，
+//!    统计四个角点经 H 映射后与无噪声投影的距离和耗时
+//! 2. 对比 IPPE、IPPE + SE(3) LM 与 IPPE + 固定仰角 LM 的精度和耗时，
+//!    统计平移、朝向、偏航误差和重投影误差
+//!
+//! 随后用两组实拍角点在 rerun 中可视化解算结果
+
 use nalgebra as na;
 use rerun as rr;
 
 use lib::rbt_base::rbt_algorithm::rbt_ippe::{
-    ARMOR_LIGHT_HEIGHT, ARMOR_LIGHT_WEIGHT, ArmorPnpSolver,
+    ARMOR_LIGHT_HEIGHT, ARMOR_LIGHT_WEIGHT, ArmorPnpSolver, find_homography,
 };
+use lib::rbt_base::rbt_algorithm::rbt_pose_refine::PoseRefiner;
+use lib::rbt_infra::rbt_cfg::PoseRefineCfg;
 use std::error::Error;
+use std::time::{Duration, Instant};
 use tracing::{debug, error, info};
 
+/// 每个场景重复加噪的次数
+const ROUNDS: usize = 50;
+/// 角点噪声幅值，像素
+const NOISE_PX: f64 = 0.8;
+const DISTANCES_MM: [f64; 4] = [1500.0, 3000.0, 5000.0, 7000.0];
+const YAWS_DEG: [f64; 5] = [-40.0, -20.0, 0.0, 20.0, 40.0];
+const ARMOR_PITCH_DEG: f64 = 15.0;
+
+/// 线性同余随机数，保证每次生成的数据相同
+struct Lcg(u64);
+
+impl Lcg {
+    /// [-1, 1) 均匀分布
+    fn next_f64(&mut self) -> f64 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+    }
+}
+
+/// 误差与耗时的累计量
+#[derive(Default)]
+struct Stats {
+    solved: usize,
+    translation_mm: f64,
+    rotation_deg: f64,
+    yaw_deg: f64,
+    reproj_px: f64,
+    elapsed: Duration,
+    iterations: usize,
+    converged: usize,
+}
+
+impl Stats {
+    fn add(
+        &mut self,
+        pose: &na::Isometry3<f64>,
+        truth: &na::Isometry3<f64>,
+        solver: &ArmorPnpSolver,
+        uvs: &[na::Point2<f64>; 4],
+        k: &na::Matrix3<f64>,
+    ) {
+        self.solved += 1;
+        self.translation_mm += (pose.translation.vector - truth.translation.vector).norm();
+        self.rotation_deg += pose.rotation.angle_to(&truth.rotation).to_degrees();
+        self.yaw_deg += (armor_yaw_deg(pose) - armor_yaw_deg(truth)).abs();
+        let sum_sq = solver
+            .object_points()
+            .iter()
+            .zip(uvs)
+            .map(|(p, uv)| (project(pose, p, k) - uv).norm_squared())
+            .sum::<f64>();
+        self.reproj_px += (sum_sq / 4.0).sqrt();
+    }
+
+    fn log(&self, name: &str, refined: bool) {
+        let n = self.solved.max(1) as f64;
+        info!(
+            "{:<10} solved {:>4}, trans {:>7.2} mm, rot {:>6.3} deg, yaw {:>6.3} deg, reproj {:>6.3} px, {:>6.2} us/solve",
+            name,
+            self.solved,
+            self.translation_mm / n,
+            self.rotation_deg / n,
+            self.yaw_deg / n,
+            self.reproj_px / n,
+            self.elapsed.as_secs_f64() * 1e6 / n
+        );
+        if refined {
+            info!(
+                "{:<10} {:.2} iterations/solve, {} / {} converged",
+                "",
+                self.iterations as f64 / n,
+                self.converged,
+                self.solved
+            );
+        }
+    }
+}
+
+/// 装甲板法线在相机 xz 平面内的偏航角
+fn armor_yaw_deg(pose: &na::Isometry3<f64>) -> f64 {
+    let normal = pose.rotation * na::Vector3::z();
+    normal.x.atan2(-normal.z).to_degrees()
+}
+
+fn project(
+    pose: &na::Isometry3<f64>,
+    p: &na::Point3<f64>,
+    k: &na::Matrix3<f64>,
+) -> na::Point2<f64> {
+    let h = k * (pose * p).coords;
+    na::Point2::new(h.x / h.z, h.y / h.z)
+}
+
+/// 正对相机时装甲板 x 轴向右、y 轴向上、法线指向相机，再让法线上仰并绕竖直方向偏航
+fn armor_pose(distance: f64, yaw_deg: f64) -> na::Isometry3<f64> {
+    let facing = na::UnitQuaternion::from_rotation_matrix(&na::Rotation3::from_matrix_unchecked(
+        na::Matrix3::from_diagonal(&na::Vector3::new(1.0, -1.0, -1.0)),
+    ));
+    let yaw = na::UnitQuaternion::from_axis_angle(&na::Vector3::y_axis(), yaw_deg.to_radians());
+    let pitch =
+        na::UnitQuaternion::from_axis_angle(&na::Vector3::x_axis(), -ARMOR_PITCH_DEG.to_radians());
+    na::Isometry3::from_parts(
+        na::Translation3::new(0.1 * distance, -0.05 * distance, distance),
+        yaw * facing * pitch,
+    )
+}
+
+/// 各向同性归一化：平移到质心并缩放到平均距离 √2，返回归一化点和归一化变换
+fn isotropic_normalize(points: &[na::Point2<f64>; 4]) -> ([na::Point2<f64>; 4], na::Matrix3<f64>) {
+    let center = points.iter().map(|p| p.coords).sum::<na::Vector2<f64>>() / 4.0;
+    let mean_dist = points
+        .iter()
+        .map(|p| (p.coords - center).norm())
+        .sum::<f64>()
+        / 4.0;
+    let scale = std::f64::consts::SQRT_2 / mean_dist;
+    let t = na::Matrix3::new(
+        scale,
+        0.0,
+        -scale * center.x,
+        0.0,
+        scale,
+        -scale * center.y,
+        0.0,
+        0.0,
+        1.0,
+    );
+    (
+        points.map(|p| na::Point2::from((p.coords - center) * scale)),
+        t,
+    )
+}
+
+/// 装甲板 ArmorPnpSolver 的单应性求法：归一化后把齐次坐标当作线性关系，H = P·W⁺
+///
+/// 忽略了每个点各自的射影尺度，只在仿射近似成立（远距离、正对）时准确
+fn homography_pinv(
+    src: &[na::Point2<f64>; 4],
+    dst: &[na::Point2<f64>; 4],
+) -> Option<na::Matrix3<f64>> {
+    let (src_norm, src_t) = isotropic_normalize(src);
+    let (dst_norm, dst_t) = isotropic_normalize(dst);
+    let homogeneous = |points: [na::Point2<f64>; 4]| {
+        na::SMatrix::<f64, 3, 4>::from_columns(&points.map(|p| p.to_homogeneous()))
+    };
+    let src_pinv = homogeneous(src_norm)
+        .svd(true, true)
+        .pseudo_inverse(1e-8)
+        .ok()?;
+    let h = dst_t.try_inverse()? * homogeneous(dst_norm) * src_pinv * src_t;
+    Some(h / h[(2, 2)])
+}
+
+/// 由目标点和图像点求单应性矩阵
+type HomographyFn = fn(&[na::Point2<f64>; 4], &[na::Point2<f64>; 4]) -> Option<na::Matrix3<f64>>;
+
+/// 对比两种单应性求法：角点经 H 映射后与无噪声投影的平均距离，以及耗时
+///
+/// 无噪声时的距离为方法本身的偏差，加噪声后为偏差与噪声的综合结果
+fn compare_homography(solver: &ArmorPnpSolver, k: &na::Matrix3<f64>) {
+    let methods: [(&str, HomographyFn); 2] = [
+        ("pinv", homography_pinv),
+        ("dlt", |src, dst| find_homography(src, dst)),
+    ];
+    let object_points = solver.object_points().map(|p| p.xy());
+    let transfer_err = |h: &na::Matrix3<f64>, ideal: &[na::Point2<f64>; 4]| {
+        object_points
+            .iter()
+            .zip(ideal)
+            .map(|(p, uv)| {
+                let mapped = h * p.to_homogeneous();
+                (mapped.xy() / mapped.z - uv.coords).norm()
+            })
+            .sum::<f64>()
+            / 4.0
+    };
+    let mut rng = Lcg(2024);
+    for distance in DISTANCES_MM {
+        let mut bias = [[f64::NAN; YAWS_DEG.len()]; 2];
+        let mut errors = [[0.0; YAWS_DEG.len()]; 2];
+        let mut elapsed = [Duration::ZERO; 2];
+        for (yaw_idx, yaw_deg) in YAWS_DEG.into_iter().enumerate() {
+            let truth = armor_pose(distance, yaw_deg);
+            let ideal = solver.object_points().map(|p| project(&truth, &p, k));
+            for (idx, (_, method)) in methods.iter().enumerate() {
+                if let Some(h) = method(&object_points, &ideal) {
+                    bias[idx][yaw_idx] = transfer_err(&h, &ideal);
+                }
+            }
+            for _ in 0..ROUNDS {
+                let uvs = ideal
+                    .map(|uv| uv + na::Vector2::new(rng.next_f64(), rng.next_f64()) * NOISE_PX);
+                for (idx, (_, method)) in methods.iter().enumerate() {
+                    let timer = Instant::now();
+                    let h = std::hint::black_box(method(&object_points, &uvs));
+                    elapsed[idx] += timer.elapsed();
+                    if let Some(h) = h {
+                        errors[idx][yaw_idx] += transfer_err(&h, &ideal) / ROUNDS as f64;
+                    }
+                }
+            }
+        }
+        info!(
+            "homography, distance {} mm, transfer error (px) at yaw {:?} deg",
+            distance, YAWS_DEG
+        );
+        for (idx, (name, _)) in methods.iter().enumerate() {
+            info!(
+                "{:<10} bias {:>6.3?}, noise ±{} px {:>6.3?}, {:>5.2} us/solve",
+                name,
+                bias[idx],
+                NOISE_PX,
+                errors[idx],
+                elapsed[idx].as_secs_f64() * 1e6 / (YAWS_DEG.len() * ROUNDS) as f64
+            );
+        }
+    }
+}
+
+/// 对比 IPPE、IPPE + SE(3) LM、IPPE + 固定仰角 LM 的精度和耗时
+fn compare_refine(solver: &ArmorPnpSolver, k: &na::Matrix3<f64>) {
+    let cfg = PoseRefineCfg {
+        enable: true,
+        max_iters: 20,
+        step_tolerance: 1e-8,
+        cost_tolerance: 1e-10,
+        fixed_pitch: true,
+        armor_pitch_deg: ARMOR_PITCH_DEG,
+    };
+    // 相机水平放置，右-下-前坐标系中竖直向上为 -y
+    let methods = [
+        ("ippe", None),
+        ("lm se3", Some(PoseRefiner::new(cfg.clone()))),
+        (
+            "lm yaw",
+            Some(PoseRefiner::new(cfg).with_gravity_up(-na::Vector3::y_axis())),
+        ),
+    ];
+    let mut rng = Lcg(2025);
+    for distance in DISTANCES_MM {
+        let mut stats = methods.each_ref().map(|_| Stats::default());
+        for yaw_deg in YAWS_DEG {
+            let truth = armor_pose(distance, yaw_deg);
+            for _ in 0..ROUNDS {
+                let uvs = solver.object_points().map(|p| {
+                    project(&truth, &p, k)
+                        + na::Vector2::new(rng.next_f64(), rng.next_f64()) * NOISE_PX
+                });
+                for ((_, refiner), stats) in methods.iter().zip(stats.iter_mut()) {
+                    let timer = Instant::now();
+                    let result = std::hint::black_box(match refiner {
+                        Some(refiner) => solver
+                            .solve_refined(&uvs, k, refiner)
+                            .map(|(pose, report)| (pose, Some(report))),
+                        None => solver.solve(&uvs, k).map(|pose| (pose, None)),
+                    });
+                    stats.elapsed += timer.elapsed();
+                    if let Some((pose, report)) = result {
+                        stats.add(&pose, &truth, solver, &uvs, k);
+                        if let Some(report) = report {
+                            stats.iterations += report.iterations;
+                            stats.converged += report.converged() as usize;
+                        }
+                    }
+                }
+            }
+        }
+        info!(
+            "distance {} mm, {} samples, noise ±{} px",
+            distance,
+            YAWS_DEG.len() * ROUNDS,
+            NOISE_PX
+        );
+        for ((name, refiner), stats) in methods.iter().zip(&stats) {
+            stats.log(name, refiner.is_some());
+        }
+    }
+}
+
 fn main() -> Result<(), Box<dyn Error>> {
+    tracing_subscriber::fmt().init();
+    let pnp_solver = ArmorPnpSolver::new().ok_or("Failed to create ArmorPnpSolver Instant")?;
+    // 合成数据使用 1280x1024 相机的内参
+    let synthetic_k = na::Matrix3::new(1600.0, 0.0, 640.0, 0.0, 1600.0, 512.0, 0.0, 0.0, 1.0);
+    compare_homography(&pnp_solver, &synthetic_k);
+    compare_refine(&pnp_solver, &synthetic_k);
+
     let rec = rr::RecordingStreamBuilder::new("pnp_visualizer")
         // .save("test.rrd")?;
         .spawn()?;
     let timer = std::time::Instant::now();
-    let pnp_solver = ArmorPnpSolver::new().ok_or("Failed to create ArmorPnpSolver Instant")?;
 
     // 相机内参 (示例，单位要和装甲板尺寸一致，这里是毫米)
     let k_matrix = na::Matrix3::new(1600.0, 0.0, 320.0, 0.0, 1705.7, 192.0, 0.0, 0.0, 1.0);
@@ -0,0 +1,116 @@
+//! 非极大值抑制性能对比
+//!
+//! 在稠密的合成候选框上对比旧版全局 NMS 与按类别的 hard / soft NMS：
+//! 5040 个 anchor 全部参与，约一半置信度高于阈值，分布在若干个相互重叠的红蓝装甲板周围
+
+use std::time::{Duration, Instant};
+use tracing::info;
+
+use lib::rbt_mod::rbt_detector::rbt_yolo::{
+    BBox, Nms, YoloCandidate, YoloLayout, intersection, union,
+};
+
+const ROUNDS: u32 = 200;
+const TARGETS: usize = 24;
+const SCORE_THRESHOLD: f32 = 0.5;
+const IOU_THRESHOLD: f32 = 0.7;
+
+/// 线性同余随机数，保证每次生成的数据相同
+struct Lcg(u64);
+
+impl Lcg {
+    fn next_f32(&mut self) -> f32 {
+        self.0 = self
+            .0
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (self.0 >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// 每个 anchor 随机落在某个目标附近，目标两两成对重叠且颜色相反
+fn dense_candidates() -> Vec<YoloCandidate> {
+    let mut rng = Lcg(2025);
+    let targets = (0..TARGETS)
+        .map(|i| {
+            let (x, y) = (
+                40.0 + (i / 2 % 6) as f32 * 100.0,
+                60.0 + (i / 12) as f32 * 150.0,
+            );
+            // 成对的目标偏移 20 像素，模拟混战中重叠的红蓝装甲板
+            let offset = (i % 2) as f32 * 20.0;
+            let class_id = 1 + i / 2 % 5 + (i % 2) * 18;
+            (x + offset, y, class_id)
+        })
+        .collect::<Vec<_>>();
+    (0..YoloLayout::ARMOR.anchor_count)
+        .map(|anchor| {
+            let (x, y, class_id) = targets[anchor % TARGETS];
+            let (dx, dy) = (rng.next_f32() * 8.0 - 4.0, rng.next_f32() * 8.0 - 4.0);
+            let (w, h) = (60.0 + rng.next_f32() * 6.0, 30.0 + rng.next_f32() * 4.0);
+            let (xc, yc) = (x + dx, y + dy);
+            (
+                BBox::new(xc - w / 2.0, yc - h / 2.0, xc + w / 2.0, yc + h / 2.0),
+                class_id,
+                rng.next_f32(),
+                anchor,
+            )
+        })
+        .collect()
+}
+
+/// 旧版实现：先按阈值筛选，再全局抑制，每轮重新分配
+fn legacy_nms(mut boxes: Vec<YoloCandidate>) -> Vec<YoloCandidate> {
+    boxes.retain(|b| b.2 >= SCORE_THRESHOLD);
+    boxes.sort_by(|box1, box2| box2.2.total_cmp(&box1.2));
+    let mut result = Vec::new();
+    while !boxes.is_empty() {
+        result.push(boxes[0]);
+        boxes = boxes
+            .iter()
+            .filter(|box1| {
+                (intersection(&boxes[0].0, &box1.0) / union(&boxes[0].0, &box1.0)) < IOU_THRESHOLD
+            })
+            .copied()
+            .collect();
+    }
+    result
+}
+
+fn bench(
+    name: &str,
+    candidates: &[YoloCandidate],
+    f: impl Fn(Vec<YoloCandidate>) -> Vec<YoloCandidate>,
+) {
+    let kept = f(candidates.to_vec()).len();
+    let mut total = Duration::ZERO;
+    for _ in 0..ROUNDS {
+        let boxes = candidates.to_vec();
+        let timer = Instant::now();
+        std::hint::black_box(f(boxes));
+        total += timer.elapsed();
+    }
+    info!(
+        "{:<12} kept {:>3} boxes, {:>8.1} us/round",
+        name,
+        kept,
+        total.as_secs_f64() * 1e6 / ROUNDS as f64
+    );
+}
+
+fn main() {
+    tracing_subscriber::fmt().init();
+    let candidates = dense_candidates();
+    info!(
+        "{} candidates, {} above score threshold {}",
+        candidates.len(),
+        candidates.iter().filter(|b| b.2 >= SCORE_THRESHOLD).count(),
+        SCORE_THRESHOLD
+    );
+
+    let hard = Nms::new(SCORE_THRESHOLD, IOU_THRESHOLD);
+    let soft = hard.with_soft(0.5);
+    bench("legacy", &candidates, legacy_nms);
+    bench("class-aware", &candidates, |boxes| hard.run(boxes));
+    bench("soft-nms", &candidates, |boxes| soft.run(boxes));
+}
@@ -3,6 +3,7 @@ extern crate rerun as rr;
 
 use tracing_appender::non_blocking::WorkerGuard;
 
+use lib::rbt_base::rbt_geometry::rbt_tf::RbtTf;
 use lib::rbt_infra::rbt_cfg::RbtCfg;
 use lib::rbt_infra::rbt_err::RbtResult;
 use lib::rbt_infra::rbt_log::logger_init;
@@ -10,6 +11,8 @@ use lib::rbt_mod::rbt_detector::pipeline;
 use lib::rbt_mod::rbt_estimator::RbtEstimator;
 use lib::rbt_mod::rbt_estimator::rbt_enemy_dynamic_model::EnemyId;
 use lib::rbt_mod::rbt_solver::enemys_solver;
+use lib::rbt_mod::rbt_source::source_from_cfg;
+use std::collections::HashMap;
 
 struct AutoAimHandle {
     pub cfg: RbtCfg,
@@ -39,16 +42,32 @@ async fn auto_aim_init() -> RbtResult<AutoAimHandle> {
 async fn main() -> RbtResult<()> {
     // 0. 初始化
     let mut auto_aim_handle = auto_aim_init().await?;
+    // 图像源由 source_cfg 配置
+    let mut source = source_from_cfg(&auto_aim_handle.cfg.source_cfg)?;
+
+    while let Some(frame) = source.next_frame()? {
+        auto_aim_handle.rec.log(
+            "world/image",
+            &rr::Image::from_image(frame.image().clone()).expect("failed to show img in rerun"),
+        )?;
 
-    loop {
         // 1. 执行 detector，使用神经网络模型，寻找所有的装甲板
-        let detector_result = pipeline(&auto_aim_handle.cfg.detector_cfg)?;
+        let detector_result = pipeline(&auto_aim_handle.cfg.detector_cfg, frame.into_image())?;
 
         // 2. 执行 solver
-        // 获取相机内参
-        let cam_k = auto_aim_handle.cfg.cam_cfg.cam_k();
+        // 单帧调试没有下位机，云台姿态按零处理
+        let tf = RbtTf::new(&auto_aim_handle.cfg.tf_cfg);
         // 解算检测到的所有装甲板，得到所有地方单位的解算结果
-        let enemys = enemys_solver(detector_result, &cam_k, &auto_aim_handle.rec)?;
+        // 单帧调试没有历史估计，不提供先验，半径使用配置的默认值
+        let enemys = enemys_solver(
+            detector_result,
+            &auto_aim_handle.cfg.cam_cfg,
+            &tf,
+            &auto_aim_handle.cfg.solver_cfg,
+            &auto_aim_handle.cfg.pose_refine_cfg,
+            &HashMap::new(),
+            &auto_aim_handle.rec,
+        )?;
 
         // 3. 执行 estimator
         // 创建对 3 号步兵的估计器
@@ -61,4 +80,6 @@ async fn main() -> RbtResult<()> {
             target_enemy_solved_result,
         );
     }
+
+    Ok(())
 }
@@ -7,6 +7,9 @@ pub mod rbt_infra;
 /// 上层模块
 pub mod rbt_mod;
 
+/// 派生宏生成的代码通过 `::lib` 路径引用本 crate
+extern crate self as lib;
+
 /// 声明常用外部 crate 别名
 extern crate nalgebra as na;
 extern crate ndarray as nd;
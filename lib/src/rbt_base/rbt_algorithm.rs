@@ -2,4 +2,5 @@ pub mod rbt_eskf;
 // 几何模块
 pub mod rbt_antigravity;
 pub mod rbt_ippe;
+pub mod rbt_pose_refine;
 pub mod rbt_sort;
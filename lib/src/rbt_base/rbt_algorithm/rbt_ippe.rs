@@ -2,19 +2,16 @@
 /// 使用 IPPE 方法，四个特征点
 use tracing::error;
 
+use crate::rbt_base::rbt_algorithm::rbt_pose_refine::{PoseRefiner, RefineReport};
+use crate::rbt_base::rbt_geometry::rbt_distortion::{self, RbtDistortion};
+
 // 硬编码的世界坐标，满足 IPPE 的规范坐标系要求 (Z=0, 中心在原点)
 pub const ARMOR_LIGHT_WEIGHT: f64 = 135.0;
 pub const ARMOR_LIGHT_HEIGHT: f64 = 55.0;
+/// 大装甲板两灯条间距，灯条长度与小装甲板相同
+pub const LARGE_ARMOR_LIGHT_WEIGHT: f64 = 230.0;
 
-// 世界坐标系点，原点在装甲板中心，Z=0平面，省去归一化
-const ARMOR_WORLD_POINTS: [na::Point3<f64>; 4] = [
-    na::Point3::new(-ARMOR_LIGHT_WEIGHT / 2.0, ARMOR_LIGHT_HEIGHT / 2.0, 0.0), // 左上
-    na::Point3::new(-ARMOR_LIGHT_WEIGHT / 2.0, -ARMOR_LIGHT_HEIGHT / 2.0, 0.0), // 左下
-    na::Point3::new(ARMOR_LIGHT_WEIGHT / 2.0, -ARMOR_LIGHT_HEIGHT / 2.0, 0.0), // 右下
-    na::Point3::new(ARMOR_LIGHT_WEIGHT / 2.0, ARMOR_LIGHT_HEIGHT / 2.0, 0.0),  // 右上
-];
-
-// 世界坐标点，但只有X, Y分量，用于平移计算
+// 世界坐标系点，原点在装甲板中心，Z=0平面，只保留X, Y分量
 const ARMOR_WORLD_POINTS_2D: [na::Point2<f64>; 4] = [
     na::Point2::new(-ARMOR_LIGHT_WEIGHT / 2.0, ARMOR_LIGHT_HEIGHT / 2.0), // 左上
     na::Point2::new(-ARMOR_LIGHT_WEIGHT / 2.0, -ARMOR_LIGHT_HEIGHT / 2.0), // 左下
@@ -22,76 +19,183 @@ const ARMOR_WORLD_POINTS_2D: [na::Point2<f64>; 4] = [
     na::Point2::new(ARMOR_LIGHT_WEIGHT / 2.0, ARMOR_LIGHT_HEIGHT / 2.0),  // 右上
 ];
 
+/// IPPE 的一个候选解
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PnpCandidate {
+    /// 目标坐标系到相机坐标系的变换
+    pub pose: na::Isometry3<f64>,
+    /// 重投影均方根误差 px
+    pub reproj_err: f64,
+}
+
+/// IPPE 的候选解，按重投影误差排序
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PnpSolutions {
+    pub best: PnpCandidate,
+    /// 另一个位于相机前方的解，不存在时为 None
+    pub alternative: Option<PnpCandidate>,
+}
+
+impl PnpSolutions {
+    /// 位姿歧义度：最优解与另一个解的重投影误差之比，取值 [0, 1]
+    ///
+    /// 接近 1 时两个解同样符合观测，正对相机时常见，仅凭重投影误差无法区分；没有另一个解时为 0
+    pub fn ambiguity(&self) -> f64 {
+        match self.alternative {
+            Some(alternative) if alternative.reproj_err > 0.0 => {
+                (self.best.reproj_err / alternative.reproj_err).min(1.0)
+            }
+            Some(_) => 1.0,
+            None => 0.0,
+        }
+    }
+}
+
+/// IPPE 所需单应性矩阵的求法
+#[derive(Debug, Clone)]
+enum HomographyMethod {
+    /// 伪逆近似 H = P·W⁺，W⁺ 预先计算
+    ///
+    /// 忽略各点的射影尺度，近距离、大斜视时略有偏差，但 3m 外噪声更小、耗时约为 DLT 的 1/6，装甲板使用
+    Pinv(na::SMatrix<f64, 4, 3>),
+    /// 归一化 DLT，无偏，用于能量机关扇叶等任意四点目标
+    Dlt,
+}
+
 /// 专为已知尺寸的平面4点目标设计的 pnp 求解器
 /// 基于 IPPE PnP 求解器
 #[derive(Debug, Clone)]
 pub struct ArmorPnpSolver {
-    pws_iso_mat_pinv: na::SMatrix<f64, 4, 3>,
-    pws_iso_t_inv: na::Matrix3<f64>,
+    pws_iso_norm: [na::Point2<f64>; 4],  // 各向同性归一化后的目标点
+    pws_iso_t: na::Matrix3<f64>,         // 目标点到归一化目标点的变换
+    object_points: [na::Point2<f64>; 4], // 以质心为原点的目标点
+    object_center: na::Vector2<f64>,     // 目标坐标系下的质心
+    distortion: RbtDistortion,           // 输入像素点的镜头畸变
+    homography: HomographyMethod,
 }
 
 impl ArmorPnpSolver {
     /// 构建一个新的装甲板求解器
     /// 提前预计算 point_world_matrix 伪逆
     pub fn new() -> Option<Self> {
-        if let Some((iso_norm_armor_world_points, iso_norm_pws_t_inv)) =
-            isotropic_normalize(&ARMOR_WORLD_POINTS_2D)
-        {
-            let pws_matrix = na::SMatrix::<f64, 3, 4>::from_columns(&[
-                iso_norm_armor_world_points[0].to_homogeneous().xyz(),
-                iso_norm_armor_world_points[1].to_homogeneous().xyz(),
-                iso_norm_armor_world_points[2].to_homogeneous().xyz(),
-                iso_norm_armor_world_points[3].to_homogeneous().xyz(),
-            ]);
-
-            let svd = pws_matrix.svd(true, true);
-            if let Ok(pws_mat_pinv) = svd.pseudo_inverse(1e-8) {
-                Some(Self {
-                    pws_iso_mat_pinv: pws_mat_pinv,
-                    pws_iso_t_inv: iso_norm_pws_t_inv,
-                })
-            } else {
-                None
-            }
-        } else {
-            None
-        }
+        Self::with_pinv_homography(ARMOR_WORLD_POINTS_2D)
+    }
+
+    /// 大装甲板（英雄、基地）
+    pub fn large() -> Option<Self> {
+        let (w, h) = (LARGE_ARMOR_LIGHT_WEIGHT / 2.0, ARMOR_LIGHT_HEIGHT / 2.0);
+        Self::with_pinv_homography([
+            na::Point2::new(-w, h),
+            na::Point2::new(-w, -h),
+            na::Point2::new(w, -h),
+            na::Point2::new(w, h),
+        ])
+    }
+
+    /// 任意 Z=0 平面上的四点目标，例如能量机关扇叶
+    ///
+    /// 目标点不需要以原点为中心，内部平移到质心求解后再换算回目标坐标系，
+    /// 四点不能共线；单应性矩阵用归一化 DLT 求解
+    pub fn with_object_points(object_points: [na::Point2<f64>; 4]) -> Option<Self> {
+        let object_center = object_points
+            .iter()
+            .fold(na::Vector2::zeros(), |acc, p| acc + p.coords)
+            / 4.0;
+        let object_points = object_points.map(|p| p - object_center);
+        let (iso_norm_points, iso_norm_t_inv) = isotropic_normalize(&object_points)?;
+        Some(Self {
+            pws_iso_norm: iso_norm_points.try_into().ok()?,
+            pws_iso_t: iso_norm_t_inv.try_inverse()?,
+            object_points,
+            object_center,
+            distortion: RbtDistortion::None,
+            homography: HomographyMethod::Dlt,
+        })
     }
 
-    /// 执行解算全部流程
+    /// 以原点为中心的装甲板，单应性矩阵用预先计算的伪逆求解
+    fn with_pinv_homography(object_points: [na::Point2<f64>; 4]) -> Option<Self> {
+        let mut solver = Self::with_object_points(object_points)?;
+        let pws_matrix = na::SMatrix::<f64, 3, 4>::from_columns(
+            &solver.pws_iso_norm.map(|p| p.to_homogeneous()),
+        );
+        let pws_pinv = pws_matrix.svd(true, true).pseudo_inverse(1e-8).ok()?;
+        solver.homography = HomographyMethod::Pinv(pws_pinv);
+        Some(solver)
+    }
+
+    /// 输入的像素点带有镜头畸变，求解前先去畸变，重投影误差按畸变后的像素计算
+    pub fn with_distortion(mut self, distortion: RbtDistortion) -> Self {
+        self.distortion = distortion;
+        self
+    }
+
+    /// 去畸变后的理想针孔像素坐标
+    pub fn undistort(
+        &self,
+        img_coord: &[na::Point2<f64>; 4],
+        cam_k: &na::Matrix3<f64>,
+    ) -> [na::Point2<f64>; 4] {
+        rbt_distortion::undistort_points(img_coord, cam_k, &self.distortion)
+    }
+
+    /// 执行解算全部流程，返回目标坐标系到相机坐标系的变换
     pub fn solve(
         &self,
         img_coord: &[na::Point2<f64>; 4],
         cam_k: &na::Matrix3<f64>,
     ) -> Option<na::Isometry3<f64>> {
-        // 使用 IPPE 算法求解出两个可能解
-        if let Some((pose1, pose2)) = self.solve_ippe(img_coord, cam_k) {
-            // 简单判断解的合理性
-            let pose1_valid = if self.is_pose_valid(&pose1) {
-                Some(pose1)
-            } else {
-                None
-            };
-            let pose2_valid = if self.is_pose_valid(&pose2) {
-                Some(pose2)
-            } else {
-                None
-            };
+        self.solve_candidates(img_coord, cam_k)
+            .map(|solutions| solutions.best.pose)
+    }
 
-            // 根据解的合理性情况返回最终解
-            match (pose1_valid, pose2_valid) {
-                (Some(p1), Some(p2)) => {
-                    let err1 = self.eval_reproj_err(&p1, img_coord, cam_k);
-                    let err2 = self.eval_reproj_err(&p2, img_coord, cam_k);
-                    if err1 < err2 { Some(p1) } else { Some(p2) }
-                }
-                (Some(p1), None) => Some(p1),
-                (None, Some(p2)) => Some(p2),
-                (None, None) => None,
-            }
-        } else {
-            None
-        }
+    /// 返回 IPPE 的两个候选解及其重投影误差，供调用方结合历史或几何约束消歧
+    pub fn solve_candidates(
+        &self,
+        img_coord: &[na::Point2<f64>; 4],
+        cam_k: &na::Matrix3<f64>,
+    ) -> Option<PnpSolutions> {
+        // 使用 IPPE 算法求解出两个可能解，剔除不合理的解
+        let (pose1, pose2) = self.solve_ippe(&self.undistort(img_coord, cam_k), cam_k)?;
+        let mut candidates = [pose1, pose2]
+            .into_iter()
+            .filter(|pose| self.is_pose_valid(pose))
+            .map(|pose| PnpCandidate {
+                reproj_err: self.eval_reproj_err(&pose, img_coord, cam_k),
+                pose: self.uncentered(pose),
+            })
+            .collect::<Vec<_>>();
+        candidates.sort_by(|a, b| a.reproj_err.total_cmp(&b.reproj_err));
+        let mut candidates = candidates.into_iter();
+        Some(PnpSolutions {
+            best: candidates.next()?,
+            alternative: candidates.next(),
+        })
+    }
+
+    /// 先用 IPPE 求解，再以其为初值做 LM 优化，同时返回收敛诊断
+    pub fn solve_refined(
+        &self,
+        img_coord: &[na::Point2<f64>; 4],
+        cam_k: &na::Matrix3<f64>,
+        refiner: &PoseRefiner,
+    ) -> Option<(na::Isometry3<f64>, RefineReport)> {
+        let pose = self.solve(img_coord, cam_k)?;
+        let ideal = self.undistort(img_coord, cam_k);
+        refiner.refine(&pose, &self.object_points(), &ideal, cam_k)
+    }
+
+    /// 目标坐标系下的四个目标点
+    pub fn object_points(&self) -> [na::Point3<f64>; 4] {
+        self.object_points
+            .map(|p| na::Point3::new(p.x + self.object_center.x, p.y + self.object_center.y, 0.0))
+    }
+
+    /// 质心坐标系下的解，平移回目标坐标系原点
+    fn uncentered(&self, mut pose: na::Isometry3<f64>) -> na::Isometry3<f64> {
+        let offset = na::Vector3::new(-self.object_center.x, -self.object_center.y, 0.0);
+        pose.translation.vector += pose.rotation * offset;
+        pose
     }
 
     /// 核心求解步骤
@@ -111,18 +215,17 @@ impl ArmorPnpSolver {
         }
 
         if let Some((p_iso_norm, img_p_iso_t_inv)) = isotropic_normalize(&p_norm) {
-            // 构造归一化图像点的齐次坐标矩阵
-            let p_norm_matrix = na::SMatrix::<f64, 3, 4>::from_columns(&[
-                p_iso_norm[0].to_homogeneous().xyz(),
-                p_iso_norm[1].to_homogeneous().xyz(),
-                p_iso_norm[2].to_homogeneous().xyz(),
-                p_iso_norm[3].to_homogeneous().xyz(),
-            ]);
-
-            // 计算单应性矩阵 h
-            let mut h = p_norm_matrix * self.pws_iso_mat_pinv;
-
-            h = img_p_iso_t_inv * h * self.pws_iso_t_inv;
+            // 归一化坐标下求单应性矩阵，再还原到原始坐标
+            let h_iso = match &self.homography {
+                HomographyMethod::Pinv(pws_pinv) => {
+                    let p_norm_matrix = na::SMatrix::<f64, 3, 4>::from_fn(|row, col| {
+                        p_iso_norm[col].to_homogeneous()[row]
+                    });
+                    p_norm_matrix * pws_pinv
+                }
+                HomographyMethod::Dlt => homography_dlt(&self.pws_iso_norm, &p_iso_norm)?,
+            };
+            let mut h = img_p_iso_t_inv * h_iso * self.pws_iso_t;
 
             // 归一化单应性矩阵
             let h_2_2 = h[(2, 2)];
@@ -259,10 +362,9 @@ impl ArmorPnpSolver {
         ata[(0, 0)] = n_f64;
         ata[(1, 1)] = n_f64;
 
-        for i in 0..4 {
-            let u = p_norm[i].x;
-            let v = p_norm[i].y;
-            let p_world_2d = ARMOR_WORLD_POINTS_2D[i];
+        for (p, p_world_2d) in p_norm.iter().zip(&self.object_points) {
+            let u = p.x;
+            let v = p.y;
 
             let rx = r_mat[(0, 0)] * p_world_2d.x + r_mat[(0, 1)] * p_world_2d.y;
             let ry = r_mat[(1, 0)] * p_world_2d.x + r_mat[(1, 1)] * p_world_2d.y;
@@ -294,8 +396,8 @@ impl ArmorPnpSolver {
             return false;
         }
         // 确保所有点变换后都在相机前方
-        for point in &ARMOR_WORLD_POINTS {
-            if (pose * point).z <= 0.0 {
+        for point in &self.object_points {
+            if (pose * na::Point3::new(point.x, point.y, 0.0)).z <= 0.0 {
                 return false;
             }
         }
@@ -311,30 +413,65 @@ impl ArmorPnpSolver {
         k: &na::Matrix3<f64>,
     ) -> f64 {
         let mut sum_sq_err = 0.0;
-        for i in 0..4 {
-            let pw = &ARMOR_WORLD_POINTS[i];
-            let pc = pose * pw; // 将世界点变换到相机坐标系
-
-            // 检查点是否在相机前方
-            if pc.z <= 1e-7 {
-                // 使用一个小的epsilon避免除零
+        for (uv, p) in uvs.iter().zip(&self.object_points) {
+            let pc = pose * na::Point3::new(p.x, p.y, 0.0); // 将世界点变换到相机坐标系
+            // 点在相机后方时该解不可用
+            let Some(reproj) = rbt_distortion::project_point(&pc, k, &self.distortion) else {
                 return f64::MAX;
-            }
-
-            // 正确的投影步骤：k(3x3) * pc(3x1) -> projected_h(3x1)
-            let projected_h = k * pc;
-
-            // 执行透视除法
-            let u_repro = projected_h.x / projected_h.z;
-            let v_repro = projected_h.y / projected_h.z;
-
-            sum_sq_err += (uvs[i].x - u_repro).powi(2) + (uvs[i].y - v_repro).powi(2);
+            };
+            sum_sq_err += (uv - reproj).norm_squared();
         }
         let ass_err = (sum_sq_err / 4.0).sqrt();
         ass_err
     }
 }
 
+/// 求平面点对之间的单应性矩阵 H，使 dst ~ H·src，结果归一化到 H[2,2] = 1
+///
+/// 两组点先各自做各向同性归一化再用 DLT 求解，至少四组对应点，例如相机标定中棋盘格到图像的映射
+pub fn find_homography(
+    src: &[na::Point2<f64>],
+    dst: &[na::Point2<f64>],
+) -> Option<na::Matrix3<f64>> {
+    let (src_norm, src_t) = isotropic_normalize(src)?;
+    let (dst_norm, dst_t) = isotropic_normalize(dst)?;
+    let h = dst_t * homography_dlt(&src_norm, &dst_norm)? * src_t.try_inverse()?;
+    let scale = h[(2, 2)];
+    if scale.abs() < 1e-12 {
+        return None;
+    }
+    Some(h / scale)
+}
+
+/// 直接线性变换求单应性矩阵 H，使 dst ~ H·src
+///
+/// 输入点应已做各向同性归一化，至少四组对应点且不能有三点共线，
+/// 取 AᵀA 最小特征值对应的特征向量作为 H 的 9 个元素
+fn homography_dlt(src: &[na::Point2<f64>], dst: &[na::Point2<f64>]) -> Option<na::Matrix3<f64>> {
+    if src.len() < 4 || src.len() != dst.len() {
+        return None;
+    }
+    let mut ata = na::SMatrix::<f64, 9, 9>::zeros();
+    for (p, q) in src.iter().zip(dst) {
+        let (x, y, u, v) = (p.x, p.y, q.x, q.y);
+        for row in [
+            [x, y, 1.0, 0.0, 0.0, 0.0, -u * x, -u * y, -u],
+            [0.0, 0.0, 0.0, x, y, 1.0, -v * x, -v * y, -v],
+        ] {
+            let row = na::SVector::<f64, 9>::from(row);
+            ata += row * row.transpose();
+        }
+    }
+    let eigen = na::SymmetricEigen::new(ata);
+    let (min_idx, _) = eigen
+        .eigenvalues
+        .iter()
+        .enumerate()
+        .min_by(|a, b| a.1.total_cmp(b.1))?;
+    let h = eigen.eigenvectors.column(min_idx);
+    Some(na::Matrix3::from_row_slice(h.as_slice()))
+}
+
 fn rotate_vec_to_z_axis(a: &na::Vector3<f64>) -> na::Matrix3<f64> {
     match nalgebra::Rotation3::rotation_between(&a.normalize(), &na::Vector3::z_axis()) {
         Some(rot) => rot.matrix().into_owned(),
@@ -410,3 +547,121 @@ fn isotropic_normalize(
     );
     Some((centered_points, transformation_matrix))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cam_k() -> na::Matrix3<f64> {
+        na::Matrix3::new(1600.0, 0.0, 640.0, 0.0, 1600.0, 512.0, 0.0, 0.0, 1.0)
+    }
+
+    /// 距离 `distance`、绕竖直方向偏航 `yaw_deg` 的装甲板，法线指向相机
+    fn armor_pose(distance: f64, yaw_deg: f64) -> na::Isometry3<f64> {
+        let facing =
+            na::UnitQuaternion::from_rotation_matrix(&na::Rotation3::from_matrix_unchecked(
+                na::Matrix3::from_diagonal(&na::Vector3::new(1.0, -1.0, -1.0)),
+            ));
+        let yaw = na::UnitQuaternion::from_axis_angle(&na::Vector3::y_axis(), yaw_deg.to_radians());
+        na::Isometry3::from_parts(na::Translation3::new(100.0, 50.0, distance), yaw * facing)
+    }
+
+    fn project(pose: &na::Isometry3<f64>) -> [na::Point2<f64>; 4] {
+        ARMOR_WORLD_POINTS_2D.map(|p| {
+            let h = cam_k() * (pose * na::Point3::new(p.x, p.y, 0.0)).coords;
+            na::Point2::new(h.x / h.z, h.y / h.z)
+        })
+    }
+
+    #[test]
+    fn test_solve_candidates() {
+        let solver = ArmorPnpSolver::new().unwrap();
+        let truth = armor_pose(2000.0, 35.0);
+        let uvs = project(&truth);
+        let solutions = solver.solve_candidates(&uvs, &cam_k()).unwrap();
+
+        // 无噪声时最优解即真值，伪逆近似在 2m、35° 斜视下只有百分之一像素量级的偏差；
+        // 另一个解重投影误差明显更大
+        assert!(solutions.best.reproj_err < 0.02);
+        assert!(solutions.best.pose.rotation.angle_to(&truth.rotation) < 0.1f64.to_radians());
+        assert!((solutions.best.pose.translation.vector - truth.translation.vector).norm() < 1.0);
+        let alternative = solutions.alternative.unwrap();
+        assert!(alternative.reproj_err > 10.0 * solutions.best.reproj_err);
+        assert!(solutions.ambiguity() < 0.05);
+        assert_eq!(solver.solve(&uvs, &cam_k()), Some(solutions.best.pose));
+
+        // DLT 求单应性矩阵时没有偏差
+        let dlt = ArmorPnpSolver::with_object_points(ARMOR_WORLD_POINTS_2D).unwrap();
+        let solutions = dlt.solve_candidates(&uvs, &cam_k()).unwrap();
+        assert!(solutions.best.reproj_err < 1e-6);
+        assert!(solutions.best.pose.rotation.angle_to(&truth.rotation) < 1e-6);
+        assert!(solutions.ambiguity() < 1e-3);
+    }
+
+    #[test]
+    fn test_frontal_view_is_ambiguous() {
+        let solver = ArmorPnpSolver::new().unwrap();
+        let truth = armor_pose(6000.0, 5.0);
+        // 远距离近正对，半像素噪声下两个解几乎无法区分
+        let noise = [(0.3, -0.2), (-0.4, 0.1), (0.2, 0.4), (-0.1, -0.3)];
+        let mut uvs = project(&truth);
+        for (uv, (du, dv)) in uvs.iter_mut().zip(noise) {
+            *uv += na::Vector2::new(du, dv);
+        }
+        let solutions = solver.solve_candidates(&uvs, &cam_k()).unwrap();
+        let alternative = solutions.alternative.unwrap();
+        assert!(solutions.ambiguity() > 0.5, "{}", solutions.ambiguity());
+        assert!(solutions.ambiguity() <= 1.0);
+        // 两个解的法线分居视线两侧
+        let normal = |pose: &na::Isometry3<f64>| (pose.rotation * na::Vector3::z()).x;
+        let sight = -truth.translation.vector.normalize().x;
+        assert!((normal(&solutions.best.pose) - sight) * (normal(&alternative.pose) - sight) < 0.0);
+    }
+
+    #[test]
+    fn test_solve_with_distortion() {
+        let distortion = RbtDistortion::BrownConrady {
+            coeffs: [-0.32, 0.12, 0.0008, -0.0005, -0.02],
+        };
+        // 靠近画面边缘的装甲板，畸变有数十像素
+        let truth = na::Isometry3::from_parts(
+            na::Translation3::new(1300.0, -900.0, 2500.0),
+            armor_pose(2500.0, 30.0).rotation,
+        );
+        let uvs = ARMOR_WORLD_POINTS_2D.map(|p| {
+            let pc = truth * na::Point3::new(p.x, p.y, 0.0);
+            rbt_distortion::project_point(&pc, &cam_k(), &distortion).unwrap()
+        });
+        assert!((uvs[0] - project(&truth)[0]).norm() > 20.0);
+
+        // 用无偏的 DLT 求单应性矩阵，误差只来自畸变处理
+        let solver = ArmorPnpSolver::with_object_points(ARMOR_WORLD_POINTS_2D)
+            .unwrap()
+            .with_distortion(distortion);
+        let solutions = solver.solve_candidates(&uvs, &cam_k()).unwrap();
+        assert!(solutions.best.reproj_err < 1e-6);
+        assert!(solutions.best.pose.rotation.angle_to(&truth.rotation) < 1e-6);
+        assert!((solutions.best.pose.translation.vector - truth.translation.vector).norm() < 1e-3);
+        // 忽略畸变时位姿明显偏离
+        let pinhole = ArmorPnpSolver::new()
+            .unwrap()
+            .solve(&uvs, &cam_k())
+            .unwrap();
+        assert!((pinhole.translation.vector - truth.translation.vector).norm() > 10.0);
+    }
+
+    #[test]
+    fn test_find_homography() {
+        let truth = na::Matrix3::new(1.2, 0.1, 300.0, -0.05, 0.9, 200.0, 1e-4, -2e-4, 1.0);
+        let src = (0..12)
+            .map(|i| na::Point2::new((i % 4) as f64 * 30.0, (i / 4) as f64 * 30.0))
+            .collect::<Vec<_>>();
+        let dst = src
+            .iter()
+            .map(|p| na::Point2::from_homogeneous(truth * p.to_homogeneous()).unwrap())
+            .collect::<Vec<_>>();
+        let h = find_homography(&src, &dst).unwrap();
+        assert!((h - truth).norm() < 1e-8, "{h}");
+        assert!(find_homography(&src[..3], &dst[..3]).is_none());
+    }
+}
@@ -0,0 +1,428 @@
+//! PnP 位姿的 Levenberg–Marquardt 优化
+//!
+//! IPPE 由单应性闭式求解，没有直接最小化重投影误差，角点有噪声时装甲板朝向抖动明显。
+//! 以 IPPE 解为初值最小化重投影误差平方和：
+//! - SE(3) 模型的扰动采用左乘形式 T ← exp(δ)·T，δ = (ω, v)，相机系下的点 p 对扰动的雅可比为 [−[p]×, I]
+//! - 装甲板仰角固定（15°）且已知重力方向时，只优化绕竖直方向的偏航 ψ 和平移，雅可比为 [up × p, I]，
+//!   少了两个自由度，角点噪声对偏航的影响更小
+//! - 阻尼加在 JᵀJ 的对角元上，代价下降时接受更新并减小阻尼，否则增大阻尼重试
+//!
+//! 优化结果附带 `RefineReport`，记录迭代次数、前后的重投影误差和终止原因。
+
+use crate::rbt_infra::rbt_cfg::PoseRefineCfg;
+
+/// 初始阻尼系数
+const INITIAL_LAMBDA: f64 = 1e-3;
+/// 阻尼系数超过该值仍无法下降时停止
+const MAX_LAMBDA: f64 = 1e8;
+/// 平均每点残差平方小于该值时认为已经收敛到精确解，单位 px²
+const EXACT_COST: f64 = 1e-20;
+
+/// 优化终止原因
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display)]
+pub enum RefineTermination {
+    /// 更新步长小于 `step_tolerance`
+    StepConverged,
+    /// 代价相对下降小于 `cost_tolerance`，或已经是精确解
+    CostConverged,
+    /// 达到最大迭代次数
+    MaxIterations,
+    /// 增大阻尼也无法降低代价，通常已处于极小值附近
+    Stalled,
+}
+
+/// 单次优化的收敛诊断
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RefineReport {
+    /// 被接受的更新次数
+    pub iterations: usize,
+    /// 优化前的重投影均方根误差 px
+    pub initial_rms: f64,
+    /// 优化后的重投影均方根误差 px
+    pub final_rms: f64,
+    pub termination: RefineTermination,
+}
+
+impl RefineReport {
+    /// 是否在迭代次数内满足收敛条件
+    pub fn converged(&self) -> bool {
+        matches!(
+            self.termination,
+            RefineTermination::StepConverged | RefineTermination::CostConverged
+        )
+    }
+}
+
+/// PnP 位姿优化器
+///
+/// 设置重力方向且 `fixed_pitch` 开启时只优化偏航和平移：
+/// 装甲板 x 轴保持水平，法线相对水平面的仰角固定为 `armor_pitch_deg`，
+/// 初值由 IPPE 解投影到该约束上得到，否则在完整的 SE(3) 上优化
+#[derive(Debug, Clone)]
+pub struct PoseRefiner {
+    cfg: PoseRefineCfg,
+    up: Option<na::Unit<na::Vector3<f64>>>,
+}
+
+impl PoseRefiner {
+    pub fn new(cfg: PoseRefineCfg) -> Self {
+        Self { cfg, up: None }
+    }
+
+    /// 相机坐标系下竖直向上的方向，由当前云台姿态给出
+    pub fn with_gravity_up(mut self, up: na::Unit<na::Vector3<f64>>) -> Self {
+        self.up = Some(up);
+        self
+    }
+
+    pub fn cfg(&self) -> &PoseRefineCfg {
+        &self.cfg
+    }
+
+    /// 以 `pose`（目标坐标系到相机坐标系）为初值优化重投影误差
+    ///
+    /// 初值下有点位于相机后方时返回 None；迭代中代价只降不升，固定仰角时从投影到约束后的初值算起
+    pub fn refine(
+        &self,
+        pose: &na::Isometry3<f64>,
+        object_points: &[na::Point3<f64>],
+        uvs: &[na::Point2<f64>],
+        k: &na::Matrix3<f64>,
+    ) -> Option<(na::Isometry3<f64>, RefineReport)> {
+        if object_points.len() != uvs.len() || object_points.len() < 3 {
+            return None;
+        }
+        let relative = |v: na::Vector3<f64>, pose: &na::Isometry3<f64>| {
+            v.norm() / pose.translation.vector.norm().max(f64::EPSILON)
+        };
+        match self.up.filter(|_| self.cfg.fixed_pitch) {
+            Some(up) => {
+                let pitch = self.cfg.armor_pitch_deg.to_radians();
+                // 扰动 (ψ, v)：绕竖直方向旋转 ψ 后平移 v
+                let d_point = |pc: &na::Point3<f64>| {
+                    let mut d_point = na::Matrix3x4::zeros();
+                    d_point.set_column(0, &up.cross(&pc.coords));
+                    d_point
+                        .fixed_view_mut::<3, 3>(0, 1)
+                        .copy_from(&na::Matrix3::identity());
+                    d_point
+                };
+                let retract = |delta: &na::Vector4<f64>, pose: &na::Isometry3<f64>| {
+                    let v = delta.fixed_rows::<3>(1).into_owned();
+                    let step = na::Isometry3::from_parts(
+                        v.into(),
+                        na::UnitQuaternion::from_axis_angle(&up, delta[0]),
+                    ) * pose;
+                    (step, delta[0].abs() + relative(v, &step))
+                };
+                constrain_pitch(pose, &up, pitch)?
+                    .iter()
+                    .filter_map(|init| {
+                        self.levenberg_marquardt(init, object_points, uvs, k, d_point, retract)
+                    })
+                    .min_by(|a, b| a.1.final_rms.total_cmp(&b.1.final_rms))
+            }
+            None => self.levenberg_marquardt::<6>(
+                pose,
+                object_points,
+                uvs,
+                k,
+                |pc| {
+                    let mut d_point = na::Matrix3x6::zeros();
+                    d_point
+                        .fixed_view_mut::<3, 3>(0, 0)
+                        .copy_from(&(-pc.coords.cross_matrix()));
+                    d_point
+                        .fixed_view_mut::<3, 3>(0, 3)
+                        .copy_from(&na::Matrix3::identity());
+                    d_point
+                },
+                |delta, pose| {
+                    let omega = delta.fixed_rows::<3>(0).into_owned();
+                    let v = delta.fixed_rows::<3>(3).into_owned();
+                    let step = na::Isometry3::new(v, omega) * pose;
+                    (step, omega.norm() + relative(v, &step))
+                },
+            ),
+        }
+    }
+
+    /// `d_point` 给出相机系点对扰动的雅可比，`retract` 把扰动作用到位姿上并给出步长
+    fn levenberg_marquardt<const D: usize>(
+        &self,
+        pose: &na::Isometry3<f64>,
+        object_points: &[na::Point3<f64>],
+        uvs: &[na::Point2<f64>],
+        k: &na::Matrix3<f64>,
+        d_point: impl Fn(&na::Point3<f64>) -> na::SMatrix<f64, 3, D>,
+        retract: impl Fn(&na::SVector<f64, D>, &na::Isometry3<f64>) -> (na::Isometry3<f64>, f64),
+    ) -> Option<(na::Isometry3<f64>, RefineReport)> {
+        let n = object_points.len() as f64;
+        let rms = |cost: f64| (cost / n).sqrt();
+        let normal_equations =
+            |pose: &na::Isometry3<f64>| normal_equations(pose, object_points, uvs, k, &d_point);
+
+        let mut pose = *pose;
+        let (mut cost, mut jtj, mut jtr) = normal_equations(&pose)?;
+        let initial_rms = rms(cost);
+        let mut lambda = INITIAL_LAMBDA;
+        let mut iterations = 0;
+        let mut termination = RefineTermination::MaxIterations;
+
+        while iterations < self.cfg.max_iters {
+            if cost / n < EXACT_COST {
+                termination = RefineTermination::CostConverged;
+                break;
+            }
+            if lambda > MAX_LAMBDA {
+                termination = RefineTermination::Stalled;
+                break;
+            }
+            let mut damped = jtj;
+            for i in 0..D {
+                damped[(i, i)] += lambda * jtj[(i, i)].max(f64::EPSILON);
+            }
+            let Some(delta) = damped.cholesky().map(|c| -c.solve(&jtr)) else {
+                lambda *= 10.0;
+                continue;
+            };
+            let (candidate, step) = retract(&delta, &pose);
+            match normal_equations(&candidate) {
+                Some((new_cost, new_jtj, new_jtr)) if new_cost < cost => {
+                    let decrease = (cost - new_cost) / cost;
+                    (pose, cost, jtj, jtr) = (candidate, new_cost, new_jtj, new_jtr);
+                    lambda = (lambda / 10.0).max(f64::EPSILON);
+                    iterations += 1;
+                    if step < self.cfg.step_tolerance {
+                        termination = RefineTermination::StepConverged;
+                        break;
+                    }
+                    if decrease < self.cfg.cost_tolerance {
+                        termination = RefineTermination::CostConverged;
+                        break;
+                    }
+                }
+                _ => lambda *= 10.0,
+            }
+        }
+
+        Some((
+            pose,
+            RefineReport {
+                iterations,
+                initial_rms,
+                final_rms: rms(cost),
+                termination,
+            },
+        ))
+    }
+}
+
+/// 把位姿投影到仰角约束上：保留法线的水平朝向和平移，x 轴取水平方向
+///
+/// IPPE 的二义性使法线朝向可能落在关于视线的镜像附近，偏航优化难以越过两者之间的代价峰，
+/// 因此同时给出关于视线镜像的朝向作为第二个初值。法线接近竖直时无法确定朝向，返回 None
+fn constrain_pitch(
+    pose: &na::Isometry3<f64>,
+    up: &na::Unit<na::Vector3<f64>>,
+    pitch: f64,
+) -> Option<[na::Isometry3<f64>; 2]> {
+    let horizontal = |v: na::Vector3<f64>| (v - up.into_inner() * v.dot(up)).try_normalize(1e-6);
+    let heading = horizontal(pose.rotation * na::Vector3::z())?;
+    let sight = horizontal(-pose.translation.vector).unwrap_or(heading);
+    let mirrored = sight * (2.0 * heading.dot(&sight)) - heading;
+    Some([heading, mirrored].map(|heading| {
+        let normal = heading * pitch.cos() + up.into_inner() * pitch.sin();
+        let x_axis = up.cross(&normal).normalize();
+        let y_axis = normal.cross(&x_axis);
+        let rotation = na::Rotation3::from_matrix_unchecked(na::Matrix3::from_columns(&[
+            x_axis, y_axis, normal,
+        ]));
+        na::Isometry3::from_parts(
+            pose.translation,
+            na::UnitQuaternion::from_rotation_matrix(&rotation),
+        )
+    }))
+}
+
+/// 代价、JᵀJ 与 Jᵀr
+type NormalEquations<const D: usize> = (f64, na::SMatrix<f64, D, D>, na::SVector<f64, D>);
+
+/// 累加所有点的残差平方和与法方程，有点位于相机后方时返回 None
+fn normal_equations<const D: usize>(
+    pose: &na::Isometry3<f64>,
+    object_points: &[na::Point3<f64>],
+    uvs: &[na::Point2<f64>],
+    k: &na::Matrix3<f64>,
+    d_point: impl Fn(&na::Point3<f64>) -> na::SMatrix<f64, 3, D>,
+) -> Option<NormalEquations<D>> {
+    let mut cost = 0.0;
+    let mut jtj = na::SMatrix::<f64, D, D>::zeros();
+    let mut jtr = na::SVector::<f64, D>::zeros();
+    for (pw, uv) in object_points.iter().zip(uvs) {
+        let pc = pose * pw;
+        let h = k * pc.coords;
+        if pc.z <= 1e-7 || h.z.abs() <= 1e-7 {
+            return None;
+        }
+        let proj = na::Vector2::new(h.x / h.z, h.y / h.z);
+        let residual = proj - uv.coords;
+
+        // 投影对相机系点的雅可比，K 的第三行一般为 (0, 0, 1)
+        let mut d_proj = na::Matrix2x3::zeros();
+        d_proj.set_row(0, &((k.row(0) - proj.x * k.row(2)) / h.z));
+        d_proj.set_row(1, &((k.row(1) - proj.y * k.row(2)) / h.z));
+        let jacobian = d_proj * d_point(&pc);
+
+        cost += residual.norm_squared();
+        jtj += jacobian.transpose() * jacobian;
+        jtr += jacobian.transpose() * residual;
+    }
+    Some((cost, jtj, jtr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rbt_base::rbt_algorithm::rbt_ippe::ArmorPnpSolver;
+
+    const PITCH_DEG: f64 = 15.0;
+
+    fn refine_cfg() -> PoseRefineCfg {
+        PoseRefineCfg {
+            enable: true,
+            max_iters: 20,
+            step_tolerance: 1e-8,
+            cost_tolerance: 1e-10,
+            fixed_pitch: true,
+            armor_pitch_deg: PITCH_DEG,
+        }
+    }
+
+    fn cam_k() -> na::Matrix3<f64> {
+        na::Matrix3::new(1600.0, 0.0, 640.0, 0.0, 1600.0, 512.0, 0.0, 0.0, 1.0)
+    }
+
+    /// 相机水平时，相机坐标系（右-下-前）中的竖直向上方向
+    fn up() -> na::Unit<na::Vector3<f64>> {
+        -na::Vector3::y_axis()
+    }
+
+    /// 3m 外、偏航 30°、法线上仰 15° 的装甲板
+    fn armor_pose() -> na::Isometry3<f64> {
+        let facing =
+            na::UnitQuaternion::from_rotation_matrix(&na::Rotation3::from_matrix_unchecked(
+                na::Matrix3::from_diagonal(&na::Vector3::new(1.0, -1.0, -1.0)),
+            ));
+        let yaw = na::UnitQuaternion::from_axis_angle(&na::Vector3::y_axis(), 30f64.to_radians());
+        let pitch =
+            na::UnitQuaternion::from_axis_angle(&na::Vector3::x_axis(), -PITCH_DEG.to_radians());
+        na::Isometry3::from_parts(
+            na::Translation3::new(300.0, -150.0, 3000.0),
+            yaw * facing * pitch,
+        )
+    }
+
+    fn project(pose: &na::Isometry3<f64>, p: &na::Point3<f64>) -> na::Point2<f64> {
+        let h = cam_k() * (pose * p).coords;
+        na::Point2::new(h.x / h.z, h.y / h.z)
+    }
+
+    fn noisy_corners(solver: &ArmorPnpSolver) -> [na::Point2<f64>; 4] {
+        let noise = [(0.4, -0.3), (-0.5, 0.2), (0.3, 0.5), (-0.2, -0.4)];
+        let object_points = solver.object_points();
+        std::array::from_fn(|i| {
+            project(&armor_pose(), &object_points[i]) + na::Vector2::new(noise[i].0, noise[i].1)
+        })
+    }
+
+    #[test]
+    fn test_refine_exact_pose() {
+        let solver = ArmorPnpSolver::new().unwrap();
+        let truth = armor_pose();
+        let object_points = solver.object_points();
+        let uvs = object_points.map(|p| project(&truth, &p));
+
+        // 初值偏离真值 1° 和 20mm，两种模型都应收敛回真值
+        let init = na::Isometry3::new(
+            na::Vector3::new(20.0, -10.0, 15.0),
+            na::Vector3::new(0.0, 1f64.to_radians(), 0.0),
+        ) * truth;
+        for refiner in [
+            PoseRefiner::new(refine_cfg()),
+            PoseRefiner::new(refine_cfg()).with_gravity_up(up()),
+        ] {
+            let (pose, report) = refiner
+                .refine(&init, &object_points, &uvs, &cam_k())
+                .unwrap();
+            assert!(report.converged(), "{report:?}");
+            assert!(
+                report.initial_rms > 1.0 && report.final_rms < 1e-6,
+                "{report:?}"
+            );
+            assert!((pose.translation.vector - truth.translation.vector).norm() < 1e-3);
+            assert!(pose.rotation.angle_to(&truth.rotation) < 1e-6);
+        }
+    }
+
+    #[test]
+    fn test_refine_full_pose() {
+        let solver = ArmorPnpSolver::new().unwrap();
+        let object_points = solver.object_points();
+        let uvs = noisy_corners(&solver);
+
+        let ippe = solver.solve(&uvs, &cam_k()).unwrap();
+        let (pose, report) = solver
+            .solve_refined(&uvs, &cam_k(), &PoseRefiner::new(refine_cfg()))
+            .unwrap();
+        assert!(report.final_rms <= report.initial_rms, "{report:?}");
+        assert!(report.iterations <= refine_cfg().max_iters);
+        // 完整模型下优化后的重投影误差不大于 IPPE
+        let reproj = |pose: &na::Isometry3<f64>| {
+            (object_points
+                .iter()
+                .zip(&uvs)
+                .map(|(p, uv)| (project(pose, p) - uv).norm_squared())
+                .sum::<f64>()
+                / 4.0)
+                .sqrt()
+        };
+        assert!(reproj(&pose) <= reproj(&ippe) + 1e-9);
+        assert!((reproj(&pose) - report.final_rms).abs() < 1e-9);
+
+        // 初值在相机后方时放弃优化
+        let behind = na::Isometry3::translation(0.0, 0.0, -3000.0);
+        assert!(
+            PoseRefiner::new(refine_cfg())
+                .refine(&behind, &object_points, &uvs, &cam_k())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_refine_fixed_pitch() {
+        let solver = ArmorPnpSolver::new().unwrap();
+        let uvs = noisy_corners(&solver);
+        let refiner = PoseRefiner::new(refine_cfg()).with_gravity_up(up());
+        let (pose, report) = solver.solve_refined(&uvs, &cam_k(), &refiner).unwrap();
+        assert!(report.converged(), "{report:?}");
+        assert!(report.final_rms < 0.5, "{report:?}");
+
+        // 结果满足仰角约束，x 轴水平
+        let normal = pose.rotation * na::Vector3::z();
+        let x_axis = pose.rotation * na::Vector3::x();
+        assert!((normal.dot(&up()) - PITCH_DEG.to_radians().sin()).abs() < 1e-9);
+        assert!(x_axis.dot(&up()).abs() < 1e-9);
+        // 朝向误差只剩偏航，小于 IPPE；平移误差主要来自角点噪声下的测距，与 IPPE 相当
+        let truth = armor_pose();
+        let ippe = solver.solve(&uvs, &cam_k()).unwrap();
+        let rot_err = pose.rotation.angle_to(&truth.rotation);
+        assert!(
+            rot_err < ippe.rotation.angle_to(&truth.rotation),
+            "{}",
+            rot_err.to_degrees()
+        );
+        let trans_err = (pose.translation.vector - truth.translation.vector).norm();
+        assert!(trans_err < 80.0, "translation error {trans_err}");
+    }
+}
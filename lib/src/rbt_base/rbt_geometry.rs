@@ -1,6 +1,8 @@
 pub mod rbt_angle;
 pub mod rbt_cylindrical2;
+pub mod rbt_distortion;
 pub mod rbt_line2;
 pub mod rbt_point2;
 pub mod rbt_point3;
 pub mod rbt_pose3;
+pub mod rbt_tf;
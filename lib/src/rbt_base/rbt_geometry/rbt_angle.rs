@@ -1,6 +1,6 @@
 //! 角度相关的扩展 trait
 //!
-//! 为 f32 类型添加角度归一化功能
+//! 为 f32 类型添加角度归一化功能，并提供多圈角度的折算函数
 
 /// 角度 trait，提供归一化方法
 pub trait Angle {
@@ -23,6 +23,16 @@ impl Angle for f32 {
     }
 }
 
+/// 将角度差折算到 [-180, 180] 度，即沿最短弧的差值
+pub fn wrap_deg(angle: f64) -> f64 {
+    angle - 360.0 * (angle / 360.0).round()
+}
+
+/// 将角度展开为与 `reference` 最接近的等价值，用于与多圈累计的角度比较或插值
+pub fn unwrap_deg(angle: f64, reference: f64) -> f64 {
+    reference + wrap_deg(angle - reference)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -50,4 +60,18 @@ mod tests {
         let angle = -std::f32::consts::PI;
         assert!((angle.norm_rad() - std::f32::consts::PI).abs() < 1e-6);
     }
+
+    #[test]
+    fn test_wrap_deg() {
+        assert!((wrap_deg(350.0) + 10.0).abs() < 1e-12);
+        assert!((wrap_deg(-190.0) - 170.0).abs() < 1e-12);
+        assert!((wrap_deg(725.0) - 5.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_unwrap_deg() {
+        // 云台累计转到 540° 时，目标 -170° 应展开到 550° 而非绕回
+        assert!((unwrap_deg(-170.0, 540.0) - 550.0).abs() < 1e-12);
+        assert!((unwrap_deg(179.0, -179.0) + 181.0).abs() < 1e-12);
+    }
 }
@@ -16,7 +16,7 @@ impl RbtCylindricalPoint2 {
         let p = xy.into();
         let (x, y) = (p.x, p.y);
         let rho = (x * x + y * y).sqrt();
-        let theta_d = y / x.atan().to_degrees();
+        let theta_d = y.atan2(x).to_degrees();
         Self::new(rho, theta_d)
     }
 }
@@ -0,0 +1,334 @@
+//! 镜头畸变模型，与 OpenCV 的 `projectPoints` / `fisheye::projectPoints` 保持一致
+//!
+//! 畸变作用在归一化相机平面上，`distort` 为理想点到畸变点，`undistort` 为其迭代逆
+
+use serde::{Deserialize, Serialize};
+
+/// 去畸变的最大迭代次数
+const UNDISTORT_MAX_ITERS: usize = 20;
+/// 去畸变的收敛阈值，归一化平面上的步长
+const UNDISTORT_TOLERANCE: f64 = 1e-12;
+
+/// 镜头畸变系数
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(tag = "model", rename_all = "snake_case")]
+pub enum RbtDistortion {
+    /// 理想针孔
+    #[default]
+    None,
+    /// Brown–Conrady 径向 + 切向畸变，系数顺序同 OpenCV: k1 k2 p1 p2 k3
+    BrownConrady { coeffs: [f64; 5] },
+    /// 等距鱼眼模型，系数顺序同 OpenCV fisheye: k1 k2 k3 k4
+    Fisheye { coeffs: [f64; 4] },
+}
+
+impl RbtDistortion {
+    pub fn is_none(&self) -> bool {
+        matches!(self, RbtDistortion::None)
+    }
+
+    /// 系数是否全部有限
+    pub fn is_finite(&self) -> bool {
+        self.coeffs().iter().all(|c| c.is_finite())
+    }
+
+    /// 按 OpenCV 顺序排列的畸变系数
+    pub fn coeffs(&self) -> &[f64] {
+        match self {
+            RbtDistortion::None => &[],
+            RbtDistortion::BrownConrady { coeffs } => coeffs,
+            RbtDistortion::Fisheye { coeffs } => coeffs,
+        }
+    }
+
+    /// 同一模型换一组系数，长度不符时返回 None，用于标定时优化系数
+    pub fn with_coeffs(&self, coeffs: &[f64]) -> Option<Self> {
+        Some(match self {
+            RbtDistortion::None if coeffs.is_empty() => RbtDistortion::None,
+            RbtDistortion::None => return None,
+            RbtDistortion::BrownConrady { .. } => RbtDistortion::BrownConrady {
+                coeffs: coeffs.try_into().ok()?,
+            },
+            RbtDistortion::Fisheye { .. } => RbtDistortion::Fisheye {
+                coeffs: coeffs.try_into().ok()?,
+            },
+        })
+    }
+
+    /// 归一化平面上的理想点加上畸变
+    pub fn distort(&self, p: na::Point2<f64>) -> na::Point2<f64> {
+        match self {
+            RbtDistortion::None => p,
+            RbtDistortion::BrownConrady { coeffs } => brown_conrady(coeffs, p).0,
+            RbtDistortion::Fisheye { coeffs } => {
+                let r = p.coords.norm();
+                if r < 1e-12 {
+                    return p;
+                }
+                let theta = r.atan();
+                na::Point2::from(p.coords * (fisheye_theta_d(coeffs, theta).0 / r))
+            }
+        }
+    }
+
+    /// 归一化平面上的畸变点还原为理想点
+    ///
+    /// Brown–Conrady 没有解析逆，用高斯牛顿迭代；鱼眼模型只需对入射角 θ 做一维牛顿迭代
+    pub fn undistort(&self, p: na::Point2<f64>) -> na::Point2<f64> {
+        match self {
+            RbtDistortion::None => p,
+            RbtDistortion::BrownConrady { coeffs } => {
+                let mut x = p;
+                for _ in 0..UNDISTORT_MAX_ITERS {
+                    let (distorted, jacobian) = brown_conrady(coeffs, x);
+                    let Some(step) = jacobian.lu().solve(&(p - distorted)) else {
+                        break;
+                    };
+                    x += step;
+                    if step.norm() < UNDISTORT_TOLERANCE {
+                        break;
+                    }
+                }
+                x
+            }
+            RbtDistortion::Fisheye { coeffs } => {
+                let theta_d = p.coords.norm();
+                if theta_d < 1e-12 {
+                    return p;
+                }
+                let mut theta = theta_d;
+                for _ in 0..UNDISTORT_MAX_ITERS {
+                    let (value, derivative) = fisheye_theta_d(coeffs, theta);
+                    let step = (value - theta_d) / derivative;
+                    theta -= step;
+                    if step.abs() < UNDISTORT_TOLERANCE {
+                        break;
+                    }
+                }
+                na::Point2::from(p.coords * (theta.tan() / theta_d))
+            }
+        }
+    }
+}
+
+/// Brown–Conrady 畸变及其对理想点的雅可比
+fn brown_conrady(
+    [k1, k2, p1, p2, k3]: &[f64; 5],
+    p: na::Point2<f64>,
+) -> (na::Point2<f64>, na::Matrix2<f64>) {
+    let (x, y) = (p.x, p.y);
+    let r2 = x * x + y * y;
+    let radial = 1.0 + r2 * (k1 + r2 * (k2 + r2 * k3));
+    // radial 对 r² 的导数
+    let d_radial = k1 + r2 * (2.0 * k2 + 3.0 * k3 * r2);
+    let distorted = na::Point2::new(
+        x * radial + 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x),
+        y * radial + p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y,
+    );
+    let cross = 2.0 * x * y * d_radial;
+    let jacobian = na::Matrix2::new(
+        radial + 2.0 * x * x * d_radial + 2.0 * p1 * y + 6.0 * p2 * x,
+        cross + 2.0 * p1 * x + 2.0 * p2 * y,
+        cross + 2.0 * p1 * x + 2.0 * p2 * y,
+        radial + 2.0 * y * y * d_radial + 6.0 * p1 * y + 2.0 * p2 * x,
+    );
+    (distorted, jacobian)
+}
+
+/// 鱼眼模型 θ_d(θ) 及其导数
+fn fisheye_theta_d([k1, k2, k3, k4]: &[f64; 4], theta: f64) -> (f64, f64) {
+    let t2 = theta * theta;
+    let value = theta * (1.0 + t2 * (k1 + t2 * (k2 + t2 * (k3 + t2 * k4))));
+    let derivative = 1.0 + t2 * (3.0 * k1 + t2 * (5.0 * k2 + t2 * (7.0 * k3 + t2 * 9.0 * k4)));
+    (value, derivative)
+}
+
+/// 将相机坐标系下的点投影为带畸变的像素坐标，点位于相机后方时返回 None
+pub fn project_point(
+    pc: &na::Point3<f64>,
+    cam_k: &na::Matrix3<f64>,
+    distortion: &RbtDistortion,
+) -> Option<na::Point2<f64>> {
+    if pc.z <= 1e-7 {
+        return None;
+    }
+    let distorted = distortion.distort(na::Point2::new(pc.x / pc.z, pc.y / pc.z));
+    let uv = cam_k * distorted.to_homogeneous();
+    Some(na::Point2::new(uv.x / uv.z, uv.y / uv.z))
+}
+
+/// 对像素点去畸变，返回同一内参下的理想针孔像素坐标，可直接交给 PnP
+pub fn undistort_points<const N: usize>(
+    uvs: &[na::Point2<f64>; N],
+    cam_k: &na::Matrix3<f64>,
+    distortion: &RbtDistortion,
+) -> [na::Point2<f64>; N] {
+    if distortion.is_none() {
+        return *uvs;
+    }
+    let Some(k_inv) = cam_k.try_inverse() else {
+        return *uvs;
+    };
+    uvs.map(|uv| {
+        let norm = k_inv * uv.to_homogeneous();
+        let ideal = distortion.undistort(na::Point2::new(norm.x / norm.z, norm.y / norm.z));
+        let uv = cam_k * ideal.to_homogeneous();
+        na::Point2::new(uv.x / uv.z, uv.y / uv.z)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// OpenCV 投影与去畸变结果，由 tools/gen_distortion_fixture.py 生成
+    ///
+    /// 文件头记录生成所用的后端，可用该脚本的 `--check` 在装有 OpenCV 的环境中核对
+    struct Fixture {
+        cam_k: na::Matrix3<f64>,
+        brown_conrady: RbtDistortion,
+        fisheye: RbtDistortion,
+        /// (模型, 相机坐标系点, 畸变像素)
+        projections: Vec<(RbtDistortion, na::Point3<f64>, na::Point2<f64>)>,
+        /// (模型, 畸变像素, 理想针孔像素)
+        undistortions: Vec<(RbtDistortion, na::Point2<f64>, na::Point2<f64>)>,
+    }
+
+    fn load_fixture() -> Fixture {
+        let text = std::fs::read_to_string(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/testdata/distortion_opencv.txt"
+        ))
+        .unwrap();
+        let mut cam_k = na::Matrix3::identity();
+        let mut brown_conrady = RbtDistortion::None;
+        let mut fisheye = RbtDistortion::None;
+        let mut rows = Vec::new();
+        for line in text
+            .lines()
+            .filter(|l| !l.starts_with('#') && !l.trim().is_empty())
+        {
+            let (key, values) = line.split_once(':').unwrap();
+            let values = values
+                .split_whitespace()
+                .map(|v| v.parse::<f64>().unwrap())
+                .collect::<Vec<_>>();
+            match key {
+                "cam_k" => cam_k = na::Matrix3::from_row_slice(&values),
+                "brown_conrady_coeffs" => {
+                    brown_conrady = RbtDistortion::BrownConrady {
+                        coeffs: values.try_into().unwrap(),
+                    }
+                }
+                "fisheye_coeffs" => {
+                    fisheye = RbtDistortion::Fisheye {
+                        coeffs: values.try_into().unwrap(),
+                    }
+                }
+                _ => rows.push((key.to_string(), values)),
+            }
+        }
+        let model = |key: &str| {
+            if key.starts_with("fisheye") {
+                fisheye
+            } else {
+                brown_conrady
+            }
+        };
+        let mut projections = Vec::new();
+        let mut undistortions = Vec::new();
+        for (key, v) in rows {
+            match key.as_str() {
+                "brown_conrady" | "fisheye" => projections.push((
+                    model(&key),
+                    na::Point3::new(v[0], v[1], v[2]),
+                    na::Point2::new(v[3], v[4]),
+                )),
+                "brown_conrady_undistort" | "fisheye_undistort" => undistortions.push((
+                    model(&key),
+                    na::Point2::new(v[0], v[1]),
+                    na::Point2::new(v[2], v[3]),
+                )),
+                _ => panic!("unknown fixture key {key}"),
+            }
+        }
+        Fixture {
+            cam_k,
+            brown_conrady,
+            fisheye,
+            projections,
+            undistortions,
+        }
+    }
+
+    #[test]
+    fn test_project_matches_opencv() {
+        let fixture = load_fixture();
+        assert!(!fixture.brown_conrady.is_none() && !fixture.fisheye.is_none());
+        assert_eq!(fixture.projections.len(), 14);
+        for (model, pc, expected) in &fixture.projections {
+            let uv = project_point(pc, &fixture.cam_k, model).unwrap();
+            assert!(
+                (uv - expected).norm() < 1e-9,
+                "{model:?} {pc} {uv} {expected}"
+            );
+        }
+        assert!(
+            project_point(
+                &na::Point3::new(0.0, 0.0, -1.0),
+                &fixture.cam_k,
+                &fixture.fisheye
+            )
+            .is_none()
+        );
+    }
+
+    #[test]
+    fn test_undistort_matches_opencv() {
+        let fixture = load_fixture();
+        assert_eq!(fixture.undistortions.len(), 14);
+        for (model, distorted, expected) in &fixture.undistortions {
+            let [ideal] = undistort_points(&[*distorted], &fixture.cam_k, model);
+            assert!(
+                (ideal - expected).norm() < 1e-6,
+                "{model:?} {distorted} {ideal} {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_undistort_recovers_pinhole() {
+        let fixture = load_fixture();
+        for (model, pc, distorted) in &fixture.projections {
+            let [ideal] = undistort_points(&[*distorted], &fixture.cam_k, model);
+            let pinhole = project_point(pc, &fixture.cam_k, &RbtDistortion::None).unwrap();
+            assert!(
+                (ideal - pinhole).norm() < 1e-6,
+                "{model:?} {pc} {ideal} {pinhole}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_distortion_cfg() {
+        #[derive(Deserialize)]
+        struct Cfg {
+            distortion: RbtDistortion,
+        }
+        let cfg: Cfg = toml::from_str(
+            "distortion = { model = \"brown_conrady\", coeffs = [-0.3, 0.1, 0.0, 0.0, 0.0] }",
+        )
+        .unwrap();
+        assert_eq!(
+            cfg.distortion,
+            RbtDistortion::BrownConrady {
+                coeffs: [-0.3, 0.1, 0.0, 0.0, 0.0]
+            }
+        );
+        let cfg: Cfg = toml::from_str("distortion = { model = \"none\" }").unwrap();
+        assert!(cfg.distortion.is_none());
+        assert!(
+            toml::from_str::<Cfg>("distortion = { model = \"fisheye\", coeffs = [0.1] }").is_err()
+        );
+    }
+}
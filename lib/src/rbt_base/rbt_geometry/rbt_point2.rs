@@ -1,5 +1,6 @@
 // 目前暂时考虑尽量使用基础类型来表示数据，在方法中转换为 nalgebra 进行转换，并返回基础数据类型
 
+use crate::rbt_base::rbt_geometry::rbt_distortion::RbtDistortion;
 use crate::rbt_infra::rbt_err::{RbtError, RbtResult};
 use std::cmp::PartialEq;
 use std::ops::Deref;
@@ -43,7 +44,12 @@ impl RbtImgPoint2 {
         &self.coord_sys
     }
 
-    pub fn img_to_cam_mut(&mut self, cam_k: &na::SMatrix<f64, 3, 3>) -> RbtResult<()> {
+    /// 像素坐标转换到归一化相机坐标，同时去除镜头畸变
+    pub fn img_to_cam_mut(
+        &mut self,
+        cam_k: &na::SMatrix<f64, 3, 3>,
+        distortion: &RbtDistortion,
+    ) -> RbtResult<()> {
         if self.coord_sys == RbtImgPoint2CoordSys::CameraNorm {
             warn!("The coordinate system is already Camera");
             return Ok(());
@@ -56,8 +62,10 @@ impl RbtImgPoint2 {
             error!("adjusted z is zero");
             return Err(RbtError::StringError("adjusted z is zero".into()));
         }
-        self.point.x = adjusted.x / adjusted.z;
-        self.point.y = adjusted.y / adjusted.z;
+        self.point = distortion.undistort(na::Point2::new(
+            adjusted.x / adjusted.z,
+            adjusted.y / adjusted.z,
+        ));
         self.coord_sys = RbtImgPoint2CoordSys::CameraNorm;
         debug!("The coord system has set to camera");
         Ok(())
@@ -1,12 +1,9 @@
 use std::ops::Deref;
 
-#[derive(Clone, Debug, PartialEq)]
-pub enum RbtPoint3CoordSys {
-    Camera, // 原点位于相机中心，以相机向前方向为 Z 轴
-    Muzzle, // 原点位于测速中心，以向前方向为 Z 轴
-    Base,   // 以云台向前方向为 X 轴，向上为 Z 轴的右手坐标系
-    World,  // 根据电控端维护的绝对位资
-}
+use crate::rbt_base::rbt_geometry::rbt_tf::{RbtTf, RbtTfFrame};
+
+/// 三维点所在的坐标系，与坐标变换树一致
+pub type RbtPoint3CoordSys = RbtTfFrame;
 
 /// 3-d point
 #[derive(Clone, Debug)]
@@ -31,9 +28,10 @@ impl RbtPoint3 {
         (point, coord_sys).into()
     }
 
-    pub fn trans_to(&mut self, target_coord_sys: &RbtPoint3CoordSys) {
-        self.point = point_tansfer(&self.point, &self.coord_sys, &target_coord_sys);
-        self.coord_sys = target_coord_sys.clone();
+    /// 将点变换到目标坐标系，`tf` 需对应该点的观测时刻
+    pub fn trans_to(&mut self, target_coord_sys: &RbtPoint3CoordSys, tf: &RbtTf) {
+        self.point = tf.lookup(self.coord_sys, *target_coord_sys) * self.point;
+        self.coord_sys = *target_coord_sys;
     }
 }
 
@@ -58,13 +56,3 @@ impl From<RbtPoint3> for na::Point3<f64> {
         rbt_point.point
     }
 }
-
-fn point_tansfer(
-    point: &na::Point3<f64>,
-    source_coord_sys: &RbtPoint3CoordSys,
-    target_coord_sys: &RbtPoint3CoordSys,
-) -> na::Point3<f64> {
-    match (source_coord_sys, target_coord_sys) {
-        (_, _) => na::Point3::new(point.x, point.y, point.z),
-    }
-}
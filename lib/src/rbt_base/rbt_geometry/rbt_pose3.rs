@@ -1,7 +1,8 @@
 use crate::rbt_base::rbt_algorithm::rbt_ippe::{ARMOR_LIGHT_HEIGHT, ARMOR_LIGHT_WEIGHT};
+use crate::rbt_base::rbt_geometry::rbt_tf::{RbtTf, RbtTfFrame};
 use crate::rbt_infra::rbt_err::{RbtError, RbtResult};
 use na::{Isometry3, Vector3};
-use tracing::error;
+use tracing::{error, warn};
 
 #[derive(PartialEq, Clone, Debug)]
 pub enum RbtPoseCoordSys {
@@ -11,53 +12,24 @@ pub enum RbtPoseCoordSys {
     WorldXyz,
 }
 
-/// 只对应坐标轴变换，实际使用时还需要左乘 Pitch 轴角度
-pub const CAMERA_AXES_TO_BODY_AXES_ROTATION: na::Rotation<f64, 3> =
-    na::Rotation3::from_matrix_unchecked(nalgebra::Matrix3::new(
-        0.0, 0.0, 1.0, // X_cam → Z_body
-        -1.0, 0.0, 0.0, // Y_cam → -X_body
-        0.0, -1.0, 0.0, // Z_cam → -Y_body
-    ));
-
 impl RbtPoseCoordSys {
+    /// 对应的变换树坐标系，ArmorXyz 依赖具体装甲板，不在变换树中
+    fn tf_frame(&self) -> Option<RbtTfFrame> {
+        match self {
+            Self::Camera => Some(RbtTfFrame::Camera),
+            Self::BaseXyz => Some(RbtTfFrame::Base),
+            Self::WorldXyz => Some(RbtTfFrame::World),
+            Self::ArmorXyz => None,
+        }
+    }
+
     // 根据当前坐标系和目标坐标系，给出对应的位姿变换
-    fn get_isometry(&self, target_coord: &Self) -> Isometry3<f64> {
-        match (self, target_coord) {
-            (Self::Camera, Self::BaseXyz) => {
-                let translation = na::Translation3::new(0.0, 15.0, 430.0);
-                // 把相机坐标轴（右-下-前）表示为在机体坐标系（前-左-上）中的方向
-                let pitch_rad = 0_f64.to_radians();
-                let pitch_rotation = na::Rotation3::from_euler_angles(0.0, pitch_rad, 0.0);
-                let total_rotation = pitch_rotation * CAMERA_AXES_TO_BODY_AXES_ROTATION;
-                let isometry = na::Isometry3::from_parts(
-                    translation,
-                    <na::UnitQuaternion<f64>>::from(total_rotation),
-                );
-                isometry
-            }
-            (Self::BaseXyz, Self::WorldXyz) => {
-                // 假设Yaw为0，实际使用时应根据机器人当前朝向设置
-                let yaw_rad = 0_f64.to_radians();
-                let yaw_rotation = na::Rotation3::from_euler_angles(0.0, 0.0, yaw_rad);
-                let isometry = na::Isometry3::from_parts(
-                    na::Translation3::new(0.0, 0.0, 0.0),
-                    <na::UnitQuaternion<f64>>::from(yaw_rotation),
-                );
-                isometry
-            }
-            (Self::WorldXyz, Self::BaseXyz) => {
-                // 反向转换
-                let yaw_rad = 0_f64.to_radians();
-                let yaw_rotation = na::Rotation3::from_euler_angles(0.0, 0.0, -yaw_rad);
-                let isometry = na::Isometry3::from_parts(
-                    na::Translation3::new(0.0, 0.0, 0.0),
-                    <na::UnitQuaternion<f64>>::from(yaw_rotation),
-                );
-                isometry
-            }
+    fn get_isometry(&self, target_coord: &Self, tf: &RbtTf) -> Isometry3<f64> {
+        match (self.tf_frame(), target_coord.tf_frame()) {
+            (Some(source), Some(target)) => tf.lookup(source, target),
             _ => {
-                let isometry = Isometry3::new(na::Vector3::new(0.0, 0.0, 0.0), na::zero());
-                isometry
+                warn!("不支持 {:?} 到 {:?} 的坐标变换", self, target_coord);
+                Isometry3::identity()
             }
         }
     }
@@ -145,8 +117,9 @@ impl RbtPose3 {
         ]
     }
 
-    pub fn coord_trans_mut(&mut self, target_coord: RbtPoseCoordSys) {
-        let rigid = self.coord_sys.get_isometry(&target_coord);
+    /// 将位姿变换到目标坐标系，`tf` 需对应该位姿的拍摄时刻
+    pub fn coord_trans_mut(&mut self, target_coord: RbtPoseCoordSys, tf: &RbtTf) {
+        let rigid = self.coord_sys.get_isometry(&target_coord, tf);
         let new_isometry = rigid * self.isometry;
         self.isometry = new_isometry;
         self.coord_sys = target_coord;
@@ -206,6 +179,10 @@ impl RbtPose3 {
     pub fn rotation(&self) -> &na::UnitQuaternion<f64> {
         &self.isometry.rotation
     }
+
+    pub fn coord_sys(&self) -> &RbtPoseCoordSys {
+        &self.coord_sys
+    }
 }
 
 /// 实现从 RbtPose3 到 rerun::Transform3D 的转换
@@ -241,3 +218,66 @@ impl From<&RbtPose3> for rerun::Transform3D {
             ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rbt_infra::rbt_cfg::TfCfg;
+    use crate::rbt_mod::rbt_comm::rbt_comm_sync::GimbalAttitude;
+
+    fn tf(yaw: f32, pitch: f32) -> RbtTf {
+        let cfg = TfCfg::test_cfg();
+        RbtTf::new(&cfg).with_attitude(&GimbalAttitude {
+            yaw,
+            pitch,
+            roll: 0.0,
+        })
+    }
+
+    /// 相机正前方 `distance` 处的位姿
+    fn camera_pose(distance: f64) -> RbtPose3 {
+        RbtPose3::new_camera(Isometry3::translation(0.0, 0.0, distance))
+    }
+
+    #[test]
+    fn test_camera_to_base_level() {
+        // 云台水平时与原先写死的外参一致
+        let mut pose = camera_pose(1000.0);
+        pose.coord_trans_mut(RbtPoseCoordSys::BaseXyz, &tf(0.0, 0.0));
+        let t = pose.translation().vector;
+        assert!((t - Vector3::new(1000.0, 15.0, 430.0)).norm() < 1e-9);
+        assert_eq!(pose.coord_sys(), &RbtPoseCoordSys::BaseXyz);
+    }
+
+    #[test]
+    fn test_camera_to_base_pitch_up() {
+        let mut pose = camera_pose(1000.0);
+        pose.coord_trans_mut(RbtPoseCoordSys::BaseXyz, &tf(0.0, 10.0));
+        let t = pose.translation().vector;
+        // 相对 pitch 轴中心的仰角增加 10 度，距离不变
+        let elevation = (t.z - 380.0).atan2(t.x).to_degrees();
+        let expected = 50f64.atan2(1000.0).to_degrees() + 10.0;
+        assert!((elevation - expected).abs() < 1e-9);
+        assert!((t.y - 15.0).abs() < 1e-9);
+        assert!(((t.x).hypot(t.z - 380.0) - 1000f64.hypot(50.0)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_world_stable_while_gimbal_moves() {
+        // 同一个世界坐标系中的目标，云台转到不同角度观察，变换回世界坐标系后应保持不变
+        let target = Isometry3::translation(3000.0, 1200.0, 600.0);
+        for (yaw, pitch) in [(0.0, 0.0), (30.0, 5.0), (-75.0, -8.0), (400.0, 12.0)] {
+            let tf = tf(yaw, pitch);
+            let camera_to_world = tf.lookup(RbtTfFrame::Camera, RbtTfFrame::World);
+            let mut pose = RbtPose3::new_camera(camera_to_world.inverse() * target);
+            pose.coord_trans_mut(RbtPoseCoordSys::WorldXyz, &tf);
+            assert!((pose.translation().vector - target.translation.vector).norm() < 1e-6);
+
+            pose.coord_trans_mut(RbtPoseCoordSys::BaseXyz, &tf);
+            let base_yaw = pose.translation().y.atan2(pose.translation().x).to_degrees();
+            let world_yaw = 1200f64.atan2(3000.0).to_degrees();
+            let diff = (world_yaw - yaw as f64 - base_yaw).to_radians();
+            assert!(diff.sin().abs() < 1e-9 && diff.cos() > 0.0);
+        }
+    }
+}
@@ -0,0 +1,279 @@
+//! 坐标变换树
+//!
+//! 所有坐标系之间的变换都由这棵树给出，结构如下：
+//!
+//! ```text
+//! World
+//! └── Base            yaw 关节，动态，来自下位机
+//!     └── Gimbal      pitch/roll 关节，动态，来自下位机；平移为静态外参
+//!         ├── Camera  相机外参，静态
+//!         └── Muzzle  枪口外参，静态
+//! ```
+//!
+//! 静态外参来自 `TfCfg`，动态关节来自下位机上报的 `SensFrame`。
+//! `RbtTfTree` 缓存一段时间内的关节状态，按时间查询得到某一时刻的快照 `RbtTf`，
+//! `RbtPose3` 和 `RbtPoint3` 都通过 `RbtTf` 完成坐标变换。
+
+use na::Isometry3;
+use tokio::time::Instant;
+
+use crate::rbt_infra::rbt_cfg::TfCfg;
+use crate::rbt_mod::rbt_comm::rbt_comm_frame::SensFrame;
+use crate::rbt_mod::rbt_comm::rbt_comm_sync::{GimbalAttitude, SensFrameBuffer};
+
+/// 坐标变换树中的坐标系
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RbtTfFrame {
+    Camera, // 原点位于相机中心，右-下-前
+    Muzzle, // 原点位于测速中心，右-下-前
+    Gimbal, // 原点位于 pitch 轴中心，前-左-上，随云台转动
+    Base,   // 原点位于 yaw 轴底部，以云台向前方向为 X 轴，向上为 Z 轴的右手坐标系
+    World,  // 根据电控端维护的绝对位姿，不随云台转动
+}
+
+/// 光学坐标轴（右-下-前）到机体坐标轴（前-左-上）的旋转，相机和枪口共用
+pub const CAMERA_AXES_TO_BODY_AXES_ROTATION: na::Rotation<f64, 3> =
+    na::Rotation3::from_matrix_unchecked(nalgebra::Matrix3::new(
+        0.0, 0.0, 1.0, // X_cam → Z_body
+        -1.0, 0.0, 0.0, // Y_cam → -X_body
+        0.0, -1.0, 0.0, // Z_cam → -Y_body
+    ));
+
+/// 某一时刻的变换树快照
+///
+/// 云台姿态均为角度制，pitch 向上为正，yaw 逆时针为正
+#[derive(Clone, Debug, PartialEq)]
+pub struct RbtTf {
+    cam_to_gimbal: Isometry3<f64>,
+    muzzle_to_gimbal: Isometry3<f64>,
+    gimbal_to_base: na::Translation3<f64>,
+    attitude: GimbalAttitude,
+}
+
+impl RbtTf {
+    /// 根据静态外参构建，云台姿态为零
+    pub fn new(cfg: &TfCfg) -> Self {
+        // 先把相机坐标轴转到云台坐标轴，再叠加安装误差
+        let cam_rotation = cfg.cam_to_gimbal_rotation() * CAMERA_AXES_TO_BODY_AXES_ROTATION;
+        Self {
+            cam_to_gimbal: Isometry3::from_parts(
+                cfg.cam_to_gimbal_translation(),
+                na::UnitQuaternion::from(cam_rotation),
+            ),
+            muzzle_to_gimbal: Isometry3::from_parts(
+                cfg.muzzle_to_gimbal_translation(),
+                na::UnitQuaternion::from(CAMERA_AXES_TO_BODY_AXES_ROTATION),
+            ),
+            gimbal_to_base: cfg.gimbal_to_base_translation(),
+            attitude: GimbalAttitude::default(),
+        }
+    }
+
+    /// 设置该时刻的云台姿态
+    pub fn with_attitude(mut self, attitude: &GimbalAttitude) -> Self {
+        self.attitude = *attitude;
+        self
+    }
+
+    pub fn attitude(&self) -> &GimbalAttitude {
+        &self.attitude
+    }
+
+    /// 父坐标系，以及当前坐标系到父坐标系的变换
+    fn parent(&self, frame: RbtTfFrame) -> Option<(RbtTfFrame, Isometry3<f64>)> {
+        match frame {
+            RbtTfFrame::Camera => Some((RbtTfFrame::Gimbal, self.cam_to_gimbal)),
+            RbtTfFrame::Muzzle => Some((RbtTfFrame::Gimbal, self.muzzle_to_gimbal)),
+            RbtTfFrame::Gimbal => {
+                let rotation = na::UnitQuaternion::from_euler_angles(
+                    (self.attitude.roll as f64).to_radians(),
+                    // 绕 Y（向左）轴正转时 X 轴朝下，因此抬头对应负角度
+                    -(self.attitude.pitch as f64).to_radians(),
+                    0.0,
+                );
+                Some((
+                    RbtTfFrame::Base,
+                    Isometry3::from_parts(self.gimbal_to_base, rotation),
+                ))
+            }
+            RbtTfFrame::Base => Some((
+                RbtTfFrame::World,
+                Isometry3::rotation(na::Vector3::z() * (self.attitude.yaw as f64).to_radians()),
+            )),
+            RbtTfFrame::World => None,
+        }
+    }
+
+    /// `frame` → World
+    pub fn to_world(&self, frame: RbtTfFrame) -> Isometry3<f64> {
+        let mut isometry = Isometry3::identity();
+        let mut current = frame;
+        while let Some((parent, to_parent)) = self.parent(current) {
+            isometry = to_parent * isometry;
+            current = parent;
+        }
+        isometry
+    }
+
+    /// `source` → `target`，作用于 `source` 坐标系下的坐标得到 `target` 坐标系下的坐标
+    pub fn lookup(&self, source: RbtTfFrame, target: RbtTfFrame) -> Isometry3<f64> {
+        if source == target {
+            return Isometry3::identity();
+        }
+        self.to_world(target).inverse() * self.to_world(source)
+    }
+}
+
+/// 带时间戳的坐标变换树
+///
+/// 静态外参只保存一份，动态关节按时间缓存，查询时线性插值
+#[derive(Clone, Debug)]
+pub struct RbtTfTree {
+    tf: RbtTf,
+    joints: SensFrameBuffer,
+}
+
+impl RbtTfTree {
+    pub fn new(cfg: &TfCfg) -> Self {
+        Self {
+            tf: RbtTf::new(cfg),
+            joints: SensFrameBuffer::new(cfg.joint_buffer_len()),
+        }
+    }
+
+    /// 写入下位机上报的关节状态
+    pub fn push_joint_state(&mut self, frame: SensFrame) {
+        self.joints.push(frame);
+    }
+
+    pub fn joints(&self) -> &SensFrameBuffer {
+        &self.joints
+    }
+
+    /// 云台姿态为零时的快照，用于没有下位机的场景
+    pub fn static_tf(&self) -> &RbtTf {
+        &self.tf
+    }
+
+    /// `at` 时刻的快照，超出缓存的时间范围时返回 None
+    pub fn at(&self, at: Instant) -> Option<RbtTf> {
+        let attitude = self.joints.attitude_at(at)?;
+        Some(self.tf.clone().with_attitude(&attitude))
+    }
+
+    /// 最新关节状态对应的快照
+    pub fn latest(&self) -> Option<RbtTf> {
+        let frame = self.joints.latest()?;
+        Some(self.tf.clone().with_attitude(&frame.data().into()))
+    }
+
+    /// `at` 时刻 `source` → `target` 的变换
+    pub fn lookup(
+        &self,
+        source: RbtTfFrame,
+        target: RbtTfFrame,
+        at: Instant,
+    ) -> Option<Isometry3<f64>> {
+        Some(self.at(at)?.lookup(source, target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rbt_mod::rbt_comm::rbt_comm_frame::{SelfFraction, SensData, TaskMode};
+    use tokio::time::Duration;
+
+    const FRAMES: [RbtTfFrame; 5] = [
+        RbtTfFrame::Camera,
+        RbtTfFrame::Muzzle,
+        RbtTfFrame::Gimbal,
+        RbtTfFrame::Base,
+        RbtTfFrame::World,
+    ];
+
+    fn tf_cfg() -> TfCfg {
+        TfCfg::test_cfg().with_cam_rpy_deg([0.5, -1.0, 2.0])
+    }
+
+    fn sens_frame(yaw: f32, pitch: f32, time_stamp: Instant) -> SensFrame {
+        let data = SensData {
+            task_mode: TaskMode::AutoShot,
+            self_fraction: SelfFraction::Red,
+            bullet_speed: 24.0,
+            gimbal_roll: 0.0,
+            gimbal_yaw: yaw,
+            gimbal_pitch: pitch,
+            yaw_speed: 0.0,
+            mcu_time_us: 0,
+            sync_host_time_us: 0,
+            sync_mcu_recv_us: 0,
+        };
+        SensFrame::with_time_stamp(data, time_stamp)
+    }
+
+    #[test]
+    fn test_lookup_composes() {
+        let tf = RbtTf::new(&tf_cfg()).with_attitude(&GimbalAttitude {
+            yaw: 37.0,
+            pitch: -6.0,
+            roll: 1.5,
+        });
+        for a in FRAMES {
+            assert_eq!(tf.lookup(a, a), Isometry3::identity());
+            for b in FRAMES {
+                for c in FRAMES {
+                    let direct = tf.lookup(a, c);
+                    let chained = tf.lookup(b, c) * tf.lookup(a, b);
+                    assert!((direct.to_homogeneous() - chained.to_homogeneous()).norm() < 1e-9);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_muzzle_in_camera() {
+        // 理想安装时，枪口相对相机的偏移只与两者位置差有关，与云台姿态无关
+        let cfg = TfCfg::test_cfg();
+        for pitch in [0.0, 15.0] {
+            let tf = RbtTf::new(&cfg).with_attitude(&GimbalAttitude {
+                yaw: 90.0,
+                pitch,
+                roll: 0.0,
+            });
+            let muzzle = tf.lookup(RbtTfFrame::Muzzle, RbtTfFrame::Camera);
+            assert!(
+                (muzzle.translation.vector - na::Vector3::new(15.0, 50.0, 120.0)).norm() < 1e-9
+            );
+            assert!(muzzle.rotation.angle() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_tree_lookup_at_time() {
+        let mut tree = RbtTfTree::new(&tf_cfg());
+        let t0 = Instant::now();
+        assert!(tree.latest().is_none());
+        tree.push_joint_state(sens_frame(0.0, 0.0, t0));
+        tree.push_joint_state(sens_frame(20.0, 4.0, t0 + Duration::from_millis(10)));
+
+        let tf = tree.at(t0 + Duration::from_millis(5)).unwrap();
+        assert!((tf.attitude().yaw - 10.0).abs() < 1e-4);
+        assert!((tf.attitude().pitch - 2.0).abs() < 1e-4);
+
+        let base_to_world = tree
+            .lookup(
+                RbtTfFrame::Base,
+                RbtTfFrame::World,
+                t0 + Duration::from_millis(5),
+            )
+            .unwrap();
+        assert!((base_to_world.rotation.angle().to_degrees() - 10.0).abs() < 1e-3);
+        assert!(base_to_world.translation.vector.norm() < 1e-9);
+
+        // 超出缓存范围
+        assert!(tree.at(t0 - Duration::from_millis(1)).is_none());
+        assert!(tree.at(t0 + Duration::from_millis(11)).is_none());
+        assert_eq!(tree.latest().unwrap().attitude().yaw, 20.0);
+    }
+}
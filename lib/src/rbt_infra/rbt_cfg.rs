@@ -2,7 +2,11 @@ use serde::{Deserialize, Serialize};
 use std::path::Path;
 
 use crate::rbt_bail_error;
+use crate::rbt_base::rbt_geometry::rbt_distortion::RbtDistortion;
 use crate::rbt_infra::rbt_err::{RbtError, RbtResult};
+use crate::rbt_mod::rbt_detector::DetectorKind;
+use crate::rbt_mod::rbt_detector::rbt_backend::OrtEp;
+use crate::rbt_mod::rbt_detector::rbt_yolo::NmsMode;
 use crate::rbt_mod::rbt_estimator::rbt_enemy_dynamic_model::EnemyFaction;
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -62,19 +66,142 @@ pub struct DetectorCfg {
     pub armor_detect_model_path: String,
     pub armor_detect_engine_path: String,
     pub buff_detect_model_path: String,
+    armor_label_path: String,
     pub camera_img_width: u64,
     pub camera_img_height: u64,
     pub infer_img_width: u64,
     pub infer_img_height: u64,
-    pub infer_full_height: u64,
     pub confidence_threshold: f32,
-    pub ort_ep: String,
+    pub nms_iou_threshold: f32,
+    pub nms_mode: NmsMode,
+    pub soft_nms_sigma: f32,
+    pub ort_ep: OrtEp,
+    pub openvino_device: String,
+    pub infer_threads: usize,
+    pub detector_kind: DetectorKind,
+}
+
+impl DetectorCfg {
+    /// 装甲板模型的标签表，模型元数据中没有标签表时使用；相对路径与模型路径一样以工作目录为基准
+    pub fn armor_label_path(&self) -> std::path::PathBuf {
+        std::path::PathBuf::from(&self.armor_label_path)
+    }
+
+    /// 相机分辨率 (宽, 高)
+    pub fn camera_img_size(&self) -> (u32, u32) {
+        (self.camera_img_width as u32, self.camera_img_height as u32)
+    }
+
+    /// 模型输入尺寸 (宽, 高)
+    pub fn infer_img_size(&self) -> (u32, u32) {
+        (self.infer_img_width as u32, self.infer_img_height as u32)
+    }
+}
+
+/// 传统视觉灯条检测器参数，像素单位均为相机原图像素
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct LightBarCfg {
+    pub brightness_threshold: u8,     // 灯条二值化亮度阈值
+    pub color_threshold: f32,         // 灯条平均蓝红通道差的最小值，用于区分颜色
+    pub min_bar_length: f32,          // 灯条最短长度
+    pub bar_aspect_ratio: [f32; 2],   // 灯条长宽比范围
+    pub max_bar_tilt_deg: f32,        // 灯条相对竖直方向的最大倾角
+    pub max_pair_angle_diff_deg: f32, // 配对灯条的最大夹角
+    pub min_pair_length_ratio: f32,   // 配对灯条短长比的最小值
+    pub max_pair_tilt_deg: f32,       // 灯条中心连线相对水平方向的最大倾角
+    pub small_armor_ratio: [f32; 2],  // 小装甲板灯条间距与灯条长度之比的范围
+    pub large_armor_ratio: [f32; 2],  // 大装甲板灯条间距与灯条长度之比的范围
+}
+
+/// 装甲板角点亚像素优化参数
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CornerRefineCfg {
+    pub enable: bool,
+    pub background_threshold: u8, // 亮度不高于该值的像素视为背景
+    pub search_margin: f32,       // 搜索区域向灯条两侧和两端外扩的距离，与灯条长度之比
+    pub max_shift: f32,           // 优化后端点偏移超过该像素数时保留原值
+}
+
+/// PnP 位姿 Levenberg–Marquardt 优化参数
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PoseRefineCfg {
+    pub enable: bool,
+    pub max_iters: usize,     // 最大迭代次数
+    pub step_tolerance: f64,  // 旋转增量 rad 与相对平移增量之和小于该值视为收敛
+    pub cost_tolerance: f64,  // 重投影误差平方和的相对下降小于该值视为收敛
+    pub fixed_pitch: bool,    // 已知云台姿态时固定装甲板仰角，只优化偏航和平移
+    pub armor_pitch_deg: f64, // 装甲板法线相对水平面的仰角
+}
+
+/// 能量机关相关参数，像素单位均为相机原图像素，长度单位 mm
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct BuffCfg {
+    pub brightness_threshold: u8, // 二值化亮度阈值
+    pub color_threshold: f32,     // 己方颜色通道与另一颜色通道之差的最小值
+    pub close_radius: u8,         // 闭运算半径，把点阵灯珠连成整块
+    pub r_logo_area: [f32; 2],    // R 标轮廓面积范围
+    pub min_blade_area: f32,      // 扇叶轮廓的最小面积
+    pub max_hub_hole_ratio: f32,  // 靠近 R 标一侧的孔洞面积与扇叶面积之比超过该值视为已激活扇叶
+    pub blade_radius: f64,        // R 标中心到待击打装甲中心的距离
+    pub target_radius: f64,       // 待击打装甲的半径
+    pub big_buff_omega: [f64; 2], // 大符转速角频率范围 rad/s
+    fit_window_ms: u64,
+    min_fit_duration_ms: u64,
+    lost_timeout_ms: u64,
+}
+
+impl BuffCfg {
+    /// 只用最近这段时间内的观测拟合转速
+    #[inline(always)]
+    pub fn fit_window(&self) -> tokio::time::Duration {
+        tokio::time::Duration::from_millis(self.fit_window_ms)
+    }
+
+    /// 大符观测时长不足时按匀速预测
+    #[inline(always)]
+    pub fn min_fit_duration(&self) -> tokio::time::Duration {
+        tokio::time::Duration::from_millis(self.min_fit_duration_ms)
+    }
+
+    /// 超过该时长没有观测到扇叶则认为能量机关已重置，清空历史
+    #[inline(always)]
+    pub fn lost_timeout(&self) -> tokio::time::Duration {
+        tokio::time::Duration::from_millis(self.lost_timeout_ms)
+    }
+}
+
+/// 图像源类型
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceKind {
+    Image, // 单张图片
+    Dir,   // 图片目录
+    Video, // MJPEG 编码的 AVI 录像
+}
+
+/// 图像源配置
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SourceCfg {
+    pub kind: SourceKind,
+    path: String,
+    pub repeat: u64,
+}
+
+impl SourceCfg {
+    /// 相对路径以工作目录为基准
+    pub fn path(&self) -> std::path::PathBuf {
+        std::path::PathBuf::from(&self.path)
+    }
 }
 
 /// 相机相关配置
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct CamCfg {
+    /// 相机原图分辨率下的内参，行优先
     cam_k: [f64; 9], // 设为私有，通过方法暴露
+    /// 镜头畸变，缺省为理想针孔
+    #[serde(default)]
+    distortion: RbtDistortion,
 }
 
 impl CamCfg {
@@ -82,6 +209,85 @@ impl CamCfg {
     pub fn cam_k(&self) -> nalgebra::Matrix3<f64> {
         nalgebra::Matrix3::from_row_slice(&self.cam_k)
     }
+
+    pub fn distortion(&self) -> &RbtDistortion {
+        &self.distortion
+    }
+}
+
+/// 坐标变换树的静态外参，云台坐标系为 前-左-上，原点位于 pitch 轴中心，单位 mm
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TfCfg {
+    cam_to_gimbal_xyz: [f64; 3],
+    cam_to_gimbal_rpy_deg: [f64; 3],
+    muzzle_to_gimbal_xyz: [f64; 3],
+    gimbal_to_base_xyz: [f64; 3],
+    joint_buffer_len: usize,
+}
+
+impl TfCfg {
+    /// 相机光心在云台坐标系中的位置 mm
+    pub fn cam_to_gimbal_translation(&self) -> nalgebra::Translation3<f64> {
+        let [x, y, z] = self.cam_to_gimbal_xyz;
+        nalgebra::Translation3::new(x, y, z)
+    }
+
+    /// 相机安装误差，在云台坐标系中按 roll/pitch/yaw 给出，理想安装为单位阵
+    pub fn cam_to_gimbal_rotation(&self) -> nalgebra::Rotation3<f64> {
+        let [roll, pitch, yaw] = self.cam_to_gimbal_rpy_deg.map(f64::to_radians);
+        nalgebra::Rotation3::from_euler_angles(roll, pitch, yaw)
+    }
+
+    /// 测速中心在云台坐标系中的位置 mm
+    pub fn muzzle_to_gimbal_translation(&self) -> nalgebra::Translation3<f64> {
+        let [x, y, z] = self.muzzle_to_gimbal_xyz;
+        nalgebra::Translation3::new(x, y, z)
+    }
+
+    /// 云台 pitch 轴中心在机体坐标系（原点位于 yaw 轴）中的位置 mm
+    pub fn gimbal_to_base_translation(&self) -> nalgebra::Translation3<f64> {
+        let [x, y, z] = self.gimbal_to_base_xyz;
+        nalgebra::Translation3::new(x, y, z)
+    }
+
+    /// 缓存的云台关节状态帧数
+    #[inline(always)]
+    pub fn joint_buffer_len(&self) -> usize {
+        self.joint_buffer_len
+    }
+}
+
+#[cfg(test)]
+impl TfCfg {
+    /// 测试用外参：相机理想安装于 pitch 轴左 15mm、上 50mm，枪口在前 120mm，pitch 轴高于 yaw 轴 380mm
+    pub(crate) fn test_cfg() -> Self {
+        Self {
+            cam_to_gimbal_xyz: [0.0, 15.0, 50.0],
+            cam_to_gimbal_rpy_deg: [0.0, 0.0, 0.0],
+            muzzle_to_gimbal_xyz: [120.0, 0.0, 0.0],
+            gimbal_to_base_xyz: [0.0, 0.0, 380.0],
+            joint_buffer_len: 64,
+        }
+    }
+
+    /// 在测试外参的基础上加入相机安装误差
+    pub(crate) fn with_cam_rpy_deg(mut self, rpy_deg: [f64; 3]) -> Self {
+        self.cam_to_gimbal_rpy_deg = rpy_deg;
+        self
+    }
+}
+
+/// 敌方中心解算参数，单位 mm
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SolverCfg {
+    /// 距离 1m 处单块装甲板位置的测量标准差，随距离线性增长
+    pub armor_position_std: f64,
+    /// 半径先验的标准差，只看到一块装甲板时决定沿法向的不确定度
+    pub radius_prior_std: f64,
+    /// 估计器尚未给出半径时使用的先验半径
+    pub default_armor_radius: f64,
+    /// IPPE 两个候选解的重投影误差比值不低于该值时，视为歧义，看到多块装甲板时由车体中心约束挑选
+    pub ambiguity_threshold: f64,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -98,15 +304,80 @@ impl EstimatorCfg {
     }
 }
 
+/// 下位机通讯方式
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CommDevice {
+    Serial,
+    Udp,
+}
+
+/// 通讯相关配置
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CommCfg {
+    pub device: CommDevice,
+    pub serial_port: String,
+    pub serial_baud_rate: u32,
+    pub udp_local_addr: std::net::SocketAddr,
+    pub udp_remote_addr: std::net::SocketAddr,
+    timeout_ms: u64,
+}
+
+impl CommCfg {
+    /// 单次收发的超时时间
+    #[inline(always)]
+    pub fn timeout(&self) -> tokio::time::Duration {
+        tokio::time::Duration::from_millis(self.timeout_ms)
+    }
+}
+
+/// 控制线程相关配置
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ControlCfg {
+    send_period_ms: u64,
+    latency_ms: u64,
+    max_predict_ms: u64,
+    pub fire_tolerance_deg: f64,
+}
+
+impl ControlCfg {
+    /// 控制指令发送周期
+    #[inline(always)]
+    pub fn send_period(&self) -> tokio::time::Duration {
+        tokio::time::Duration::from_millis(self.send_period_ms)
+    }
+
+    /// 从发送指令到云台响应的总延迟，预测时额外外推这段时间
+    #[inline(always)]
+    pub fn latency(&self) -> tokio::time::Duration {
+        tokio::time::Duration::from_millis(self.latency_ms)
+    }
+
+    /// 目标状态超过该时长未更新则不再预测
+    #[inline(always)]
+    pub fn max_predict(&self) -> tokio::time::Duration {
+        tokio::time::Duration::from_millis(self.max_predict_ms)
+    }
+}
+
 /// 总配置
 #[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct RbtCfg {
     pub game_cfg: GameCfg,
     pub general_cfg: GeneralCfg,
     pub detector_cfg: DetectorCfg,
+    pub lightbar_cfg: LightBarCfg,
+    pub corner_refine_cfg: CornerRefineCfg,
+    pub pose_refine_cfg: PoseRefineCfg,
+    pub buff_cfg: BuffCfg,
+    pub source_cfg: SourceCfg,
     pub cam_cfg: CamCfg,
+    pub tf_cfg: TfCfg,
     pub logger_cfg: LoggerCfg,
+    pub solver_cfg: SolverCfg,
     pub estimator_cfg: EstimatorCfg,
+    pub comm_cfg: CommCfg,
+    pub control_cfg: ControlCfg,
 }
 
 impl RbtCfg {
@@ -138,6 +409,146 @@ impl RbtCfg {
                 format!("Bullet speed = {} > 25.0", self.general_cfg.bullet_speed).to_string()
             ));
         }
+        if self.comm_cfg.timeout_ms == 0 {
+            rbt_bail_error!(RbtError::InvalidConfig(
+                "comm_cfg/timeout_ms must be greater than 0".to_string()
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.detector_cfg.nms_iou_threshold) {
+            rbt_bail_error!(RbtError::InvalidConfig(format!(
+                "detector_cfg/nms_iou_threshold = {} not in [0, 1]",
+                self.detector_cfg.nms_iou_threshold
+            )));
+        }
+        if self.detector_cfg.soft_nms_sigma <= 0.0 {
+            rbt_bail_error!(RbtError::InvalidConfig(format!(
+                "detector_cfg/soft_nms_sigma = {} <= 0.0",
+                self.detector_cfg.soft_nms_sigma
+            )));
+        }
+        for (name, [min, max]) in [
+            ("bar_aspect_ratio", self.lightbar_cfg.bar_aspect_ratio),
+            ("small_armor_ratio", self.lightbar_cfg.small_armor_ratio),
+            ("large_armor_ratio", self.lightbar_cfg.large_armor_ratio),
+        ] {
+            if min <= 0.0 || min >= max {
+                rbt_bail_error!(RbtError::InvalidConfig(format!(
+                    "lightbar_cfg/{} = [{}, {}] is not a valid range",
+                    name, min, max
+                )));
+            }
+        }
+        if self.corner_refine_cfg.search_margin <= 0.0 || self.corner_refine_cfg.max_shift <= 0.0 {
+            rbt_bail_error!(RbtError::InvalidConfig(format!(
+                "corner_refine_cfg/search_margin = {} and max_shift = {} must be positive",
+                self.corner_refine_cfg.search_margin, self.corner_refine_cfg.max_shift
+            )));
+        }
+        let pose_refine_cfg = &self.pose_refine_cfg;
+        if pose_refine_cfg.step_tolerance <= 0.0 || pose_refine_cfg.cost_tolerance <= 0.0 {
+            rbt_bail_error!(RbtError::InvalidConfig(format!(
+                "pose_refine_cfg/step_tolerance = {} and cost_tolerance = {} must be positive",
+                pose_refine_cfg.step_tolerance, pose_refine_cfg.cost_tolerance
+            )));
+        }
+        for (name, [min, max]) in [
+            ("r_logo_area", self.buff_cfg.r_logo_area.map(f64::from)),
+            ("big_buff_omega", self.buff_cfg.big_buff_omega),
+        ] {
+            if min <= 0.0 || min >= max {
+                rbt_bail_error!(RbtError::InvalidConfig(format!(
+                    "buff_cfg/{} = [{}, {}] is not a valid range",
+                    name, min, max
+                )));
+            }
+        }
+        if self.buff_cfg.blade_radius <= 0.0 || self.buff_cfg.target_radius <= 0.0 {
+            rbt_bail_error!(RbtError::InvalidConfig(format!(
+                "buff_cfg/blade_radius = {} and target_radius = {} must be positive",
+                self.buff_cfg.blade_radius, self.buff_cfg.target_radius
+            )));
+        }
+        // 检测头最大步长为 32，模型输入尺寸必须是 32 的倍数
+        let (infer_width, infer_height) = self.detector_cfg.infer_img_size();
+        if [infer_width, infer_height]
+            .iter()
+            .any(|&len| len == 0 || len % 32 != 0)
+        {
+            rbt_bail_error!(RbtError::InvalidConfig(format!(
+                "detector_cfg/infer_img_size {}x{} must be a positive multiple of 32",
+                infer_width, infer_height
+            )));
+        }
+        // 关键点已还原到相机原图像素坐标，内参也必须是原图分辨率下的
+        let (cam_width, cam_height) = self.detector_cfg.camera_img_size();
+        let cam_k = self.cam_cfg.cam_k();
+        let (cx, cy) = (cam_k[(0, 2)], cam_k[(1, 2)]);
+        if !(0.0..cam_width as f64).contains(&cx) || !(0.0..cam_height as f64).contains(&cy) {
+            rbt_bail_error!(RbtError::InvalidConfig(format!(
+                "cam_cfg/cam_k principal point ({}, {}) is outside the {}x{} camera image",
+                cx, cy, cam_width, cam_height
+            )));
+        }
+        if !self.cam_cfg.distortion.is_finite() {
+            rbt_bail_error!(RbtError::InvalidConfig(format!(
+                "cam_cfg/distortion = {:?} must be finite",
+                self.cam_cfg.distortion
+            )));
+        }
+        let solver_cfg = &self.solver_cfg;
+        if solver_cfg.armor_position_std <= 0.0
+            || solver_cfg.radius_prior_std <= 0.0
+            || solver_cfg.default_armor_radius <= 0.0
+        {
+            rbt_bail_error!(RbtError::InvalidConfig(format!(
+                "solver_cfg = {:?} must be positive",
+                solver_cfg
+            )));
+        }
+        if !(0.0..=1.0).contains(&solver_cfg.ambiguity_threshold) {
+            rbt_bail_error!(RbtError::InvalidConfig(format!(
+                "solver_cfg/ambiguity_threshold = {} must be in [0, 1]",
+                solver_cfg.ambiguity_threshold
+            )));
+        }
+        if self.tf_cfg.joint_buffer_len < 2 {
+            rbt_bail_error!(RbtError::InvalidConfig(
+                "tf_cfg/joint_buffer_len must be at least 2".to_string()
+            ));
+        }
+        if self.control_cfg.send_period_ms == 0 {
+            rbt_bail_error!(RbtError::InvalidConfig(
+                "control_cfg/send_period_ms must be greater than 0".to_string()
+            ));
+        }
+        if self.control_cfg.fire_tolerance_deg <= 0.0 {
+            rbt_bail_error!(RbtError::InvalidConfig(
+                format!(
+                    "control_cfg/fire_tolerance_deg = {} <= 0.0",
+                    self.control_cfg.fire_tolerance_deg
+                )
+            ));
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_repo_cfg_valid() {
+        let mut cfg = RbtCfg::from_toml().unwrap();
+        // 主点落在 1280x720 原图之外，多半是填成了其他分辨率下的内参
+        cfg.cam_cfg.cam_k = [3200.0, 0.0, 640.0, 0.0, 3411.4, 760.0, 0.0, 0.0, 1.0];
+        assert!(cfg.validation().is_err());
+
+        let mut cfg = RbtCfg::from_toml().unwrap();
+        cfg.detector_cfg.infer_img_width = 416;
+        cfg.detector_cfg.infer_img_height = 416;
+        assert!(cfg.validation().is_ok());
+        cfg.detector_cfg.infer_img_height = 400;
+        assert!(cfg.validation().is_err());
+    }
+}
@@ -44,6 +44,18 @@ pub enum RbtError {
     #[error("Failed to get camera frame: {0}")]
     InvalidArmorClassIndex(usize),
 
+    #[error("Frame source error: {0}")]
+    FrameSourceError(String),
+
+    #[error("Model output shape mismatch: expected {expected:?}, got {actual:?}")]
+    OutputShapeMismatch {
+        expected: (usize, usize),
+        actual: (usize, usize),
+    },
+
+    #[error("Image error: {0}")]
+    ImageError(#[from] image::ImageError),
+
     #[error("Cal yaw angle under other coordinate")]
     CalAngleDisUnderOtherCoord,
 
@@ -94,4 +106,6 @@ pub enum CommError {
     InvalidStartOfFrame,
     #[error("结束帧错误")]
     InvalidEndOfFrame,
+    #[error("字段取值非法")]
+    InvalidFieldValue,
 }
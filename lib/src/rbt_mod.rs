@@ -1,6 +1,9 @@
 pub mod rbt_armor; // 通讯帧定义
+pub mod rbt_buff; // 能量机关（仅库，应用尚未接入）
 pub mod rbt_comm;
+pub mod rbt_controller; // 控制器
 pub mod rbt_detector; // 目标检测器
 pub mod rbt_estimator; // 估计器
 pub mod rbt_solver;
+pub mod rbt_source; // 图像源
 // 求解器 // 通信模块
@@ -1,4 +1,4 @@
-use crate::rbt_mod::rbt_detector::rbt_yolo::YoloLabel;
+use crate::rbt_mod::rbt_detector::rbt_label::YoloLabel;
 use crate::rbt_mod::rbt_estimator::rbt_enemy_dynamic_model::{
     EnemyArmorType, EnemyFaction, EnemyId,
 };
@@ -13,19 +13,8 @@ pub mod tracked_armor;
 
 pub type ArmorId = EnemyId;
 
-// pub struct ArmorLabel(ArmorColor, ArmorId);
 pub type ArmorLabel = YoloLabel;
 
-impl ArmorLabel {
-    fn armor_type(&self) -> ArmorType {
-        if self.id() == &ArmorId::Hero1 {
-            ArmorType::Large
-        } else {
-            ArmorType::Small
-        }
-    }
-}
-
 pub type ArmorColor = EnemyFaction;
 
 /// ArmorType 是根据 ID 判断的，所以不放在 Label 里面
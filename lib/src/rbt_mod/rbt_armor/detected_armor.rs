@@ -1,10 +1,12 @@
 use crate::rbt_base::rbt_geometry::rbt_point2::RbtImgPoint2;
+use crate::rbt_mod::rbt_armor::ArmorType;
 
 /// 作为 Detector 的输出和 Solver 的输入
 #[derive(Debug, Clone)]
 pub struct DetectedArmor {
     key_points: [RbtImgPoint2; 5],
-    id: usize, // 当前帧画面唯一 id，用于区分每一块装甲板
+    id: usize,             // 当前帧画面唯一 id，用于区分每一块装甲板
+    armor_type: ArmorType, // 大小装甲板，决定 PnP 使用的灯条尺寸
 }
 
 impl DetectedArmor {
@@ -19,9 +21,20 @@ impl DetectedArmor {
         DetectedArmor {
             key_points: [center, lt, lb, rb, rt],
             id,
+            armor_type: ArmorType::Small,
         }
     }
 
+    /// 设置大小装甲板，默认为小装甲板
+    pub fn with_armor_type(mut self, armor_type: ArmorType) -> Self {
+        self.armor_type = armor_type;
+        self
+    }
+
+    pub fn armor_type(&self) -> ArmorType {
+        self.armor_type
+    }
+
     /// 根据五点坐标来创建
     pub fn from_corner_coords(corner: &[f32; 10], id: usize) -> Self {
         DetectedArmor {
@@ -33,6 +46,7 @@ impl DetectedArmor {
                 RbtImgPoint2::new_screen_pixel(corner[8], corner[9]),
             ],
             id,
+            armor_type: ArmorType::Small,
         }
     }
 
@@ -64,4 +78,9 @@ impl DetectedArmor {
     pub fn corner_points(&self) -> [RbtImgPoint2; 4] {
         [self.lt(), self.lb(), self.rb(), self.rt()]
     }
+
+    /// 替换四个角点，顺序为 lt、lb、rb、rt，中心点不变
+    pub fn set_corner_points(&mut self, corners: [RbtImgPoint2; 4]) {
+        self.key_points[1..].copy_from_slice(&corners);
+    }
 }
@@ -1,4 +1,5 @@
 use crate::rbt_base::rbt_geometry::rbt_pose3::{RbtPose3, RbtPoseCoordSys};
+use crate::rbt_base::rbt_geometry::rbt_tf::RbtTf;
 use crate::rbt_mod::rbt_armor::detected_armor::DetectedArmor;
 use na::Isometry3;
 use std::ops::{Deref, DerefMut};
@@ -8,7 +9,9 @@ use std::ops::{Deref, DerefMut};
 #[derive(Debug, Clone)]
 pub struct SolvedArmor {
     detected_armor: DetectedArmor,
-    pose: RbtPose3, // 从 pnp 中得到
+    pose: RbtPose3,                // 从 pnp 中得到
+    alternative: Option<RbtPose3>, // IPPE 的另一个候选位姿
+    ambiguity: f64,                // 两个候选位姿的歧义度，见 `PnpSolutions::ambiguity`
     pub enemy_yaw: f64,
     base_yaw: f64,
     radius: f64,
@@ -26,6 +29,8 @@ impl SolvedArmor {
         SolvedArmor {
             detected_armor,
             pose: RbtPose3::new(pose, RbtPoseCoordSys::Camera),
+            alternative: None,
+            ambiguity: 0.0,
             enemy_yaw,
             base_yaw,
             radius,
@@ -59,4 +64,37 @@ impl SolvedArmor {
     pub fn pose_mut(&mut self) -> &mut RbtPose3 {
         &mut self.pose
     }
+
+    /// 记录相机坐标系下的另一个候选位姿及歧义度
+    pub fn set_alternative(&mut self, pose: Isometry3<f64>, ambiguity: f64) {
+        self.alternative = Some(RbtPose3::new(pose, RbtPoseCoordSys::Camera));
+        self.ambiguity = ambiguity;
+    }
+
+    pub fn alternative(&self) -> Option<&RbtPose3> {
+        self.alternative.as_ref()
+    }
+
+    pub fn ambiguity(&self) -> f64 {
+        self.ambiguity
+    }
+
+    /// 改用另一个候选位姿，原位姿成为候选，没有候选位姿时返回 false
+    pub fn swap_alternative(&mut self) -> bool {
+        match self.alternative.as_mut() {
+            Some(alternative) => {
+                std::mem::swap(&mut self.pose, alternative);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 位姿和候选位姿一起变换到目标坐标系
+    pub fn coord_trans_mut(&mut self, target_coord: RbtPoseCoordSys, tf: &RbtTf) {
+        self.pose.coord_trans_mut(target_coord.clone(), tf);
+        if let Some(alternative) = self.alternative.as_mut() {
+            alternative.coord_trans_mut(target_coord, tf);
+        }
+    }
 }
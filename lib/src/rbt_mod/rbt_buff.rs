@@ -0,0 +1,485 @@
+//! 能量机关识别与击打预测
+//!
+//! 下位机上报 `TaskMode::HitSmallBuff` / `TaskMode::HitBigBuff` 时使用，流程：
+//! 1. `BuffDetector` 检测 R 标和待击打扇叶的关键点
+//! 2. 平面 PnP（`ArmorPnpSolver::with_object_points`）解出扇叶位姿，经坐标变换树转到世界坐标系
+//! 3. `BuffPredictor` 在能量机关平面内测量扇叶转角，拟合小符匀速、大符 a·sin(ωt)+b 的转动规律
+//! 4. 按弹丸飞行时间外推到命中时刻的装甲中心，给出云台瞄准角
+//!
+//! 扇叶被击中后待击打扇叶会跳到另一片，五片扇叶互差 72°，转角按 72° 取模展开，
+//! 因此跳变不影响转速拟合。
+//!
+//! 目前只有传统视觉检测，`detector_cfg/buff_detect_model_path` 预留给神经网络检测器。
+//!
+//! 本模块目前仅作为库提供，`app/` 下的程序（包括 `auto_aim_async`）都还没有调用它：
+//! 流水线里没有按 `TaskMode` 切换到能量机关的分支。接入时由调用方在收到打符模式后
+//! 构造 `RbtBuff`，对每帧图像调用 `RbtBuff::process`，再用 `BuffPredictor::predict` 得到
+//! `BuffAim` 交给控制器。
+
+use image::DynamicImage;
+use std::collections::VecDeque;
+use tokio::time::{Duration, Instant};
+use tracing::debug;
+
+use crate::rbt_base::rbt_algorithm::rbt_ippe::ArmorPnpSolver;
+use crate::rbt_base::rbt_geometry::rbt_tf::{RbtTf, RbtTfFrame};
+use crate::rbt_infra::rbt_cfg::{BuffCfg, CamCfg};
+use crate::rbt_infra::rbt_err::{RbtError, RbtResult};
+use crate::rbt_mod::rbt_armor::ArmorColor;
+use crate::rbt_mod::rbt_comm::rbt_comm_frame::TaskMode;
+use crate::rbt_mod::rbt_controller::{AimPoint, aim_point};
+
+use rbt_buff_detector::{BuffDetection, BuffDetector, blade_object_points};
+use rbt_speed_fit::{SpeedCurve, fit_constant, fit_sine};
+
+/// 传统视觉检测
+pub mod rbt_buff_detector;
+/// 转速拟合
+pub mod rbt_speed_fit;
+
+/// 相邻扇叶之间的夹角
+const BLADE_INTERVAL: f64 = std::f64::consts::TAU / 5.0;
+/// 拟合所需的最少观测数
+const MIN_FIT_SAMPLES: usize = 10;
+/// 预测命中点时迭代修正飞行时间的次数
+const FLIGHT_TIME_ITERS: usize = 3;
+
+/// 能量机关模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuffMode {
+    Small, // 小符，匀速转动
+    Big,   // 大符，正弦变速转动
+}
+
+impl BuffMode {
+    /// 自瞄模式返回 None
+    pub fn from_task_mode(mode: TaskMode) -> Option<Self> {
+        match mode {
+            TaskMode::HitSmallBuff => Some(Self::Small),
+            TaskMode::HitBigBuff => Some(Self::Big),
+            TaskMode::AutoShot => None,
+        }
+    }
+}
+
+/// 预测的命中点
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BuffAim {
+    /// 命中时刻装甲中心在世界坐标系下的位置 mm
+    pub target: na::Point3<f64>,
+    pub aim: AimPoint,
+}
+
+/// 能量机关所在平面，转角在该平面内测量，从己方看去逆时针为正
+#[derive(Debug, Clone)]
+struct BuffPlane {
+    center_sum: na::Vector3<f64>,
+    count: usize,
+    e1: na::Vector3<f64>, // 从己方看去向右
+    e2: na::Vector3<f64>, // 竖直向上在平面内的投影
+}
+
+impl BuffPlane {
+    /// 平面法向接近竖直时无法确定基向量，返回 None
+    fn new(normal: &na::Vector3<f64>) -> Option<Self> {
+        let up = na::Vector3::z();
+        let e2 = (up - normal * normal.dot(&up)).try_normalize(1e-6)?;
+        Some(Self {
+            center_sum: na::Vector3::zeros(),
+            count: 0,
+            e1: e2.cross(normal),
+            e2,
+        })
+    }
+
+    /// 能量机关固定不动，R 标位置取所有观测的平均
+    fn center(&self) -> na::Point3<f64> {
+        (self.center_sum / self.count.max(1) as f64).into()
+    }
+
+    fn angle_of(&self, dir: &na::Vector3<f64>) -> f64 {
+        dir.dot(&self.e2).atan2(dir.dot(&self.e1))
+    }
+
+    fn point_at(&self, angle: f64, radius: f64) -> na::Point3<f64> {
+        self.center() + (self.e1 * angle.cos() + self.e2 * angle.sin()) * radius
+    }
+}
+
+/// 扇叶转动预测器，输入为世界坐标系下的扇叶位姿
+pub struct BuffPredictor {
+    mode: BuffMode,
+    cfg: BuffCfg,
+    plane: Option<BuffPlane>,
+    epoch: Option<Instant>, // 本次激活的起始时刻，拟合时间以此为零点
+    last_update: Option<Instant>,
+    samples: VecDeque<(f64, f64)>, // (时间 s, 展开后的转角 rad)
+    blade_offset: f64,             // 当前待击打扇叶与展开转角之差，为 72° 的整数倍
+    curve: Option<SpeedCurve>,
+}
+
+impl BuffPredictor {
+    pub fn new(mode: BuffMode, cfg: BuffCfg) -> Self {
+        Self {
+            mode,
+            cfg,
+            plane: None,
+            epoch: None,
+            last_update: None,
+            samples: VecDeque::new(),
+            blade_offset: 0.0,
+            curve: None,
+        }
+    }
+
+    pub fn mode(&self) -> BuffMode {
+        self.mode
+    }
+
+    /// 切换大小符时清空历史
+    pub fn set_mode(&mut self, mode: BuffMode) {
+        if mode != self.mode {
+            self.mode = mode;
+            self.reset();
+        }
+    }
+
+    pub fn cfg(&self) -> &BuffCfg {
+        &self.cfg
+    }
+
+    /// 配置热更新
+    pub fn set_cfg(&mut self, cfg: BuffCfg) {
+        self.cfg = cfg;
+    }
+
+    pub fn reset(&mut self) {
+        self.plane = None;
+        self.epoch = None;
+        self.last_update = None;
+        self.samples.clear();
+        self.blade_offset = 0.0;
+        self.curve = None;
+    }
+
+    /// 当前拟合结果，时间零点为本次激活的首次观测
+    pub fn curve(&self) -> Option<&SpeedCurve> {
+        self.curve.as_ref()
+    }
+
+    /// R 标中心在世界坐标系下的位置
+    pub fn center(&self) -> Option<na::Point3<f64>> {
+        self.plane.as_ref().map(BuffPlane::center)
+    }
+
+    /// 加入一次观测，`blade_to_world` 为扇叶坐标系到世界坐标系的变换
+    pub fn update(&mut self, time: Instant, blade_to_world: &na::Isometry3<f64>) {
+        if self
+            .last_update
+            .is_some_and(|last| time.saturating_duration_since(last) > self.cfg.lost_timeout())
+        {
+            debug!("Buff lost for too long, reset predictor");
+            self.reset();
+        }
+        let center = blade_to_world * na::Point3::origin();
+        let target = blade_to_world * na::Point3::new(0.0, self.cfg.blade_radius, 0.0);
+        if self.plane.is_none() {
+            let Some(plane) = BuffPlane::new(&(blade_to_world.rotation * na::Vector3::z())) else {
+                return;
+            };
+            self.plane = Some(plane);
+        }
+        let Some(plane) = self.plane.as_mut() else {
+            return;
+        };
+        plane.center_sum += center.coords;
+        plane.count += 1;
+        let raw = plane.angle_of(&(target - center));
+
+        let epoch = *self.epoch.get_or_insert(time);
+        let t = time.saturating_duration_since(epoch).as_secs_f64();
+        let angle = match self.samples.back() {
+            Some(&(_, last)) => last + wrap_blade(raw - (last + self.blade_offset)),
+            None => raw,
+        };
+        self.blade_offset = raw - angle;
+        self.samples.push_back((t, angle));
+        self.last_update = Some(time);
+
+        let window = self.cfg.fit_window().as_secs_f64();
+        while self.samples.front().is_some_and(|&(t0, _)| t - t0 > window) {
+            self.samples.pop_front();
+        }
+        self.curve = self.fit();
+    }
+
+    fn fit(&self) -> Option<SpeedCurve> {
+        if self.samples.len() < MIN_FIT_SAMPLES {
+            return None;
+        }
+        let samples = self.samples.iter().copied().collect::<Vec<_>>();
+        let span = samples[samples.len() - 1].0 - samples[0].0;
+        match self.mode {
+            BuffMode::Big if span >= self.cfg.min_fit_duration().as_secs_f64() => {
+                fit_sine(&samples, self.cfg.big_buff_omega).or_else(|| fit_constant(&samples))
+            }
+            // 大符观测不足时先按匀速外推
+            _ => fit_constant(&samples),
+        }
+    }
+
+    /// `time` 时刻待击打装甲中心在世界坐标系下的位置
+    pub fn target_at(&self, time: Instant) -> Option<na::Point3<f64>> {
+        let t = time.saturating_duration_since(self.epoch?).as_secs_f64();
+        self.target_at_secs(t)
+    }
+
+    fn target_at_secs(&self, t: f64) -> Option<na::Point3<f64>> {
+        let angle = self.curve.as_ref()?.angle(t) + self.blade_offset;
+        Some(self.plane.as_ref()?.point_at(angle, self.cfg.blade_radius))
+    }
+
+    /// 预测 `now` 时刻发出指令后弹丸命中时的装甲位置及瞄准角
+    ///
+    /// `latency` 为指令到云台响应的延迟，飞行时间按命中点距离迭代修正，瞄准角从 `tf` 下的枪口位置计算
+    pub fn predict(
+        &self,
+        now: Instant,
+        latency: Duration,
+        bullet_speed: f64,
+        tf: &RbtTf,
+    ) -> Option<BuffAim> {
+        let t = (now.saturating_duration_since(self.epoch?) + latency).as_secs_f64();
+        let muzzle = tf.lookup(RbtTfFrame::Muzzle, RbtTfFrame::World) * na::Point3::origin();
+        let mut flight_time = 0.0;
+        let mut result = None;
+        for _ in 0..FLIGHT_TIME_ITERS {
+            let target = self.target_at_secs(t + flight_time)?;
+            let aim = aim_point(&muzzle, &target, bullet_speed);
+            flight_time = aim.flight_time;
+            result = Some(BuffAim { target, aim });
+        }
+        result
+    }
+}
+
+/// 将角度差折算到 (-36°, 36°]，消除扇叶切换带来的跳变
+fn wrap_blade(angle: f64) -> f64 {
+    angle - BLADE_INTERVAL * (angle / BLADE_INTERVAL).round()
+}
+
+/// 能量机关全流程：检测、PnP、转动预测
+pub struct RbtBuff {
+    detector: BuffDetector,
+    pnp_solver: ArmorPnpSolver,
+    predictor: BuffPredictor,
+}
+
+impl RbtBuff {
+    pub fn new(mode: BuffMode, cfg: &BuffCfg) -> RbtResult<Self> {
+        let pnp_solver = ArmorPnpSolver::with_object_points(blade_object_points(cfg)).ok_or(
+            RbtError::StringError("Failed to create buff PnP solver".to_string()),
+        )?;
+        Ok(Self {
+            detector: BuffDetector::new(cfg.clone()),
+            pnp_solver,
+            predictor: BuffPredictor::new(mode, cfg.clone()),
+        })
+    }
+
+    pub fn detector(&self) -> &BuffDetector {
+        &self.detector
+    }
+
+    pub fn predictor(&self) -> &BuffPredictor {
+        &self.predictor
+    }
+
+    pub fn predictor_mut(&mut self) -> &mut BuffPredictor {
+        &mut self.predictor
+    }
+
+    /// 处理 `time` 时刻采集的一帧，`color` 为能量机关颜色，`tf` 为该时刻的坐标变换，
+    /// PnP 使用 `cam_cfg` 中的内参和镜头畸变
+    ///
+    /// 检测或 PnP 失败时返回 None，预测器保持原状态
+    pub fn process(
+        &mut self,
+        img: &DynamicImage,
+        color: &ArmorColor,
+        cam_cfg: &CamCfg,
+        tf: &RbtTf,
+        time: Instant,
+    ) -> Option<BuffDetection> {
+        let detection = self.detector.detect(img, color)?;
+        let pnp_solver = self
+            .pnp_solver
+            .clone()
+            .with_distortion(*cam_cfg.distortion());
+        let blade_to_cam = pnp_solver.solve(&detection.keypoints(), &cam_cfg.cam_k())?;
+        let blade_to_world = tf.lookup(RbtTfFrame::Camera, RbtTfFrame::World) * blade_to_cam;
+        self.predictor.update(time, &blade_to_world);
+        Some(detection)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rbt_mod::rbt_buff::rbt_buff_detector::tests::{buff_cfg, synthetic_buff};
+
+    const CAM_K: na::Matrix3<f64> =
+        na::Matrix3::new(1600.0, 0.0, 640.0, 0.0, 1600.0, 360.0, 0.0, 0.0, 1.0);
+
+    /// 正对己方的能量机关，R 标位于 (7000, 500, 1200)，待击打扇叶转角为 `angle`
+    fn blade_pose(angle: f64) -> na::Isometry3<f64> {
+        let normal = -na::Vector3::x();
+        let (e1, e2) = (-na::Vector3::y(), na::Vector3::z());
+        let dir = e1 * angle.cos() + e2 * angle.sin();
+        let rotation = na::Rotation3::from_basis_unchecked(&[dir.cross(&normal), dir, normal]);
+        na::Isometry3::from_parts(
+            na::Translation3::new(7000.0, 500.0, 1200.0),
+            na::UnitQuaternion::from_rotation_matrix(&rotation),
+        )
+    }
+
+    /// 固定种子的伪随机噪声，范围 [-amp, amp]
+    fn noise(i: usize, amp: f64) -> f64 {
+        ((i as f64 * 12.9898).sin() * 43758.5453).fract() * amp
+    }
+
+    #[test]
+    fn test_blade_pnp() {
+        let cfg = buff_cfg();
+        let solver = ArmorPnpSolver::with_object_points(blade_object_points(&cfg)).unwrap();
+        // 相机坐标系：右-下-前，能量机关在前方 6m，略微偏转
+        let truth = na::Isometry3::from_parts(
+            na::Translation3::new(300.0, -800.0, 6000.0),
+            na::UnitQuaternion::from_euler_angles(std::f64::consts::PI + 0.1, 0.3, 0.8),
+        );
+        let uvs = blade_object_points(&cfg).map(|p| {
+            let pc = truth * na::Point3::new(p.x, p.y, 0.0);
+            let uv = CAM_K * pc.coords;
+            na::Point2::new(uv.x / uv.z, uv.y / uv.z)
+        });
+        let pose = solver.solve(&uvs, &CAM_K).unwrap();
+        assert!((pose.translation.vector - truth.translation.vector).norm() < 1e-6);
+        assert!(pose.rotation.angle_to(&truth.rotation) < 1e-9);
+    }
+
+    #[test]
+    fn test_process_synthetic_image() {
+        let cfg = buff_cfg();
+        let mut buff = RbtBuff::new(BuffMode::Small, &cfg).unwrap();
+        // 合成图中装甲中心距 R 标 140 像素，对应 700mm，相机位于正前方
+        let cam_cfg = |distortion: &str| -> CamCfg {
+            toml::from_str(&format!(
+                "cam_k = [1000.0, 0.0, 320.0, 0.0, 1000.0, 240.0, 0.0, 0.0, 1.0]\n\
+                 distortion = {distortion}"
+            ))
+            .unwrap()
+        };
+        let distance = 1000.0 * cfg.blade_radius / 140.0;
+        let tf: crate::rbt_infra::rbt_cfg::TfCfg = toml::from_str(
+            "cam_to_gimbal_xyz = [0.0, 0.0, 0.0]\n\
+             cam_to_gimbal_rpy_deg = [0.0, 0.0, 0.0]\n\
+             muzzle_to_gimbal_xyz = [0.0, 0.0, 0.0]\n\
+             gimbal_to_base_xyz = [0.0, 0.0, 0.0]\n\
+             joint_buffer_len = 8",
+        )
+        .unwrap();
+        let tf = RbtTf::new(&tf);
+        let img = DynamicImage::ImageRgb8(synthetic_buff(90.0));
+        let pinhole = cam_cfg("{ model = \"none\" }");
+        buff.process(&img, &ArmorColor::R, &pinhole, &tf, Instant::now())
+            .unwrap();
+        // 世界坐标系前-左-上，R 标在正前方
+        let center = buff.predictor().center().unwrap();
+        assert!((center - na::Point3::new(distance, 0.0, 0.0)).norm() < 0.02 * distance);
+
+        // 镜头畸变参与 PnP：同一幅图按桶形畸变去畸变后，扇叶看起来更大、更近
+        let mut buff = RbtBuff::new(BuffMode::Small, &cfg).unwrap();
+        let barrel = cam_cfg("{ model = \"brown_conrady\", coeffs = [-0.5, 0.0, 0.0, 0.0, 0.0] }");
+        buff.process(&img, &ArmorColor::R, &barrel, &tf, Instant::now())
+            .unwrap();
+        let distorted_center = buff.predictor().center().unwrap();
+        assert!(
+            distorted_center.x < 0.995 * center.x,
+            "{distorted_center} {center}"
+        );
+    }
+
+    #[test]
+    fn test_predict_big_buff() {
+        let truth = SpeedCurve::Sine {
+            theta0: 0.4,
+            a: 0.95,
+            omega: 1.92,
+            phi: 1.3,
+            b: 2.090 - 0.95,
+            direction: 1.0,
+        };
+        let mut predictor = BuffPredictor::new(BuffMode::Big, buff_cfg());
+        let start = Instant::now();
+        let at = |t: f64| start + Duration::from_secs_f64(t);
+        // 100Hz 观测 3s，1.7s 时扇叶被击中，待击打扇叶跳到相隔两片的位置
+        for i in 0..300 {
+            let t = i as f64 * 0.01;
+            let jump = if t >= 1.7 { 2.0 * BLADE_INTERVAL } else { 0.0 };
+            let angle = truth.angle(t) + jump + noise(i, 0.3f64.to_radians());
+            predictor.update(at(t), &blade_pose(angle));
+        }
+        assert!(matches!(predictor.curve(), Some(SpeedCurve::Sine { .. })));
+
+        let tf_cfg = crate::rbt_infra::rbt_cfg::TfCfg::test_cfg();
+        let tf = RbtTf::new(&tf_cfg);
+        let muzzle = na::Point3::new(120.0, 0.0, 380.0);
+
+        let (now, latency, bullet_speed) = (at(2.99), Duration::from_millis(100), 25.0);
+        let aim = predictor.predict(now, latency, bullet_speed, &tf).unwrap();
+        let t_hit = 2.99 + 0.1 + aim.aim.flight_time;
+        let expected = blade_pose(truth.angle(t_hit) + 2.0 * BLADE_INTERVAL)
+            * na::Point3::new(0.0, 700.0, 0.0);
+        assert!(
+            (aim.target - expected).norm() < 15.0,
+            "error = {} mm",
+            (aim.target - expected).norm()
+        );
+        assert!((aim.aim.flight_time - aim.aim.distance / 1000.0 / bullet_speed).abs() < 1e-9);
+        // 瞄准角从枪口而非世界原点计算
+        let offset = expected - muzzle;
+        assert!((aim.aim.distance - offset.xy().norm()).abs() < 15.0);
+        assert!((aim.aim.yaw - offset.y.atan2(offset.x).to_degrees()).abs() < 0.2);
+        assert!(aim.aim.pitch > offset.z.atan2(aim.aim.distance).to_degrees());
+    }
+
+    #[test]
+    fn test_small_buff_and_reset() {
+        let speed = -std::f64::consts::FRAC_PI_3;
+        let mut predictor = BuffPredictor::new(BuffMode::Small, buff_cfg());
+        let start = Instant::now();
+        let at = |t: f64| start + Duration::from_secs_f64(t);
+        for i in 0..50 {
+            let t = i as f64 * 0.02;
+            predictor.update(at(t), &blade_pose(1.0 + speed * t));
+        }
+        let Some(SpeedCurve::Constant { speed: fitted, .. }) = predictor.curve() else {
+            panic!("expected constant curve");
+        };
+        assert!((fitted - speed).abs() < 1e-6);
+        let target = predictor.target_at(at(1.5)).unwrap();
+        let expected = blade_pose(1.0 + speed * 1.5) * na::Point3::new(0.0, 700.0, 0.0);
+        assert!((target - expected).norm() < 1e-3);
+
+        // 长时间丢失后重新开始
+        predictor.update(at(3.0), &blade_pose(0.0));
+        assert!(predictor.curve().is_none());
+        predictor.set_mode(BuffMode::Big);
+        assert!(predictor.center().is_none());
+        assert_eq!(BuffMode::from_task_mode(TaskMode::AutoShot), None);
+        assert_eq!(
+            BuffMode::from_task_mode(TaskMode::HitBigBuff),
+            Some(BuffMode::Big)
+        );
+    }
+}
@@ -0,0 +1,377 @@
+//! 能量机关传统视觉检测
+//!
+//! 流程：
+//! 1. 按亮度和己方颜色通道差二值化，闭运算把点阵灯珠连成整块，提取轮廓
+//! 2. 面积在 `r_logo_area` 内且近似方形的外轮廓作为 R 标候选，面积超过 `min_blade_area` 的外轮廓作为扇叶候选
+//! 3. 已激活扇叶的整个框架都会点亮，靠近 R 标一侧围出较大的孔洞；待击打扇叶只有流水灯条和装甲，
+//!    据此排除已激活扇叶
+//! 4. 扇叶主轴应指向 R 标，按 R 标到主轴的距离选出最匹配的一对
+//! 5. 以背离 R 标的扇叶主轴为轴，取装甲最外侧点和左右两侧点作为关键点
+//!
+//! 关键点依次为 R 标中心、装甲左侧、装甲外侧、装甲右侧，坐标为相机原图像素，
+//! 与 `blade_object_points` 一一对应，可直接用于平面 PnP。
+
+use image::{DynamicImage, GrayImage, Luma};
+use imageproc::contours::{BorderType, Contour, find_contours};
+use imageproc::distance_transform::Norm;
+use imageproc::morphology::close;
+
+use crate::rbt_infra::rbt_cfg::BuffCfg;
+use crate::rbt_mod::rbt_armor::ArmorColor;
+
+/// R 标轮廓长短轴之比的上限
+const MAX_R_LOGO_ASPECT: f32 = 2.0;
+/// 扇叶主轴与 R 标连线的最大夹角，单位度
+const MAX_BLADE_AXIS_ANGLE_DEG: f32 = 20.0;
+/// 取极值点时，与极值相差不超过该像素数的轮廓点一起取平均
+const EXTREME_TOLERANCE: f32 = 1.0;
+
+/// 一帧中检测到的待击打扇叶
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BuffDetection {
+    pub r_center: na::Point2<f32>,
+    pub left: na::Point2<f32>,
+    pub outer: na::Point2<f32>,
+    pub right: na::Point2<f32>,
+}
+
+impl BuffDetection {
+    /// PnP 用的四个关键点：R 标中心、装甲左侧、装甲外侧、装甲右侧
+    pub fn keypoints(&self) -> [na::Point2<f64>; 4] {
+        [self.r_center, self.left, self.outer, self.right].map(|p| p.cast::<f64>())
+    }
+
+    /// 待击打装甲中心的近似像素位置
+    pub fn target_center(&self) -> na::Point2<f32> {
+        na::center(&self.left, &self.right)
+    }
+}
+
+/// 扇叶坐标系下与 `BuffDetection::keypoints` 对应的点
+///
+/// 原点为 R 标中心，Y 轴由 R 标指向待击打装甲，X 轴向右，Z=0 为能量机关所在平面
+pub fn blade_object_points(cfg: &BuffCfg) -> [na::Point2<f64>; 4] {
+    let (d, r) = (cfg.blade_radius, cfg.target_radius);
+    [
+        na::Point2::new(0.0, 0.0),
+        na::Point2::new(-r, d),
+        na::Point2::new(0.0, d + r),
+        na::Point2::new(r, d),
+    ]
+}
+
+/// 一个轮廓的几何统计
+#[derive(Debug, Clone)]
+struct Blob {
+    points: Vec<na::Point2<f32>>,
+    centroid: na::Point2<f32>,
+    area: f32,
+    axis: na::Vector2<f32>, // 主轴方向，单位向量
+    major: f32,             // 沿主轴的长度
+    minor: f32,             // 垂直主轴的宽度
+}
+
+impl Blob {
+    fn new(contour: &Contour<i32>) -> Option<Self> {
+        if contour.points.len() < 3 {
+            return None;
+        }
+        let points = contour
+            .points
+            .iter()
+            .map(|p| na::Point2::new(p.x as f32, p.y as f32))
+            .collect::<Vec<_>>();
+        let n = points.len() as f32;
+        let mean = points
+            .iter()
+            .fold(na::Vector2::zeros(), |acc, p| acc + p.coords)
+            / n;
+        let cov = points.iter().fold(na::Matrix2::zeros(), |acc, p| {
+            let d = p.coords - mean;
+            acc + d * d.transpose()
+        }) / n;
+        let eigen = na::SymmetricEigen::new(cov);
+        let major_idx = if eigen.eigenvalues[0] >= eigen.eigenvalues[1] {
+            0
+        } else {
+            1
+        };
+        let axis: na::Vector2<f32> = eigen.eigenvectors.column(major_idx).into();
+        let normal = na::Vector2::new(-axis.y, axis.x);
+        let extent = |dir: &na::Vector2<f32>| {
+            let (min, max) = points.iter().fold((f32::MAX, f32::MIN), |(min, max), p| {
+                let s = (p.coords - mean).dot(dir);
+                (min.min(s), max.max(s))
+            });
+            max - min + 1.0
+        };
+        // 鞋带公式，轮廓点为像素中心，面积略小于像素数
+        let area = points
+            .iter()
+            .zip(points.iter().cycle().skip(1))
+            .map(|(a, b)| a.x * b.y - b.x * a.y)
+            .sum::<f32>()
+            .abs()
+            / 2.0;
+        Some(Self {
+            centroid: mean.into(),
+            area,
+            axis,
+            major: extent(&axis),
+            minor: extent(&normal),
+            points,
+        })
+    }
+}
+
+/// 能量机关检测器
+pub struct BuffDetector {
+    cfg: BuffCfg,
+}
+
+impl BuffDetector {
+    pub fn new(cfg: BuffCfg) -> Self {
+        Self { cfg }
+    }
+
+    pub fn cfg(&self) -> &BuffCfg {
+        &self.cfg
+    }
+
+    /// 配置热更新
+    pub fn set_cfg(&mut self, cfg: BuffCfg) {
+        self.cfg = cfg;
+    }
+
+    /// 检测 `color` 颜色能量机关的待击打扇叶，击打己方能量机关时传入己方颜色
+    pub fn detect(&self, img: &DynamicImage, color: &ArmorColor) -> Option<BuffDetection> {
+        let mask = close(
+            &self.binarize(img, color),
+            Norm::LInf,
+            self.cfg.close_radius,
+        );
+        let contours = find_contours::<i32>(&mask);
+
+        let mut r_logos = Vec::new();
+        let mut blades = Vec::new();
+        for (idx, contour) in contours.iter().enumerate() {
+            if contour.border_type != BorderType::Outer {
+                continue;
+            }
+            let Some(blob) = Blob::new(contour) else {
+                continue;
+            };
+            let [min_area, max_area] = self.cfg.r_logo_area;
+            if (min_area..=max_area).contains(&blob.area)
+                && blob.major / blob.minor <= MAX_R_LOGO_ASPECT
+            {
+                r_logos.push(blob);
+            } else if blob.area >= self.cfg.min_blade_area {
+                let holes = contours
+                    .iter()
+                    .filter(|c| c.border_type == BorderType::Hole && c.parent == Some(idx))
+                    .filter_map(Blob::new)
+                    .collect::<Vec<_>>();
+                blades.push((blob, holes));
+            }
+        }
+
+        let max_axis_cos = MAX_BLADE_AXIS_ANGLE_DEG.to_radians().cos();
+        let mut best: Option<(f32, &Blob, &Blob)> = None;
+        for (blade, holes) in &blades {
+            for r_logo in &r_logos {
+                let link = blade.centroid - r_logo.centroid;
+                let dist = link.norm();
+                if dist < f32::EPSILON || blade.axis.dot(&link).abs() / dist < max_axis_cos {
+                    continue;
+                }
+                if self.is_activated(blade, holes, r_logo) {
+                    continue;
+                }
+                // R 标到扇叶主轴的距离，按扇叶长度归一化
+                let offset = blade.axis.perp(&link).abs() / blade.major;
+                if best.is_none_or(|(cost, _, _)| offset < cost) {
+                    best = Some((offset, blade, r_logo));
+                }
+            }
+        }
+        let (_, blade, r_logo) = best?;
+        Some(self.keypoints(blade, r_logo.centroid))
+    }
+
+    /// 亮度和颜色同时满足的像素置为 255
+    fn binarize(&self, img: &DynamicImage, color: &ArmorColor) -> GrayImage {
+        let rgb = img.to_rgb8();
+        let threshold = self.cfg.brightness_threshold;
+        GrayImage::from_fn(rgb.width(), rgb.height(), |x, y| {
+            let [r, g, b] = rgb.get_pixel(x, y).0;
+            let diff = match color {
+                ArmorColor::B => b as f32 - r as f32,
+                ArmorColor::R => r as f32 - b as f32,
+            };
+            let lit = r.max(g).max(b) > threshold && diff > self.cfg.color_threshold;
+            Luma([if lit { 255 } else { 0 }])
+        })
+    }
+
+    /// 靠近 R 标一侧有较大孔洞时为已激活扇叶
+    fn is_activated(&self, blade: &Blob, holes: &[Blob], r_logo: &Blob) -> bool {
+        let mid = (blade.centroid - r_logo.centroid).norm();
+        holes.iter().any(|hole| {
+            (hole.centroid - r_logo.centroid).norm() < mid
+                && hole.area >= self.cfg.max_hub_hole_ratio * blade.area
+        })
+    }
+
+    /// 沿扇叶主轴取装甲外侧点，在装甲范围内取左右两侧点
+    fn keypoints(&self, blade: &Blob, r_center: na::Point2<f32>) -> BuffDetection {
+        // 扇叶主轴比 R 标到轮廓重心的连线更稳定，方向取背离 R 标
+        let axis = if blade.axis.dot(&(blade.centroid - r_center)) < 0.0 {
+            -blade.axis
+        } else {
+            blade.axis
+        };
+        // 图像 Y 轴向下，顺时针旋转 90° 得到扇叶朝上时的右侧方向
+        let right_dir = na::Vector2::new(-axis.y, axis.x);
+        let local = blade
+            .points
+            .iter()
+            .map(|p| {
+                let d = p - r_center;
+                (d.dot(&axis), d.dot(&right_dir))
+            })
+            .collect::<Vec<_>>();
+
+        let outer_s = local.iter().map(|(s, _)| *s).fold(f32::MIN, f32::max);
+        // 按实际尺寸比例估计装甲直径对应的像素长度，只在这一段内找左右两侧
+        let ratio = self.cfg.target_radius / (self.cfg.blade_radius + self.cfg.target_radius);
+        let plate_start = outer_s * (1.0 - 2.0 * ratio as f32);
+        let plate = local
+            .iter()
+            .copied()
+            .filter(|(s, _)| *s >= plate_start)
+            .collect::<Vec<_>>();
+
+        let to_image = |(s, v): (f32, f32)| r_center + axis * s + right_dir * v;
+        let outer = extreme_mean(&plate, |(s, _)| *s);
+        let left = extreme_mean(&plate, |(_, v)| -*v);
+        let right = extreme_mean(&plate, |(_, v)| *v);
+        BuffDetection {
+            r_center,
+            left: to_image(left),
+            outer: to_image(outer),
+            right: to_image(right),
+        }
+    }
+}
+
+/// `key` 最大的点，与最大值相差 `EXTREME_TOLERANCE` 以内的点取平均，减小锯齿的影响
+fn extreme_mean(points: &[(f32, f32)], key: impl Fn(&(f32, f32)) -> f32) -> (f32, f32) {
+    let max = points.iter().map(&key).fold(f32::MIN, f32::max);
+    let (sum_s, sum_v, n) = points
+        .iter()
+        .filter(|p| key(p) >= max - EXTREME_TOLERANCE)
+        .fold((0.0, 0.0, 0.0), |(s, v, n), p| (s + p.0, v + p.1, n + 1.0));
+    (sum_s / n, sum_v / n)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+    use imageproc::drawing::{draw_filled_circle_mut, draw_filled_rect_mut, draw_polygon_mut};
+    use imageproc::point::Point;
+    use imageproc::rect::Rect;
+
+    pub(crate) fn buff_cfg() -> BuffCfg {
+        toml::from_str(
+            "brightness_threshold = 120\n\
+             color_threshold = 60.0\n\
+             close_radius = 2\n\
+             r_logo_area = [40.0, 1000.0]\n\
+             min_blade_area = 1500.0\n\
+             max_hub_hole_ratio = 0.05\n\
+             blade_radius = 700.0\n\
+             target_radius = 150.0\n\
+             big_buff_omega = [1.884, 2.0]\n\
+             fit_window_ms = 6000\n\
+             min_fit_duration_ms = 1500\n\
+             lost_timeout_ms = 1000",
+        )
+        .unwrap()
+    }
+
+    const RED: Rgb<u8> = Rgb([250, 40, 30]);
+
+    /// 画一片扇叶，`angle` 为图像中由 R 标指向装甲的方向（度，逆时针为正），
+    /// 每毫米 0.2 像素，`activated` 时画出整个框架
+    fn draw_blade(img: &mut RgbImage, center: (f32, f32), angle: f32, activated: bool) {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        let (axis, right) = ((cos, -sin), (sin, cos));
+        let at = |s: f32, v: f32| {
+            (
+                center.0 + axis.0 * s + right.0 * v,
+                center.1 + axis.1 * s + right.1 * v,
+            )
+        };
+        let quad = |img: &mut RgbImage, s: [f32; 2], v: [f32; 2]| {
+            let poly = [(s[0], v[0]), (s[1], v[0]), (s[1], v[1]), (s[0], v[1])].map(|(s, v)| {
+                let (x, y) = at(s, v);
+                Point::new(x.round() as i32, y.round() as i32)
+            });
+            draw_polygon_mut(img, &poly, RED);
+        };
+        // 装甲中心距 R 标 140 像素，半径 30 像素
+        let (x, y) = at(140.0, 0.0);
+        draw_filled_circle_mut(img, (x.round() as i32, y.round() as i32), 30, RED);
+        if activated {
+            // 框架：两条侧边和靠近 R 标的横梁，中间留空
+            quad(img, [30.0, 115.0], [-25.0, -18.0]);
+            quad(img, [30.0, 115.0], [18.0, 25.0]);
+            quad(img, [30.0, 37.0], [-25.0, 25.0]);
+        } else {
+            // 流水灯条
+            quad(img, [25.0, 115.0], [-4.0, 4.0]);
+        }
+    }
+
+    /// 中心在 (320, 240) 的合成能量机关，待击打扇叶方向为 `target_angle`
+    pub(crate) fn synthetic_buff(target_angle: f32) -> RgbImage {
+        let mut img = RgbImage::from_pixel(640, 480, Rgb([20, 20, 20]));
+        draw_filled_rect_mut(&mut img, Rect::at(313, 233).of_size(14, 14), RED);
+        draw_blade(&mut img, (320.0, 240.0), target_angle, false);
+        draw_blade(&mut img, (320.0, 240.0), target_angle + 144.0, true);
+        draw_blade(&mut img, (320.0, 240.0), target_angle - 72.0, true);
+        img
+    }
+
+    #[test]
+    fn test_detect_target_blade() {
+        let detector = BuffDetector::new(buff_cfg());
+        for angle in [90.0f32, 20.0, -130.0] {
+            let img = DynamicImage::ImageRgb8(synthetic_buff(angle));
+            let det = detector.detect(&img, &ArmorColor::R).unwrap();
+            assert!(na::distance(&det.r_center, &na::Point2::new(320.0, 240.0)) < 1.0);
+            let (sin, cos) = angle.to_radians().sin_cos();
+            let expect = |s: f32, v: f32| {
+                na::Point2::new(320.0 + cos * s + sin * v, 240.0 - sin * s + cos * v)
+            };
+            assert!(
+                na::distance(&det.outer, &expect(170.0, 0.0)) < 1.5,
+                "{angle}"
+            );
+            assert!(
+                na::distance(&det.left, &expect(140.0, -30.0)) < 1.5,
+                "{angle}"
+            );
+            assert!(
+                na::distance(&det.right, &expect(140.0, 30.0)) < 1.5,
+                "{angle}"
+            );
+            assert!(na::distance(&det.target_center(), &expect(140.0, 0.0)) < 1.0);
+        }
+        // 颜色不符时检测不到
+        let img = DynamicImage::ImageRgb8(synthetic_buff(90.0));
+        assert!(detector.detect(&img, &ArmorColor::B).is_none());
+    }
+}
@@ -0,0 +1,216 @@
+//! 能量机关转速拟合
+//!
+//! 观测量为扇叶转角 θ(t)（已展开，rad），直接对角度拟合，避免对含噪声的角度差分求速度：
+//! - 小符匀速转动：θ(t) = θ0 + b·t
+//! - 大符转速 spd(t) = a·sin(ω·t + φ) + b，积分得到
+//!   θ(t) = θ0 + b·t − a/ω·cos(ω·t + φ)
+//!
+//! 给定 ω 时大符模型可以改写为 θ0 + b·t + c1·cos(ω·t) + c2·sin(ω·t)，对其余参数是线性的，
+//! 因此只需要在 `big_buff_omega` 范围内对 ω 做一维搜索，每个 ω 解一次线性最小二乘。
+//! 转向由 b 的符号给出，a、b 均取正值。
+
+/// 拟合得到的转动规律，时间单位 s，角度单位 rad，逆时针为正
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SpeedCurve {
+    /// 匀速转动，`speed` 带符号
+    Constant { theta0: f64, speed: f64 },
+    /// 正弦变速转动，`direction` 为 ±1
+    Sine {
+        theta0: f64,
+        a: f64,
+        omega: f64,
+        phi: f64,
+        b: f64,
+        direction: f64,
+    },
+}
+
+impl SpeedCurve {
+    /// `t` 时刻的转角
+    pub fn angle(&self, t: f64) -> f64 {
+        match *self {
+            Self::Constant { theta0, speed } => theta0 + speed * t,
+            Self::Sine {
+                theta0,
+                a,
+                omega,
+                phi,
+                b,
+                direction,
+            } => theta0 + direction * (b * t - a / omega * (omega * t + phi).cos()),
+        }
+    }
+
+    /// `t` 时刻的角速度
+    pub fn speed(&self, t: f64) -> f64 {
+        match *self {
+            Self::Constant { speed, .. } => speed,
+            Self::Sine {
+                a,
+                omega,
+                phi,
+                b,
+                direction,
+                ..
+            } => direction * (a * (omega * t + phi).sin() + b),
+        }
+    }
+}
+
+/// ω 粗搜索的网格数
+const OMEGA_GRID: usize = 32;
+/// 黄金分割细化的迭代次数
+const OMEGA_REFINE_ITERS: usize = 24;
+
+/// 匀速模型线性拟合，样本为 (时间, 转角)，样本不足或时间跨度为零时返回 None
+pub fn fit_constant(samples: &[(f64, f64)]) -> Option<SpeedCurve> {
+    if samples.len() < 2 {
+        return None;
+    }
+    let n = samples.len() as f64;
+    let t_mean = samples.iter().map(|(t, _)| t).sum::<f64>() / n;
+    let a_mean = samples.iter().map(|(_, a)| a).sum::<f64>() / n;
+    let (mut stt, mut sta) = (0.0, 0.0);
+    for (t, a) in samples {
+        stt += (t - t_mean) * (t - t_mean);
+        sta += (t - t_mean) * (a - a_mean);
+    }
+    if stt < 1e-12 {
+        return None;
+    }
+    let speed = sta / stt;
+    Some(SpeedCurve::Constant {
+        theta0: a_mean - speed * t_mean,
+        speed,
+    })
+}
+
+/// 正弦变速模型拟合，`omega_range` 为 ω 的搜索范围
+pub fn fit_sine(samples: &[(f64, f64)], [omega_min, omega_max]: [f64; 2]) -> Option<SpeedCurve> {
+    if samples.len() < 5 || omega_min <= 0.0 || omega_max <= omega_min {
+        return None;
+    }
+    let step = (omega_max - omega_min) / (OMEGA_GRID - 1) as f64;
+    let (best, _) = (0..OMEGA_GRID)
+        .map(|i| omega_min + step * i as f64)
+        .filter_map(|omega| Some((omega, linear_fit(samples, omega)?.1)))
+        .min_by(|a, b| a.1.total_cmp(&b.1))?;
+
+    // 在最优网格点两侧做黄金分割搜索
+    let cost = |omega: f64| linear_fit(samples, omega).map_or(f64::MAX, |(_, cost)| cost);
+    let ratio = (5f64.sqrt() - 1.0) / 2.0;
+    let (mut lo, mut hi) = ((best - step).max(omega_min), (best + step).min(omega_max));
+    let (mut x1, mut x2) = (hi - ratio * (hi - lo), lo + ratio * (hi - lo));
+    let (mut f1, mut f2) = (cost(x1), cost(x2));
+    for _ in 0..OMEGA_REFINE_ITERS {
+        if f1 < f2 {
+            hi = x2;
+            (x2, f2) = (x1, f1);
+            x1 = hi - ratio * (hi - lo);
+            f1 = cost(x1);
+        } else {
+            lo = x1;
+            (x1, f1) = (x2, f2);
+            x2 = lo + ratio * (hi - lo);
+            f2 = cost(x2);
+        }
+    }
+    let omega = (lo + hi) / 2.0;
+
+    let ([theta0, slope, c1, c2], _) = linear_fit(samples, omega)?;
+    let direction = if slope < 0.0 { -1.0 } else { 1.0 };
+    // direction·(−a/ω)·cos(ωt + φ) = c1·cos(ωt) + c2·sin(ωt)
+    Some(SpeedCurve::Sine {
+        theta0,
+        a: omega * c1.hypot(c2),
+        omega,
+        phi: (direction * c2).atan2(-direction * c1),
+        b: slope.abs(),
+        direction,
+    })
+}
+
+/// 给定 ω 时求解 θ0 + b·t + c1·cos(ωt) + c2·sin(ωt)，返回参数和残差平方和
+fn linear_fit(samples: &[(f64, f64)], omega: f64) -> Option<([f64; 4], f64)> {
+    let basis = |t: f64| na::Vector4::new(1.0, t, (omega * t).cos(), (omega * t).sin());
+    let (mut ata, mut atb) = (na::Matrix4::<f64>::zeros(), na::Vector4::<f64>::zeros());
+    for &(t, angle) in samples {
+        let row = basis(t);
+        ata += row * row.transpose();
+        atb += row * angle;
+    }
+    let x = ata.cholesky()?.solve(&atb);
+    let cost = samples
+        .iter()
+        .map(|&(t, angle)| (basis(t).dot(&x) - angle).powi(2))
+        .sum();
+    Some(([x[0], x[1], x[2], x[3]], cost))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 固定种子的伪随机噪声，范围 [-amp, amp]
+    fn noise(i: usize, amp: f64) -> f64 {
+        let x = ((i as u64)
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407)
+            >> 33) as f64
+            / (1u64 << 31) as f64;
+        (x * 2.0 - 1.0) * amp
+    }
+
+    #[test]
+    fn test_fit_constant() {
+        let samples = (0..100)
+            .map(|i| {
+                let t = i as f64 * 0.01;
+                (t, 0.3 - std::f64::consts::FRAC_PI_3 * t + noise(i, 0.005))
+            })
+            .collect::<Vec<_>>();
+        let curve = fit_constant(&samples).unwrap();
+        assert!((curve.speed(0.0) + std::f64::consts::FRAC_PI_3).abs() < 0.01);
+        assert!((curve.angle(0.0) - 0.3).abs() < 0.005);
+        assert!(fit_constant(&samples[..1]).is_none());
+    }
+
+    #[test]
+    fn test_fit_sine() {
+        let truth = SpeedCurve::Sine {
+            theta0: 1.0,
+            a: 0.9,
+            omega: 1.95,
+            phi: 0.7,
+            b: 2.090 - 0.9,
+            direction: -1.0,
+        };
+        // 100Hz 观测 3s，约一个周期，角度噪声约 0.3°
+        let samples = (0..300)
+            .map(|i| {
+                let t = 5.0 + i as f64 * 0.01;
+                (t, truth.angle(t) + noise(i, 0.005))
+            })
+            .collect::<Vec<_>>();
+        let curve = fit_sine(&samples, [1.884, 2.0]).unwrap();
+        let SpeedCurve::Sine {
+            a,
+            omega,
+            b,
+            direction,
+            ..
+        } = curve
+        else {
+            panic!("expected sine curve");
+        };
+        assert_eq!(direction, -1.0);
+        assert!((omega - 1.95).abs() < 0.02, "omega = {omega}");
+        assert!((a - 0.9).abs() < 0.05, "a = {a}");
+        assert!((b - 1.19).abs() < 0.05, "b = {b}");
+        // 外推 0.5s 的角度误差小于 0.5°
+        for t in [8.0, 8.2, 8.5] {
+            assert!((curve.angle(t) - truth.angle(t)).abs() < 0.5f64.to_radians());
+            assert!((curve.speed(t) - truth.speed(t)).abs() < 0.1);
+        }
+    }
+}
@@ -1,2 +1,6 @@
+pub mod rbt_comm_crc;
+pub mod rbt_comm_decoder;
 pub mod rbt_comm_device;
 pub mod rbt_comm_frame;
+pub mod rbt_comm_sim;
+pub mod rbt_comm_sync;
@@ -0,0 +1,71 @@
+//! 通讯校验
+//!
+//! 与大疆裁判系统协议保持一致，下位机可以直接复用官方的校验代码：
+//! - CRC8: 多项式 0x31（反射 0x8C），初值 0xFF，用于帧头
+//! - CRC16: 多项式 0x1021（反射 0x8408），初值 0xFFFF，用于整帧
+
+pub const CRC8_INIT: u8 = 0xFF;
+pub const CRC16_INIT: u16 = 0xFFFF;
+
+const CRC8_TABLE: [u8; 256] = {
+    let mut table = [0u8; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u8;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x01 != 0 { (crc >> 1) ^ 0x8C } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+const CRC16_TABLE: [u16; 256] = {
+    let mut table = [0u16; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u16;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x0001 != 0 { (crc >> 1) ^ 0x8408 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// 计算 CRC8
+pub fn crc8(bytes: &[u8]) -> u8 {
+    bytes
+        .iter()
+        .fold(CRC8_INIT, |crc, &byte| CRC8_TABLE[(crc ^ byte) as usize])
+}
+
+/// 计算 CRC16
+pub fn crc16(bytes: &[u8]) -> u16 {
+    bytes.iter().fold(CRC16_INIT, |crc, &byte| {
+        (crc >> 8) ^ CRC16_TABLE[((crc ^ byte as u16) & 0x00FF) as usize]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crc_check_value() {
+        assert_eq!(crc8(b"123456789"), 0x0B);
+        assert_eq!(crc16(b"123456789"), 0x6F91);
+    }
+
+    #[test]
+    fn test_crc_empty() {
+        assert_eq!(crc8(&[]), CRC8_INIT);
+        assert_eq!(crc16(&[]), CRC16_INIT);
+    }
+}
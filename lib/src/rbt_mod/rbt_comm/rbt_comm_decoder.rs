@@ -0,0 +1,341 @@
+//! 流式帧解码器
+//!
+//! 串口每次读到的只是字节流中的任意一段，可能包含半帧、噪声或者丢失的字节。
+//! 解码器负责缓存字节，寻找帧头 `SOF`，校验帧头 CRC8、帧尾 `EOF` 和整帧 CRC16，
+//! 丢弃损坏的帧并重新同步，最终输出完整的数据帧及其序号。适用于任意实现了 `CommData` 的帧类型。
+
+use std::marker::PhantomData;
+use tracing::debug;
+
+use crate::rbt_mod::rbt_comm::rbt_comm_frame::{CommData, FRAME_HEADER_SIZE};
+
+/// 解码统计信息
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct CommDecoderStats {
+    /// 成功解码的帧数
+    pub decoded_frames: u64,
+    /// 找到帧头但校验失败而被丢弃的帧数
+    pub corrupted_frames: u64,
+    /// 被丢弃的字节数（帧头之前的噪声，以及损坏帧的帧头）
+    pub dropped_bytes: u64,
+}
+
+/// 流式帧解码器
+pub struct CommDecoder<T: CommData> {
+    buffer: Vec<u8>,
+    stats: CommDecoderStats,
+    _marker: PhantomData<T>,
+}
+
+impl<T: CommData> Default for CommDecoder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: CommData> CommDecoder<T> {
+    pub fn new() -> Self {
+        Self {
+            buffer: Vec::with_capacity(T::FRAME_SIZE * 4),
+            stats: CommDecoderStats::default(),
+            _marker: PhantomData,
+        }
+    }
+
+    /// 追加新收到的字节
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// 尝试从缓存中取出下一帧及其序号，数据不足时返回 None
+    pub fn next_frame(&mut self) -> Option<(u8, T)> {
+        loop {
+            // 1. 丢弃帧头之前的所有字节
+            match self.buffer.iter().position(|&byte| byte == T::SOF) {
+                Some(0) => {}
+                Some(sof_idx) => self.drop_front(sof_idx),
+                None => {
+                    self.drop_front(self.buffer.len());
+                    return None;
+                }
+            }
+
+            // 2. 帧头到达后先校验 CRC8，尽早排除数据段中出现的伪帧头
+            if self.buffer.len() < FRAME_HEADER_SIZE {
+                return None;
+            }
+            if let Err(err) = T::validate_header(&self.buffer) {
+                debug!("Drop corrupted frame header: {}", err);
+                self.stats.corrupted_frames += 1;
+                self.drop_front(1);
+                continue;
+            }
+
+            // 3. 等待一整帧到达
+            if self.buffer.len() < T::FRAME_SIZE {
+                return None;
+            }
+
+            // 4. 校验并解码，失败则跳过当前帧头重新同步
+            match T::deserialize(&self.buffer[..T::FRAME_SIZE]) {
+                Ok(frame) => {
+                    self.buffer.drain(..T::FRAME_SIZE);
+                    self.stats.decoded_frames += 1;
+                    return Some(frame);
+                }
+                Err(err) => {
+                    debug!("Drop corrupted frame: {}", err);
+                    self.stats.corrupted_frames += 1;
+                    self.drop_front(1);
+                }
+            }
+        }
+    }
+
+    /// 追加字节并取出其中所有完整的帧
+    pub fn decode(&mut self, bytes: &[u8]) -> Vec<(u8, T)> {
+        self.push(bytes);
+        std::iter::from_fn(|| self.next_frame()).collect()
+    }
+
+    pub fn stats(&self) -> &CommDecoderStats {
+        &self.stats
+    }
+
+    /// 缓存中尚未处理的字节数
+    pub fn pending(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /// 清空缓存，统计信息保留
+    pub fn clear(&mut self) {
+        self.drop_front(self.buffer.len());
+    }
+
+    fn drop_front(&mut self, len: usize) {
+        self.buffer.drain(..len);
+        self.stats.dropped_bytes += len as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rbt_mod::rbt_comm::rbt_comm_frame::{SelfFraction, SensData, TaskMode};
+    use proptest::prelude::*;
+
+    const SOF: u8 = <SensData as CommData>::SOF;
+    const EOF: u8 = <SensData as CommData>::EOF;
+    const FRAME_SIZE: usize = <SensData as CommData>::FRAME_SIZE;
+
+    /// 负载中不出现帧头帧尾的 f32
+    fn payload_f32() -> impl Strategy<Value = f32> {
+        let byte = any::<u8>().prop_filter("no SOF/EOF", |b| *b != SOF && *b != EOF);
+        // 最高字节小于 0x7F，排除 NaN 和无穷
+        let high = (0u8..0x7F).prop_filter("no SOF", |b| *b != SOF);
+        (byte.clone(), byte.clone(), byte, high)
+            .prop_map(|(b0, b1, b2, b3)| f32::from_le_bytes([b0, b1, b2, b3]))
+    }
+
+    fn sens_data() -> impl Strategy<Value = SensData> {
+        (
+            prop_oneof![
+                Just(TaskMode::AutoShot),
+                Just(TaskMode::HitBigBuff),
+                Just(TaskMode::HitSmallBuff)
+            ],
+            prop_oneof![Just(SelfFraction::Red), Just(SelfFraction::Blue)],
+            payload_f32(),
+            payload_f32(),
+            payload_f32(),
+            payload_f32(),
+            payload_f32(),
+        )
+            .prop_map(
+                |(task_mode, self_fraction, bullet_speed, roll, yaw, pitch, yaw_speed)| SensData {
+                    task_mode,
+                    self_fraction,
+                    bullet_speed,
+                    gimbal_roll: roll,
+                    gimbal_yaw: yaw,
+                    gimbal_pitch: pitch,
+                    yaw_speed,
+                    mcu_time_us: 0,
+                    sync_host_time_us: 0,
+                    sync_mcu_recv_us: 0,
+                },
+            )
+    }
+
+    /// 按顺序为每一帧编号，帧数小于 SOF，序号中不会出现帧头
+    fn sens_frames(len: std::ops::Range<usize>) -> impl Strategy<Value = Vec<(u8, SensData)>> {
+        prop::collection::vec(sens_data(), len).prop_map(|frames| {
+            frames
+                .into_iter()
+                .enumerate()
+                .map(|(idx, data)| (idx as u8, data))
+                .collect()
+        })
+    }
+
+    fn garbage() -> impl Strategy<Value = Vec<u8>> {
+        prop::collection::vec(any::<u8>().prop_filter("no SOF", |b| *b != SOF), 0..40)
+    }
+
+    fn to_bytes((seq, data): &(u8, SensData)) -> Vec<u8> {
+        let mut buffer = vec![0u8; FRAME_SIZE];
+        data.serialize(*seq, &mut buffer).unwrap();
+        buffer
+    }
+
+    /// 按随机切分点分块喂给解码器
+    fn feed_split(
+        decoder: &mut CommDecoder<SensData>,
+        stream: &[u8],
+        chunk_sizes: &[usize],
+    ) -> Vec<(u8, SensData)> {
+        let mut frames = Vec::new();
+        let mut rest = stream;
+        let mut sizes = chunk_sizes.iter().cycle();
+        while !rest.is_empty() {
+            let size = (*sizes.next().unwrap()).min(rest.len());
+            let (chunk, tail) = rest.split_at(size);
+            frames.extend(decoder.decode(chunk));
+            rest = tail;
+        }
+        frames
+    }
+
+    fn chunk_sizes() -> impl Strategy<Value = Vec<usize>> {
+        prop::collection::vec(1usize..2 * FRAME_SIZE, 1..16)
+    }
+
+    proptest! {
+        #[test]
+        fn test_split_stream(frames in sens_frames(1..20), sizes in chunk_sizes()) {
+            let stream: Vec<u8> = frames.iter().flat_map(to_bytes).collect();
+            let mut decoder = CommDecoder::<SensData>::new();
+            let decoded = feed_split(&mut decoder, &stream, &sizes);
+            prop_assert_eq!(&decoded, &frames);
+            prop_assert_eq!(decoder.stats().corrupted_frames, 0);
+            prop_assert_eq!(decoder.stats().dropped_bytes, 0);
+            prop_assert_eq!(decoder.pending(), 0);
+        }
+
+        #[test]
+        fn test_garbage_between_frames(
+            frames in sens_frames(1..20),
+            noises in prop::collection::vec(garbage(), 20),
+            tail in garbage(),
+            sizes in chunk_sizes(),
+        ) {
+            let mut stream = Vec::new();
+            let mut garbage_len = tail.len();
+            for (frame, noise) in frames.iter().zip(noises.iter()) {
+                garbage_len += noise.len();
+                stream.extend_from_slice(noise);
+                stream.extend(to_bytes(frame));
+            }
+            stream.extend_from_slice(&tail);
+
+            let mut decoder = CommDecoder::<SensData>::new();
+            let decoded = feed_split(&mut decoder, &stream, &sizes);
+            prop_assert_eq!(decoded, frames);
+            prop_assert_eq!(decoder.stats().dropped_bytes, garbage_len as u64);
+        }
+
+        #[test]
+        fn test_dropped_byte_resync(
+            frames in sens_frames(2..20),
+            broken in any::<prop::sample::Index>(),
+            lost_byte in 0..FRAME_SIZE,
+            sizes in chunk_sizes(),
+        ) {
+            let broken = broken.index(frames.len());
+            let mut stream = Vec::new();
+            for (idx, frame) in frames.iter().enumerate() {
+                let mut bytes = to_bytes(frame);
+                if idx == broken {
+                    bytes.remove(lost_byte);
+                }
+                stream.extend(bytes);
+            }
+            // 损坏帧中可能残留伪帧头，补一段空闲字节让解码器走完
+            stream.extend([0u8; FRAME_SIZE]);
+
+            let mut decoder = CommDecoder::<SensData>::new();
+            let decoded = feed_split(&mut decoder, &stream, &sizes);
+            let mut expected = frames.clone();
+            expected.remove(broken);
+            prop_assert_eq!(decoded, expected);
+        }
+
+        #[test]
+        fn test_bit_flip_rejected(
+            frames in sens_frames(1..20),
+            broken in any::<prop::sample::Index>(),
+            flip_bit in 0..FRAME_SIZE * 8,
+            sizes in chunk_sizes(),
+        ) {
+            // 任意一位翻转都必须被校验发现，不能把错误的数据交给云台
+            let broken = broken.index(frames.len());
+            let mut stream = Vec::new();
+            for (idx, frame) in frames.iter().enumerate() {
+                let mut bytes = to_bytes(frame);
+                if idx == broken {
+                    bytes[flip_bit / 8] ^= 1 << (flip_bit % 8);
+                }
+                stream.extend(bytes);
+            }
+            stream.extend([0u8; FRAME_SIZE]);
+
+            let mut decoder = CommDecoder::<SensData>::new();
+            let decoded = feed_split(&mut decoder, &stream, &sizes);
+            let mut expected = frames.clone();
+            expected.remove(broken);
+            prop_assert_eq!(decoded, expected);
+        }
+
+        #[test]
+        fn test_random_bytes_accounting(
+            stream in prop::collection::vec(any::<u8>(), 0..512),
+            sizes in chunk_sizes(),
+        ) {
+            // 任意输入都不应 panic，且每个字节要么被解码，要么被丢弃，要么仍在缓存中
+            let mut decoder = CommDecoder::<SensData>::new();
+            feed_split(&mut decoder, &stream, &sizes);
+            let stats = decoder.stats();
+            prop_assert_eq!(
+                stats.decoded_frames * FRAME_SIZE as u64
+                    + stats.dropped_bytes
+                    + decoder.pending() as u64,
+                stream.len() as u64
+            );
+        }
+    }
+
+    #[test]
+    fn test_corrupted_end_of_frame() {
+        let data = SensData {
+            task_mode: TaskMode::AutoShot,
+            self_fraction: SelfFraction::Red,
+            bullet_speed: 24.0,
+            gimbal_roll: 0.0,
+            gimbal_yaw: 1.0,
+            gimbal_pitch: 2.0,
+            yaw_speed: 0.0,
+            mcu_time_us: 0,
+            sync_host_time_us: 0,
+            sync_mcu_recv_us: 0,
+        };
+        let mut broken = to_bytes(&(0, data.clone()));
+        broken[FRAME_SIZE - 1] = 0x00;
+        let mut stream = broken;
+        stream.extend(to_bytes(&(1, data.clone())));
+
+        let mut decoder = CommDecoder::<SensData>::new();
+        assert_eq!(decoder.decode(&stream), vec![(1, data)]);
+        assert!(decoder.stats().corrupted_frames >= 1);
+        assert_eq!(decoder.stats().dropped_bytes, FRAME_SIZE as u64);
+    }
+}
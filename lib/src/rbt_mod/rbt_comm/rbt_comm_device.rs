@@ -1,11 +1,71 @@
+use crate::rbt_infra::rbt_cfg::{CommCfg, CommDevice};
 use crate::rbt_infra::rbt_err::RbtResult;
-// use crate::rbt_mod::rbt_comm::{CommData, CtrlData, CtrlFrame, SensData, SensFrame};
+use crate::rbt_mod::rbt_comm::rbt_comm_frame::{CtrlData, SensFrame};
+use crate::rbt_mod::rbt_comm::rbt_comm_sync::RbtTimeSync;
+use rbt_serial::RbtSerial;
+use rbt_udp::RbtUdp;
 
+/// 上位机通讯设备接口
+///
+/// 所有方法均为异步方法，返回的 Future 需要满足 Send，便于在 tokio 任务之间移动
 pub trait RbtComm {
-    fn open(&mut self) -> RbtResult<()>;
-    fn send(&mut self, data: &[u8]) -> RbtResult<()>;
-    fn receive(&mut self) -> RbtResult<Vec<u8>>;
-    fn close(&mut self) -> RbtResult<()>;
+    fn open(&mut self) -> impl Future<Output = RbtResult<()>> + Send;
+    fn send(&mut self, data: &CtrlData) -> impl Future<Output = RbtResult<()>> + Send;
+    fn receive(&mut self) -> impl Future<Output = RbtResult<SensFrame>> + Send;
+    fn close(&mut self) -> impl Future<Output = RbtResult<()>> + Send;
+}
+
+/// 根据 `[comm_cfg]` 选择的通讯设备
+pub enum RbtCommDevice {
+    Serial(RbtSerial),
+    Udp(RbtUdp),
+}
+
+impl RbtCommDevice {
+    pub fn from_cfg(cfg: &CommCfg) -> Self {
+        match cfg.device {
+            CommDevice::Serial => RbtCommDevice::Serial(RbtSerial::new(cfg)),
+            CommDevice::Udp => RbtCommDevice::Udp(RbtUdp::new(cfg)),
+        }
+    }
+
+    /// 与下位机的时间同步状态
+    pub fn time_sync(&self) -> &RbtTimeSync {
+        match self {
+            RbtCommDevice::Serial(serial) => serial.time_sync(),
+            RbtCommDevice::Udp(udp) => udp.time_sync(),
+        }
+    }
+}
+
+impl RbtComm for RbtCommDevice {
+    async fn open(&mut self) -> RbtResult<()> {
+        match self {
+            RbtCommDevice::Serial(serial) => serial.open().await,
+            RbtCommDevice::Udp(udp) => udp.open().await,
+        }
+    }
+
+    async fn send(&mut self, data: &CtrlData) -> RbtResult<()> {
+        match self {
+            RbtCommDevice::Serial(serial) => serial.send(data).await,
+            RbtCommDevice::Udp(udp) => udp.send(data).await,
+        }
+    }
+
+    async fn receive(&mut self) -> RbtResult<SensFrame> {
+        match self {
+            RbtCommDevice::Serial(serial) => serial.receive().await,
+            RbtCommDevice::Udp(udp) => udp.receive().await,
+        }
+    }
+
+    async fn close(&mut self) -> RbtResult<()> {
+        match self {
+            RbtCommDevice::Serial(serial) => serial.close().await,
+            RbtCommDevice::Udp(udp) => udp.close().await,
+        }
+    }
 }
 
 pub mod rbt_usb {
@@ -40,61 +100,667 @@ pub mod rbt_usb {
 /// 串口通讯(usart)
 /// 基于 tokio_serial 封装
 pub mod rbt_serial {
-    use tokio_serial::SerialStream;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::time::Instant;
+    use tokio_serial::{SerialPortBuilderExt, SerialStream};
+    use tracing::{error, info, warn};
 
     use super::RbtComm;
-    use crate::rbt_infra::rbt_err::RbtResult;
+    use crate::rbt_infra::rbt_cfg::CommCfg;
+    use crate::rbt_infra::rbt_err::{CommError, RbtResult};
+    use crate::rbt_mod::rbt_comm::rbt_comm_decoder::{CommDecoder, CommDecoderStats};
+    use crate::rbt_mod::rbt_comm::rbt_comm_frame::{
+        CommData, CommSeqCounter, CtrlData, SensData, SensFrame,
+    };
+    use crate::rbt_mod::rbt_comm::rbt_comm_sync::RbtTimeSync;
+
+    /// 单次从串口读取的最大字节数
+    const READ_CHUNK_SIZE: usize = 64;
 
     pub struct RbtSerial {
+        cfg: CommCfg,
         serial_stream: Option<SerialStream>,
-        last_record_seq: u8,
+        decoder: CommDecoder<SensData>,
+        send_seq: u8,
+        seq_counter: CommSeqCounter,
+        time_sync: RbtTimeSync,
+    }
+
+    impl RbtSerial {
+        /// 根据配置创建串口，需要调用 `open` 之后才能收发
+        pub fn new(cfg: &CommCfg) -> Self {
+            Self {
+                cfg: cfg.clone(),
+                serial_stream: None,
+                decoder: CommDecoder::new(),
+                send_seq: 0,
+                seq_counter: CommSeqCounter::default(),
+                time_sync: RbtTimeSync::new(),
+            }
+        }
+
+        /// 使用已经打开的串口流创建，主要用于伪终端(pty)测试
+        pub fn from_stream(cfg: &CommCfg, serial_stream: SerialStream) -> Self {
+            Self {
+                cfg: cfg.clone(),
+                serial_stream: Some(serial_stream),
+                decoder: CommDecoder::new(),
+                send_seq: 0,
+                seq_counter: CommSeqCounter::default(),
+                time_sync: RbtTimeSync::new(),
+            }
+        }
+
+        pub fn is_open(&self) -> bool {
+            self.serial_stream.is_some()
+        }
+
+        /// 接收端解码统计，可用于观察链路质量
+        pub fn decoder_stats(&self) -> &CommDecoderStats {
+            self.decoder.stats()
+        }
+
+        /// 接收帧序号统计
+        pub fn seq_counter(&self) -> &CommSeqCounter {
+            &self.seq_counter
+        }
+
+        /// 与下位机的时间同步状态
+        pub fn time_sync(&self) -> &RbtTimeSync {
+            &self.time_sync
+        }
+
+        fn stream_mut(&mut self) -> Result<&mut SerialStream, CommError> {
+            self.serial_stream.as_mut().ok_or(CommError::NoPort)
+        }
+    }
+
+    /// 将打开串口时的错误映射为 CommError
+    fn map_open_err(err: tokio_serial::Error) -> CommError {
+        error!("Failed to open serial port: {}", err);
+        match err.kind() {
+            tokio_serial::ErrorKind::NoDevice
+            | tokio_serial::ErrorKind::Io(std::io::ErrorKind::NotFound) => CommError::NoPort,
+            tokio_serial::ErrorKind::Io(
+                std::io::ErrorKind::ResourceBusy | std::io::ErrorKind::PermissionDenied,
+            ) => CommError::PortOccupied,
+            tokio_serial::ErrorKind::Io(_) => CommError::IoError,
+            // EBUSY 没有对应的 io::ErrorKind，只能从描述中区分
+            tokio_serial::ErrorKind::Unknown if err.description.contains("busy") => {
+                CommError::PortOccupied
+            }
+            _ => CommError::SystemError,
+        }
+    }
+
+    /// 将读写过程中的错误映射为 CommError
+    fn map_io_err(err: std::io::Error) -> CommError {
+        warn!("Serial io error: {}", err);
+        match err.kind() {
+            std::io::ErrorKind::TimedOut => CommError::TimeOut,
+            std::io::ErrorKind::NotFound => CommError::NoPort,
+            _ => CommError::IoError,
+        }
     }
 
     impl RbtComm for RbtSerial {
-        fn open(&mut self) -> RbtResult<()> {
+        async fn open(&mut self) -> RbtResult<()> {
+            if self.is_open() {
+                warn!("Serial port {} is already open", self.cfg.serial_port);
+                return Ok(());
+            }
+            let serial_stream = tokio_serial::new(&self.cfg.serial_port, self.cfg.serial_baud_rate)
+                .timeout(self.cfg.timeout())
+                .open_native_async()
+                .map_err(map_open_err)?;
+            info!(
+                "Serial port {} opened with baud rate {}",
+                self.cfg.serial_port, self.cfg.serial_baud_rate
+            );
+            self.serial_stream = Some(serial_stream);
             Ok(())
         }
 
-        fn send(&mut self, data: &[u8]) -> RbtResult<()> {
+        async fn send(&mut self, data: &CtrlData) -> RbtResult<()> {
+            let data = CtrlData {
+                sync_host_time_us: self.time_sync.host_time_us(Instant::now()),
+                ..data.clone()
+            };
+            let mut buffer = [0u8; CtrlData::FRAME_SIZE];
+            data.serialize(self.send_seq, &mut buffer)?;
+
+            let timeout = self.cfg.timeout();
+            let stream = self.stream_mut()?;
+            tokio::time::timeout(timeout, stream.write_all(&buffer))
+                .await
+                .map_err(|_| CommError::TimeOut)?
+                .map_err(map_io_err)?;
+            self.send_seq = self.send_seq.wrapping_add(1);
             Ok(())
         }
 
-        fn receive(&mut self) -> RbtResult<Vec<u8>> {
-            Ok(vec![])
+        async fn receive(&mut self) -> RbtResult<SensFrame> {
+            // 先取出缓存中已经完整的帧
+            if let Some((seq, data)) = self.decoder.next_frame() {
+                self.seq_counter.record(seq);
+                return Ok(self.time_sync.stamp(data, Instant::now()));
+            }
+
+            // 字节流可能被任意切分，整个接收过程共用一个超时
+            let deadline = Instant::now() + self.cfg.timeout();
+            let stream = self.serial_stream.as_mut().ok_or(CommError::NoPort)?;
+            let mut buffer = [0u8; READ_CHUNK_SIZE];
+            loop {
+                let len = tokio::time::timeout_at(deadline, stream.read(&mut buffer))
+                    .await
+                    .map_err(|_| CommError::TimeOut)?
+                    .map_err(map_io_err)?;
+                if len == 0 {
+                    warn!("Serial port {} reached end of stream", self.cfg.serial_port);
+                    return Err(CommError::IoError.into());
+                }
+                self.decoder.push(&buffer[..len]);
+                if let Some((seq, data)) = self.decoder.next_frame() {
+                    self.seq_counter.record(seq);
+                    return Ok(self.time_sync.stamp(data, Instant::now()));
+                }
+            }
         }
 
-        fn close(&mut self) -> RbtResult<()> {
+        async fn close(&mut self) -> RbtResult<()> {
+            if let Some(mut serial_stream) = self.serial_stream.take() {
+                self.decoder.clear();
+                serial_stream.shutdown().await.map_err(map_io_err)?;
+                info!("Serial port {} closed", self.cfg.serial_port);
+            }
             Ok(())
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::rbt_infra::rbt_err::RbtError;
+        use crate::rbt_mod::rbt_comm::rbt_comm_frame::{
+            AimingState, SelfFraction, ShotBuffMode, ShotMode, TaskMode,
+        };
+        use tokio_serial::SerialPort;
+
+        fn comm_cfg(port: &str) -> CommCfg {
+            toml::from_str(&format!(
+                "device = \"serial\"\n\
+                 serial_port = \"{port}\"\n\
+                 serial_baud_rate = 115200\n\
+                 udp_local_addr = \"127.0.0.1:0\"\n\
+                 udp_remote_addr = \"127.0.0.1:9\"\n\
+                 timeout_ms = 50"
+            ))
+            .unwrap()
+        }
+
+        fn sens_data() -> SensData {
+            SensData {
+                task_mode: TaskMode::HitSmallBuff,
+                self_fraction: SelfFraction::Blue,
+                bullet_speed: 23.5,
+                gimbal_roll: 0.5,
+                gimbal_yaw: -12.25,
+                gimbal_pitch: 3.75,
+                yaw_speed: 1.5,
+                mcu_time_us: 0,
+                sync_host_time_us: 0,
+                sync_mcu_recv_us: 0,
+            }
+        }
+
+        #[tokio::test]
+        async fn test_send_and_receive_over_pty() {
+            let (mut mcu, host) = SerialStream::pair().unwrap();
+            let mut serial = RbtSerial::from_stream(&comm_cfg("pty"), host);
+
+            let ctrl_data = CtrlData {
+                gimbal_yaw: 10.5,
+                gimbal_pitch: -2.0,
+                shot_mode: ShotMode::AutoFire,
+                shot_buff_mode: ShotBuffMode::ShotBuffOff,
+                aiming_state: AimingState::AimingWithTarget,
+                sync_host_time_us: 0,
+            };
+            serial.send(&ctrl_data).await.unwrap();
+            let mut buffer = [0u8; CtrlData::FRAME_SIZE];
+            mcu.read_exact(&mut buffer).await.unwrap();
+            let (seq, sent) = CtrlData::deserialize(&buffer).unwrap();
+            assert_eq!(seq, 0);
+            // 时间同步字段由通讯层在发送时填写
+            assert_eq!(CtrlData { sync_host_time_us: 0, ..sent }, ctrl_data);
+            serial.send(&ctrl_data).await.unwrap();
+            mcu.read_exact(&mut buffer).await.unwrap();
+            assert_eq!(CtrlData::deserialize(&buffer).unwrap().0, 1);
+
+            let mut buffer = [0u8; SensData::FRAME_SIZE];
+            sens_data().serialize(0, &mut buffer).unwrap();
+            // 模拟串口分两次到达
+            mcu.write_all(&buffer[..7]).await.unwrap();
+            mcu.write_all(&buffer[7..]).await.unwrap();
+            let sens_frame = serial.receive().await.unwrap();
+            assert_eq!(sens_frame.data(), &sens_data());
+        }
+
+        #[tokio::test]
+        async fn test_receive_resync_over_pty() {
+            let (mut mcu, host) = SerialStream::pair().unwrap();
+            let mut serial = RbtSerial::from_stream(&comm_cfg("pty"), host);
+
+            let frame = |seq: u8| {
+                let mut buffer = [0u8; SensData::FRAME_SIZE];
+                sens_data().serialize(seq, &mut buffer).unwrap();
+                buffer
+            };
+            // 噪声 + 半帧 + 两个完整帧
+            let mut stream = vec![0x00, 0x12, 0xEE];
+            stream.extend_from_slice(&frame(0)[..10]);
+            stream.extend_from_slice(&frame(1));
+            stream.extend_from_slice(&frame(2));
+            mcu.write_all(&stream).await.unwrap();
+
+            assert_eq!(serial.receive().await.unwrap().data(), &sens_data());
+            assert_eq!(serial.receive().await.unwrap().data(), &sens_data());
+            assert_eq!(serial.decoder_stats().decoded_frames, 2);
+            assert!(serial.decoder_stats().corrupted_frames >= 1);
+            assert_eq!(serial.seq_counter().lost_frames(), 0);
+        }
+
+        #[tokio::test]
+        async fn test_count_lost_frames() {
+            let (mut mcu, host) = SerialStream::pair().unwrap();
+            let mut serial = RbtSerial::from_stream(&comm_cfg("pty"), host);
+
+            // 序号 254 -> 255 -> (0, 1 丢失) -> 2 -> (3 校验失败) -> 4
+            let mut stream = Vec::new();
+            for seq in [254u8, 255, 2, 3, 4] {
+                let mut buffer = [0u8; SensData::FRAME_SIZE];
+                sens_data().serialize(seq, &mut buffer).unwrap();
+                if seq == 3 {
+                    buffer[12] ^= 0x40;
+                }
+                stream.extend_from_slice(&buffer);
+            }
+            mcu.write_all(&stream).await.unwrap();
+
+            for _ in 0..4 {
+                serial.receive().await.unwrap();
+            }
+            assert_eq!(serial.seq_counter().received_frames(), 4);
+            assert_eq!(serial.seq_counter().lost_frames(), 3);
+            assert_eq!(serial.decoder_stats().corrupted_frames, 1);
+        }
+
+        #[tokio::test]
+        async fn test_receive_time_out() {
+            let (_mcu, host) = SerialStream::pair().unwrap();
+            let mut serial = RbtSerial::from_stream(&comm_cfg("pty"), host);
+            let err = serial.receive().await.unwrap_err();
+            assert!(matches!(err, RbtError::CommError(CommError::TimeOut)));
+        }
+
+        #[tokio::test]
+        async fn test_open_by_path() {
+            let (_mcu, slave) = SerialStream::pair().unwrap();
+            let slave_name = slave.name().unwrap();
+            drop(slave);
+
+            let mut serial = RbtSerial::new(&comm_cfg(&slave_name));
+            serial.open().await.unwrap();
+            assert!(serial.is_open());
+            serial.close().await.unwrap();
+            assert!(!serial.is_open());
+        }
+
+        #[test]
+        fn test_map_open_err() {
+            // root 用户不受 TIOCEXCL 限制，这里直接构造 EBUSY 对应的错误
+            let busy = tokio_serial::Error::new(
+                tokio_serial::ErrorKind::Unknown,
+                "Device or resource busy",
+            );
+            assert!(matches!(map_open_err(busy), CommError::PortOccupied));
+            let missing = tokio_serial::Error::new(
+                tokio_serial::ErrorKind::Io(std::io::ErrorKind::NotFound),
+                "No such file or directory",
+            );
+            assert!(matches!(map_open_err(missing), CommError::NoPort));
+            let no_device =
+                tokio_serial::Error::new(tokio_serial::ErrorKind::NoDevice, "No such device");
+            assert!(matches!(map_open_err(no_device), CommError::NoPort));
+            let denied = tokio_serial::Error::new(
+                tokio_serial::ErrorKind::Io(std::io::ErrorKind::PermissionDenied),
+                "Permission denied",
+            );
+            assert!(matches!(map_open_err(denied), CommError::PortOccupied));
+        }
+
+        #[tokio::test]
+        async fn test_open_missing_port() {
+            let mut serial = RbtSerial::new(&comm_cfg("/dev/rbt_no_such_port"));
+            let err = serial.open().await.unwrap_err();
+            assert!(matches!(err, RbtError::CommError(CommError::NoPort)));
+        }
+
+        #[tokio::test]
+        async fn test_receive_before_open() {
+            let mut serial = RbtSerial::new(&comm_cfg("/dev/rbt_no_such_port"));
+            let err = serial.receive().await.unwrap_err();
+            assert!(matches!(err, RbtError::CommError(CommError::NoPort)));
+        }
+    }
 }
 
+/// UDP通讯
+/// 基于 tokio::net::UdpSocket 封装，用于网口下位机和仿真器
+///
+/// 每个数据报可以包含一帧或多帧，帧格式与串口完全一致
 pub mod rbt_udp {
-    // /// UDP通讯
-    // /// 基于 tokio_udp 封装
-    // use tokio_udp::UdpSocket;
-    // use crate::rbt_infra::rbt_err::RbtResult;
+    use tokio::net::UdpSocket;
+    use tokio::time::Instant;
+    use tracing::{info, warn};
 
-    // pub struct RbtUdp {
-    //     udp_socket: Option<UdpSocket>,
-    //     last_record_seq: u8,
-    // }
+    use super::RbtComm;
+    use crate::rbt_infra::rbt_cfg::CommCfg;
+    use crate::rbt_infra::rbt_err::{CommError, RbtResult};
+    use crate::rbt_mod::rbt_comm::rbt_comm_decoder::{CommDecoder, CommDecoderStats};
+    use crate::rbt_mod::rbt_comm::rbt_comm_frame::{
+        CommData, CommSeqCounter, CtrlData, SensData, SensFrame,
+    };
+    use crate::rbt_mod::rbt_comm::rbt_comm_sync::RbtTimeSync;
 
-    // impl RbtComm for RbtUdp {
-    //     fn open(&mut self) -> RbtResult<()> {
-    //         Ok(())
-    //     }
+    /// 单个数据报的最大长度
+    const MAX_DATAGRAM_SIZE: usize = 1024;
 
-    //     fn send(&mut self, data: &[u8]) -> RbtResult<()> {
-    //         Ok(())
-    //     }
+    pub struct RbtUdp {
+        cfg: CommCfg,
+        udp_socket: Option<UdpSocket>,
+        decoder: CommDecoder<SensData>,
+        send_seq: u8,
+        seq_counter: CommSeqCounter,
+        time_sync: RbtTimeSync,
+    }
 
-    //     fn receive(&mut self) -> RbtResult<Vec<u8>> {
-    //         Ok(vec![])
-    //     }
+    impl RbtUdp {
+        /// 根据配置创建，需要调用 `open` 之后才能收发
+        pub fn new(cfg: &CommCfg) -> Self {
+            Self {
+                cfg: cfg.clone(),
+                udp_socket: None,
+                decoder: CommDecoder::new(),
+                send_seq: 0,
+                seq_counter: CommSeqCounter::default(),
+                time_sync: RbtTimeSync::new(),
+            }
+        }
 
-    //     fn close(&mut self) -> RbtResult<()> {
-    //         Ok(())
-    //     }
-    // }
+        pub fn is_open(&self) -> bool {
+            self.udp_socket.is_some()
+        }
+
+        /// 实际绑定的本机地址，配置端口为 0 时由系统分配
+        pub fn local_addr(&self) -> RbtResult<std::net::SocketAddr> {
+            let socket = self.udp_socket.as_ref().ok_or(CommError::NoPort)?;
+            Ok(socket.local_addr().map_err(map_io_err)?)
+        }
+
+        /// 接收端解码统计，可用于观察链路质量
+        pub fn decoder_stats(&self) -> &CommDecoderStats {
+            self.decoder.stats()
+        }
+
+        /// 接收帧序号统计
+        pub fn seq_counter(&self) -> &CommSeqCounter {
+            &self.seq_counter
+        }
+
+        /// 与下位机的时间同步状态
+        pub fn time_sync(&self) -> &RbtTimeSync {
+            &self.time_sync
+        }
+    }
+
+    /// 将 socket 错误映射为 CommError
+    fn map_io_err(err: std::io::Error) -> CommError {
+        warn!("Udp io error: {}", err);
+        match err.kind() {
+            std::io::ErrorKind::TimedOut => CommError::TimeOut,
+            std::io::ErrorKind::AddrInUse => CommError::PortOccupied,
+            std::io::ErrorKind::AddrNotAvailable => CommError::NoPort,
+            _ => CommError::IoError,
+        }
+    }
+
+    impl RbtComm for RbtUdp {
+        async fn open(&mut self) -> RbtResult<()> {
+            if self.is_open() {
+                warn!("Udp socket {} is already open", self.cfg.udp_local_addr);
+                return Ok(());
+            }
+            let udp_socket = UdpSocket::bind(self.cfg.udp_local_addr)
+                .await
+                .map_err(map_io_err)?;
+            // connect 之后只接收来自下位机地址的数据报
+            udp_socket
+                .connect(self.cfg.udp_remote_addr)
+                .await
+                .map_err(map_io_err)?;
+            info!(
+                "Udp socket bound to {} and connected to {}",
+                udp_socket.local_addr().map_err(map_io_err)?,
+                self.cfg.udp_remote_addr
+            );
+            self.udp_socket = Some(udp_socket);
+            Ok(())
+        }
+
+        async fn send(&mut self, data: &CtrlData) -> RbtResult<()> {
+            let data = CtrlData {
+                sync_host_time_us: self.time_sync.host_time_us(Instant::now()),
+                ..data.clone()
+            };
+            let mut buffer = [0u8; CtrlData::FRAME_SIZE];
+            data.serialize(self.send_seq, &mut buffer)?;
+
+            let socket = self.udp_socket.as_ref().ok_or(CommError::NoPort)?;
+            tokio::time::timeout(self.cfg.timeout(), socket.send(&buffer))
+                .await
+                .map_err(|_| CommError::TimeOut)?
+                .map_err(map_io_err)?;
+            self.send_seq = self.send_seq.wrapping_add(1);
+            Ok(())
+        }
+
+        async fn receive(&mut self) -> RbtResult<SensFrame> {
+            // 先取出上一个数据报中剩余的帧
+            if let Some((seq, data)) = self.decoder.next_frame() {
+                self.seq_counter.record(seq);
+                return Ok(self.time_sync.stamp(data, Instant::now()));
+            }
+
+            let deadline = Instant::now() + self.cfg.timeout();
+            let socket = self.udp_socket.as_ref().ok_or(CommError::NoPort)?;
+            let mut buffer = [0u8; MAX_DATAGRAM_SIZE];
+            loop {
+                let len = match tokio::time::timeout_at(deadline, socket.recv(&mut buffer)).await {
+                    Err(_) => return Err(CommError::TimeOut.into()),
+                    Ok(Ok(len)) => len,
+                    // 下位机未启动时系统会返回 ICMP 端口不可达，继续等待直到超时
+                    Ok(Err(err)) if err.kind() == std::io::ErrorKind::ConnectionRefused => {
+                        continue;
+                    }
+                    Ok(Err(err)) => return Err(map_io_err(err).into()),
+                };
+                self.decoder.push(&buffer[..len]);
+                if let Some((seq, data)) = self.decoder.next_frame() {
+                    self.seq_counter.record(seq);
+                    return Ok(self.time_sync.stamp(data, Instant::now()));
+                }
+            }
+        }
+
+        async fn close(&mut self) -> RbtResult<()> {
+            if self.udp_socket.take().is_some() {
+                self.decoder.clear();
+                info!("Udp socket {} closed", self.cfg.udp_local_addr);
+            }
+            Ok(())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::rbt_infra::rbt_err::RbtError;
+        use crate::rbt_mod::rbt_comm::rbt_comm_frame::{
+            AimingState, SelfFraction, ShotBuffMode, ShotMode, TaskMode,
+        };
+
+        fn comm_cfg(local: &str, remote: &str) -> CommCfg {
+            toml::from_str(&format!(
+                "device = \"udp\"\n\
+                 serial_port = \"/dev/ttyACM0\"\n\
+                 serial_baud_rate = 115200\n\
+                 udp_local_addr = \"{local}\"\n\
+                 udp_remote_addr = \"{remote}\"\n\
+                 timeout_ms = 50"
+            ))
+            .unwrap()
+        }
+
+        fn sens_frame(seq: u8) -> [u8; SensData::FRAME_SIZE] {
+            let mut buffer = [0u8; SensData::FRAME_SIZE];
+            SensData {
+                task_mode: TaskMode::AutoShot,
+                self_fraction: SelfFraction::Red,
+                bullet_speed: 24.5,
+                gimbal_roll: 0.0,
+                gimbal_yaw: 90.0 + seq as f32,
+                gimbal_pitch: -1.5,
+                yaw_speed: 0.25,
+                mcu_time_us: 0,
+                sync_host_time_us: 0,
+                sync_mcu_recv_us: 0,
+            }
+            .serialize(seq, &mut buffer)
+            .unwrap();
+            buffer
+        }
+
+        /// 在本机回环地址上用一个普通 socket 充当下位机
+        async fn loopback() -> (UdpSocket, RbtUdp) {
+            let mcu = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let mcu_addr = mcu.local_addr().unwrap().to_string();
+            let mut udp = RbtUdp::new(&comm_cfg("127.0.0.1:0", &mcu_addr));
+            udp.open().await.unwrap();
+            mcu.connect(udp.local_addr().unwrap()).await.unwrap();
+            (mcu, udp)
+        }
+
+        #[tokio::test]
+        async fn test_send_and_receive_over_loopback() {
+            let (mcu, mut udp) = loopback().await;
+
+            let ctrl_data = CtrlData {
+                gimbal_yaw: 45.0,
+                gimbal_pitch: 2.5,
+                shot_mode: ShotMode::AimOnly,
+                shot_buff_mode: ShotBuffMode::ShotBuffOn,
+                aiming_state: AimingState::AimingNoTarget,
+                sync_host_time_us: 0,
+            };
+            udp.send(&ctrl_data).await.unwrap();
+            let mut buffer = [0u8; 64];
+            let len = mcu.recv(&mut buffer).await.unwrap();
+            assert_eq!(len, CtrlData::FRAME_SIZE);
+            let (seq, sent) = CtrlData::deserialize(&buffer[..len]).unwrap();
+            assert_eq!(seq, 0);
+            assert_eq!(CtrlData { sync_host_time_us: 0, ..sent }, ctrl_data);
+
+            mcu.send(&sens_frame(0)).await.unwrap();
+            let frame = udp.receive().await.unwrap();
+            assert_eq!(frame.data().gimbal_yaw, 90.0);
+
+            udp.close().await.unwrap();
+            assert!(!udp.is_open());
+        }
+
+        #[tokio::test]
+        async fn test_multi_frame_datagram() {
+            let (mcu, mut udp) = loopback().await;
+
+            // 一个数据报中带两帧，中间丢失序号 1
+            let mut datagram = sens_frame(0).to_vec();
+            datagram.extend_from_slice(&sens_frame(2));
+            mcu.send(&datagram).await.unwrap();
+
+            assert_eq!(udp.receive().await.unwrap().data().gimbal_yaw, 90.0);
+            assert_eq!(udp.receive().await.unwrap().data().gimbal_yaw, 92.0);
+            assert_eq!(udp.seq_counter().received_frames(), 2);
+            assert_eq!(udp.seq_counter().lost_frames(), 1);
+        }
+
+        #[tokio::test]
+        async fn test_ignore_corrupted_datagram() {
+            let (mcu, mut udp) = loopback().await;
+
+            let mut corrupted = sens_frame(0);
+            corrupted[10] ^= 0x01;
+            mcu.send(&corrupted).await.unwrap();
+            mcu.send(&sens_frame(1)).await.unwrap();
+
+            assert_eq!(udp.receive().await.unwrap().data().gimbal_yaw, 91.0);
+            assert!(udp.decoder_stats().corrupted_frames >= 1);
+        }
+
+        #[tokio::test]
+        async fn test_receive_time_out() {
+            let (_mcu, mut udp) = loopback().await;
+            let err = udp.receive().await.unwrap_err();
+            assert!(matches!(err, RbtError::CommError(CommError::TimeOut)));
+        }
+
+        #[tokio::test]
+        async fn test_receive_time_out_without_mcu() {
+            // 下位机端口未监听时同样表现为超时
+            let port = {
+                let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+                socket.local_addr().unwrap().to_string()
+            };
+            let mut udp = RbtUdp::new(&comm_cfg("127.0.0.1:0", &port));
+            udp.open().await.unwrap();
+            udp.send(&CtrlData {
+                gimbal_yaw: 0.0,
+                gimbal_pitch: 0.0,
+                shot_mode: ShotMode::DoNothing,
+                shot_buff_mode: ShotBuffMode::ShotBuffOff,
+                aiming_state: AimingState::NoAimingNoTarget,
+                sync_host_time_us: 0,
+            })
+            .await
+            .unwrap();
+            let err = udp.receive().await.unwrap_err();
+            assert!(matches!(err, RbtError::CommError(CommError::TimeOut)));
+        }
+
+        #[tokio::test]
+        async fn test_address_in_use() {
+            let (_mcu, udp) = loopback().await;
+            let occupied = udp.local_addr().unwrap().to_string();
+            let mut other = RbtUdp::new(&comm_cfg(&occupied, "127.0.0.1:9"));
+            let err = other.open().await.unwrap_err();
+            assert!(matches!(err, RbtError::CommError(CommError::PortOccupied)));
+        }
+
+        #[tokio::test]
+        async fn test_receive_before_open() {
+            let mut udp = RbtUdp::new(&comm_cfg("127.0.0.1:0", "127.0.0.1:9"));
+            let err = udp.receive().await.unwrap_err();
+            assert!(matches!(err, RbtError::CommError(CommError::NoPort)));
+        }
+    }
 }
@@ -1,52 +1,238 @@
 /// 该文件定义了上下位机通讯的数据结构
 /// 卢钟瑾 2025.08.06
-use crate::rbt_infra::rbt_err::{CommError, RbtResult};
+use crate::rbt_infra::rbt_err::{CommError, RbtError, RbtResult};
+use crate::rbt_mod::rbt_comm::rbt_comm_crc::{crc8, crc16};
 use tracing::warn;
 
+/// 派生宏，与同名 trait 一起导出
+pub use rbt_derive::{CommData, CommField};
+
+/// 帧头长度: SOF + SEQ + CRC8
+pub const FRAME_HEADER_SIZE: usize = 3;
+/// 帧尾长度: CRC16 + EOF
+pub const FRAME_TAIL_SIZE: usize = 3;
+
 /// 帧结构 trait
 /// 类型大小确定
+///
+/// 帧格式: `SOF | SEQ | CRC8 | payload | CRC16 | EOF`
+/// - CRC8 校验 SOF 和 SEQ，便于在流中尽早排除伪帧头
+/// - CRC16 校验 CRC16 之前的全部字节，小端序
+/// - SEQ 为滚动序号，每发送一帧加一，接收端据此统计丢帧
 pub trait CommData: Sized {
-    const FRAME_SIZE: usize;
+    const PAYLOAD_SIZE: usize;
+    const FRAME_SIZE: usize = FRAME_HEADER_SIZE + Self::PAYLOAD_SIZE + FRAME_TAIL_SIZE;
     const SOF: u8;
     const EOF: u8;
 
-    fn serialize(&self, buffer: &mut [u8]) -> RbtResult<()>;
+    /// 序列化数据段，`payload` 长度为 `PAYLOAD_SIZE`
+    fn serialize_payload(&self, payload: &mut [u8]);
 
-    fn deserialize(buffer: &[u8]) -> RbtResult<Self>;
+    /// 反序列化数据段，`payload` 长度为 `PAYLOAD_SIZE`，字段取值非法时返回错误
+    fn deserialize_payload(payload: &[u8]) -> RbtResult<Self>;
 
-    /// 检查帧长度，帧头和帧尾
-    fn validate_frame(buffer: &[u8]) -> RbtResult<()> {
+    /// 序列化为完整的一帧，并写入序号和校验
+    fn serialize(&self, seq: u8, buffer: &mut [u8]) -> RbtResult<()> {
+        Self::validate_frame_len(buffer)?;
+
+        let crc16_idx = Self::FRAME_SIZE - FRAME_TAIL_SIZE;
+        buffer[0] = Self::SOF;
+        buffer[1] = seq;
+        buffer[2] = crc8(&buffer[..2]);
+        self.serialize_payload(&mut buffer[FRAME_HEADER_SIZE..crc16_idx]);
+        let crc = crc16(&buffer[..crc16_idx]);
+        buffer[crc16_idx..crc16_idx + 2].copy_from_slice(&crc.to_le_bytes());
+        buffer[Self::FRAME_SIZE - 1] = Self::EOF;
+
+        Ok(())
+    }
+
+    /// 校验并反序列化一帧，返回帧序号和数据
+    fn deserialize(buffer: &[u8]) -> RbtResult<(u8, Self)> {
+        Self::validate_frame(buffer)?;
+
+        let payload = &buffer[FRAME_HEADER_SIZE..Self::FRAME_SIZE - FRAME_TAIL_SIZE];
+        Ok((buffer[1], Self::deserialize_payload(payload)?))
+    }
+
+    /// 检查帧长度，序列化时写入的缓冲区只需要满足该条件
+    fn validate_frame_len(buffer: &[u8]) -> RbtResult<()> {
         if buffer.len() != Self::FRAME_SIZE {
             return Err(CommError::FrameLengthError.into());
         }
+        Ok(())
+    }
+
+    /// 检查帧头和帧头校验，只需要前 `FRAME_HEADER_SIZE` 个字节
+    fn validate_header(buffer: &[u8]) -> RbtResult<()> {
+        if buffer.len() < FRAME_HEADER_SIZE {
+            return Err(CommError::FrameLengthError.into());
+        }
         if buffer[0] != Self::SOF {
             return Err(CommError::InvalidStartOfFrame.into());
         }
-        if buffer[buffer.len() - 1] != Self::EOF {
+        if crc8(&buffer[..2]) != buffer[2] {
+            return Err(CommError::CorruptedFrame.into());
+        }
+        Ok(())
+    }
+
+    /// 检查帧长度，帧头，帧尾和校验
+    fn validate_frame(buffer: &[u8]) -> RbtResult<()> {
+        Self::validate_frame_len(buffer)?;
+        Self::validate_header(buffer)?;
+        if buffer[Self::FRAME_SIZE - 1] != Self::EOF {
             return Err(CommError::InvalidEndOfFrame.into());
         }
+        let crc16_idx = Self::FRAME_SIZE - FRAME_TAIL_SIZE;
+        let crc = u16::from_le_bytes([buffer[crc16_idx], buffer[crc16_idx + 1]]);
+        if crc16(&buffer[..crc16_idx]) != crc {
+            return Err(CommError::CorruptedFrame.into());
+        }
         Ok(())
     }
 }
 
+/// 帧内字段 trait
+/// 按小端序编码，占用固定的 `SIZE` 字节
+///
+/// 基础数值类型已经实现，`#[repr(u8)]` 枚举通过 `#[derive(CommField)]` 实现
+pub trait CommField: Sized {
+    const SIZE: usize;
+
+    /// 写入字段，`bytes` 长度为 `SIZE`
+    fn write_le(&self, bytes: &mut [u8]);
+
+    /// 读取字段，`bytes` 长度为 `SIZE`，取值非法时返回 `CommError::InvalidFieldValue`
+    fn read_le(bytes: &[u8]) -> RbtResult<Self>;
+
+    /// 根据种子生成确定的样例值，用于派生宏生成的往返测试
+    fn sample(seed: u8) -> Self;
+}
+
+macro_rules! impl_comm_field_for_num {
+    ($($ty:ty),*) => {
+        $(
+            impl CommField for $ty {
+                const SIZE: usize = size_of::<$ty>();
+
+                fn write_le(&self, bytes: &mut [u8]) {
+                    bytes.copy_from_slice(&self.to_le_bytes());
+                }
+
+                fn read_le(bytes: &[u8]) -> RbtResult<Self> {
+                    let mut le_bytes = [0u8; size_of::<$ty>()];
+                    le_bytes.copy_from_slice(bytes);
+                    Ok(<$ty>::from_le_bytes(le_bytes))
+                }
+
+                fn sample(seed: u8) -> Self {
+                    // 各字节互不相同，字节序或偏移写错时往返测试能发现
+                    let mut le_bytes = [0u8; size_of::<$ty>()];
+                    for (idx, byte) in le_bytes.iter_mut().enumerate() {
+                        *byte = seed.wrapping_mul(111).wrapping_add(idx as u8 + 1);
+                    }
+                    <$ty>::from_le_bytes(le_bytes)
+                }
+            }
+        )*
+    };
+}
+
+impl_comm_field_for_num!(u8, i8, u16, i16, u32, i32, u64, i64, f32, f64);
+
+impl CommField for bool {
+    const SIZE: usize = 1;
+
+    fn write_le(&self, bytes: &mut [u8]) {
+        bytes[0] = *self as u8;
+    }
+
+    fn read_le(bytes: &[u8]) -> RbtResult<Self> {
+        Ok(bytes[0] != 0)
+    }
+
+    fn sample(seed: u8) -> Self {
+        seed % 2 == 1
+    }
+}
+
+/// 派生宏生成的枚举解析遇到非法值时调用，返回的错误使整帧被丢弃
+#[doc(hidden)]
+pub fn invalid_comm_field(type_name: &str, value: u8) -> RbtError {
+    warn!("Invalid {} value: {:#04x}", type_name, value);
+    CommError::InvalidFieldValue.into()
+}
+
+/// 根据上一帧序号计算两帧之间丢失的帧数，序号按 u8 回绕
+pub fn lost_frames_between(last_seq: u8, seq: u8) -> u8 {
+    seq.wrapping_sub(last_seq).wrapping_sub(1)
+}
+
+/// 接收帧序号统计，各通讯设备共用
+#[derive(Debug, Default, Clone)]
+pub struct CommSeqCounter {
+    last_record_seq: u8,
+    received_frames: u64,
+    lost_frames: u64,
+}
+
+impl CommSeqCounter {
+    /// 记录一帧的序号，返回与上一帧之间丢失的帧数
+    pub fn record(&mut self, seq: u8) -> u8 {
+        let lost = if self.received_frames > 0 {
+            lost_frames_between(self.last_record_seq, seq)
+        } else {
+            0
+        };
+        if lost > 0 {
+            warn!(
+                "Lost {} frame(s) between seq {} and {}",
+                lost, self.last_record_seq, seq
+            );
+            self.lost_frames += lost as u64;
+        }
+        self.last_record_seq = seq;
+        self.received_frames += 1;
+        lost
+    }
+
+    pub fn last_record_seq(&self) -> u8 {
+        self.last_record_seq
+    }
+
+    /// 成功接收的帧数
+    pub fn received_frames(&self) -> u64 {
+        self.received_frames
+    }
+
+    /// 根据帧序号推算出的丢帧数
+    pub fn lost_frames(&self) -> u64 {
+        self.lost_frames
+    }
+}
+
 /// 下发控制数据
 ///
-/// * `gimbal_yaw` - 云台偏航角
+/// * `gimbal_yaw` - 云台偏航角，展开到与最近上报的 `SensData::gimbal_yaw` 相差不超过 180° 的等价角
 /// * `gimbal_pitch` - 云台俯仰角
 /// * `shot_mode` - 射击模式
 /// * `shot_buff_mode` - 射击缓冲模式
 /// * `aiming_state` - 瞄准状态
-#[derive(Debug)]
+/// * `sync_host_time_us` - 上位机发送时刻，用于时间同步，由通讯层在发送时填写
+#[derive(Debug, Clone, PartialEq, CommData)]
+#[comm_frame(sof = 0x33, eof = 0xEE)]
 pub struct CtrlData {
     pub gimbal_yaw: f32,
     pub gimbal_pitch: f32,
     pub shot_mode: ShotMode,
     pub shot_buff_mode: ShotBuffMode,
     pub aiming_state: AimingState,
+    pub sync_host_time_us: u32,
 }
 
 /// 瞄准状态枚举
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, CommField)]
 #[repr(u8)]
 pub enum AimingState {
     NoAimingNoTarget = 0x00,
@@ -56,7 +242,7 @@ pub enum AimingState {
 }
 
 /// 射击缓冲模式枚举
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, CommField)]
 #[repr(u8)]
 pub enum ShotBuffMode {
     ShotBuffOff = 0x00, // 禁用射击缓冲模式
@@ -64,7 +250,7 @@ pub enum ShotBuffMode {
 }
 
 /// 射击模式枚举
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, CommField)]
 #[repr(u8)]
 pub enum ShotMode {
     DoNothing = 0x00, // 不瞄准也不发射
@@ -73,122 +259,20 @@ pub enum ShotMode {
     ShotOnce = 0x03,  // 单次射击模式
 }
 
-impl AimingState {
-    pub fn from_u8(value: u8) -> Self {
-        match value {
-            0x00 => AimingState::NoAimingNoTarget,
-            0x11 => AimingState::CommunicateNoCamera,
-            0x22 => AimingState::AimingNoTarget,
-            0x33 => AimingState::AimingWithTarget,
-            _ => {
-                // 默认值
-                warn!("Invalid aim mode state {}", value);
-                AimingState::NoAimingNoTarget
-            }
-        }
-    }
-}
-
-impl ShotBuffMode {
-    pub fn from_u8(value: u8) -> Self {
-        match value {
-            0x00 => ShotBuffMode::ShotBuffOff,
-            0x01 => ShotBuffMode::ShotBuffOn,
-            _ => {
-                // 默认值
-                warn!("Invalid shot buff mode value {}", value);
-                ShotBuffMode::ShotBuffOff
-            }
-        }
-    }
-}
-
-impl ShotMode {
-    pub fn from_u8(value: u8) -> Self {
-        match value {
-            0x00 => ShotMode::DoNothing,
-            0x01 => ShotMode::AimOnly,
-            0x02 => ShotMode::AutoFire,
-            0x03 => ShotMode::ShotOnce,
-            _ => {
-                // 默认值
-                warn!("Invalid shot mode value: {}", value);
-                ShotMode::DoNothing
-            }
-        }
-    }
-}
-
-impl From<AimingState> for u8 {
-    fn from(state: AimingState) -> u8 {
-        state as u8
-    }
-}
-
-impl From<ShotBuffMode> for u8 {
-    fn from(mode: ShotBuffMode) -> u8 {
-        mode as u8
-    }
-}
-
-impl From<ShotMode> for u8 {
-    fn from(mode: ShotMode) -> u8 {
-        mode as u8
-    }
-}
-
-impl CommData for CtrlData {
-    const FRAME_SIZE: usize = 13;
-    const SOF: u8 = 0x33;
-    const EOF: u8 = 0xEE;
-
-    /// 序列化
-    fn serialize(&self, buffer: &mut [u8]) -> RbtResult<()> {
-        Self::validate_frame(buffer)?;
-
-        buffer[0] = Self::SOF;
-        let gimbal_yaw_bytes = self.gimbal_yaw.to_le_bytes();
-        buffer[1..5].copy_from_slice(&gimbal_yaw_bytes);
-        let gimbal_pitch_bytes = self.gimbal_pitch.to_le_bytes();
-        buffer[5..9].copy_from_slice(&gimbal_pitch_bytes);
-        buffer[9] = self.shot_mode.into();
-        buffer[10] = self.shot_buff_mode.into();
-        buffer[11] = self.aiming_state.into();
-        buffer[12] = Self::EOF;
-
-        Ok(())
-    }
-
-    /// 反序列化
-    fn deserialize(buffer: &[u8]) -> RbtResult<Self> {
-        Self::validate_frame(buffer)?;
-
-        let mut bytes = [0u8; 13];
-        bytes.copy_from_slice(&buffer[0..13]);
-
-        let gimbal_yaw = f32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]);
-        let gimbal_pitch = f32::from_le_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]);
-
-        Ok(Self {
-            gimbal_yaw,
-            gimbal_pitch,
-            shot_mode: ShotMode::from_u8(bytes[9]),
-            shot_buff_mode: ShotBuffMode::from_u8(bytes[10]),
-            aiming_state: AimingState::from_u8(bytes[11]),
-        })
-    }
-}
-
 /// 接受反馈数据
 ///
 /// - task_mode: 任务模式
 /// - self_team: 自身队伍
 /// - bullet_speed: 子弹速度
 /// - gimbal_roll: 云台横滚角
-/// - gimbal_yaw: 云台偏航角
+/// - gimbal_yaw: 云台偏航角，可为多圈累计值或折算在一圈以内，上位机均按最短弧处理
 /// - gimbal_pitch: 云台俯仰角,
 /// - yaw_speed: 偏航速度
-#[derive(Debug)]
+/// - mcu_time_us: 下位机采样姿态的时刻，下位机时钟 us
+/// - sync_host_time_us: 下位机最近收到的控制帧中的 `sync_host_time_us` 原样回传
+/// - sync_mcu_recv_us: 下位机收到该控制帧的时刻，下位机时钟 us
+#[derive(Debug, Clone, PartialEq, CommData)]
+#[comm_frame(sof = 0x33, eof = 0xEE)]
 pub struct SensData {
     pub task_mode: TaskMode,
     pub self_fraction: SelfFraction,
@@ -197,10 +281,13 @@ pub struct SensData {
     pub gimbal_yaw: f32,
     pub gimbal_pitch: f32,
     pub yaw_speed: f32,
+    pub mcu_time_us: u32,
+    pub sync_host_time_us: u32,
+    pub sync_mcu_recv_us: u32,
 }
 
 /// 任务模式枚举
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, CommField)]
 #[repr(u8)]
 pub enum TaskMode {
     AutoShot = 0x01,     // 自瞄
@@ -209,109 +296,17 @@ pub enum TaskMode {
 }
 
 /// 自身队伍枚举
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, CommField)]
 #[repr(u8)]
 pub enum SelfFraction {
     Red = 0xAA,  // 红色队伍
     Blue = 0xBB, // 蓝色队伍
 }
 
-impl TaskMode {
-    pub fn from_u8(value: u8) -> Self {
-        match value {
-            0x01 => TaskMode::AutoShot,
-            0x02 => TaskMode::HitBigBuff,
-            0x03 => TaskMode::HitSmallBuff,
-            _ => {
-                // 默认值
-                warn!("Invalid self fraction value: {}", value);
-                TaskMode::AutoShot
-            }
-        }
-    }
-}
-
-impl SelfFraction {
-    pub fn from_u8(value: u8) -> Self {
-        match value {
-            0xAA => SelfFraction::Red,
-            0xBB => SelfFraction::Blue,
-            _ => {
-                // 默认值
-                warn!("Invalid self fraction value: {}", value);
-                SelfFraction::Red
-            }
-        }
-    }
-}
-
-impl From<TaskMode> for u8 {
-    fn from(mode: TaskMode) -> u8 {
-        mode as u8
-    }
-}
-
-impl From<SelfFraction> for u8 {
-    fn from(team: SelfFraction) -> u8 {
-        team as u8
-    }
-}
-
-impl CommData for SensData {
-    const FRAME_SIZE: usize = 24;
-    const SOF: u8 = 0x33;
-    const EOF: u8 = 0xEE;
-
-    /// 序列化操作
-    fn serialize(&self, buffer: &mut [u8]) -> RbtResult<()> {
-        Self::validate_frame(buffer)?;
-
-        buffer[0] = Self::SOF;
-        buffer[1] = self.task_mode.into();
-        buffer[2] = self.self_fraction.into();
-        let bullet_speed_bytes = self.bullet_speed.to_le_bytes();
-        buffer[3..7].copy_from_slice(&bullet_speed_bytes);
-        let gimbal_roll_bytes = self.gimbal_roll.to_le_bytes();
-        buffer[7..11].copy_from_slice(&gimbal_roll_bytes);
-        let gimbal_yaw_bytes = self.gimbal_yaw.to_le_bytes();
-        buffer[11..15].copy_from_slice(&gimbal_yaw_bytes);
-        let gimbal_pitch_bytes = self.gimbal_pitch.to_le_bytes();
-        buffer[15..19].copy_from_slice(&gimbal_pitch_bytes);
-        let yaw_speed_bytes = self.yaw_speed.to_le_bytes();
-        buffer[19..23].copy_from_slice(&yaw_speed_bytes);
-        buffer[23] = Self::EOF;
-
-        Ok(())
-    }
-
-    /// 反序列化操作
-    fn deserialize(buffer: &[u8]) -> RbtResult<Self> {
-        Self::validate_frame(buffer)?;
-
-        let mut bytes = [0u8; 24];
-        bytes.copy_from_slice(&buffer[0..24]);
-
-        let task_mode = TaskMode::from_u8(bytes[1]);
-        let self_fraction = SelfFraction::from_u8(bytes[2]);
-        let bullet_speed = f32::from_le_bytes([bytes[3], bytes[4], bytes[5], bytes[6]]);
-        let gimbal_roll = f32::from_le_bytes([bytes[7], bytes[8], bytes[9], bytes[10]]);
-        let gimbal_yaw = f32::from_le_bytes([bytes[11], bytes[12], bytes[13], bytes[14]]);
-        let gimbal_pitch = f32::from_le_bytes([bytes[15], bytes[16], bytes[17], bytes[18]]);
-        let yaw_speed = f32::from_le_bytes([bytes[19], bytes[20], bytes[21], bytes[22]]);
-
-        Ok(Self {
-            task_mode,
-            self_fraction,
-            bullet_speed,
-            gimbal_roll,
-            gimbal_yaw,
-            gimbal_pitch,
-            yaw_speed,
-        })
-    }
-}
-
 /// 带时间戳记录的传感器帧
+///
+/// 时间同步完成后时间戳为下位机采样时刻换算到上位机的时刻，否则为接收时刻
+#[derive(Debug, Clone)]
 pub struct SensFrame {
     data: SensData,
     time_stamp: tokio::time::Instant,
@@ -325,6 +320,10 @@ impl SensFrame {
         }
     }
 
+    pub fn with_time_stamp(data: SensData, time_stamp: tokio::time::Instant) -> Self {
+        SensFrame { data, time_stamp }
+    }
+
     pub fn data(&self) -> &SensData {
         &self.data
     }
@@ -335,6 +334,7 @@ impl SensFrame {
 }
 
 /// 带时间戳记录的控制帧
+#[derive(Debug)]
 pub struct CtrlFrame {
     data: CtrlData,
     time_stamp: tokio::time::Instant,
@@ -356,3 +356,90 @@ impl CtrlFrame {
         &self.time_stamp
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctrl_data() -> CtrlData {
+        CtrlData {
+            gimbal_yaw: 12.5,
+            gimbal_pitch: -3.25,
+            shot_mode: ShotMode::AutoFire,
+            shot_buff_mode: ShotBuffMode::ShotBuffOff,
+            aiming_state: AimingState::AimingWithTarget,
+            sync_host_time_us: 0x0102_0304,
+        }
+    }
+
+    #[test]
+    fn test_frame_layout() {
+        assert_eq!(CtrlData::FRAME_SIZE, 21);
+        assert_eq!(SensData::FRAME_SIZE, 40);
+
+        let mut buffer = [0u8; CtrlData::FRAME_SIZE];
+        ctrl_data().serialize(7, &mut buffer).unwrap();
+        assert_eq!(buffer[0], CtrlData::SOF);
+        assert_eq!(buffer[1], 7);
+        assert_eq!(buffer[2], crc8(&buffer[..2]));
+        assert_eq!(&buffer[3..7], &12.5f32.to_le_bytes());
+        assert_eq!(&buffer[14..18], &[0x04, 0x03, 0x02, 0x01]);
+        assert_eq!(
+            u16::from_le_bytes([buffer[18], buffer[19]]),
+            crc16(&buffer[..18])
+        );
+        assert_eq!(buffer[20], CtrlData::EOF);
+        assert_eq!(CtrlData::deserialize(&buffer).unwrap(), (7, ctrl_data()));
+    }
+
+    #[test]
+    fn test_corrupted_payload() {
+        let mut buffer = [0u8; CtrlData::FRAME_SIZE];
+        ctrl_data().serialize(0, &mut buffer).unwrap();
+        // yaw 的最高位翻转，帧头帧尾仍然正确
+        buffer[6] ^= 0x80;
+        let err = CtrlData::deserialize(&buffer).unwrap_err();
+        assert!(matches!(err, RbtError::CommError(CommError::CorruptedFrame)));
+    }
+
+    #[test]
+    fn test_corrupted_header() {
+        let mut buffer = [0u8; CtrlData::FRAME_SIZE];
+        ctrl_data().serialize(0, &mut buffer).unwrap();
+        buffer[1] = 1;
+        let err = CtrlData::validate_header(&buffer).unwrap_err();
+        assert!(matches!(err, RbtError::CommError(CommError::CorruptedFrame)));
+    }
+
+    #[test]
+    fn test_enum_field() {
+        let mut byte = [0u8; 1];
+        SelfFraction::Blue.write_le(&mut byte);
+        assert_eq!(byte, [0xBB]);
+        assert_eq!(SelfFraction::read_le(&byte).unwrap(), SelfFraction::Blue);
+        assert!(matches!(
+            TaskMode::read_le(&[0x7F]),
+            Err(RbtError::CommError(CommError::InvalidFieldValue))
+        ));
+    }
+
+    #[test]
+    fn test_invalid_enum_frame_dropped() {
+        let mut buffer = [0u8; CtrlData::FRAME_SIZE];
+        ctrl_data().serialize(0, &mut buffer).unwrap();
+        // 校验正确但 shot_mode 取值非法的帧整帧丢弃，不能按默认变体解析
+        buffer[11] = 0x7F;
+        let crc = crc16(&buffer[..18]);
+        buffer[18..20].copy_from_slice(&crc.to_le_bytes());
+        let err = CtrlData::deserialize(&buffer).unwrap_err();
+        assert!(matches!(err, RbtError::CommError(CommError::InvalidFieldValue)));
+    }
+
+    #[test]
+    fn test_lost_frames_between() {
+        assert_eq!(lost_frames_between(0, 1), 0);
+        assert_eq!(lost_frames_between(10, 13), 2);
+        assert_eq!(lost_frames_between(255, 0), 0);
+        assert_eq!(lost_frames_between(254, 2), 3);
+    }
+}
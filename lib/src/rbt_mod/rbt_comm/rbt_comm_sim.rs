@@ -0,0 +1,599 @@
+//! 虚拟下位机
+//!
+//! 在没有电控硬件时扮演下位机：
+//! 1. 以固定频率（默认 1 kHz）发送 `SensData`，弹速、任务模式和操作手的云台指令由脚本给出
+//! 2. 接收 `CtrlData`，自瞄接管时以其中的角度作为云台目标
+//! 3. 用二阶系统模拟云台响应，并统计自瞄接管期间的跟踪误差
+//!
+//! 仿真时间按发送周期累加，与墙上时间无关，便于在 CI 上得到稳定的结果
+
+use std::f32::consts::PI;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::UdpSocket;
+use tokio::time::Duration;
+use tracing::{info, warn};
+
+use crate::rbt_infra::rbt_err::{CommError, RbtResult};
+use crate::rbt_mod::rbt_comm::rbt_comm_decoder::{CommDecoder, CommDecoderStats};
+use crate::rbt_mod::rbt_comm::rbt_comm_frame::{
+    AimingState, CommData, CommSeqCounter, CtrlData, SelfFraction, SensData, TaskMode,
+};
+
+/// 正弦波形 `offset + amplitude * sin(2π * freq_hz * t)`，单位与字段一致
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimWave {
+    pub offset: f32,
+    pub amplitude: f32,
+    pub freq_hz: f32,
+}
+
+impl SimWave {
+    pub fn constant(value: f32) -> Self {
+        Self::sine(value, 0.0, 0.0)
+    }
+
+    pub fn sine(offset: f32, amplitude: f32, freq_hz: f32) -> Self {
+        Self {
+            offset,
+            amplitude,
+            freq_hz,
+        }
+    }
+
+    pub fn value(&self, t: f32) -> f32 {
+        self.offset + self.amplitude * (2.0 * PI * self.freq_hz * t).sin()
+    }
+}
+
+/// 脚本中的一段
+///
+/// * `yaw`/`pitch` - 操作手给出的云台指令（度），自瞄未接管时云台跟随该指令
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimSegment {
+    pub duration: Duration,
+    pub task_mode: TaskMode,
+    pub bullet_speed: f32,
+    pub yaw: SimWave,
+    pub pitch: SimWave,
+}
+
+/// 仿真脚本，按顺序执行各段，结束后从头循环
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimScript {
+    segments: Vec<SimSegment>,
+}
+
+impl SimScript {
+    pub fn new(segments: Vec<SimSegment>) -> RbtResult<Self> {
+        if segments.is_empty() || segments.iter().any(|s| s.duration.is_zero()) {
+            return Err(crate::rbt_infra::rbt_err::RbtError::InvalidConfig(
+                "Simulator script must have segments with non-zero duration".to_string(),
+            ));
+        }
+        Ok(Self { segments })
+    }
+
+    /// 默认脚本：静止 -> 操作手缓慢扫描 -> 打符模式
+    pub fn default_script() -> Self {
+        Self {
+            segments: vec![
+                SimSegment {
+                    duration: Duration::from_secs(2),
+                    task_mode: TaskMode::AutoShot,
+                    bullet_speed: 23.5,
+                    yaw: SimWave::constant(0.0),
+                    pitch: SimWave::constant(0.0),
+                },
+                SimSegment {
+                    duration: Duration::from_secs(5),
+                    task_mode: TaskMode::AutoShot,
+                    bullet_speed: 24.0,
+                    yaw: SimWave::sine(0.0, 30.0, 0.2),
+                    pitch: SimWave::sine(-2.0, 5.0, 0.5),
+                },
+                SimSegment {
+                    duration: Duration::from_secs(3),
+                    task_mode: TaskMode::HitSmallBuff,
+                    bullet_speed: 24.5,
+                    yaw: SimWave::constant(10.0),
+                    pitch: SimWave::constant(8.0),
+                },
+            ],
+        }
+    }
+
+    pub fn total_duration(&self) -> Duration {
+        self.segments.iter().map(|s| s.duration).sum()
+    }
+
+    /// 取出 `t` 时刻所在的段，以及段内时间（秒）
+    pub fn segment_at(&self, t: Duration) -> (&SimSegment, f32) {
+        let total = self.total_duration().as_nanos();
+        let mut local = Duration::from_nanos((t.as_nanos() % total) as u64);
+        for segment in self.segments.iter() {
+            if local < segment.duration {
+                return (segment, local.as_secs_f32());
+            }
+            local -= segment.duration;
+        }
+        unreachable!("segment lookup is bounded by total duration")
+    }
+}
+
+/// 云台二阶响应参数
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimGimbalCfg {
+    pub natural_freq_hz: f32,
+    pub damping: f32,
+    /// 最大角速度（度/秒）
+    pub max_speed: f32,
+}
+
+impl Default for SimGimbalCfg {
+    fn default() -> Self {
+        Self {
+            natural_freq_hz: 8.0,
+            damping: 0.9,
+            max_speed: 720.0,
+        }
+    }
+}
+
+/// 云台动力学模型，yaw/pitch 两轴独立
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimGimbal {
+    cfg: SimGimbalCfg,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub yaw_speed: f32,
+    pub pitch_speed: f32,
+}
+
+impl SimGimbal {
+    pub fn new(cfg: SimGimbalCfg) -> Self {
+        Self {
+            cfg,
+            yaw: 0.0,
+            pitch: 0.0,
+            yaw_speed: 0.0,
+            pitch_speed: 0.0,
+        }
+    }
+
+    /// 以 `dt` 秒推进一步，半隐式欧拉积分
+    pub fn step(&mut self, target_yaw: f32, target_pitch: f32, dt: f32) {
+        let omega = 2.0 * PI * self.cfg.natural_freq_hz;
+        let axis = |pos: &mut f32, vel: &mut f32, target: f32| {
+            let acc = omega * omega * (target - *pos) - 2.0 * self.cfg.damping * omega * *vel;
+            *vel = (*vel + acc * dt).clamp(-self.cfg.max_speed, self.cfg.max_speed);
+            *pos += *vel * dt;
+        };
+        axis(&mut self.yaw, &mut self.yaw_speed, target_yaw);
+        axis(&mut self.pitch, &mut self.pitch_speed, target_pitch);
+    }
+}
+
+/// 仿真统计
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SimReport {
+    pub sim_time: Duration,
+    pub sens_frames: u64,
+    pub ctrl_frames: u64,
+    pub ctrl_lost_frames: u64,
+    pub ctrl_decoder: CommDecoderStats,
+    /// 自瞄接管期间的采样数
+    pub tracking_samples: u64,
+    /// 跟踪误差（度），为云台姿态与 `CtrlData` 目标角度的距离
+    pub tracking_rms: f32,
+    pub tracking_max: f32,
+    pub tracking_last: f32,
+}
+
+/// 虚拟下位机
+pub struct McuSim {
+    script: SimScript,
+    gimbal: SimGimbal,
+    self_fraction: SelfFraction,
+    /// 超过该时间未收到 `CtrlData` 则认为自瞄断开，云台交还操作手
+    ctrl_hold: Duration,
+    sim_time: Duration,
+    /// 下位机时钟相对仿真时间的偏差 us，用于验证时间同步
+    clock_offset_us: u32,
+    send_seq: u8,
+    decoder: CommDecoder<CtrlData>,
+    seq_counter: CommSeqCounter,
+    last_ctrl: Option<(CtrlData, Duration)>,
+    sens_frames: u64,
+    tracking_sq_sum: f64,
+    report: SimReport,
+}
+
+impl McuSim {
+    pub fn new(script: SimScript, self_fraction: SelfFraction) -> Self {
+        Self {
+            script,
+            gimbal: SimGimbal::new(SimGimbalCfg::default()),
+            self_fraction,
+            ctrl_hold: Duration::from_millis(50),
+            sim_time: Duration::ZERO,
+            clock_offset_us: 0,
+            send_seq: 0,
+            decoder: CommDecoder::new(),
+            seq_counter: CommSeqCounter::default(),
+            last_ctrl: None,
+            sens_frames: 0,
+            tracking_sq_sum: 0.0,
+            report: SimReport::default(),
+        }
+    }
+
+    pub fn with_gimbal_cfg(mut self, cfg: SimGimbalCfg) -> Self {
+        self.gimbal = SimGimbal::new(cfg);
+        self
+    }
+
+    pub fn with_clock_offset_us(mut self, clock_offset_us: u32) -> Self {
+        self.clock_offset_us = clock_offset_us;
+        self
+    }
+
+    /// 仿真时刻 `t` 对应的下位机时钟 us
+    fn mcu_time_us(&self, t: Duration) -> u32 {
+        (t.as_micros() as u32).wrapping_add(self.clock_offset_us)
+    }
+
+    pub fn gimbal(&self) -> &SimGimbal {
+        &self.gimbal
+    }
+
+    pub fn sim_time(&self) -> Duration {
+        self.sim_time
+    }
+
+    /// 当前生效的自瞄指令
+    fn active_ctrl(&self) -> Option<&CtrlData> {
+        self.last_ctrl.as_ref().and_then(|(ctrl, received_at)| {
+            let fresh = self.sim_time.saturating_sub(*received_at) <= self.ctrl_hold;
+            (fresh && ctrl.aiming_state == AimingState::AimingWithTarget).then_some(ctrl)
+        })
+    }
+
+    /// 推进 `dt` 并生成这一时刻的传感器数据
+    pub fn step(&mut self, dt: Duration) -> SensData {
+        self.sim_time += dt;
+        let (segment, t) = self.script.segment_at(self.sim_time);
+        let (task_mode, bullet_speed) = (segment.task_mode, segment.bullet_speed);
+        let (operator_yaw, operator_pitch) = (segment.yaw.value(t), segment.pitch.value(t));
+
+        let auto_aim = self
+            .active_ctrl()
+            .map(|ctrl| (ctrl.gimbal_yaw, ctrl.gimbal_pitch));
+        let (target_yaw, target_pitch) = auto_aim.unwrap_or((operator_yaw, operator_pitch));
+        self.gimbal
+            .step(target_yaw, target_pitch, dt.as_secs_f32());
+
+        let (sync_host_time_us, sync_mcu_recv_us) = self
+            .last_ctrl
+            .as_ref()
+            .map_or((0, 0), |(ctrl, received_at)| {
+                (ctrl.sync_host_time_us, self.mcu_time_us(*received_at))
+            });
+
+        if auto_aim.is_some() {
+            let err = (target_yaw - self.gimbal.yaw).hypot(target_pitch - self.gimbal.pitch);
+            self.tracking_sq_sum += (err as f64).powi(2);
+            self.report.tracking_samples += 1;
+            self.report.tracking_max = self.report.tracking_max.max(err);
+            self.report.tracking_last = err;
+        }
+
+        SensData {
+            task_mode,
+            self_fraction: self.self_fraction,
+            bullet_speed,
+            gimbal_roll: 0.0,
+            gimbal_yaw: self.gimbal.yaw,
+            gimbal_pitch: self.gimbal.pitch,
+            yaw_speed: self.gimbal.yaw_speed,
+            mcu_time_us: self.mcu_time_us(self.sim_time),
+            // 回传最近一帧控制帧的时间戳，用于上位机时间同步
+            sync_host_time_us,
+            sync_mcu_recv_us,
+        }
+    }
+
+    /// 推进 `dt` 并序列化为一帧
+    pub fn tick(&mut self, dt: Duration) -> RbtResult<[u8; SensData::FRAME_SIZE]> {
+        let data = self.step(dt);
+        let mut buffer = [0u8; SensData::FRAME_SIZE];
+        data.serialize(self.send_seq, &mut buffer)?;
+        self.send_seq = self.send_seq.wrapping_add(1);
+        self.sens_frames += 1;
+        Ok(buffer)
+    }
+
+    /// 处理从上位机收到的字节
+    pub fn feed(&mut self, bytes: &[u8]) {
+        for (seq, ctrl) in self.decoder.decode(bytes) {
+            self.seq_counter.record(seq);
+            self.last_ctrl = Some((ctrl, self.sim_time));
+        }
+    }
+
+    pub fn report(&self) -> SimReport {
+        let samples = self.report.tracking_samples;
+        SimReport {
+            sim_time: self.sim_time,
+            sens_frames: self.sens_frames,
+            ctrl_frames: self.seq_counter.received_frames(),
+            ctrl_lost_frames: self.seq_counter.lost_frames(),
+            ctrl_decoder: *self.decoder.stats(),
+            tracking_rms: if samples > 0 {
+                (self.tracking_sq_sum / samples as f64).sqrt() as f32
+            } else {
+                0.0
+            },
+            ..self.report.clone()
+        }
+    }
+
+    /// 通过字节流（伪终端/串口）运行 `duration` 的仿真时间
+    pub async fn run_stream<S>(
+        &mut self,
+        stream: S,
+        period: Duration,
+        duration: Duration,
+    ) -> RbtResult<SimReport>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        let (mut reader, mut writer) = tokio::io::split(stream);
+        let mut ticker = tokio::time::interval(period);
+        let mut buffer = [0u8; 256];
+        while self.sim_time < duration {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let frame = self.tick(period)?;
+                    writer.write_all(&frame).await?;
+                }
+                len = reader.read(&mut buffer) => {
+                    match len? {
+                        0 => {
+                            warn!("Simulator link closed by host");
+                            return Err(CommError::IoError.into());
+                        }
+                        len => self.feed(&buffer[..len]),
+                    }
+                }
+            }
+        }
+        let report = self.report();
+        info!("Simulator finished: {:?}", report);
+        Ok(report)
+    }
+
+    /// 通过已经 connect 到上位机的 UDP socket 运行 `duration` 的仿真时间
+    pub async fn run_udp(
+        &mut self,
+        socket: &UdpSocket,
+        period: Duration,
+        duration: Duration,
+    ) -> RbtResult<SimReport> {
+        let mut ticker = tokio::time::interval(period);
+        let mut buffer = [0u8; 1024];
+        while self.sim_time < duration {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let frame = self.tick(period)?;
+                    // 上位机未启动时发送会因 ICMP 端口不可达失败，忽略即可
+                    if let Err(err) = socket.send(&frame).await
+                        && err.kind() != std::io::ErrorKind::ConnectionRefused
+                    {
+                        return Err(err.into());
+                    }
+                }
+                len = socket.recv(&mut buffer) => {
+                    match len {
+                        Ok(len) => self.feed(&buffer[..len]),
+                        Err(err) if err.kind() == std::io::ErrorKind::ConnectionRefused => {}
+                        Err(err) => return Err(err.into()),
+                    }
+                }
+            }
+        }
+        let report = self.report();
+        info!("Simulator finished: {:?}", report);
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rbt_infra::rbt_cfg::CommCfg;
+    use crate::rbt_mod::rbt_comm::rbt_comm_device::RbtComm;
+    use crate::rbt_mod::rbt_comm::rbt_comm_device::rbt_serial::RbtSerial;
+    use crate::rbt_mod::rbt_comm::rbt_comm_device::rbt_udp::RbtUdp;
+    use crate::rbt_mod::rbt_comm::rbt_comm_frame::{ShotBuffMode, ShotMode};
+    use tokio_serial::SerialStream;
+
+    const PERIOD: Duration = Duration::from_millis(1);
+    /// 闭环测试中上下位机一问一答的次数
+    const EXCHANGES: u64 = 500;
+
+    fn comm_cfg(local: &str, remote: &str) -> CommCfg {
+        toml::from_str(&format!(
+            "device = \"udp\"\n\
+             serial_port = \"pty\"\n\
+             serial_baud_rate = 115200\n\
+             udp_local_addr = \"{local}\"\n\
+             udp_remote_addr = \"{remote}\"\n\
+             timeout_ms = 1000"
+        ))
+        .unwrap()
+    }
+
+    fn aim_at(yaw: f32, pitch: f32) -> CtrlData {
+        CtrlData {
+            gimbal_yaw: yaw,
+            gimbal_pitch: pitch,
+            shot_mode: ShotMode::AimOnly,
+            shot_buff_mode: ShotBuffMode::ShotBuffOff,
+            aiming_state: AimingState::AimingWithTarget,
+            sync_host_time_us: 0,
+        }
+    }
+
+    fn still_script() -> SimScript {
+        SimScript::new(vec![SimSegment {
+            duration: Duration::from_secs(1),
+            task_mode: TaskMode::AutoShot,
+            bullet_speed: 24.0,
+            yaw: SimWave::constant(0.0),
+            pitch: SimWave::constant(0.0),
+        }])
+        .unwrap()
+    }
+
+    /// 上位机：收到一帧回一帧固定目标
+    async fn host_reply(host: &mut impl RbtComm) -> SensData {
+        let frame = host.receive().await.unwrap();
+        host.send(&aim_at(20.0, 5.0)).await.unwrap();
+        frame.data().clone()
+    }
+
+    /// 下位机每发一帧都等到上位机的回复再进入下一周期，统计结果与调度无关：
+    /// 每个周期都收到一帧控制帧，控制帧在发送之后到达，从第二个周期开始跟踪
+    fn assert_lockstep(report: &SimReport) {
+        assert_eq!(report.sens_frames, EXCHANGES);
+        assert_eq!(report.ctrl_frames, EXCHANGES);
+        assert_eq!(report.ctrl_lost_frames, 0);
+        assert_eq!(report.ctrl_decoder.corrupted_frames, 0);
+        assert_eq!(report.tracking_samples, EXCHANGES - 1);
+        assert!(report.tracking_last < 0.5, "{report:?}");
+    }
+
+    #[test]
+    fn test_gimbal_converges() {
+        let mut gimbal = SimGimbal::new(SimGimbalCfg::default());
+        for _ in 0..1000 {
+            gimbal.step(30.0, -10.0, 0.001);
+        }
+        assert!((gimbal.yaw - 30.0).abs() < 0.05);
+        assert!((gimbal.pitch + 10.0).abs() < 0.05);
+        // 速度上限
+        let mut gimbal = SimGimbal::new(SimGimbalCfg::default());
+        gimbal.step(1.0e4, 0.0, 0.01);
+        assert!(gimbal.yaw_speed <= SimGimbalCfg::default().max_speed);
+    }
+
+    #[test]
+    fn test_script_segments() {
+        let script = SimScript::default_script();
+        assert_eq!(script.total_duration(), Duration::from_secs(10));
+        let (segment, t) = script.segment_at(Duration::from_millis(8500));
+        assert_eq!(segment.task_mode, TaskMode::HitSmallBuff);
+        assert!((t - 1.5).abs() < 1e-6);
+        // 脚本循环
+        let (segment, _) = script.segment_at(Duration::from_millis(10500));
+        assert_eq!(segment.yaw, SimWave::constant(0.0));
+        assert!(SimScript::new(vec![]).is_err());
+    }
+
+    #[test]
+    fn test_operator_and_auto_aim() {
+        let mut sim = McuSim::new(SimScript::default_script(), SelfFraction::Blue);
+        // 未收到 CtrlData 时跟随操作手
+        for _ in 0..3000 {
+            sim.step(PERIOD);
+        }
+        assert!(sim.gimbal().yaw.abs() > 1.0);
+        assert_eq!(sim.report().tracking_samples, 0);
+
+        // 自瞄接管，每 2ms 一帧
+        let mut buffer = [0u8; CtrlData::FRAME_SIZE];
+        for step in 0..500u16 {
+            if step % 2 == 0 {
+                aim_at(-15.0, 3.0).serialize((step / 2) as u8, &mut buffer).unwrap();
+                sim.feed(&buffer);
+            }
+            sim.step(PERIOD);
+        }
+        let report = sim.report();
+        assert_eq!(report.ctrl_frames, 250);
+        assert_eq!(report.tracking_samples, 500);
+        assert!(report.tracking_last < 0.1);
+        assert!(report.tracking_max > 10.0);
+
+        // 自瞄断开后交还操作手
+        for _ in 0..100 {
+            sim.step(PERIOD);
+        }
+        assert_eq!(sim.report().tracking_samples, 500 + 48);
+    }
+
+    #[tokio::test]
+    async fn test_closed_loop_over_pty() {
+        let (mut mcu, host) = SerialStream::pair().unwrap();
+        let mut host = RbtSerial::from_stream(&comm_cfg("127.0.0.1:0", "127.0.0.1:9"), host);
+        let mut sim = McuSim::new(still_script(), SelfFraction::Red);
+
+        let mut buffer = [0u8; CtrlData::FRAME_SIZE];
+        let mut last = None;
+        for _ in 0..EXCHANGES {
+            mcu.write_all(&sim.tick(PERIOD).unwrap()).await.unwrap();
+            last = Some(host_reply(&mut host).await);
+            mcu.read_exact(&mut buffer).await.unwrap();
+            sim.feed(&buffer);
+        }
+        assert_lockstep(&sim.report());
+        assert!((last.unwrap().gimbal_yaw - 20.0).abs() < 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_closed_loop_over_udp() {
+        let mcu = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mcu_addr = mcu.local_addr().unwrap().to_string();
+        let mut host = RbtUdp::new(&comm_cfg("127.0.0.1:0", &mcu_addr));
+        host.open().await.unwrap();
+        mcu.connect(host.local_addr().unwrap()).await.unwrap();
+        let mut sim = McuSim::new(still_script(), SelfFraction::Red);
+
+        let mut buffer = [0u8; 1024];
+        let mut last = None;
+        for _ in 0..EXCHANGES {
+            mcu.send(&sim.tick(PERIOD).unwrap()).await.unwrap();
+            last = Some(host_reply(&mut host).await);
+            let len = mcu.recv(&mut buffer).await.unwrap();
+            sim.feed(&buffer[..len]);
+        }
+        assert_lockstep(&sim.report());
+        assert!((last.unwrap().gimbal_pitch - 5.0).abs() < 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_run_stream_frame_count() {
+        let (mut mcu, mut host) = SerialStream::pair().unwrap();
+        let mut sim = McuSim::new(still_script(), SelfFraction::Red);
+
+        // 仿真时间按周期累加，发送的帧数只取决于仿真时长
+        let (report, frames) = tokio::join!(
+            sim.run_stream(&mut mcu, PERIOD, Duration::from_millis(50)),
+            async {
+                let mut decoder = CommDecoder::<SensData>::new();
+                let mut frames = Vec::new();
+                let mut buffer = [0u8; 256];
+                while frames.len() < 50 {
+                    let len = host.read(&mut buffer).await.unwrap();
+                    frames.extend(decoder.decode(&buffer[..len]));
+                }
+                frames
+            }
+        );
+        assert_eq!(report.unwrap().sens_frames, 50);
+        for (idx, (seq, data)) in frames.iter().enumerate() {
+            assert_eq!(*seq as usize, idx);
+            assert_eq!(data.mcu_time_us as usize, (idx + 1) * 1000);
+        }
+    }
+}
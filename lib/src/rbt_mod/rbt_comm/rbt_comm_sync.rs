@@ -0,0 +1,538 @@
+//! 上下位机时间同步
+//!
+//! 采用 NTP 式的乒乓测量，复用现有的收发帧，不需要额外的帧类型：
+//! 1. 上位机在每个控制帧中写入发送时刻 `t1`（上位机时钟）
+//! 2. 下位机记录收到该帧的时刻 `t2`，并在之后的传感器帧中回传 `t1` 和 `t2`，
+//!    同时写入本帧的姿态采样时刻 `t3`（下位机时钟）
+//! 3. 上位机记录收到传感器帧的时刻 `t4`
+//!
+//! 时钟偏差 `offset = ((t2 - t1) + (t3 - t4)) / 2`，往返延迟 `delay = (t4 - t1) - (t3 - t2)`。
+//! 只保留延迟较小的样本，对 `offset` 随时间做线性拟合得到偏差和漂移，
+//! 据此把下位机采样时刻换算为上位机的 `Instant`。
+//!
+//! `SensFrameBuffer` 缓存最近的传感器帧，按图像采集时刻插值出云台姿态。
+
+use std::collections::VecDeque;
+use tokio::time::{Duration, Instant};
+
+use crate::rbt_base::rbt_geometry::rbt_angle::wrap_deg;
+use crate::rbt_mod::rbt_comm::rbt_comm_frame::{SensData, SensFrame};
+
+/// 参与拟合的最大样本数
+const SYNC_WINDOW: usize = 256;
+/// 时钟模型建立所需的最少样本数
+const SYNC_MIN_SAMPLES: usize = 4;
+/// 延迟比最小延迟大出该值的样本视为受排队影响，不参与拟合
+const SYNC_DELAY_SLACK_US: i64 = 100;
+
+/// 一次乒乓测量
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SyncSample {
+    /// 测量时刻，上位机时钟 us
+    pub host_us: i64,
+    /// 下位机时钟减上位机时钟 us
+    pub offset_us: i64,
+    /// 往返延迟 us
+    pub delay_us: i64,
+}
+
+/// 线性时钟模型 `offset(t) = offset_us + drift * (t - ref_us)`
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ClockModel {
+    ref_us: f64,
+    offset_us: f64,
+    drift: f64,
+}
+
+/// 时间同步器
+#[derive(Debug, Clone)]
+pub struct RbtTimeSync {
+    epoch: Instant,
+    /// 上一个处理过的 `t1`，下位机会重复回传同一个 `t1`，每个只用一次
+    last_ping: Option<u32>,
+    /// 上一个下位机时间戳及其展开值，用于处理 u32 回绕
+    last_mcu: Option<(u32, i64)>,
+    samples: VecDeque<SyncSample>,
+    model: Option<ClockModel>,
+}
+
+impl Default for RbtTimeSync {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RbtTimeSync {
+    pub fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            last_ping: None,
+            last_mcu: None,
+            samples: VecDeque::with_capacity(SYNC_WINDOW),
+            model: None,
+        }
+    }
+
+    /// 上位机时钟零点
+    pub fn epoch(&self) -> Instant {
+        self.epoch
+    }
+
+    /// 上位机时钟 us
+    fn host_us(&self, at: Instant) -> i64 {
+        at.saturating_duration_since(self.epoch).as_micros() as i64
+    }
+
+    /// 写入控制帧的上位机时间戳，约 71 分钟回绕一次
+    pub fn host_time_us(&self, at: Instant) -> u32 {
+        self.host_us(at) as u32
+    }
+
+    /// 按与 `reference` 最近的原则展开 u32 时间戳
+    fn unwrap_near(raw: u32, reference: (u32, i64)) -> i64 {
+        reference.1 + raw.wrapping_sub(reference.0) as i32 as i64
+    }
+
+    /// 展开下位机时间戳，没有参考时直接使用原值
+    fn unwrap_mcu(&self, raw: u32) -> i64 {
+        self.last_mcu
+            .map_or(raw as i64, |reference| Self::unwrap_near(raw, reference))
+    }
+
+    /// 记录一帧传感器数据，`received_at` 为接收时刻
+    pub fn record(&mut self, data: &SensData, received_at: Instant) {
+        let t3 = self.unwrap_mcu(data.mcu_time_us);
+        self.last_mcu = Some((data.mcu_time_us, t3));
+
+        // 下位机尚未收到控制帧时回传 0
+        let ping = data.sync_host_time_us;
+        if ping == 0 || self.last_ping == Some(ping) {
+            return;
+        }
+        self.last_ping = Some(ping);
+
+        let t4 = self.host_us(received_at);
+        let t1 = Self::unwrap_near(ping, (t4 as u32, t4));
+        let t2 = Self::unwrap_near(data.sync_mcu_recv_us, (data.mcu_time_us, t3));
+        let delay_us = (t4 - t1) - (t3 - t2);
+        if t1 > t4 || delay_us < 0 {
+            // 回传的时间戳不合理，多半是下位机重启
+            return;
+        }
+        self.add_sample(SyncSample {
+            host_us: (t1 + t4) / 2,
+            offset_us: ((t2 - t1) + (t3 - t4)) / 2,
+            delay_us,
+        });
+    }
+
+    /// 加入一个测量样本并更新时钟模型
+    pub fn add_sample(&mut self, sample: SyncSample) {
+        if self.samples.len() == SYNC_WINDOW {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+        self.model = self.fit();
+    }
+
+    /// 对低延迟样本做最小二乘拟合
+    fn fit(&self) -> Option<ClockModel> {
+        let min_delay = self.samples.iter().map(|s| s.delay_us).min()?;
+        let good: Vec<&SyncSample> = self
+            .samples
+            .iter()
+            .filter(|s| s.delay_us <= min_delay + SYNC_DELAY_SLACK_US)
+            .collect();
+        let n = good.len() as f64;
+        let ref_us = good.iter().map(|s| s.host_us as f64).sum::<f64>() / n;
+        let offset_us = good.iter().map(|s| s.offset_us as f64).sum::<f64>() / n;
+        let (sxy, sxx) = good.iter().fold((0.0, 0.0), |(sxy, sxx), s| {
+            let dx = s.host_us as f64 - ref_us;
+            (sxy + dx * (s.offset_us as f64 - offset_us), sxx + dx * dx)
+        });
+        let drift = if good.len() >= 2 && sxx > 0.0 {
+            sxy / sxx
+        } else {
+            0.0
+        };
+        Some(ClockModel {
+            ref_us,
+            offset_us,
+            drift,
+        })
+    }
+
+    /// 样本数足够，可以信任换算结果
+    pub fn is_synced(&self) -> bool {
+        self.model.is_some() && self.samples.len() >= SYNC_MIN_SAMPLES
+    }
+
+    /// 当前时刻下位机时钟减上位机时钟 us
+    pub fn offset_us(&self) -> Option<f64> {
+        let model = self.model?;
+        let now = self.host_us(Instant::now()) as f64;
+        Some(model.offset_us + model.drift * (now - model.ref_us))
+    }
+
+    /// 下位机时钟相对上位机的漂移 ppm
+    pub fn drift_ppm(&self) -> Option<f64> {
+        self.model.map(|model| model.drift * 1e6)
+    }
+
+    /// 最近窗口内的最小往返延迟
+    pub fn min_delay(&self) -> Option<Duration> {
+        self.samples
+            .iter()
+            .map(|s| s.delay_us)
+            .min()
+            .map(|delay| Duration::from_micros(delay as u64))
+    }
+
+    /// 将下位机时间戳换算为上位机时刻，未同步时返回 None
+    pub fn mcu_to_host(&self, mcu_time_us: u32) -> Option<Instant> {
+        if !self.is_synced() {
+            return None;
+        }
+        let model = self.model?;
+        let mcu = self.unwrap_mcu(mcu_time_us) as f64;
+        // host = mcu - offset(host)，解一次线性方程
+        let host_us = (mcu - model.offset_us + model.drift * model.ref_us) / (1.0 + model.drift);
+        if host_us < 0.0 {
+            return None;
+        }
+        Some(self.epoch + Duration::from_micros(host_us.round() as u64))
+    }
+
+    /// 记录一帧并生成带时间戳的传感器帧，未同步时使用接收时刻
+    pub fn stamp(&mut self, data: SensData, received_at: Instant) -> SensFrame {
+        self.record(&data, received_at);
+        let time_stamp = self
+            .mcu_to_host(data.mcu_time_us)
+            .map_or(received_at, |time_stamp| time_stamp.min(received_at));
+        SensFrame::with_time_stamp(data, time_stamp)
+    }
+}
+
+/// 云台姿态，角度制
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct GimbalAttitude {
+    pub yaw: f32,
+    pub pitch: f32,
+    pub roll: f32,
+}
+
+impl From<&SensData> for GimbalAttitude {
+    fn from(data: &SensData) -> Self {
+        Self {
+            yaw: data.gimbal_yaw,
+            pitch: data.gimbal_pitch,
+            roll: data.gimbal_roll,
+        }
+    }
+}
+
+/// 传感器帧环形缓冲区，按时间戳有序
+#[derive(Debug, Clone)]
+pub struct SensFrameBuffer {
+    frames: VecDeque<SensFrame>,
+    capacity: usize,
+}
+
+impl SensFrameBuffer {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            frames: VecDeque::with_capacity(capacity),
+            capacity: capacity.max(2),
+        }
+    }
+
+    /// 加入一帧，缓冲区满时丢弃最旧的帧
+    pub fn push(&mut self, frame: SensFrame) {
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        // 时钟模型更新可能让时间戳轻微回退，按时间插入保持有序
+        let idx = self
+            .frames
+            .partition_point(|f| f.time_stamp() <= frame.time_stamp());
+        self.frames.insert(idx, frame);
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn latest(&self) -> Option<&SensFrame> {
+        self.frames.back()
+    }
+
+    /// 在 `at` 时刻线性插值云台姿态，超出缓存的时间范围时返回 None
+    ///
+    /// yaw 可以是下位机累计的多圈角度，也可以折算在一圈以内（例如 (-180°, 180°]），
+    /// 两帧之间沿最短弧插值，结果与 `at` 之前的一帧处于同一圈；
+    /// 相邻两帧的 yaw 变化需小于 180°，500Hz 上报时总能满足
+    pub fn attitude_at(&self, at: Instant) -> Option<GimbalAttitude> {
+        let idx = self.frames.partition_point(|f| *f.time_stamp() <= at);
+        if idx == 0 {
+            return None;
+        }
+        let before = &self.frames[idx - 1];
+        if *before.time_stamp() == at {
+            return Some(before.data().into());
+        }
+        let after = self.frames.get(idx)?;
+
+        let span = after
+            .time_stamp()
+            .duration_since(*before.time_stamp())
+            .as_secs_f32();
+        let ratio = at.duration_since(*before.time_stamp()).as_secs_f32() / span;
+        let lerp = |a: f32, b: f32| a + (b - a) * ratio;
+        let (a, b) = (before.data(), after.data());
+        let yaw_delta = wrap_deg((b.gimbal_yaw - a.gimbal_yaw) as f64) as f32;
+        Some(GimbalAttitude {
+            yaw: a.gimbal_yaw + yaw_delta * ratio,
+            pitch: lerp(a.gimbal_pitch, b.gimbal_pitch),
+            roll: lerp(a.gimbal_roll, b.gimbal_roll),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rbt_mod::rbt_comm::rbt_comm_frame::{SelfFraction, TaskMode};
+
+    fn sens_data(mcu_time_us: u32, ping: u32, mcu_recv_us: u32) -> SensData {
+        SensData {
+            task_mode: TaskMode::AutoShot,
+            self_fraction: SelfFraction::Red,
+            bullet_speed: 24.0,
+            gimbal_roll: 0.0,
+            gimbal_yaw: 0.0,
+            gimbal_pitch: 0.0,
+            yaw_speed: 0.0,
+            mcu_time_us,
+            sync_host_time_us: ping,
+            sync_mcu_recv_us: mcu_recv_us,
+        }
+    }
+
+    /// 下位机时钟：mcu = offset + (1 + drift) * host，按 u32 回绕
+    fn mcu_clock(host_us: i64, offset_us: i64, drift: f64) -> u32 {
+        (offset_us + (host_us as f64 * (1.0 + drift)).round() as i64) as u32
+    }
+
+    /// 模拟一段时间的乒乓测量，返回同步器
+    fn simulate(offset_us: i64, drift: f64, rounds: usize) -> RbtTimeSync {
+        let mut sync = RbtTimeSync::new();
+        let epoch = sync.epoch();
+        for round in 0..rounds {
+            // 2ms 一个控制帧，上下行延迟不对称且带排队抖动
+            let t1 = 1_000 + round as i64 * 2_000;
+            let jitter = [0, 900, 150, 3_000, 40][round % 5];
+            let t2_host = t1 + 300 + jitter;
+            let t3_host = t2_host + 200;
+            let t4 = t3_host + 300 + jitter / 2;
+            let data = sens_data(
+                mcu_clock(t3_host, offset_us, drift),
+                sync.host_time_us(epoch + Duration::from_micros(t1 as u64)),
+                mcu_clock(t2_host, offset_us, drift),
+            );
+            sync.record(&data, epoch + Duration::from_micros(t4 as u64));
+        }
+        sync
+    }
+
+    #[test]
+    fn test_offset_and_drift() {
+        let drift = 200e-6;
+        let sync = simulate(-123_456_789, drift, 200);
+        assert!(sync.is_synced());
+        assert!((sync.drift_ppm().unwrap() - 200.0).abs() < 5.0);
+        assert_eq!(sync.min_delay(), Some(Duration::from_micros(600)));
+
+        // 下位机在 host = 0.3s 时采样，应换算回该时刻
+        let host_us = 300_000;
+        let mcu = mcu_clock(host_us, -123_456_789, drift);
+        let mapped = sync.mcu_to_host(mcu).unwrap();
+        let expected = sync.epoch() + Duration::from_micros(host_us as u64);
+        // 上下行不对称带来的偏差为抖动的 1/4 量级
+        let err = mapped.max(expected) - mapped.min(expected);
+        assert!(err < Duration::from_micros(50), "{err:?}");
+    }
+
+    #[test]
+    fn test_mcu_clock_wrap() {
+        // 下位机时钟在测量过程中回绕
+        let offset_us = u32::MAX as i64 - 100_000;
+        let sync = simulate(offset_us, 0.0, 100);
+        assert!(sync.is_synced());
+        let host_us = 150_000;
+        let mapped = sync
+            .mcu_to_host(mcu_clock(host_us, offset_us, 0.0))
+            .unwrap();
+        let expected = sync.epoch() + Duration::from_micros(host_us as u64);
+        assert!(mapped.max(expected) - mapped.min(expected) < Duration::from_micros(50));
+    }
+
+    #[test]
+    fn test_unsynced_and_repeated_ping() {
+        let mut sync = RbtTimeSync::new();
+        let epoch = sync.epoch();
+        let now = epoch + Duration::from_millis(10);
+        // 下位机还没有收到控制帧
+        let frame = sync.stamp(sens_data(5_000, 0, 0), now);
+        assert_eq!(*frame.time_stamp(), now);
+        assert!(sync.mcu_to_host(5_000).is_none());
+
+        // 同一个 t1 只使用一次
+        for _ in 0..SYNC_MIN_SAMPLES {
+            sync.record(&sens_data(9_000, 8_000, 8_500), now);
+        }
+        assert!(!sync.is_synced());
+    }
+
+    fn attitude_frame(epoch: Instant, ms: u64, yaw: f32, pitch: f32) -> SensFrame {
+        let mut data = sens_data(0, 0, 0);
+        data.gimbal_yaw = yaw;
+        data.gimbal_pitch = pitch;
+        data.gimbal_roll = -pitch;
+        SensFrame::with_time_stamp(data, epoch + Duration::from_millis(ms))
+    }
+
+    #[test]
+    fn test_attitude_interpolation() {
+        let epoch = Instant::now();
+        let mut buffer = SensFrameBuffer::new(3);
+        buffer.push(attitude_frame(epoch, 0, 0.0, 0.0));
+        buffer.push(attitude_frame(epoch, 20, 20.0, -4.0));
+        // 乱序到达的帧按时间插入
+        buffer.push(attitude_frame(epoch, 10, 10.0, 2.0));
+        assert_eq!(buffer.len(), 3);
+
+        let attitude = buffer
+            .attitude_at(epoch + Duration::from_millis(15))
+            .unwrap();
+        assert!((attitude.yaw - 15.0).abs() < 1e-4);
+        assert!((attitude.pitch + 1.0).abs() < 1e-4);
+        assert!((attitude.roll - 1.0).abs() < 1e-4);
+        assert_eq!(
+            buffer
+                .attitude_at(epoch + Duration::from_millis(10))
+                .unwrap()
+                .yaw,
+            10.0
+        );
+        assert!(
+            buffer
+                .attitude_at(epoch + Duration::from_millis(25))
+                .is_none()
+        );
+
+        // 容量满后丢弃最旧的帧
+        buffer.push(attitude_frame(epoch, 30, 30.0, 0.0));
+        assert!(
+            buffer
+                .attitude_at(epoch + Duration::from_millis(5))
+                .is_none()
+        );
+        assert_eq!(buffer.latest().unwrap().data().gimbal_yaw, 30.0);
+    }
+
+    #[test]
+    fn test_attitude_yaw_wrap() {
+        let epoch = Instant::now();
+        let mut buffer = SensFrameBuffer::new(4);
+        // 折算到 (-180°, 180°] 的 yaw 跨过 ±180° 时沿最短弧插值
+        buffer.push(attitude_frame(epoch, 0, 178.0, 0.0));
+        buffer.push(attitude_frame(epoch, 10, -178.0, 0.0));
+        let attitude = buffer
+            .attitude_at(epoch + Duration::from_millis(5))
+            .unwrap();
+        assert!((attitude.yaw - 180.0).abs() < 1e-4);
+
+        // 多圈累计的 yaw 照常插值
+        buffer.push(attitude_frame(epoch, 20, 538.0, 0.0));
+        buffer.push(attitude_frame(epoch, 30, 542.0, 0.0));
+        let attitude = buffer
+            .attitude_at(epoch + Duration::from_millis(25))
+            .unwrap();
+        assert!((attitude.yaw - 540.0).abs() < 1e-4);
+    }
+
+    /// 时钟暂停，虚拟下位机的仿真时间与上位机时钟同步推进，结果与机器负载无关
+    #[tokio::test(start_paused = true)]
+    async fn test_sync_with_sim() {
+        use crate::rbt_infra::rbt_cfg::CommCfg;
+        use crate::rbt_mod::rbt_comm::rbt_comm_device::RbtComm;
+        use crate::rbt_mod::rbt_comm::rbt_comm_device::rbt_udp::RbtUdp;
+        use crate::rbt_mod::rbt_comm::rbt_comm_frame::{
+            AimingState, CtrlData, ShotBuffMode, ShotMode,
+        };
+        use crate::rbt_mod::rbt_comm::rbt_comm_sim::McuSim;
+        use tokio::net::UdpSocket;
+
+        let mcu = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let comm_cfg: CommCfg = toml::from_str(&format!(
+            "device = \"udp\"\n\
+             serial_port = \"pty\"\n\
+             serial_baud_rate = 115200\n\
+             udp_local_addr = \"127.0.0.1:0\"\n\
+             udp_remote_addr = \"{}\"\n\
+             timeout_ms = 100",
+            mcu.local_addr().unwrap()
+        ))
+        .unwrap();
+        let mut host = RbtUdp::new(&comm_cfg);
+        host.open().await.unwrap();
+        mcu.connect(host.local_addr().unwrap()).await.unwrap();
+        // 下位机时钟在仿真过程中回绕
+        let mut sim = McuSim::new(
+            crate::rbt_mod::rbt_comm::rbt_comm_sim::SimScript::default_script(),
+            SelfFraction::Red,
+        )
+        .with_clock_offset_us(u32::MAX - 200_000);
+
+        let ctrl = CtrlData {
+            gimbal_yaw: 0.0,
+            gimbal_pitch: 0.0,
+            shot_mode: ShotMode::DoNothing,
+            shot_buff_mode: ShotBuffMode::ShotBuffOff,
+            aiming_state: AimingState::AimingNoTarget,
+            sync_host_time_us: 0,
+        };
+        let duration = Duration::from_millis(600);
+        let (report, min_err) = tokio::join!(
+            sim.run_udp(&mcu, Duration::from_millis(1), duration),
+            async {
+                let mut min_err = Duration::MAX;
+                for idx in 0..500 {
+                    let frame = host.receive().await.unwrap();
+                    let received_at = Instant::now();
+                    host.send(&ctrl).await.unwrap();
+                    if idx < 200 {
+                        continue;
+                    }
+                    assert!(host.time_sync().is_synced());
+                    // 不经过接收时刻截断的换算结果，不能明显晚于接收时刻
+                    let sampled_at = host
+                        .time_sync()
+                        .mcu_to_host(frame.data().mcu_time_us)
+                        .unwrap();
+                    assert!(sampled_at <= received_at + Duration::from_millis(1));
+                    min_err = min_err.min(received_at.saturating_duration_since(sampled_at));
+                }
+                min_err
+            }
+        );
+        report.unwrap();
+        // 积压在 socket 中的帧采样时刻本就更早，只要求最新的帧紧贴接收时刻
+        assert!(min_err < Duration::from_millis(2), "{min_err:?}");
+        assert!(host.time_sync().min_delay().unwrap() < Duration::from_millis(3));
+    }
+}
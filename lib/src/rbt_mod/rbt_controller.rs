@@ -0,0 +1,457 @@
+//! 控制器模块
+//!
+//! 以固定频率（默认 500Hz）向下位机发送控制指令。
+//! 控制线程持有估计器发布的最新目标状态，每个周期将其外推到 "当前时刻 + 链路延迟 + 弹丸飞行时间"，
+//! 计算预测装甲板的 yaw/pitch 并叠加重力补偿，最终生成 `CtrlData` 发送给下位机。
+//! 同时接收下位机的 `SensData`，用于获取当前云台姿态和实际弹速。
+
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
+
+use crate::rbt_base::rbt_algorithm::rbt_antigravity::calculate_compensated_pitch;
+use crate::rbt_base::rbt_algorithm::rbt_eskf::StrategyDynamicModel;
+use crate::rbt_base::rbt_geometry::rbt_angle::{unwrap_deg, wrap_deg};
+use crate::rbt_base::rbt_geometry::rbt_tf::{RbtTfFrame, RbtTfTree};
+use crate::rbt_infra::rbt_cfg::ControlCfg;
+use crate::rbt_infra::rbt_err::{CommError, RbtError, RbtResult};
+use crate::rbt_infra::rbt_global::IS_RUNNING;
+use crate::rbt_mod::rbt_comm::rbt_comm_device::RbtComm;
+use crate::rbt_mod::rbt_comm::rbt_comm_frame::{
+    AimingState, CtrlData, SensData, ShotBuffMode, ShotMode,
+};
+use crate::rbt_mod::rbt_estimator::AimTarget;
+use crate::rbt_mod::rbt_estimator::rbt_enemy_dynamic_model::{EnemyESKFState, EnemyModel};
+use crate::rbt_mod::rbt_estimator::rbt_estimator_state::EstimatorStateMachine;
+
+/// 预测得到的瞄准点
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AimPoint {
+    /// 云台 yaw，角度制
+    pub yaw: f64,
+    /// 补偿重力后的云台 pitch，角度制
+    pub pitch: f64,
+    /// 枪口到预测装甲板的水平距离 mm
+    pub distance: f64,
+    /// 弹丸飞行时间 s
+    pub flight_time: f64,
+}
+
+/// 从枪口 `muzzle` 瞄准世界坐标系下 `target` 的瞄准点，装甲板和能量机关共用
+///
+/// 枪口位置随云台转动，取当前姿态下的位置；云台到位时枪口指向目标，与实际弹道起点一致
+pub fn aim_point(
+    muzzle: &na::Point3<f64>,
+    target: &na::Point3<f64>,
+    bullet_speed: f64,
+) -> AimPoint {
+    let offset = target - muzzle;
+    let distance = offset.x.hypot(offset.y);
+    let raw_pitch = offset.z.atan2(distance).to_degrees();
+    // 重力补偿函数的距离单位为米
+    let pitch = calculate_compensated_pitch(raw_pitch, distance / 1000.0, bullet_speed)
+        .unwrap_or(raw_pitch);
+    AimPoint {
+        yaw: offset.y.atan2(offset.x).to_degrees(),
+        pitch,
+        distance,
+        flight_time: distance / 1000.0 / bullet_speed,
+    }
+}
+
+/// 控制器
+pub struct RbtController {
+    cfg: ControlCfg,
+    default_bullet_speed: f64, // 下位机未上报弹速时使用 m/s
+    enemy_model: EnemyModel,
+    last_sens: Option<SensData>,
+    tf_tree: Option<Arc<Mutex<RbtTfTree>>>, // 与视觉线程共享的坐标变换树
+}
+
+impl RbtController {
+    pub fn new(cfg: &ControlCfg, default_bullet_speed: f64) -> Self {
+        Self {
+            cfg: cfg.clone(),
+            default_bullet_speed,
+            enemy_model: EnemyModel {},
+            last_sens: None,
+            tf_tree: None,
+        }
+    }
+
+    /// 将收到的传感器帧作为关节状态写入共享的坐标变换树，供视觉线程按图像采集时刻查询
+    pub fn with_tf_tree(mut self, tf_tree: Arc<Mutex<RbtTfTree>>) -> Self {
+        self.tf_tree = Some(tf_tree);
+        self
+    }
+
+    /// 记录下位机最新上报的数据
+    pub fn update_sens(&mut self, sens: SensData) {
+        self.last_sens = Some(sens);
+    }
+
+    pub fn last_sens(&self) -> Option<&SensData> {
+        self.last_sens.as_ref()
+    }
+
+    /// 当前弹速 m/s，优先使用下位机上报值
+    pub fn bullet_speed(&self) -> f64 {
+        match &self.last_sens {
+            Some(sens) if sens.bullet_speed > 0.0 => sens.bullet_speed as f64,
+            _ => self.default_bullet_speed,
+        }
+    }
+
+    /// 将目标状态外推 `dt` 秒
+    fn propagate(&self, state: &EnemyESKFState, dt: f64) -> EnemyESKFState {
+        let mut state = state.clone();
+        self.enemy_model.update_nominal_state(
+            &mut state,
+            dt,
+            &[0.0; 11],
+            &EstimatorStateMachine::Track { jump: false },
+        );
+        state
+    }
+
+    /// 枪口在世界坐标系下的位置，取最新关节状态；未接入坐标变换树时退化为世界原点
+    fn muzzle(&self) -> na::Point3<f64> {
+        let Some(tf_tree) = &self.tf_tree else {
+            return na::Point3::origin();
+        };
+        let tf_tree = tf_tree.lock().unwrap();
+        let tf = tf_tree
+            .latest()
+            .unwrap_or_else(|| tf_tree.static_tf().clone());
+        tf.lookup(RbtTfFrame::Muzzle, RbtTfFrame::World) * na::Point3::origin()
+    }
+
+    /// 计算给定状态下跟踪装甲板的瞄准点
+    fn armor_aim_point(&self, state: &EnemyESKFState, muzzle: &na::Point3<f64>) -> AimPoint {
+        let (theta, armor_yaw) = (state.theta.to_radians(), state.armor_yaw.to_radians());
+        // 装甲板位于车体中心朝向己方一侧
+        let armor = na::Point3::new(
+            state.distance * theta.cos() - state.armor_r * armor_yaw.cos(),
+            state.distance * theta.sin() - state.armor_r * armor_yaw.sin(),
+            state.armor_height,
+        );
+        aim_point(muzzle, &armor, self.bullet_speed())
+    }
+
+    /// 预测 `now` 时刻发出指令后，弹丸命中时装甲板的瞄准点
+    pub fn predict(&self, target: &AimTarget, now: Instant) -> AimPoint {
+        let dt = now.saturating_duration_since(target.time_stamp) + self.cfg.latency();
+        let dt = dt.as_secs_f64();
+        let muzzle = self.muzzle();
+        // 先按当前距离估计飞行时间，再外推一次修正
+        let flight_time = self
+            .armor_aim_point(&self.propagate(&target.state, dt), &muzzle)
+            .flight_time;
+        self.armor_aim_point(&self.propagate(&target.state, dt + flight_time), &muzzle)
+    }
+
+    /// 生成 `now` 时刻的控制指令
+    pub fn ctrl_data(&self, target: Option<&AimTarget>, now: Instant) -> CtrlData {
+        let target = target.filter(|target| {
+            now.saturating_duration_since(target.time_stamp) <= self.cfg.max_predict()
+        });
+        let Some(target) = target else {
+            // 无目标时保持云台当前姿态
+            let (yaw, pitch) = self
+                .last_sens
+                .as_ref()
+                .map(|sens| (sens.gimbal_yaw, sens.gimbal_pitch))
+                .unwrap_or_default();
+            return CtrlData {
+                gimbal_yaw: yaw,
+                gimbal_pitch: pitch,
+                shot_mode: ShotMode::DoNothing,
+                shot_buff_mode: ShotBuffMode::ShotBuffOff,
+                aiming_state: AimingState::AimingNoTarget,
+                sync_host_time_us: 0,
+            };
+        };
+
+        let mut aim = self.predict(target, now);
+        // 预测 yaw 位于 (-180°, 180°]，下位机 yaw 可能多圈累计，展开到离当前云台最近的等价角，避免绕远路
+        let mut on_target = false;
+        if let Some(sens) = &self.last_sens {
+            let gimbal_yaw = sens.gimbal_yaw as f64;
+            aim.yaw = unwrap_deg(aim.yaw, gimbal_yaw);
+            // 云台到位后才开火
+            on_target = wrap_deg(aim.yaw - gimbal_yaw).abs() < self.cfg.fire_tolerance_deg;
+        }
+        CtrlData {
+            gimbal_yaw: aim.yaw as f32,
+            gimbal_pitch: aim.pitch as f32,
+            shot_mode: if target.fire && on_target {
+                ShotMode::AutoFire
+            } else {
+                ShotMode::AimOnly
+            },
+            shot_buff_mode: ShotBuffMode::ShotBuffOff,
+            aiming_state: AimingState::AimingWithTarget,
+            sync_host_time_us: 0,
+        }
+    }
+
+    /// 控制循环：按 `send_period` 发送控制指令，空闲时接收下位机数据
+    ///
+    /// `target_rx` 中始终为估计器最新发布的目标，`IS_RUNNING` 置 false 后退出
+    pub async fn run<C: RbtComm>(
+        &mut self,
+        comm: &mut C,
+        target_rx: watch::Receiver<Option<AimTarget>>,
+    ) -> RbtResult<()> {
+        let period = self.cfg.send_period();
+        let mut ticker = tokio::time::interval(period);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        info!("Controller started, period: {:?}", period);
+        while IS_RUNNING.load(std::sync::atomic::Ordering::SeqCst) {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let ctrl = {
+                        let target = target_rx.borrow();
+                        self.ctrl_data(target.as_ref(), Instant::now())
+                    };
+                    if let Err(err) = comm.send(&ctrl).await {
+                        warn!("Controller failed to send: {}", err);
+                    }
+                }
+                frame = comm.receive() => {
+                    match frame {
+                        Ok(frame) => {
+                            if let Some(tf_tree) = &self.tf_tree {
+                                tf_tree.lock().unwrap().push_joint_state(frame.clone());
+                            }
+                            self.update_sens(frame.data().clone());
+                        }
+                        Err(RbtError::CommError(CommError::TimeOut)) => {
+                            debug!("Controller receive timeout");
+                        }
+                        Err(err) => {
+                            warn!("Controller failed to receive: {}", err);
+                            tokio::time::sleep(period).await;
+                        }
+                    }
+                }
+            }
+        }
+        info!("Controller: Stopping as IS_RUNNING is false");
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rbt_infra::rbt_cfg::{CommCfg, TfCfg};
+    use crate::rbt_mod::rbt_comm::rbt_comm_device::rbt_udp::RbtUdp;
+    use crate::rbt_mod::rbt_comm::rbt_comm_frame::{SelfFraction, TaskMode};
+    use crate::rbt_mod::rbt_comm::rbt_comm_sim::{McuSim, SimScript, SimSegment, SimWave};
+    use crate::rbt_mod::rbt_estimator::rbt_enemy_dynamic_model::EnemyId;
+    use std::time::Duration;
+    use tokio::net::UdpSocket;
+
+    fn control_cfg(max_predict_ms: u64) -> ControlCfg {
+        toml::from_str(&format!(
+            "send_period_ms = 2\n\
+             latency_ms = 30\n\
+             max_predict_ms = {max_predict_ms}\n\
+             fire_tolerance_deg = 1.5"
+        ))
+        .unwrap()
+    }
+
+    /// 静止目标，装甲板正对己方
+    fn still_state(theta: f64, distance: f64) -> EnemyESKFState {
+        EnemyESKFState {
+            theta,
+            distance,
+            v_tang: 0.0,
+            v_norm: 0.0,
+            v_spin: 0.0,
+            a_tang: 0.0,
+            a_norm: 0.0,
+            a_spin: 0.0,
+            armor_yaw: theta,
+            armor_r: 250.0,
+            armor_height: 0.0,
+        }
+    }
+
+    fn target(state: EnemyESKFState, time_stamp: Instant) -> AimTarget {
+        AimTarget {
+            enemy_id: EnemyId::Infantry3,
+            state,
+            fire: true,
+            time_stamp,
+        }
+    }
+
+    fn sens(yaw: f32) -> SensData {
+        SensData {
+            task_mode: TaskMode::AutoShot,
+            self_fraction: SelfFraction::Red,
+            bullet_speed: 0.0,
+            gimbal_roll: 0.0,
+            gimbal_yaw: yaw,
+            gimbal_pitch: -1.0,
+            yaw_speed: 0.0,
+            mcu_time_us: 0,
+            sync_host_time_us: 0,
+            sync_mcu_recv_us: 0,
+        }
+    }
+
+    #[test]
+    fn test_no_target_holds_gimbal() {
+        let mut controller = RbtController::new(&control_cfg(200), 24.0);
+        controller.update_sens(sens(12.0));
+        let ctrl = controller.ctrl_data(None, Instant::now());
+        assert_eq!(ctrl.aiming_state, AimingState::AimingNoTarget);
+        assert_eq!(ctrl.shot_mode, ShotMode::DoNothing);
+        assert_eq!((ctrl.gimbal_yaw, ctrl.gimbal_pitch), (12.0, -1.0));
+    }
+
+    #[test]
+    fn test_stale_target_ignored() {
+        let controller = RbtController::new(&control_cfg(200), 24.0);
+        let now = Instant::now();
+        let stale = target(still_state(10.0, 5000.0), now);
+        let ctrl = controller.ctrl_data(Some(&stale), now + Duration::from_millis(300));
+        assert_eq!(ctrl.aiming_state, AimingState::AimingNoTarget);
+        let ctrl = controller.ctrl_data(Some(&stale), now + Duration::from_millis(100));
+        assert_eq!(ctrl.aiming_state, AimingState::AimingWithTarget);
+    }
+
+    #[test]
+    fn test_still_target_with_gravity() {
+        let mut controller = RbtController::new(&control_cfg(200), 24.0);
+        let now = Instant::now();
+        let still = target(still_state(10.0, 5000.0), now);
+        let aim = controller.predict(&still, now);
+        assert!((aim.yaw - 10.0).abs() < 1e-9);
+        assert!((aim.distance - 4750.0).abs() < 1e-6);
+        assert!((aim.flight_time - 4.75 / 24.0).abs() < 1e-9);
+        // 平视目标需要抬枪
+        assert!(aim.pitch > 0.0);
+
+        // 云台未到位只瞄准，到位后开火
+        controller.update_sens(sens(0.0));
+        assert_eq!(
+            controller.ctrl_data(Some(&still), now).shot_mode,
+            ShotMode::AimOnly
+        );
+        controller.update_sens(sens(10.5));
+        assert_eq!(
+            controller.ctrl_data(Some(&still), now).shot_mode,
+            ShotMode::AutoFire
+        );
+    }
+
+    #[test]
+    fn test_yaw_unwrapped_near_gimbal() {
+        let mut controller = RbtController::new(&control_cfg(200), 24.0);
+        let now = Instant::now();
+        let behind = target(still_state(-179.0, 5000.0), now);
+
+        // 云台位于 179°，目标在 -179°，应转过 2° 而不是 358°
+        controller.update_sens(sens(179.0));
+        let ctrl = controller.ctrl_data(Some(&behind), now);
+        assert!((ctrl.gimbal_yaw - 181.0).abs() < 1e-3);
+        assert_eq!(ctrl.shot_mode, ShotMode::AimOnly);
+
+        // 多圈累计的云台 yaw 与目标相差整圈时视为到位
+        controller.update_sens(sens(-179.5 + 720.0));
+        let ctrl = controller.ctrl_data(Some(&behind), now);
+        assert!((ctrl.gimbal_yaw - (-179.0 + 720.0)).abs() < 1e-3);
+        assert_eq!(ctrl.shot_mode, ShotMode::AutoFire);
+    }
+
+    #[test]
+    fn test_aim_from_muzzle() {
+        let tf_cfg = TfCfg::test_cfg();
+        let tf_tree = Arc::new(Mutex::new(RbtTfTree::new(&tf_cfg)));
+        let controller = RbtController::new(&control_cfg(200), 24.0).with_tf_tree(tf_tree);
+        let now = Instant::now();
+        let still = target(still_state(0.0, 5000.0), now);
+
+        // 枪口位于 yaw 轴前方 120mm、地面以上 380mm，装甲板与地面齐平
+        let aim = controller.predict(&still, now);
+        assert!((aim.distance - (4750.0 - 120.0)).abs() < 1e-6);
+        let raw_pitch = (-380.0f64).atan2(aim.distance).to_degrees();
+        let expected = calculate_compensated_pitch(raw_pitch, aim.distance / 1000.0, 24.0).unwrap();
+        assert!((aim.pitch - expected).abs() < 1e-9);
+        assert!(aim.pitch < 0.0);
+    }
+
+    #[test]
+    fn test_predict_spinning_target() {
+        let controller = RbtController::new(&control_cfg(200), 24.0);
+        let now = Instant::now();
+        let mut state = still_state(0.0, 5000.0);
+        state.v_spin = 90.0;
+        let spinning = target(state, now - Duration::from_millis(20));
+        let aim = controller.predict(&spinning, now);
+
+        // 外推时长 = 已过去的 20ms + 30ms 延迟 + 飞行时间
+        let flight_time = controller
+            .armor_aim_point(&spinning.state, &na::Point3::origin())
+            .flight_time;
+        let expected = controller.propagate(&spinning.state, 0.05 + flight_time);
+        let expected = controller.armor_aim_point(&expected, &na::Point3::origin());
+        assert!((aim.yaw - expected.yaw).abs() < 1e-9);
+        assert!(aim.yaw > 0.0);
+    }
+
+    /// 时钟暂停，控制周期和仿真周期都只在运行时空闲时推进，结果与机器负载无关
+    #[tokio::test(start_paused = true)]
+    async fn test_closed_loop_with_sim() {
+        let mcu = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let comm_cfg: CommCfg = toml::from_str(&format!(
+            "device = \"udp\"\n\
+             serial_port = \"pty\"\n\
+             serial_baud_rate = 115200\n\
+             udp_local_addr = \"127.0.0.1:0\"\n\
+             udp_remote_addr = \"{}\"\n\
+             timeout_ms = 100",
+            mcu.local_addr().unwrap()
+        ))
+        .unwrap();
+        let mut host = RbtUdp::new(&comm_cfg);
+        host.open().await.unwrap();
+        mcu.connect(host.local_addr().unwrap()).await.unwrap();
+
+        let script = SimScript::new(vec![SimSegment {
+            duration: Duration::from_secs(1),
+            task_mode: TaskMode::AutoShot,
+            bullet_speed: 24.0,
+            yaw: SimWave::constant(0.0),
+            pitch: SimWave::constant(0.0),
+        }])
+        .unwrap();
+        let mut sim = McuSim::new(script, SelfFraction::Red);
+
+        let tf_cfg = TfCfg::test_cfg();
+        let tf_tree = Arc::new(Mutex::new(RbtTfTree::new(&tf_cfg)));
+        let mut controller =
+            RbtController::new(&control_cfg(10_000), 24.0).with_tf_tree(tf_tree.clone());
+        let still = target(still_state(15.0, 5000.0), Instant::now());
+        let expected = controller.predict(&still, Instant::now());
+        let (_target_tx, target_rx) = watch::channel(Some(still));
+
+        let report = tokio::select! {
+            report = sim.run_udp(&mcu, Duration::from_millis(1), Duration::from_millis(600)) => report.unwrap(),
+            _ = controller.run(&mut host, target_rx) => unreachable!(),
+        };
+        assert!(report.ctrl_frames >= 100, "{report:?}");
+        assert!(report.tracking_last < 0.5, "{report:?}");
+        assert!((sim.gimbal().yaw as f64 - expected.yaw).abs() < 0.5);
+        assert!((sim.gimbal().pitch as f64 - expected.pitch).abs() < 0.5);
+        // 控制器收到了下位机的数据
+        assert!(controller.last_sens().is_some());
+        assert_eq!(tf_tree.lock().unwrap().joints().len(), 64);
+    }
+}
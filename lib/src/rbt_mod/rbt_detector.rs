@@ -1,164 +1,99 @@
-use image::{DynamicImage, GenericImageView, ImageReader};
+use image::{DynamicImage, GenericImageView};
 use ndarray as nd;
 use ort::{
-    execution_providers, inputs,
+    inputs,
     session::{Session, SessionOutputs},
     value::TensorRef,
 };
+use serde::{Deserialize, Serialize};
 use std::cmp::PartialEq;
 use std::collections::HashMap;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
 use crate::rbt_base::rbt_geometry::rbt_point2::RbtImgPoint2;
 use crate::rbt_infra::rbt_err::{RbtError, RbtResult};
 use crate::rbt_infra::rbt_global::GENERIC_RBT_CFG;
-use crate::rbt_mod::rbt_armor::{ArmorId, ArmorLabel};
-pub use crate::rbt_mod::rbt_detector::rbt_yolo::{BBox, YOLO_LABEL_TABLE};
-use crate::rbt_mod::rbt_detector::rbt_yolo::{intersection, union};
+use crate::rbt_mod::rbt_detector::rbt_backend::build_session;
+use crate::rbt_mod::rbt_detector::rbt_lightbar::LightBarDetector;
+use crate::rbt_mod::rbt_detector::rbt_refine::{CornerRefiner, brightness_image};
+pub use crate::rbt_mod::rbt_detector::rbt_yolo::BBox;
+use crate::rbt_mod::rbt_detector::rbt_yolo::{Letterbox, YoloDecoder, letterbox};
 use crate::rbt_mod::rbt_estimator::rbt_enemy_dynamic_model::EnemyId;
 use crate::rbt_mod::rbt_solver::RbtSolver;
 use crate::{rbt_infra::rbt_cfg, rbt_mod::rbt_armor::detected_armor::DetectedArmor};
 
+pub mod rbt_backend;
 pub mod rbt_frame;
+pub mod rbt_label;
+pub mod rbt_lightbar;
+pub mod rbt_refine;
 pub mod rbt_yolo;
 
+/// 装甲板检测器类型
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DetectorKind {
+    /// YOLO 神经网络检测
+    #[default]
+    Yolo,
+    /// 传统视觉灯条检测，不需要模型
+    LightBar,
+}
+
 pub struct ArmorDetector {
     img: DynamicImage,
     input: nd::Array<f32, nd::Dim<[usize; 4]>>,
+    letterbox: Letterbox,
+    decoder: YoloDecoder,
 }
 
 impl ArmorDetector {
-    fn init(_cfg: &rbt_cfg::DetectorCfg) -> ArmorDetector {
-        Self {
-            img: ImageReader::open("./imgs/test_resize.jpg")
-                .unwrap()
-                .decode()
-                .unwrap(),
-            input: nd::Array::zeros((1, 3, 384, 640)),
+    fn init(
+        cfg: &rbt_cfg::DetectorCfg,
+        session: &Session,
+        img: DynamicImage,
+    ) -> RbtResult<ArmorDetector> {
+        if img.dimensions() != cfg.camera_img_size() {
+            warn!(
+                "Image size {:?} differs from detector_cfg camera size {:?}",
+                img.dimensions(),
+                cfg.camera_img_size()
+            );
         }
+        let (width, height) = cfg.infer_img_size();
+        Ok(Self {
+            img,
+            input: nd::Array::zeros((1, 3, height as usize, width as usize)),
+            letterbox: Letterbox::default(),
+            decoder: YoloDecoder::from_session(cfg, session)?,
+        })
     }
 
     /// 前处理
     /// 主要包含：
-    /// 1. 调整图片大小（主要耗时操作）
-    /// 2. 填充灰色
+    /// 1. 等比缩放图片（主要耗时操作）
+    /// 2. 居中填充灰色，记录 letterbox 变换用于后处理还原坐标
     fn pre_process(&mut self) {
-        // self.img = self.img.resize(640, 360, FilterType::Triangle);
-        let gray = 114.0;
-
-        for pixel in self.img.pixels() {
-            let x = pixel.0 as _;
-            let y = pixel.1 as _;
-            let [r, g, b, _] = pixel.2.0;
-
-            if (12..360).contains(&y) {
-                self.input[[0, 0, y, x]] = r as f32;
-                self.input[[0, 1, y, x]] = g as f32;
-                self.input[[0, 2, y, x]] = b as f32;
-            } else {
-                self.input[[0, 0, y, x]] = gray;
-                self.input[[0, 1, y, x]] = gray;
-                self.input[[0, 2, y, x]] = gray;
-            }
-        }
+        self.letterbox = letterbox(&mut self.input, &self.img);
     }
 
     /// 后处理
-    /// 1. 筛选0.8以上置信度的装甲板
+    /// 1. 筛选置信度高于阈值的装甲板
     /// 2. 利用IOU筛选装甲板
-    /// 3. 统计装甲板信息
-    /// 4. 切片装甲板图片
+    /// 3. 统计装甲板信息，坐标还原到相机原图
     pub fn post_process(
         &self,
         outputs: &SessionOutputs,
-    ) -> ort::Result<HashMap<EnemyId, Vec<DetectedArmor>>> {
+    ) -> RbtResult<HashMap<EnemyId, Vec<DetectedArmor>>> {
         // f32
         let output = outputs["output0"]
             .try_extract_array::<f32>()?
             .t()
             .into_owned();
-
-        let mut boxes = Vec::new();
         let output = output.slice(nd::s![.., .., 0]);
-        for (idx, row) in output.axis_iter(nd::Axis(0)).enumerate() {
-            let row: Vec<_> = row.iter().copied().collect();
-            let (class_id, prob) = row[4..40]
-                .iter()
-                .enumerate()
-                .map(|(index, value)| (index, *value))
-                .reduce(|accum, row| if row.1 > accum.1 { row } else { accum })
-                .unwrap();
-            if prob < 0.8 {
-                continue;
-            }
-            let xc = row[0];
-            let yc = row[1];
-            let w = row[2];
-            let h = row[3];
-
-            let half_w = w / 2.0;
-            let half_h = h / 2.0;
-
-            boxes.push((
-                BBox::new(xc - half_w, yc - half_h, xc + half_w, yc + half_h),
-                class_id,
-                prob,
-                idx,
-            ));
-        }
-
-        // 非极大值抑制
-        // 作用是寻找最准确的目标检测框
-        boxes.sort_by(|box1, box2| box2.2.total_cmp(&box1.2));
-        let mut result = Vec::new();
-        while !boxes.is_empty() {
-            result.push(boxes[0]);
-            boxes = boxes
-                .iter()
-                .filter(|box1| {
-                    intersection(&boxes[0].0, &box1.0) / union(&boxes[0].0, &box1.0) < 0.7
-                })
-                .copied()
-                .collect();
-        }
-
-        // 收集结果
-        let mut armors = HashMap::with_capacity(6);
-
-        let mut idx_id = 0usize;
-        for (_, class_id, _, idx) in result {
-            let armor_label = &YOLO_LABEL_TABLE[class_id];
-            if armor_label.color()
-                == &GENERIC_RBT_CFG
-                    .read()
-                    .unwrap()
-                    .game_cfg
-                    .self_fraction()
-                    .unwrap()
-            {
-                continue;
-            }
-            let armor_id = armor_label.id().clone();
-            let corner_coords = [
-                output[[idx, 0]],
-                output[[idx, 1]],
-                output[[idx, 40]],
-                output[[idx, 41]],
-                output[[idx, 41]],
-                output[[idx, 41]],
-                output[[idx, 41]],
-                output[[idx, 41]],
-                output[[idx, 41]],
-                output[[idx, 41]],
-            ];
-            let detected_armor = DetectedArmor::from_corner_coords(&corner_coords, idx_id);
-            armors
-                .entry(armor_id)
-                .or_insert(Vec::new())
-                .push(detected_armor);
-            idx_id += 1; // 用于辨识帧画面所有装甲板的唯一id
-        }
-        Ok(armors)
+        let self_faction = GENERIC_RBT_CFG.read().unwrap().game_cfg.self_fraction();
+        self.decoder
+            .decode(output, &self.letterbox, self_faction.as_ref())
     }
 }
 
@@ -174,36 +109,31 @@ impl ArmorDetector {
 /// iGPU + OPENVINO + oneAPI + oneDNN: FP16 10ms
 /// CUDA 12.6: FP16 5ms
 /// TensorRT 10: FP16 2.5ms
-pub fn pipeline(cfg: &rbt_cfg::DetectorCfg) -> RbtResult<HashMap<EnemyId, Vec<DetectedArmor>>> {
-    // build session
-    let session_builder = Session::builder()?;
-    let mut session = if cfg.ort_ep == "TensorRT" {
-        session_builder.with_execution_providers([
-            execution_providers::TensorRTExecutionProvider::default()
-                .with_engine_cache(true)
-                .with_engine_cache_path(cfg.armor_detect_engine_path.as_str())
-                .with_fp16(true)
-                .build()
-                .error_on_failure(),
-        ])?
-    } else if cfg.ort_ep == "OpenVINO" {
-        session_builder.with_execution_providers([
-            execution_providers::OpenVINOExecutionProvider::default()
-                .with_device_type("GPU")
-                .build()
-                .error_on_failure(),
-        ])?
-    } else {
-        error!("Unsupported execution provider: {}", cfg.ort_ep);
-        return Err(RbtError::UnsupportedExecutionProvider(cfg.ort_ep.clone()));
+///
+/// `detector_kind` 为 lightbar，或推理会话构建失败时，使用传统视觉灯条检测
+pub fn pipeline(
+    cfg: &rbt_cfg::DetectorCfg,
+    img: DynamicImage,
+) -> RbtResult<HashMap<EnemyId, Vec<DetectedArmor>>> {
+    if cfg.detector_kind == DetectorKind::LightBar {
+        return lightbar_pipeline(&img);
     }
-    .with_optimization_level(ort::session::builder::GraphOptimizationLevel::Level3)?
-    .with_inter_threads(16)?
-    .commit_from_file(cfg.armor_detect_model_path.as_str())?;
+
+    // build session
+    let mut session = match build_session(cfg) {
+        Ok(session) => session,
+        Err(err) => {
+            error!(
+                "Failed to build session: {}, fall back to light bar detector",
+                err
+            );
+            return lightbar_pipeline(&img);
+        }
+    };
 
     // init armor detector
     let tim = std::time::Instant::now();
-    let mut detector = ArmorDetector::init(cfg);
+    let mut detector = ArmorDetector::init(cfg, &session, img)?;
     let elapsed = tim.elapsed();
     info!("Initialization time elapsed: {:?}", elapsed);
 
@@ -224,5 +154,35 @@ pub fn pipeline(cfg: &rbt_cfg::DetectorCfg) -> RbtResult<HashMap<EnemyId, Vec<De
     let elapsed = tim.elapsed();
     info!("Postprocessing time elapsed: {:?}", elapsed);
 
-    Ok(result)
+    Ok(refine_corners(&detector.img, result))
+}
+
+/// 传统视觉灯条检测，参数读取 `lightbar_cfg`，检测颜色由 `game_cfg/enemy_fraction` 决定
+fn lightbar_pipeline(img: &DynamicImage) -> RbtResult<HashMap<EnemyId, Vec<DetectedArmor>>> {
+    let (lightbar_cfg, enemy) = {
+        let cfg = GENERIC_RBT_CFG.read().unwrap();
+        (cfg.lightbar_cfg.clone(), cfg.game_cfg.enemy_fraction())
+    };
+    let enemy = enemy.ok_or_else(|| {
+        RbtError::InvalidConfig("game_cfg/enemy_fraction must be R or B".to_string())
+    })?;
+
+    let tim = std::time::Instant::now();
+    let result = LightBarDetector::new(lightbar_cfg).detect(img, &enemy);
+    info!("Light bar detection time elapsed: {:?}", tim.elapsed());
+    Ok(refine_corners(img, result))
+}
+
+/// `corner_refine_cfg/enable` 打开时，在 PnP 前对角点做亚像素优化
+fn refine_corners(
+    img: &DynamicImage,
+    mut armors: HashMap<EnemyId, Vec<DetectedArmor>>,
+) -> HashMap<EnemyId, Vec<DetectedArmor>> {
+    let refine_cfg = GENERIC_RBT_CFG.read().unwrap().corner_refine_cfg.clone();
+    if refine_cfg.enable {
+        let tim = std::time::Instant::now();
+        CornerRefiner::new(refine_cfg).refine_all(&brightness_image(img), &mut armors);
+        info!("Corner refinement time elapsed: {:?}", tim.elapsed());
+    }
+    armors
 }
@@ -0,0 +1,218 @@
+//! 推理后端
+//!
+//! 每个 onnxruntime 执行器（EP）对应一个 `InferenceBackend` 实现，由 `DetectorCfg::ort_ep` 选择。
+//! 除 CPU 外的后端都需要开启对应的 cargo feature（`openvino` / `tensorrt` / `cuda`），
+//! 未开启时选择该后端会返回 `RbtError::UnsupportedExecutionProvider`。
+//! 所有后端共用 `build_session` 构建推理会话，图优化等级和线程数等公共参数只在这里设置。
+
+use ort::session::Session;
+use ort::session::builder::{GraphOptimizationLevel, SessionBuilder};
+use serde::{Deserialize, Serialize};
+use tracing::{error, info};
+
+use crate::rbt_infra::rbt_cfg::DetectorCfg;
+use crate::rbt_infra::rbt_err::{RbtError, RbtResult};
+
+/// 配置文件中可选的执行器
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OrtEp {
+    #[default]
+    #[serde(rename = "CPU")]
+    Cpu,
+    #[serde(rename = "OpenVINO")]
+    OpenVino,
+    #[serde(rename = "TensorRT")]
+    TensorRt,
+    #[serde(rename = "CUDA")]
+    Cuda,
+}
+
+/// 推理后端，负责向 session builder 注册执行器
+pub trait InferenceBackend {
+    /// 后端名称，用于日志
+    fn name(&self) -> &'static str;
+
+    /// 注册执行器，注册失败时直接返回错误，不会静默回退到 CPU
+    fn register(&self, builder: SessionBuilder, cfg: &DetectorCfg) -> RbtResult<SessionBuilder>;
+}
+
+/// 纯 CPU 推理，onnxruntime 默认执行器，不需要额外注册
+pub struct CpuBackend;
+
+impl InferenceBackend for CpuBackend {
+    fn name(&self) -> &'static str {
+        "CPU"
+    }
+
+    fn register(&self, builder: SessionBuilder, _cfg: &DetectorCfg) -> RbtResult<SessionBuilder> {
+        Ok(builder)
+    }
+}
+
+/// Intel CPU / iGPU 推理
+#[cfg(feature = "openvino")]
+pub struct OpenVinoBackend;
+
+#[cfg(feature = "openvino")]
+impl InferenceBackend for OpenVinoBackend {
+    fn name(&self) -> &'static str {
+        "OpenVINO"
+    }
+
+    fn register(&self, builder: SessionBuilder, cfg: &DetectorCfg) -> RbtResult<SessionBuilder> {
+        use ort::execution_providers::OpenVINOExecutionProvider;
+        Ok(
+            builder.with_execution_providers([OpenVINOExecutionProvider::default()
+                .with_device_type(cfg.openvino_device.as_str())
+                .build()
+                .error_on_failure()])?,
+        )
+    }
+}
+
+/// NVIDIA TensorRT 推理，引擎缓存在 `armor_detect_engine_path`
+#[cfg(feature = "tensorrt")]
+pub struct TensorRtBackend;
+
+#[cfg(feature = "tensorrt")]
+impl InferenceBackend for TensorRtBackend {
+    fn name(&self) -> &'static str {
+        "TensorRT"
+    }
+
+    fn register(&self, builder: SessionBuilder, cfg: &DetectorCfg) -> RbtResult<SessionBuilder> {
+        use ort::execution_providers::TensorRTExecutionProvider;
+        Ok(
+            builder.with_execution_providers([TensorRTExecutionProvider::default()
+                .with_engine_cache(true)
+                .with_engine_cache_path(cfg.armor_detect_engine_path.as_str())
+                .with_fp16(true)
+                .build()
+                .error_on_failure()])?,
+        )
+    }
+}
+
+/// NVIDIA CUDA 推理
+#[cfg(feature = "cuda")]
+pub struct CudaBackend;
+
+#[cfg(feature = "cuda")]
+impl InferenceBackend for CudaBackend {
+    fn name(&self) -> &'static str {
+        "CUDA"
+    }
+
+    fn register(&self, builder: SessionBuilder, _cfg: &DetectorCfg) -> RbtResult<SessionBuilder> {
+        use ort::execution_providers::CUDAExecutionProvider;
+        Ok(
+            builder.with_execution_providers([CUDAExecutionProvider::default()
+                .build()
+                .error_on_failure()])?,
+        )
+    }
+}
+
+/// 根据配置选择推理后端
+pub fn backend_from_cfg(cfg: &DetectorCfg) -> RbtResult<Box<dyn InferenceBackend + Send + Sync>> {
+    match cfg.ort_ep {
+        OrtEp::Cpu => Ok(Box::new(CpuBackend)),
+        #[cfg(feature = "openvino")]
+        OrtEp::OpenVino => Ok(Box::new(OpenVinoBackend)),
+        #[cfg(feature = "tensorrt")]
+        OrtEp::TensorRt => Ok(Box::new(TensorRtBackend)),
+        #[cfg(feature = "cuda")]
+        OrtEp::Cuda => Ok(Box::new(CudaBackend)),
+        #[allow(unreachable_patterns)]
+        ep => {
+            error!(
+                "{:?} backend is not enabled, rebuild with its cargo feature",
+                ep
+            );
+            Err(RbtError::UnsupportedExecutionProvider(format!("{:?}", ep)))
+        }
+    }
+}
+
+/// 根据配置构建推理会话
+pub fn build_session(cfg: &DetectorCfg) -> RbtResult<Session> {
+    let backend = backend_from_cfg(cfg)?;
+    let session = backend
+        .register(Session::builder()?, cfg)?
+        .with_optimization_level(GraphOptimizationLevel::Level3)?
+        .with_inter_threads(cfg.infer_threads)?
+        .commit_from_file(cfg.armor_detect_model_path.as_str())?;
+    info!("Inference session built with {} backend", backend.name());
+    Ok(session)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector_cfg(ort_ep: &str) -> Result<DetectorCfg, toml::de::Error> {
+        toml::from_str(&format!(
+            "armor_detect_model_path = \"./model/armor/best_fp16_norm.onnx\"\n\
+             armor_detect_engine_path = \"./model/armor\"\n\
+             buff_detect_model_path = \"./model/buff/buff.onnx\"\n\
+             armor_label_path = \"cfg/armor_labels.toml\"\n\
+             camera_img_width = 1280\n\
+             camera_img_height = 720\n\
+             infer_img_width = 640\n\
+             infer_img_height = 384\n\
+             confidence_threshold = 0.8\n\
+             nms_mode = \"hard\"\n\
+             nms_iou_threshold = 0.7\n\
+             soft_nms_sigma = 0.5\n\
+             ort_ep = \"{ort_ep}\"\n\
+             openvino_device = \"GPU\"\n\
+             infer_threads = 4\n\
+             detector_kind = \"yolo\""
+        ))
+    }
+
+    #[test]
+    fn test_cpu_backend_always_available() {
+        let cfg = detector_cfg("CPU").unwrap();
+        assert_eq!(cfg.ort_ep, OrtEp::Cpu);
+        assert_eq!(backend_from_cfg(&cfg).unwrap().name(), "CPU");
+    }
+
+    #[test]
+    fn test_backend_feature_gate() {
+        for (name, ep, enabled) in [
+            ("OpenVINO", OrtEp::OpenVino, cfg!(feature = "openvino")),
+            ("TensorRT", OrtEp::TensorRt, cfg!(feature = "tensorrt")),
+            ("CUDA", OrtEp::Cuda, cfg!(feature = "cuda")),
+        ] {
+            let cfg = detector_cfg(name).unwrap();
+            assert_eq!(cfg.ort_ep, ep);
+            match backend_from_cfg(&cfg) {
+                Ok(backend) => {
+                    assert!(enabled);
+                    assert_eq!(backend.name(), name);
+                }
+                Err(RbtError::UnsupportedExecutionProvider(_)) => assert!(!enabled),
+                Err(err) => panic!("unexpected error: {err}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_unknown_backend_rejected() {
+        assert!(detector_cfg("NPU").is_err());
+    }
+
+    #[test]
+    #[ignore = "需要 onnxruntime 动态库和模型文件"]
+    fn test_build_cpu_session() {
+        let mut cfg = detector_cfg("CPU").unwrap();
+        cfg.armor_detect_model_path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../model/armor/best_fp16_norm.onnx"
+        )
+        .to_string();
+        let session = build_session(&cfg).unwrap();
+        assert_eq!(session.inputs.len(), 1);
+    }
+}
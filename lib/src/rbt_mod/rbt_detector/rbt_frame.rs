@@ -1,6 +1,8 @@
+use image::GrayImage;
 use tokio::time::Instant;
 
 use crate::rbt_infra::rbt_global::FAILED_COUNT;
+use crate::rbt_mod::rbt_detector::rbt_yolo::Letterbox;
 use tracing::{debug, error, warn};
 
 pub struct RbtFrame {
@@ -8,6 +10,8 @@ pub struct RbtFrame {
     pub data: RbtFrameData,
     id: u64,
     stage: RbtFrameStage,
+    letterbox: Letterbox,          // 原图到模型输入的变换，后处理时用于还原坐标
+    brightness: Option<GrayImage>, // 原图亮度，开启角点优化时由预处理写入
 }
 
 pub enum RbtFrameStage {
@@ -18,15 +22,19 @@ pub enum RbtFrameStage {
 }
 
 impl RbtFrame {
-    pub fn new() -> Self {
+    /// 模型输入张量按 `width` x `height`（即 `DetectorCfg::infer_img_size`）分配，
+    /// 推理结果的形状由模型决定，推理后通过 `set_infer_data` 写入
+    pub fn new(width: u32, height: u32) -> Self {
         Self {
             time: Instant::now(),
             data: RbtFrameData {
-                pre_infer: nd::Array4::<f32>::zeros([1, 3, 384, 640]),
-                infer_post: nd::Array3::<f32>::zeros([5040, 48, 1]),
+                pre_infer: nd::Array4::<f32>::zeros([1, 3, height as usize, width as usize]),
+                infer_post: nd::Array3::<f32>::zeros([0, 0, 1]),
             },
             id: 0,
             stage: RbtFrameStage::Init,
+            letterbox: Letterbox::default(),
+            brightness: None,
         }
     }
 
@@ -46,6 +54,27 @@ impl RbtFrame {
         self.time
     }
 
+    /// 设置为图像采集时刻
+    pub fn set_time(&mut self, time: Instant) {
+        self.time = time;
+    }
+
+    pub fn letterbox(&self) -> &Letterbox {
+        &self.letterbox
+    }
+
+    pub fn set_letterbox(&mut self, letterbox: Letterbox) {
+        self.letterbox = letterbox;
+    }
+
+    pub fn brightness(&self) -> Option<&GrayImage> {
+        self.brightness.as_ref()
+    }
+
+    pub fn set_brightness(&mut self, brightness: GrayImage) {
+        self.brightness = Some(brightness);
+    }
+
     pub fn pre_data(&mut self) -> nd::ArrayViewMut4<f32> {
         self.data.pre_infer.view_mut()
     }
@@ -54,6 +83,11 @@ impl RbtFrame {
         self.data.infer_post.view_mut()
     }
 
+    /// 写入推理结果，形状由模型输出决定
+    pub fn set_infer_data(&mut self, data: nd::Array3<f32>) {
+        self.data.infer_post = data;
+    }
+
     pub fn time_used(&self) -> std::time::Duration {
         self.time.elapsed()
     }
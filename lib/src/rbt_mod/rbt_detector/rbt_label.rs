@@ -0,0 +1,196 @@
+//! 模型类别标签表
+//!
+//! 类别序号到装甲板颜色、编号和大小的映射，按以下顺序查找：
+//! 1. ONNX 模型元数据中的 `rbt_labels` 字段（`tools/embed_labels.py` 写入）
+//! 2. `detector_cfg/armor_label_path` 指向的 TOML 文件
+//!
+//! 两者内容格式相同，`labels` 数组的下标即类别序号：
+//! ```toml
+//! labels = [
+//!     { color = "B", id = "Hero1" },
+//!     { color = "B", id = "Infantry3", armor = "Large" }, # 平衡步兵
+//! ]
+//! ```
+//! `armor` 省略时由编号推断。模型重新训练、调整类别顺序后只需更新标签表，不需要重新编译；
+//! 启动时由标签数量推出输出布局，并与模型实际的输出形状比对。
+
+use ort::session::Session;
+use serde::Deserialize;
+use std::path::Path;
+use tracing::info;
+
+use crate::rbt_infra::rbt_err::{RbtError, RbtResult};
+use crate::rbt_mod::rbt_armor::{ArmorColor, ArmorId, ArmorType};
+
+/// 模型元数据中保存标签表的字段名
+pub const LABEL_METADATA_KEY: &str = "rbt_labels";
+
+/// 单个类别的标签
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "LabelEntry")]
+pub struct YoloLabel {
+    color: ArmorColor,
+    id: ArmorId,
+    armor_type: ArmorType,
+}
+
+/// 标签表文件中的一项
+#[derive(Deserialize)]
+struct LabelEntry {
+    color: ArmorColor,
+    id: ArmorId,
+    armor: Option<ArmorType>,
+}
+
+impl From<LabelEntry> for YoloLabel {
+    fn from(entry: LabelEntry) -> Self {
+        Self {
+            armor_type: entry
+                .armor
+                .unwrap_or_else(|| ArmorType::from_enemy_id(&entry.id)),
+            color: entry.color,
+            id: entry.id,
+        }
+    }
+}
+
+impl YoloLabel {
+    pub fn new(color: ArmorColor, id: ArmorId) -> Self {
+        Self {
+            armor_type: ArmorType::from_enemy_id(&id),
+            color,
+            id,
+        }
+    }
+
+    pub fn id(&self) -> &ArmorId {
+        &self.id
+    }
+
+    pub fn color(&self) -> &ArmorColor {
+        &self.color
+    }
+
+    pub fn armor_type(&self) -> &ArmorType {
+        &self.armor_type
+    }
+}
+
+/// 类别标签表
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct YoloLabelTable {
+    labels: Vec<YoloLabel>,
+}
+
+impl YoloLabelTable {
+    pub fn new(labels: Vec<YoloLabel>) -> RbtResult<Self> {
+        if labels.is_empty() {
+            return Err(RbtError::InvalidConfig("label table is empty".to_string()));
+        }
+        Ok(Self { labels })
+    }
+
+    pub fn from_toml_str(s: &str) -> RbtResult<Self> {
+        let table = toml::from_str::<Self>(s)?;
+        Self::new(table.labels)
+    }
+
+    /// 读取 TOML 标签表文件
+    pub fn open(path: impl AsRef<Path>) -> RbtResult<Self> {
+        Self::from_toml_str(&std::fs::read_to_string(path)?)
+    }
+
+    /// 优先使用模型元数据中的标签表，没有时读取 `fallback` 文件
+    pub fn from_session(session: &Session, fallback: impl AsRef<Path>) -> RbtResult<Self> {
+        if let Some(labels) = session.metadata()?.custom(LABEL_METADATA_KEY)? {
+            let table = Self::from_toml_str(&labels)?;
+            info!("{} labels loaded from model metadata", table.len());
+            return Ok(table);
+        }
+        let table = Self::open(fallback.as_ref())?;
+        info!(
+            "{} labels loaded from {}",
+            table.len(),
+            fallback.as_ref().display()
+        );
+        Ok(table)
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    /// 类别序号对应的标签
+    pub fn get(&self, class_id: usize) -> RbtResult<&YoloLabel> {
+        self.labels
+            .get(class_id)
+            .ok_or(RbtError::InvalidArmorClassIndex(class_id))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &YoloLabel> {
+        self.labels.iter()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// 仓库自带的标签表，与当前装甲板模型对应
+    pub(crate) fn armor_labels() -> YoloLabelTable {
+        YoloLabelTable::open(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/../cfg/armor_labels.toml"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_builtin_armor_labels() {
+        let labels = armor_labels();
+        assert_eq!(labels.len(), 36);
+        // 前 18 类为蓝色，后 18 类为红色
+        assert!(labels.iter().take(18).all(|l| l.color() == &ArmorColor::B));
+        assert!(labels.iter().skip(18).all(|l| l.color() == &ArmorColor::R));
+        assert_eq!(labels.get(1).unwrap().id(), &ArmorId::Hero1);
+        assert_eq!(labels.get(1).unwrap().armor_type(), &ArmorType::Large);
+        assert_eq!(labels.get(21).unwrap().id(), &ArmorId::Infantry3);
+        assert!(matches!(
+            labels.get(36),
+            Err(RbtError::InvalidArmorClassIndex(36))
+        ));
+    }
+
+    #[test]
+    fn test_labels_from_toml() {
+        let labels = YoloLabelTable::from_toml_str(
+            r#"labels = [
+                { color = "R", id = "Infantry5" },
+                { color = "B", id = "Infantry4", armor = "Large" },
+                { color = "B", id = "Base" },
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(labels.len(), 3);
+        assert_eq!(labels.get(0).unwrap().armor_type(), &ArmorType::Small);
+        assert_eq!(labels.get(1).unwrap().armor_type(), &ArmorType::Large);
+        assert_eq!(labels.get(2).unwrap().id(), &ArmorId::Base);
+        assert_eq!(labels.get(2).unwrap().armor_type(), &ArmorType::Large);
+    }
+
+    #[test]
+    fn test_invalid_labels_rejected() {
+        assert!(YoloLabelTable::from_toml_str("labels = []").is_err());
+        assert!(
+            YoloLabelTable::from_toml_str(r#"labels = [{ color = "G", id = "Hero1" }]"#).is_err()
+        );
+        assert!(
+            YoloLabelTable::from_toml_str(r#"labels = [{ color = "B", id = "Infantry6" }]"#)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,537 @@
+//! 传统视觉灯条检测
+//!
+//! 不依赖神经网络的装甲板检测器，模型或推理后端不可用时作为兜底，也可以由
+//! `detector_cfg/detector_kind = "lightbar"` 直接选用。流程：
+//! 1. 亮度阈值二值化，提取外轮廓
+//! 2. 对轮廓点做主成分分析，拟合灯条的中轴、长度和宽度，按长宽比和倾角筛选
+//! 3. 按轮廓上蓝红通道差判断灯条颜色，只保留敌方颜色
+//! 4. 按夹角、长度比、间距和连线倾角两两配对，间距与灯条长度之比决定大小装甲板
+//! 5. 截取两灯条之间的数字区域交给 `NumberClassifier` 识别编号
+//!
+//! 输出的关键点顺序与 YOLO 解码一致：中心、左上、左下、右下、右上，坐标为相机原图像素。
+
+use image::{DynamicImage, GrayImage, Luma, RgbImage};
+use imageproc::contours::{BorderType, find_contours};
+use imageproc::geometric_transformations::{Interpolation, Projection, warp_into};
+use std::collections::HashMap;
+
+use crate::rbt_base::rbt_geometry::rbt_point2::RbtImgPoint2;
+use crate::rbt_infra::rbt_cfg::LightBarCfg;
+use crate::rbt_mod::rbt_armor::detected_armor::DetectedArmor;
+use crate::rbt_mod::rbt_armor::{ArmorColor, ArmorId, ArmorType};
+
+/// 数字区域透视变换后的尺寸（宽，高）
+pub const NUMBER_ROI_SIZE: (u32, u32) = (32, 32);
+
+/// 拟合后的灯条
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LightBar {
+    top: na::Point2<f32>,
+    bottom: na::Point2<f32>,
+    width: f32,
+}
+
+impl LightBar {
+    pub fn new(top: na::Point2<f32>, bottom: na::Point2<f32>, width: f32) -> Self {
+        Self { top, bottom, width }
+    }
+
+    pub fn top(&self) -> na::Point2<f32> {
+        self.top
+    }
+
+    pub fn bottom(&self) -> na::Point2<f32> {
+        self.bottom
+    }
+
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    pub fn center(&self) -> na::Point2<f32> {
+        na::center(&self.top, &self.bottom)
+    }
+
+    pub fn length(&self) -> f32 {
+        na::distance(&self.top, &self.bottom)
+    }
+
+    /// 相对竖直方向的倾角，单位度，顶端偏右为正
+    pub fn tilt(&self) -> f32 {
+        let axis = self.bottom - self.top;
+        (-axis.x).atan2(axis.y).to_degrees()
+    }
+
+    /// 按轮廓点拟合灯条，轮廓退化时返回 None
+    fn fit(points: &[na::Point2<f32>]) -> Option<Self> {
+        if points.len() < 3 {
+            return None;
+        }
+        let n = points.len() as f32;
+        let mean = points
+            .iter()
+            .fold(na::Vector2::zeros(), |acc, p| acc + p.coords)
+            / n;
+        let cov = points.iter().fold(na::Matrix2::zeros(), |acc, p| {
+            let d = p.coords - mean;
+            acc + d * d.transpose()
+        }) / n;
+        let eigen = na::SymmetricEigen::new(cov);
+        let major = if eigen.eigenvalues[0] >= eigen.eigenvalues[1] {
+            0
+        } else {
+            1
+        };
+        let mut axis: na::Vector2<f32> = eigen.eigenvectors.column(major).into();
+        // 中轴统一指向图像下方
+        if axis.y < 0.0 {
+            axis = -axis;
+        }
+        let normal = na::Vector2::new(-axis.y, axis.x);
+        let (mut min_a, mut max_a, mut min_n, mut max_n) = (f32::MAX, f32::MIN, f32::MAX, f32::MIN);
+        for p in points {
+            let d = p.coords - mean;
+            let (a, b) = (d.dot(&axis), d.dot(&normal));
+            min_a = min_a.min(a);
+            max_a = max_a.max(a);
+            min_n = min_n.min(b);
+            max_n = max_n.max(b);
+        }
+        let center = mean + normal * (min_n + max_n) / 2.0;
+        Some(Self {
+            top: (center + axis * min_a).into(),
+            bottom: (center + axis * max_a).into(),
+            width: max_n - min_n + 1.0,
+        })
+    }
+}
+
+/// 数字识别接口，输入为两灯条之间透视校正后的灰度图，尺寸为 `NUMBER_ROI_SIZE`
+///
+/// `armor_type` 为按灯条间距推断的大小，识别出不是装甲板时返回 `ArmorId::Invalid`
+pub trait NumberClassifier: Send + Sync {
+    fn classify(&self, roi: &GrayImage, armor_type: ArmorType) -> ArmorId;
+}
+
+/// 不识别数字，所有装甲板编号均为 `ArmorId::Unknown`，由解算阶段每帧只保留一块
+pub struct NoNumberClassifier;
+
+impl NumberClassifier for NoNumberClassifier {
+    fn classify(&self, _roi: &GrayImage, _armor_type: ArmorType) -> ArmorId {
+        ArmorId::Unknown
+    }
+}
+
+/// 灯条配对结果
+struct LightBarPair {
+    left: usize,
+    right: usize,
+    armor_type: ArmorType,
+    cost: f32,
+}
+
+/// 传统视觉灯条装甲板检测器
+pub struct LightBarDetector {
+    cfg: LightBarCfg,
+    classifier: Box<dyn NumberClassifier>,
+}
+
+impl LightBarDetector {
+    pub fn new(cfg: LightBarCfg) -> Self {
+        Self {
+            cfg,
+            classifier: Box::new(NoNumberClassifier),
+        }
+    }
+
+    /// 替换数字识别器
+    pub fn with_classifier(mut self, classifier: Box<dyn NumberClassifier>) -> Self {
+        self.classifier = classifier;
+        self
+    }
+
+    pub fn cfg(&self) -> &LightBarCfg {
+        &self.cfg
+    }
+
+    /// 配置热更新
+    pub fn set_cfg(&mut self, cfg: LightBarCfg) {
+        self.cfg = cfg;
+    }
+
+    /// 检测 `enemy` 颜色的装甲板，按编号分组
+    pub fn detect(
+        &self,
+        img: &DynamicImage,
+        enemy: &ArmorColor,
+    ) -> HashMap<ArmorId, Vec<DetectedArmor>> {
+        let rgb = img.to_rgb8();
+        let bars = self.find_light_bars(&rgb, enemy);
+        let gray = img.to_luma8();
+
+        let mut armors = HashMap::new();
+        for (armor_idx, pair) in self.pair_light_bars(&bars).into_iter().enumerate() {
+            let (left, right) = (&bars[pair.left], &bars[pair.right]);
+            let id = match number_roi(&gray, left, right) {
+                Some(roi) => self.classifier.classify(&roi, pair.armor_type),
+                None => ArmorId::Invalid,
+            };
+            let corners = [left.top, left.bottom, right.bottom, right.top];
+            let center = corners
+                .iter()
+                .fold(na::Vector2::zeros(), |acc, p| acc + p.coords)
+                / 4.0;
+            let [lt, lb, rb, rt] = corners.map(|p| RbtImgPoint2::new_screen_pixel(p.x, p.y));
+            armors.entry(id).or_insert_with(Vec::new).push(
+                DetectedArmor::new(
+                    RbtImgPoint2::new_screen_pixel(center.x, center.y),
+                    lt,
+                    lb,
+                    rb,
+                    rt,
+                    armor_idx,
+                )
+                .with_armor_type(pair.armor_type),
+            );
+        }
+        armors
+    }
+
+    /// 二值化、提取轮廓并拟合灯条，只保留 `enemy` 颜色
+    pub fn find_light_bars(&self, rgb: &RgbImage, enemy: &ArmorColor) -> Vec<LightBar> {
+        let threshold = self.cfg.brightness_threshold;
+        let mask = GrayImage::from_fn(rgb.width(), rgb.height(), |x, y| {
+            let max = rgb.get_pixel(x, y).0.into_iter().max().unwrap_or(0);
+            Luma([if max > threshold { 255 } else { 0 }])
+        });
+
+        find_contours::<i32>(&mask)
+            .into_iter()
+            .filter(|contour| contour.border_type == BorderType::Outer)
+            .filter(|contour| self.bar_color(rgb, &contour.points) == Some(*enemy))
+            .filter_map(|contour| {
+                let points = contour
+                    .points
+                    .iter()
+                    .map(|p| na::Point2::new(p.x as f32, p.y as f32))
+                    .collect::<Vec<_>>();
+                LightBar::fit(&points)
+            })
+            .filter(|bar| self.is_light_bar(bar))
+            .collect()
+    }
+
+    /// 轮廓上平均蓝红通道差超过阈值时判定颜色，灯条中心过曝发白，只看边缘
+    fn bar_color(
+        &self,
+        rgb: &RgbImage,
+        points: &[imageproc::point::Point<i32>],
+    ) -> Option<ArmorColor> {
+        if points.is_empty() {
+            return None;
+        }
+        let diff = points
+            .iter()
+            .map(|p| {
+                let [r, _, b] = rgb.get_pixel(p.x as u32, p.y as u32).0;
+                b as f32 - r as f32
+            })
+            .sum::<f32>()
+            / points.len() as f32;
+        if diff > self.cfg.color_threshold {
+            Some(ArmorColor::B)
+        } else if -diff > self.cfg.color_threshold {
+            Some(ArmorColor::R)
+        } else {
+            None
+        }
+    }
+
+    fn is_light_bar(&self, bar: &LightBar) -> bool {
+        let [min_ratio, max_ratio] = self.cfg.bar_aspect_ratio;
+        let ratio = bar.length() / bar.width();
+        bar.length() >= self.cfg.min_bar_length
+            && (min_ratio..=max_ratio).contains(&ratio)
+            && bar.tilt().abs() <= self.cfg.max_bar_tilt_deg
+    }
+
+    /// 两两配对灯条，按代价从小到大贪心选取，每根灯条只使用一次
+    fn pair_light_bars(&self, bars: &[LightBar]) -> Vec<LightBarPair> {
+        let mut order = (0..bars.len()).collect::<Vec<_>>();
+        order.sort_by(|&a, &b| bars[a].center().x.total_cmp(&bars[b].center().x));
+
+        let mut pairs = Vec::new();
+        for (i, &left) in order.iter().enumerate() {
+            for &right in &order[i + 1..] {
+                if let Some(pair) = self.match_pair(bars, left, right) {
+                    pairs.push(pair);
+                }
+            }
+        }
+        pairs.sort_by(|a, b| a.cost.total_cmp(&b.cost));
+
+        let mut used = vec![false; bars.len()];
+        pairs.retain(|pair| {
+            if used[pair.left] || used[pair.right] {
+                return false;
+            }
+            used[pair.left] = true;
+            used[pair.right] = true;
+            true
+        });
+        // 输出顺序从左到右，保证装甲板序号稳定
+        pairs.sort_by(|a, b| bars[a.left].center().x.total_cmp(&bars[b.left].center().x));
+        pairs
+    }
+
+    /// 几何约束检查，通过时返回配对及其代价
+    fn match_pair(&self, bars: &[LightBar], left: usize, right: usize) -> Option<LightBarPair> {
+        let cfg = &self.cfg;
+        let (l, r) = (&bars[left], &bars[right]);
+
+        let angle_diff = (l.tilt() - r.tilt()).abs();
+        if angle_diff > cfg.max_pair_angle_diff_deg {
+            return None;
+        }
+        let length_ratio = l.length().min(r.length()) / l.length().max(r.length());
+        if length_ratio < cfg.min_pair_length_ratio {
+            return None;
+        }
+        let link = r.center() - l.center();
+        let pair_tilt = link.y.abs().atan2(link.x.abs()).to_degrees();
+        if pair_tilt > cfg.max_pair_tilt_deg {
+            return None;
+        }
+        let mean_length = (l.length() + r.length()) / 2.0;
+        let ratio = link.norm() / mean_length;
+        let in_range = |[min, max]: [f32; 2]| (min..=max).contains(&ratio);
+        let armor_type = if in_range(cfg.small_armor_ratio) {
+            ArmorType::Small
+        } else if in_range(cfg.large_armor_ratio) {
+            ArmorType::Large
+        } else {
+            return None;
+        };
+        // 两灯条之间不能夹着其他灯条
+        let (min_y, max_y) = (l.top.y.min(r.top.y), l.bottom.y.max(r.bottom.y));
+        let blocked = bars.iter().enumerate().any(|(k, bar)| {
+            let c = bar.center();
+            k != left
+                && k != right
+                && c.x > l.center().x
+                && c.x < r.center().x
+                && c.y > min_y
+                && c.y < max_y
+        });
+        if blocked {
+            return None;
+        }
+
+        let cost = angle_diff / cfg.max_pair_angle_diff_deg.max(f32::EPSILON)
+            + (1.0 - length_ratio)
+            + pair_tilt / cfg.max_pair_tilt_deg.max(f32::EPSILON);
+        Some(LightBarPair {
+            left,
+            right,
+            armor_type,
+            cost,
+        })
+    }
+}
+
+/// 截取两灯条之间的数字区域
+///
+/// 灯条沿中轴延长到两倍长度以覆盖数字上下边缘，左右各向内收缩 15% 避开灯条光晕
+fn number_roi(gray: &GrayImage, left: &LightBar, right: &LightBar) -> Option<GrayImage> {
+    const INSET: f32 = 0.15;
+    let extend = |bar: &LightBar| {
+        let c = bar.center();
+        (c + (bar.top - c) * 2.0, c + (bar.bottom - c) * 2.0)
+    };
+    let ((lt, lb), (rt, rb)) = (extend(left), extend(right));
+    let lerp = |a: na::Point2<f32>, b: na::Point2<f32>, t: f32| a + (b - a) * t;
+    let from = [
+        lerp(lt, rt, INSET),
+        lerp(rt, lt, INSET),
+        lerp(rb, lb, INSET),
+        lerp(lb, rb, INSET),
+    ]
+    .map(|p| (p.x, p.y));
+    let (w, h) = (NUMBER_ROI_SIZE.0 as f32, NUMBER_ROI_SIZE.1 as f32);
+    let projection =
+        Projection::from_control_points(from, [(0.0, 0.0), (w, 0.0), (w, h), (0.0, h)])?;
+    let mut roi = GrayImage::new(NUMBER_ROI_SIZE.0, NUMBER_ROI_SIZE.1);
+    warp_into(
+        gray,
+        &projection,
+        Interpolation::Bilinear,
+        Luma([0]),
+        &mut roi,
+    );
+    Some(roi)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgb;
+    use imageproc::drawing::draw_polygon_mut;
+    use imageproc::point::Point;
+
+    fn lightbar_cfg() -> LightBarCfg {
+        LightBarCfg {
+            brightness_threshold: 160,
+            color_threshold: 40.0,
+            min_bar_length: 8.0,
+            bar_aspect_ratio: [2.0, 15.0],
+            max_bar_tilt_deg: 40.0,
+            max_pair_angle_diff_deg: 10.0,
+            min_pair_length_ratio: 0.6,
+            max_pair_tilt_deg: 30.0,
+            small_armor_ratio: [1.5, 3.2],
+            large_armor_ratio: [3.2, 5.5],
+        }
+    }
+
+    /// 以 `top`、`bottom` 为中轴画一根宽 5 像素的灯条
+    fn draw_bar(img: &mut RgbImage, top: (f32, f32), bottom: (f32, f32), color: Rgb<u8>) {
+        let axis = na::Vector2::new(bottom.0 - top.0, bottom.1 - top.1).normalize();
+        let offset = na::Vector2::new(-axis.y, axis.x) * 2.0;
+        let poly = [
+            (top.0 + offset.x, top.1 + offset.y),
+            (bottom.0 + offset.x, bottom.1 + offset.y),
+            (bottom.0 - offset.x, bottom.1 - offset.y),
+            (top.0 - offset.x, top.1 - offset.y),
+        ]
+        .map(|(x, y)| Point::new(x.round() as i32, y.round() as i32));
+        draw_polygon_mut(img, &poly, color);
+    }
+
+    fn assert_near(point: RbtImgPoint2, expected: (f32, f32)) {
+        let err =
+            ((point.x - expected.0 as f64).powi(2) + (point.y - expected.1 as f64).powi(2)).sqrt();
+        assert!(err < 1.5, "{:?} vs {:?}", (point.x, point.y), expected);
+    }
+
+    fn armor_image(color: Rgb<u8>, shift: f32) -> RgbImage {
+        let mut img = RgbImage::from_pixel(320, 240, Rgb([20, 20, 20]));
+        draw_bar(
+            &mut img,
+            (100.0 + shift, 100.0),
+            (100.0 - shift, 140.0),
+            color,
+        );
+        draw_bar(
+            &mut img,
+            (200.0 + shift, 100.0),
+            (200.0 - shift, 140.0),
+            color,
+        );
+        img
+    }
+
+    #[test]
+    fn test_detect_blue_armor() {
+        let detector = LightBarDetector::new(lightbar_cfg());
+        let img = DynamicImage::ImageRgb8(armor_image(Rgb([40, 80, 255]), 0.0));
+        let armors = detector.detect(&img, &ArmorColor::B);
+        assert_eq!(armors.len(), 1);
+        let armor = &armors[&ArmorId::Unknown][0];
+        assert_eq!(armor.armor_type(), ArmorType::Small);
+        assert_near(armor.lt(), (100.0, 100.0));
+        assert_near(armor.lb(), (100.0, 140.0));
+        assert_near(armor.rb(), (200.0, 140.0));
+        assert_near(armor.rt(), (200.0, 100.0));
+        assert_near(armor.center(), (150.0, 120.0));
+    }
+
+    #[test]
+    fn test_detect_large_armor() {
+        let detector = LightBarDetector::new(lightbar_cfg());
+        let mut img = RgbImage::from_pixel(320, 240, Rgb([20, 20, 20]));
+        let blue = Rgb([40, 80, 255]);
+        // 间距与灯条长度之比为 4.5，落在大装甲板范围内
+        draw_bar(&mut img, (60.0, 100.0), (60.0, 140.0), blue);
+        draw_bar(&mut img, (240.0, 100.0), (240.0, 140.0), blue);
+        let armors = detector.detect(&DynamicImage::ImageRgb8(img), &ArmorColor::B);
+        assert_eq!(armors[&ArmorId::Unknown][0].armor_type(), ArmorType::Large);
+    }
+
+    #[test]
+    fn test_ignore_other_color() {
+        let detector = LightBarDetector::new(lightbar_cfg());
+        let img = DynamicImage::ImageRgb8(armor_image(Rgb([255, 80, 40]), 0.0));
+        assert!(detector.detect(&img, &ArmorColor::B).is_empty());
+        assert_eq!(detector.detect(&img, &ArmorColor::R).len(), 1);
+    }
+
+    #[test]
+    fn test_detect_tilted_armor() {
+        let detector = LightBarDetector::new(lightbar_cfg());
+        let img = DynamicImage::ImageRgb8(armor_image(Rgb([40, 80, 255]), 8.0));
+        let bars = detector.find_light_bars(img.as_rgb8().unwrap(), &ArmorColor::B);
+        assert_eq!(bars.len(), 2);
+        // 顶端偏右 16 像素，长 40 像素
+        let expected = (16.0f32).atan2(40.0).to_degrees();
+        assert!(bars.iter().all(|bar| (bar.tilt() - expected).abs() < 2.0));
+
+        let armors = detector.detect(&img, &ArmorColor::B);
+        let armor = &armors[&ArmorId::Unknown][0];
+        assert_near(armor.lt(), (108.0, 100.0));
+        assert_near(armor.lb(), (92.0, 140.0));
+        assert_near(armor.rb(), (192.0, 140.0));
+        assert_near(armor.rt(), (208.0, 100.0));
+    }
+
+    #[test]
+    fn test_reject_unpaired_bars() {
+        let detector = LightBarDetector::new(lightbar_cfg());
+        let mut img = RgbImage::from_pixel(320, 240, Rgb([20, 20, 20]));
+        let blue = Rgb([40, 80, 255]);
+        // 间距过大
+        draw_bar(&mut img, (20.0, 100.0), (20.0, 140.0), blue);
+        draw_bar(&mut img, (300.0, 100.0), (300.0, 140.0), blue);
+        // 长度差距过大
+        draw_bar(&mut img, (120.0, 20.0), (120.0, 60.0), blue);
+        draw_bar(&mut img, (200.0, 30.0), (200.0, 45.0), blue);
+        let img = DynamicImage::ImageRgb8(img);
+        assert!(detector.detect(&img, &ArmorColor::B).is_empty());
+    }
+
+    /// 按数字区域平均亮度区分编号
+    struct BrightnessClassifier;
+
+    impl NumberClassifier for BrightnessClassifier {
+        fn classify(&self, roi: &GrayImage, armor_type: ArmorType) -> ArmorId {
+            assert_eq!(roi.dimensions(), NUMBER_ROI_SIZE);
+            let mean = roi.pixels().map(|p| p.0[0] as f32).sum::<f32>() / roi.len() as f32;
+            match (armor_type, mean > 60.0) {
+                (ArmorType::Small, true) => ArmorId::Infantry3,
+                (ArmorType::Large, true) => ArmorId::Hero1,
+                _ => ArmorId::Invalid,
+            }
+        }
+    }
+
+    #[test]
+    fn test_number_classifier_hook() {
+        let detector =
+            LightBarDetector::new(lightbar_cfg()).with_classifier(Box::new(BrightnessClassifier));
+        let mut img = armor_image(Rgb([40, 80, 255]), 0.0);
+        // 在两灯条之间画一个白色数字块
+        draw_polygon_mut(
+            &mut img,
+            &[
+                Point::new(135, 95),
+                Point::new(165, 95),
+                Point::new(165, 145),
+                Point::new(135, 145),
+            ],
+            Rgb([230, 230, 230]),
+        );
+        let armors = detector.detect(&DynamicImage::ImageRgb8(img), &ArmorColor::B);
+        // 白色数字块不是有颜色的灯条，不影响配对
+        assert_eq!(armors[&ArmorId::Infantry3].len(), 1);
+        assert!(!armors.contains_key(&ArmorId::Invalid));
+    }
+}
@@ -0,0 +1,350 @@
+//! 装甲板角点亚像素优化
+//!
+//! 网络回归的角点误差通常在一两个像素，远距离时 IPPE 的位姿精度主要受此限制。
+//! 对每根灯条，在预测端点附近截取搜索区域：
+//! 1. 以亮度减去背景阈值为权重，求加权质心和主轴，得到灯条中轴线
+//! 2. 沿中轴统计亮度分布，两端亮度降到一半的位置即为灯条端点，线性插值到亚像素
+//!
+//! 优化失败或端点偏移过大时保留原值，由 `corner_refine_cfg/enable` 开启。
+
+use image::{DynamicImage, GrayImage, Luma};
+use std::collections::HashMap;
+
+use crate::rbt_base::rbt_geometry::rbt_point2::RbtImgPoint2;
+use crate::rbt_infra::rbt_cfg::CornerRefineCfg;
+use crate::rbt_mod::rbt_armor::ArmorId;
+use crate::rbt_mod::rbt_armor::detected_armor::DetectedArmor;
+
+/// 短于该像素数的灯条不做优化
+const MIN_BAR_LENGTH: f32 = 4.0;
+/// 搜索区域外扩的最小像素数
+const MIN_MARGIN: f32 = 2.0;
+
+/// 取各通道最大值作为亮度，彩色灯条在灰度图中偏暗，不能直接用亮度分量
+pub fn brightness_image(img: &DynamicImage) -> GrayImage {
+    let rgb = img.to_rgb8();
+    GrayImage::from_fn(rgb.width(), rgb.height(), |x, y| {
+        Luma([rgb.get_pixel(x, y).0.into_iter().max().unwrap_or(0)])
+    })
+}
+
+/// 角点亚像素优化器
+pub struct CornerRefiner {
+    cfg: CornerRefineCfg,
+}
+
+impl CornerRefiner {
+    pub fn new(cfg: CornerRefineCfg) -> Self {
+        Self { cfg }
+    }
+
+    /// 优化所有装甲板的角点，`brightness` 由 `brightness_image` 生成
+    pub fn refine_all(
+        &self,
+        brightness: &GrayImage,
+        armors: &mut HashMap<ArmorId, Vec<DetectedArmor>>,
+    ) {
+        armors
+            .values_mut()
+            .flatten()
+            .for_each(|armor| self.refine(brightness, armor));
+    }
+
+    /// 左右灯条分别优化，某一侧失败时该侧保留原值
+    pub fn refine(&self, brightness: &GrayImage, armor: &mut DetectedArmor) {
+        let to_f32 = |p: RbtImgPoint2| na::Point2::new(p.x as f32, p.y as f32);
+        let to_img = |p: na::Point2<f32>| RbtImgPoint2::new_screen_pixel(p.x, p.y);
+        let [mut lt, mut lb, mut rb, mut rt] = armor.corner_points();
+        if let Some((top, bottom)) = self.refine_bar(brightness, to_f32(lt), to_f32(lb)) {
+            (lt, lb) = (to_img(top), to_img(bottom));
+        }
+        if let Some((top, bottom)) = self.refine_bar(brightness, to_f32(rt), to_f32(rb)) {
+            (rt, rb) = (to_img(top), to_img(bottom));
+        }
+        armor.set_corner_points([lt, lb, rb, rt]);
+    }
+
+    /// 优化一根灯条的上下端点
+    fn refine_bar(
+        &self,
+        img: &GrayImage,
+        top: na::Point2<f32>,
+        bottom: na::Point2<f32>,
+    ) -> Option<(na::Point2<f32>, na::Point2<f32>)> {
+        let length = na::distance(&top, &bottom);
+        if length < MIN_BAR_LENGTH {
+            return None;
+        }
+        let axis = (bottom - top) / length;
+        let normal = na::Vector2::new(-axis.y, axis.x);
+        let margin = (self.cfg.search_margin * length).max(MIN_MARGIN);
+
+        // 搜索区域为沿灯条方向外扩的矩形，取其外接框遍历
+        let corners = [
+            top - (axis + normal) * margin,
+            top - (axis - normal) * margin,
+            bottom + (axis + normal) * margin,
+            bottom + (axis - normal) * margin,
+        ];
+        let (min, max) = corners.iter().fold(
+            (na::Vector2::repeat(f32::MAX), na::Vector2::repeat(f32::MIN)),
+            |(min, max), p| (min.inf(&p.coords), max.sup(&p.coords)),
+        );
+        let (x0, y0) = (min.x.floor().max(0.0) as u32, min.y.floor().max(0.0) as u32);
+        let (x1, y1) = (
+            (max.x.ceil() as u32).min(img.width().checked_sub(1)?),
+            (max.y.ceil() as u32).min(img.height().checked_sub(1)?),
+        );
+
+        let background = self.cfg.background_threshold as f32;
+        let mut samples = Vec::new();
+        for y in y0..=y1 {
+            for x in x0..=x1 {
+                let p = na::Point2::new(x as f32, y as f32);
+                let v = p - top;
+                let (along, across) = (v.dot(&axis), v.dot(&normal));
+                if along < -margin || along > length + margin || across.abs() > margin {
+                    continue;
+                }
+                let weight = img.get_pixel(x, y).0[0] as f32 - background;
+                if weight > 0.0 {
+                    samples.push((p, weight));
+                }
+            }
+        }
+        if samples.len() < 3 {
+            return None;
+        }
+
+        // 加权质心与主轴
+        let total = samples.iter().map(|(_, w)| w).sum::<f32>();
+        let mean = samples
+            .iter()
+            .fold(na::Vector2::zeros(), |acc, (p, w)| acc + p.coords * *w)
+            / total;
+        let cov = samples.iter().fold(na::Matrix2::zeros(), |acc, (p, w)| {
+            let d = p.coords - mean;
+            acc + d * d.transpose() * *w
+        }) / total;
+        let eigen = na::SymmetricEigen::new(cov);
+        let major = eigen.eigenvalues.imax();
+        let mut fitted_axis: na::Vector2<f32> = eigen.eigenvectors.column(major).into();
+        if fitted_axis.dot(&axis) < 0.0 {
+            fitted_axis = -fitted_axis;
+        }
+
+        // 沿主轴每像素一格统计亮度，以中位数作为灯条亮度，两端降到一半处为端点
+        let along = samples
+            .iter()
+            .map(|(p, w)| ((p.coords - mean).dot(&fitted_axis), *w))
+            .collect::<Vec<_>>();
+        let t_min = along.iter().map(|(t, _)| *t).fold(f32::MAX, f32::min);
+        let t_max = along.iter().map(|(t, _)| *t).fold(f32::MIN, f32::max);
+        let mut profile = vec![0.0f32; (t_max - t_min).floor() as usize + 1];
+        for (t, w) in along {
+            profile[(t - t_min).floor() as usize] += w;
+        }
+        let mut sorted = profile.clone();
+        sorted.sort_by(f32::total_cmp);
+        let half = sorted[sorted.len() / 2] / 2.0;
+
+        let bin = |k: isize| {
+            usize::try_from(k)
+                .ok()
+                .and_then(|k| profile.get(k).copied())
+                .unwrap_or(0.0)
+        };
+        let center = |k: isize| t_min + k as f32 + 0.5;
+        let first = profile.iter().position(|&v| v >= half)? as isize;
+        let last = profile.iter().rposition(|&v| v >= half)? as isize;
+        let t_top = center(first - 1) + (half - bin(first - 1)) / (bin(first) - bin(first - 1));
+        let t_bottom = center(last) + (bin(last) - half) / (bin(last) - bin(last + 1));
+
+        let refined_top = na::Point2::from(mean + fitted_axis * t_top);
+        let refined_bottom = na::Point2::from(mean + fitted_axis * t_bottom);
+        if na::distance(&refined_top, &top) > self.cfg.max_shift
+            || na::distance(&refined_bottom, &bottom) > self.cfg.max_shift
+        {
+            return None;
+        }
+        Some((refined_top, refined_bottom))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rbt_base::rbt_algorithm::rbt_ippe::{
+        ARMOR_LIGHT_HEIGHT, ARMOR_LIGHT_WEIGHT, ArmorPnpSolver,
+    };
+    use image::{Rgb, RgbImage};
+
+    /// 灯条物理宽度，单位 mm
+    const BAR_WIDTH: f64 = 10.0;
+
+    fn refine_cfg() -> CornerRefineCfg {
+        CornerRefineCfg {
+            enable: true,
+            background_threshold: 40,
+            search_margin: 0.25,
+            max_shift: 3.0,
+        }
+    }
+
+    fn cam_k() -> na::Matrix3<f64> {
+        na::Matrix3::new(1300.0, 0.0, 640.0, 0.0, 1300.0, 360.0, 0.0, 0.0, 1.0)
+    }
+
+    fn project(pose: &na::Isometry3<f64>, p: na::Point3<f64>) -> na::Point2<f64> {
+        let uv = cam_k() * (pose * p).coords;
+        na::Point2::new(uv.x / uv.z, uv.y / uv.z)
+    }
+
+    /// 装甲板四个角点（灯条端点）的模型坐标，顺序与 `ArmorPnpSolver` 一致
+    fn armor_points() -> [na::Point3<f64>; 4] {
+        let (x, y) = (ARMOR_LIGHT_WEIGHT / 2.0, ARMOR_LIGHT_HEIGHT / 2.0);
+        [
+            na::Point3::new(-x, y, 0.0),
+            na::Point3::new(-x, -y, 0.0),
+            na::Point3::new(x, -y, 0.0),
+            na::Point3::new(x, y, 0.0),
+        ]
+    }
+
+    /// 装甲板在 3 米外，带偏航和滚转
+    fn armor_pose() -> na::Isometry3<f64> {
+        let rotation = na::UnitQuaternion::from_euler_angles(0.0, 0.0, 8f64.to_radians())
+            * na::UnitQuaternion::from_euler_angles(0.0, 30f64.to_radians(), 0.0)
+            * na::UnitQuaternion::from_euler_angles(std::f64::consts::PI, 0.0, 0.0);
+        na::Isometry3::from_parts(na::Translation3::new(120.0, -60.0, 3000.0), rotation)
+    }
+
+    /// 4x4 超采样渲染两根灯条，像素 (x, y) 覆盖 [x-0.5, x+0.5] × [y-0.5, y+0.5]
+    fn render_armor(pose: &na::Isometry3<f64>) -> DynamicImage {
+        let mut img = RgbImage::from_pixel(1280, 720, Rgb([15, 15, 20]));
+        let [lt, lb, rb, rt] = armor_points();
+        for (top, bottom) in [(lt, lb), (rt, rb)] {
+            let half = na::Vector3::new(BAR_WIDTH / 2.0, 0.0, 0.0);
+            let quad =
+                [top - half, top + half, bottom + half, bottom - half].map(|p| project(pose, p));
+            let inside = |x: f64, y: f64| {
+                let signs = (0..4).map(|i| {
+                    let (a, b) = (quad[i], quad[(i + 1) % 4]);
+                    (b.x - a.x) * (y - a.y) - (b.y - a.y) * (x - a.x) >= 0.0
+                });
+                let signs = signs.collect::<Vec<_>>();
+                signs.iter().all(|&s| s) || signs.iter().all(|&s| !s)
+            };
+            let (x0, x1) = (
+                quad.iter().map(|p| p.x).fold(f64::MAX, f64::min),
+                quad.iter().map(|p| p.x).fold(f64::MIN, f64::max),
+            );
+            let (y0, y1) = (
+                quad.iter().map(|p| p.y).fold(f64::MAX, f64::min),
+                quad.iter().map(|p| p.y).fold(f64::MIN, f64::max),
+            );
+            for y in (y0.floor() as u32)..=(y1.ceil() as u32) {
+                for x in (x0.floor() as u32)..=(x1.ceil() as u32) {
+                    let covered = (0..16)
+                        .filter(|i| {
+                            let (dx, dy) = ((i % 4) as f64 + 0.5, (i / 4) as f64 + 0.5);
+                            inside(x as f64 - 0.5 + dx / 4.0, y as f64 - 0.5 + dy / 4.0)
+                        })
+                        .count() as f32
+                        / 16.0;
+                    let bg = img.get_pixel(x, y).0;
+                    let fg = [60, 140, 250];
+                    img.put_pixel(
+                        x,
+                        y,
+                        Rgb(std::array::from_fn(|c| {
+                            (bg[c] as f32 + covered * (fg[c] as f32 - bg[c] as f32)).round() as u8
+                        })),
+                    );
+                }
+            }
+        }
+        DynamicImage::ImageRgb8(img)
+    }
+
+    fn rms(a: &[na::Point2<f64>; 4], b: &[na::Point2<f64>; 4]) -> f64 {
+        (a.iter()
+            .zip(b)
+            .map(|(a, b)| (a - b).norm_squared())
+            .sum::<f64>()
+            / 4.0)
+            .sqrt()
+    }
+
+    fn corners(armor: &DetectedArmor) -> [na::Point2<f64>; 4] {
+        armor.corner_points().map(|p| *p)
+    }
+
+    /// 模拟网络回归的角点，每个角点偏离 1~1.5 像素
+    fn noisy_armor(truth: &[na::Point2<f64>; 4]) -> DetectedArmor {
+        let offsets = [(1.2, -0.8), (-1.0, 1.1), (0.9, 1.3), (-1.3, -0.7)];
+        let [lt, lb, rb, rt] = std::array::from_fn(|i| {
+            RbtImgPoint2::new_screen_pixel(
+                (truth[i].x + offsets[i].0) as f32,
+                (truth[i].y + offsets[i].1) as f32,
+            )
+        });
+        let c = truth
+            .iter()
+            .fold(na::Vector2::zeros(), |acc, p| acc + p.coords)
+            / 4.0;
+        DetectedArmor::new(
+            RbtImgPoint2::new_screen_pixel(c.x as f32, c.y as f32),
+            lt,
+            lb,
+            rb,
+            rt,
+            0,
+        )
+    }
+
+    #[test]
+    fn test_refine_synthetic_armor() {
+        let pose = armor_pose();
+        let truth = armor_points().map(|p| project(&pose, p));
+        let brightness = brightness_image(&render_armor(&pose));
+
+        let mut armor = noisy_armor(&truth);
+        let before = corners(&armor);
+        CornerRefiner::new(refine_cfg()).refine(&brightness, &mut armor);
+        let after = corners(&armor);
+        let (err_before, err_after) = (rms(&before, &truth), rms(&after, &truth));
+        assert!(err_after < 0.3, "corner rms {err_before} -> {err_after}");
+        assert!(err_after < err_before / 3.0);
+
+        // 用优化前后的角点解 PnP，比较真实角点在解出位姿下的重投影误差
+        let solver = ArmorPnpSolver::new().unwrap();
+        let reproj = |uvs: &[na::Point2<f64>; 4]| {
+            let solved = solver.solve(uvs, &cam_k()).unwrap();
+            let projected = armor_points().map(|p| project(&solved, p));
+            let distance_err = (solved.translation.vector - pose.translation.vector).norm();
+            (rms(&projected, &truth), distance_err)
+        };
+        let (reproj_before, dist_before) = reproj(&before);
+        let (reproj_after, dist_after) = reproj(&after);
+        assert!(
+            reproj_after < reproj_before / 3.0,
+            "reprojection rms {reproj_before} -> {reproj_after}"
+        );
+        assert!(
+            dist_after < dist_before,
+            "translation error {dist_before} -> {dist_after}"
+        );
+    }
+
+    #[test]
+    fn test_keep_corners_without_light_bar() {
+        let pose = armor_pose();
+        let truth = armor_points().map(|p| project(&pose, p));
+        let brightness = brightness_image(&DynamicImage::new_rgb8(1280, 720));
+        let mut armor = noisy_armor(&truth);
+        let before = corners(&armor);
+        CornerRefiner::new(refine_cfg()).refine(&brightness, &mut armor);
+        assert_eq!(corners(&armor), before);
+    }
+}
@@ -2,96 +2,485 @@
 // 感谢 wjt, tyk 对神经网络的贡献
 
 use image::GenericImageView;
+use image::imageops::FilterType;
+use ort::session::Session;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::ops::Range;
 
-use crate::rbt_mod::rbt_armor::{ArmorColor, ArmorId, ArmorLabel};
+use crate::rbt_base::rbt_geometry::rbt_point2::RbtImgPoint2;
+use crate::rbt_infra::rbt_cfg::DetectorCfg;
+use crate::rbt_infra::rbt_err::{RbtError, RbtResult};
+use crate::rbt_mod::rbt_armor::detected_armor::DetectedArmor;
+use crate::rbt_mod::rbt_armor::{ArmorColor, ArmorId};
+use crate::rbt_mod::rbt_detector::rbt_label::YoloLabelTable;
 
 const GRAY: f32 = 114.0;
 
-pub fn letterbox(input_array: &mut nd::Array4<f32>, resized_img: &image::DynamicImage) {
+/// letterbox 变换：等比缩放后居中填充到模型输入尺寸
+///
+/// 模型输入坐标 = 原图坐标 * `scale` + `pad`，检测结果需要经过逆变换回到相机原图像素坐标，
+/// 才能与相机内参 `cam_k` 对应
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Letterbox {
+    scale: f32,
+    pad: (u32, u32),
+    resized: (u32, u32),
+    input: (u32, u32),
+}
+
+impl Default for Letterbox {
+    /// 恒等变换
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            pad: (0, 0),
+            resized: (0, 0),
+            input: (0, 0),
+        }
+    }
+}
+
+impl Letterbox {
+    /// 根据原图尺寸和模型输入尺寸计算缩放和填充
+    pub fn new(src: (u32, u32), input: (u32, u32)) -> Self {
+        let scale = (input.0 as f32 / src.0 as f32).min(input.1 as f32 / src.1 as f32);
+        let resized = (
+            ((src.0 as f32 * scale).round() as u32).min(input.0),
+            ((src.1 as f32 * scale).round() as u32).min(input.1),
+        );
+        Self {
+            scale,
+            pad: ((input.0 - resized.0) / 2, (input.1 - resized.1) / 2),
+            resized,
+            input,
+        }
+    }
+
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// 左侧和上方的填充像素
+    pub fn pad(&self) -> (u32, u32) {
+        self.pad
+    }
+
+    /// 缩放后的图像尺寸
+    pub fn resized(&self) -> (u32, u32) {
+        self.resized
+    }
+
+    /// 原图像素坐标 → 模型输入坐标
+    pub fn to_input(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            x * self.scale + self.pad.0 as f32,
+            y * self.scale + self.pad.1 as f32,
+        )
+    }
+
+    /// 模型输入坐标 → 原图像素坐标
+    pub fn to_source(&self, x: f32, y: f32) -> (f32, f32) {
+        (
+            (x - self.pad.0 as f32) / self.scale,
+            (y - self.pad.1 as f32) / self.scale,
+        )
+    }
+
+    /// 模型输出的关键点映射回原图
+    pub fn point_to_source(&self, x: f32, y: f32) -> RbtImgPoint2 {
+        let (x, y) = self.to_source(x, y);
+        RbtImgPoint2::new_screen_pixel(x, y)
+    }
+
+    /// 模型输出的检测框映射回原图
+    pub fn bbox_to_source(&self, bbox: &BBox) -> BBox {
+        let (x1, y1) = self.to_source(bbox.x1(), bbox.y1());
+        let (x2, y2) = self.to_source(bbox.x2(), bbox.y2());
+        BBox::new(x1, y1, x2, y2)
+    }
+}
+
+/// 将任意尺寸的图像 letterbox 到 `input_array`（形状为 `[1, 3, H, W]`）中，返回所用的变换
+///
+/// `input_array` 既可以是独立的数组，也可以是 `RbtFrame::pre_data` 的视图
+pub fn letterbox<S: nd::DataMut<Elem = f32>>(
+    input_array: &mut nd::ArrayBase<S, nd::Ix4>,
+    img: &image::DynamicImage,
+) -> Letterbox {
+    let (_, _, height, width) = input_array.dim();
+    let lb = Letterbox::new(img.dimensions(), (width as u32, height as u32));
+    let resized_img = if img.dimensions() == lb.resized {
+        std::borrow::Cow::Borrowed(img)
+    } else {
+        std::borrow::Cow::Owned(img.resize_exact(lb.resized.0, lb.resized.1, FilterType::Triangle))
+    };
+
+    input_array.fill(GRAY);
+    let (pad_x, pad_y) = (lb.pad.0 as usize, lb.pad.1 as usize);
     for (x, y, pixel) in resized_img.pixels() {
-        let x = x as usize;
-        let y = y as usize;
+        let x = x as usize + pad_x;
+        let y = y as usize + pad_y;
         let [r, g, b, _] = pixel.0;
 
-        let y_new = y + 12; // 调整 y 坐标以适应预处理要求
-
         // 将像素值填充到数组中
-        if (12..372).contains(&y_new) {
-            input_array[[0, 0, y_new, x]] = r as f32;
-            input_array[[0, 1, y_new, x]] = g as f32;
-            input_array[[0, 2, y_new, x]] = b as f32;
-        } else {
-            input_array[[0, 0, y, x]] = GRAY;
-            input_array[[0, 1, y, x]] = GRAY;
-            input_array[[0, 2, y, x]] = GRAY;
+        input_array[[0, 0, y, x]] = r as f32;
+        input_array[[0, 1, y, x]] = g as f32;
+        input_array[[0, 2, y, x]] = b as f32;
+    }
+    lb
+}
+
+/// YOLO-pose 模型输出布局
+///
+/// 输出张量转置后每个 anchor 占一行，每行依次为检测框、各类别置信度和关键点坐标
+#[derive(Debug, Clone, PartialEq)]
+pub struct YoloLayout {
+    /// 检测框 (xc, yc, w, h) 的起始列
+    pub bbox_offset: usize,
+    /// 类别置信度所在的列
+    pub class_range: Range<usize>,
+    /// 中心点和四个关键点（左上、左下、右下、右上）x 坐标所在的列，y 坐标紧随其后
+    pub keypoint_offsets: [usize; 5],
+    /// anchor 数量
+    pub anchor_count: usize,
+}
+
+impl YoloLayout {
+    /// 当前装甲板模型的布局，共 36 类，640x384 输入
+    pub const ARMOR: Self = Self::armor(36, (640, 384));
+
+    /// 装甲板模型：类别紧跟检测框，关键点在类别之后，中心点复用检测框中心，
+    /// anchor 数由输入尺寸 `(width, height)` 决定
+    pub const fn armor(class_count: usize, input_size: (u32, u32)) -> Self {
+        let kpt = 4 + class_count;
+        Self {
+            bbox_offset: 0,
+            class_range: 4..kpt,
+            keypoint_offsets: [0, kpt, kpt + 2, kpt + 4, kpt + 6],
+            anchor_count: Self::anchor_count(input_size),
         }
     }
+
+    /// 三个检测头（步长 8、16、32）的 anchor 总数，640x384 输入下为 80x48 + 40x24 + 20x12
+    pub const fn anchor_count((width, height): (u32, u32)) -> usize {
+        let (width, height) = (width as usize, height as usize);
+        let mut count = 0;
+        let mut stride = 8;
+        while stride <= 32 {
+            count += width.div_ceil(stride) * height.div_ceil(stride);
+            stride *= 2;
+        }
+        count
+    }
+
+    /// 每个 anchor 的输出长度
+    pub fn row_len(&self) -> usize {
+        let keypoint_end = self.keypoint_offsets.iter().max().map_or(0, |x| x + 2);
+        (self.bbox_offset + 4)
+            .max(self.class_range.end)
+            .max(keypoint_end)
+    }
+
+    pub fn class_count(&self) -> usize {
+        self.class_range.len()
+    }
+
+    /// 检查模型输出 `[1, row_len, anchor_count]` 的形状，动态维度（-1）不检查
+    pub fn check_output_shape(&self, shape: &[i64]) -> RbtResult<()> {
+        let [_, row_len, anchor_count] = shape else {
+            return Err(RbtError::InvalidConfig(format!(
+                "model output should be 3-dimensional, got {:?}",
+                shape
+            )));
+        };
+        let matches = |dim: i64, expected: usize| dim < 0 || dim as usize == expected;
+        if !matches(*row_len, self.row_len()) || !matches(*anchor_count, self.anchor_count) {
+            return Err(RbtError::OutputShapeMismatch {
+                expected: (self.anchor_count, self.row_len()),
+                actual: (*anchor_count as usize, *row_len as usize),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// YOLO-pose 输出解码器，同步和异步流水线共用
+///
+/// 解码流程：
+/// 1. 取每个 anchor 置信度最高的类别，丢弃低于阈值的候选框
+/// 2. 按类别进行非极大值抑制
+/// 3. 过滤己方装甲板，关键点经 letterbox 逆变换回到相机原图像素坐标，按 `EnemyId` 分组
+#[derive(Debug, Clone)]
+pub struct YoloDecoder {
+    layout: YoloLayout,
+    labels: YoloLabelTable,
+    nms: Nms,
+}
+
+impl YoloDecoder {
+    pub fn new(layout: YoloLayout, labels: YoloLabelTable, nms: Nms) -> RbtResult<Self> {
+        if layout.class_count() != labels.len() {
+            return Err(RbtError::InvalidConfig(format!(
+                "model has {} classes but label table has {} labels",
+                layout.class_count(),
+                labels.len()
+            )));
+        }
+        Ok(Self {
+            layout,
+            labels,
+            nms,
+        })
+    }
+
+    /// 读取模型对应的标签表，由标签数量和输入尺寸推出输出布局并与模型的输出形状比对
+    pub fn from_session(cfg: &DetectorCfg, session: &Session) -> RbtResult<Self> {
+        let labels = YoloLabelTable::from_session(session, cfg.armor_label_path())?;
+        let layout = YoloLayout::armor(labels.len(), cfg.infer_img_size());
+        let output = session
+            .outputs
+            .first()
+            .ok_or_else(|| RbtError::InvalidConfig("model has no output".to_string()))?;
+        if let Some(shape) = output.output_type.tensor_shape() {
+            layout.check_output_shape(shape)?;
+        }
+        Self::new(layout, labels, Nms::from_cfg(cfg))
+    }
+
+    pub fn layout(&self) -> &YoloLayout {
+        &self.layout
+    }
+
+    pub fn labels(&self) -> &YoloLabelTable {
+        &self.labels
+    }
+
+    /// 更新 NMS 参数，用于配置热更新
+    pub fn set_nms(&mut self, nms: Nms) {
+        self.nms = nms;
+    }
+
+    /// 解码形状为 `[anchor_count, row_len]` 的模型输出
+    ///
+    /// `self_faction` 为 None 时不过滤己方装甲板
+    pub fn decode(
+        &self,
+        output: nd::ArrayView2<f32>,
+        letterbox: &Letterbox,
+        self_faction: Option<&ArmorColor>,
+    ) -> RbtResult<HashMap<ArmorId, Vec<DetectedArmor>>> {
+        let mut armors = HashMap::with_capacity(6);
+        let mut armor_idx = 0usize; // 当前帧画面所有装甲板的唯一 id
+        for detection in self.detections(output, letterbox)? {
+            let label = self.labels.get(detection.class_id)?;
+            if self_faction.is_some_and(|faction| label.color() == faction) {
+                continue;
+            }
+            let [center, lt, lb, rb, rt] = detection.keypoints;
+            armors.entry(*label.id()).or_insert_with(Vec::new).push(
+                DetectedArmor::new(center, lt, lb, rb, rt, armor_idx)
+                    .with_armor_type(*label.armor_type()),
+            );
+            armor_idx += 1;
+        }
+        Ok(armors)
+    }
+
+    /// 解码并做非极大值抑制，保留类别序号、置信度和检测框，按置信度降序排列
+    ///
+    /// 坐标均已还原到相机原图像素，离线评估等需要置信度的场景使用
+    pub fn detections(
+        &self,
+        output: nd::ArrayView2<f32>,
+        letterbox: &Letterbox,
+    ) -> RbtResult<Vec<YoloDetection>> {
+        let expected = (self.layout.anchor_count, self.layout.row_len());
+        if output.dim() != expected {
+            return Err(RbtError::OutputShapeMismatch {
+                expected,
+                actual: output.dim(),
+            });
+        }
+
+        Ok(self
+            .nms
+            .run(self.candidates(&output))
+            .into_iter()
+            .map(|(bbox, class_id, score, idx)| YoloDetection {
+                class_id,
+                score,
+                bbox: letterbox.bbox_to_source(&bbox),
+                keypoints: self.layout.keypoint_offsets.map(|offset| {
+                    letterbox.point_to_source(output[[idx, offset]], output[[idx, offset + 1]])
+                }),
+            })
+            .collect())
+    }
+
+    fn candidates(&self, output: &nd::ArrayView2<f32>) -> Vec<YoloCandidate> {
+        let bbox = self.layout.bbox_offset;
+        output
+            .axis_iter(nd::Axis(0))
+            .enumerate()
+            .filter_map(|(idx, row)| {
+                let (class_id, prob) = row
+                    .slice(nd::s![self.layout.class_range.clone()])
+                    .iter()
+                    .copied()
+                    .enumerate()
+                    .reduce(|accum, item| if item.1 > accum.1 { item } else { accum })?;
+                if prob < self.nms.score_threshold() {
+                    return None;
+                }
+                let (xc, yc) = (row[bbox], row[bbox + 1]);
+                let (half_w, half_h) = (row[bbox + 2] / 2.0, row[bbox + 3] / 2.0);
+                Some((
+                    BBox::new(xc - half_w, yc - half_h, xc + half_w, yc + half_h),
+                    class_id,
+                    prob,
+                    idx,
+                ))
+            })
+            .collect()
+    }
+}
+
+/// 解码后的单个检测结果，坐标为相机原图像素
+#[derive(Debug, Clone)]
+pub struct YoloDetection {
+    pub class_id: usize,
+    pub score: f32,
+    pub bbox: BBox,
+    /// 中心、左上、左下、右下、右上
+    pub keypoints: [RbtImgPoint2; 5],
+}
+
+/// 候选框：(检测框, 类别, 置信度, anchor 序号)
+pub type YoloCandidate = (BBox, usize, f32, usize);
+
+/// 非极大值抑制方式
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum NmsMode {
+    /// 重叠度超过阈值的框直接丢弃
+    #[default]
+    Hard,
+    /// 按重叠度以高斯函数衰减置信度，衰减到阈值以下再丢弃，适合装甲板相互遮挡的场景
+    Soft,
 }
 
-pub fn nms(mut boxes: Vec<(BBox, usize, f32, usize)>) -> Vec<(BBox, usize, f32, usize)> {
-    boxes.sort_by(|box1, box2| box2.2.total_cmp(&box1.2));
-    // 按置信度降序排序
-    let mut result = Vec::new();
-    while !boxes.is_empty() {
-        result.push(boxes[0]); // 选择置信度最高的框
-        boxes = boxes
+/// 按类别进行的非极大值抑制
+///
+/// 只有同一类别（颜色 + 编号）的框会相互抑制，混战中重叠的红蓝装甲板会分别保留
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Nms {
+    score_threshold: f32,
+    iou_threshold: f32,
+    mode: NmsMode,
+    soft_sigma: f32,
+}
+
+impl Nms {
+    pub fn new(score_threshold: f32, iou_threshold: f32) -> Self {
+        Self {
+            score_threshold,
+            iou_threshold,
+            mode: NmsMode::Hard,
+            soft_sigma: 0.5,
+        }
+    }
+
+    /// 改用 soft-NMS，`sigma` 越小衰减越快
+    pub fn with_soft(mut self, sigma: f32) -> Self {
+        self.mode = NmsMode::Soft;
+        self.soft_sigma = sigma;
+        self
+    }
+
+    /// 替换置信度阈值，评估时用较低的阈值得到完整的 PR 曲线
+    pub fn with_score_threshold(mut self, score_threshold: f32) -> Self {
+        self.score_threshold = score_threshold;
+        self
+    }
+
+    pub fn from_cfg(cfg: &DetectorCfg) -> Self {
+        Self {
+            score_threshold: cfg.confidence_threshold,
+            iou_threshold: cfg.nms_iou_threshold,
+            mode: cfg.nms_mode,
+            soft_sigma: cfg.soft_nms_sigma,
+        }
+    }
+
+    pub fn score_threshold(&self) -> f32 {
+        self.score_threshold
+    }
+
+    pub fn iou_threshold(&self) -> f32 {
+        self.iou_threshold
+    }
+
+    pub fn mode(&self) -> NmsMode {
+        self.mode
+    }
+
+    /// 返回保留的框，按置信度降序排列
+    pub fn run(&self, mut boxes: Vec<YoloCandidate>) -> Vec<YoloCandidate> {
+        // 先按置信度筛掉绝大多数背景框，再排序
+        boxes.retain(|b| b.2 >= self.score_threshold);
+        match self.mode {
+            NmsMode::Hard => self.hard(boxes),
+            NmsMode::Soft => self.soft(boxes),
+        }
+    }
+
+    fn hard(&self, mut boxes: Vec<YoloCandidate>) -> Vec<YoloCandidate> {
+        // 按类别分段，段内置信度降序，只在段内两两比较
+        boxes.sort_unstable_by(|box1, box2| box1.1.cmp(&box2.1).then(box2.2.total_cmp(&box1.2)));
+        let mut suppressed = vec![false; boxes.len()];
+        let mut result = Vec::new();
+        let mut start = 0;
+        while start < boxes.len() {
+            let end = start + boxes[start..].partition_point(|b| b.1 == boxes[start].1);
+            for i in start..end {
+                if suppressed[i] {
+                    continue;
+                }
+                result.push(boxes[i]); // 该类别剩余框中置信度最高的
+                for j in i + 1..end {
+                    if !suppressed[j] && iou(&boxes[i].0, &boxes[j].0) > self.iou_threshold {
+                        suppressed[j] = true;
+                    }
+                }
+            }
+            start = end;
+        }
+        result.sort_unstable_by(|box1, box2| box2.2.total_cmp(&box1.2));
+        result
+    }
+
+    fn soft(&self, mut boxes: Vec<YoloCandidate>) -> Vec<YoloCandidate> {
+        let mut result = Vec::new();
+        while let Some(best) = boxes
             .iter()
-            .filter(|box1| (intersection(&boxes[0].0, &box1.0) / union(&boxes[0].0, &box1.0)) < 0.7)
-            .copied()
-            .collect(); // 去除与当前框重叠度较高的框
-    }
-    result
-}
-
-pub struct YoloLabel(ArmorColor, ArmorId);
-
-impl YoloLabel {
-    pub fn id(&self) -> &ArmorId {
-        &self.1
-    }
-    pub fn color(&self) -> &ArmorColor {
-        &self.0
-    }
-}
-
-pub const YOLO_LABEL_TABLE: [ArmorLabel; 36] = [
-    YoloLabel(ArmorColor::B, ArmorId::Invalid),
-    YoloLabel(ArmorColor::B, ArmorId::Hero1),
-    YoloLabel(ArmorColor::B, ArmorId::Engineer2),
-    YoloLabel(ArmorColor::B, ArmorId::Infantry3),
-    YoloLabel(ArmorColor::B, ArmorId::Infantry4),
-    YoloLabel(ArmorColor::B, ArmorId::Sentry7),
-    YoloLabel(ArmorColor::B, ArmorId::Outpost8),
-    YoloLabel(ArmorColor::B, ArmorId::Invalid),
-    YoloLabel(ArmorColor::B, ArmorId::Invalid),
-    YoloLabel(ArmorColor::B, ArmorId::Invalid),
-    YoloLabel(ArmorColor::B, ArmorId::Invalid),
-    YoloLabel(ArmorColor::B, ArmorId::Invalid),
-    YoloLabel(ArmorColor::B, ArmorId::Invalid),
-    YoloLabel(ArmorColor::B, ArmorId::Invalid),
-    YoloLabel(ArmorColor::B, ArmorId::Invalid),
-    YoloLabel(ArmorColor::B, ArmorId::Invalid),
-    YoloLabel(ArmorColor::B, ArmorId::Invalid),
-    YoloLabel(ArmorColor::B, ArmorId::Invalid),
-    YoloLabel(ArmorColor::R, ArmorId::Invalid),
-    YoloLabel(ArmorColor::R, ArmorId::Hero1),
-    YoloLabel(ArmorColor::R, ArmorId::Engineer2),
-    YoloLabel(ArmorColor::R, ArmorId::Infantry3),
-    YoloLabel(ArmorColor::R, ArmorId::Infantry4),
-    YoloLabel(ArmorColor::R, ArmorId::Sentry7),
-    YoloLabel(ArmorColor::R, ArmorId::Outpost8),
-    YoloLabel(ArmorColor::R, ArmorId::Invalid),
-    YoloLabel(ArmorColor::R, ArmorId::Invalid),
-    YoloLabel(ArmorColor::R, ArmorId::Invalid),
-    YoloLabel(ArmorColor::R, ArmorId::Invalid),
-    YoloLabel(ArmorColor::R, ArmorId::Invalid),
-    YoloLabel(ArmorColor::R, ArmorId::Invalid),
-    YoloLabel(ArmorColor::R, ArmorId::Invalid),
-    YoloLabel(ArmorColor::R, ArmorId::Invalid),
-    YoloLabel(ArmorColor::R, ArmorId::Invalid),
-    YoloLabel(ArmorColor::R, ArmorId::Invalid),
-    YoloLabel(ArmorColor::R, ArmorId::Invalid),
-];
+            .enumerate()
+            .max_by(|(_, box1), (_, box2)| box1.2.total_cmp(&box2.2))
+            .map(|(idx, _)| idx)
+        {
+            let best = boxes.swap_remove(best);
+            if best.2 < self.score_threshold {
+                break; // 剩余的框都已衰减到阈值以下
+            }
+            for other in boxes.iter_mut().filter(|other| other.1 == best.1) {
+                let overlap = iou(&best.0, &other.0);
+                other.2 *= (-overlap * overlap / self.soft_sigma).exp();
+            }
+            result.push(best);
+        }
+        result
+    }
+}
 
 /// BoundingBox yolo模型候选框
 /// 因为目前跟神经网络交互的部分暂时还是 f32，所以暂时没有提供泛型实现
@@ -124,10 +513,10 @@ impl BBox {
     }
 }
 
-/// 计算 BBox 的交集
+/// 计算 BBox 的交集，不相交时为 0
 pub fn intersection(box1: &BBox, box2: &BBox) -> f32 {
-    (box1.x2().min(box2.x2()) - box1.x1().max(box2.x1()))
-        * (box1.y2().min(box2.y2()) - box1.y1().max(box2.y1()))
+    (box1.x2().min(box2.x2()) - box1.x1().max(box2.x1())).max(0.0)
+        * (box1.y2().min(box2.y2()) - box1.y1().max(box2.y1())).max(0.0)
 }
 
 /// 计算 BBox 的并集
@@ -136,3 +525,309 @@ pub fn union(box1: &BBox, box2: &BBox) -> f32 {
         + ((box2.x2() - box2.x1()) * (box2.y2() - box2.y1()))
         - intersection(box1, box2)
 }
+
+/// 计算 BBox 的交并比
+pub fn iou(box1: &BBox, box2: &BBox) -> f32 {
+    let union = union(box1, box2);
+    if union > 0.0 {
+        intersection(box1, box2) / union
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rbt_mod::rbt_detector::rbt_frame::{RbtFrame, RbtFrameStage};
+    use crate::rbt_mod::rbt_detector::rbt_label::tests::armor_labels;
+    use image::{DynamicImage, Rgb, RgbImage};
+
+    #[test]
+    fn test_letterbox_camera_to_model() {
+        // 1280x720 → 640x384，与原先写死的 640x360 + 上下各 12 像素一致
+        let lb = Letterbox::new((1280, 720), (640, 384));
+        assert_eq!(lb.scale(), 0.5);
+        assert_eq!(lb.resized(), (640, 360));
+        assert_eq!(lb.pad(), (0, 12));
+        assert_eq!(lb.to_input(1280.0, 720.0), (640.0, 372.0));
+        assert_eq!(lb.to_source(320.0, 192.0), (640.0, 360.0));
+
+        // 竖直方向更长的图像在左右填充
+        let lb = Letterbox::new((600, 800), (640, 384));
+        assert_eq!(lb.resized(), (288, 384));
+        assert_eq!(lb.pad(), (176, 0));
+        for (x, y) in [(0.0, 0.0), (123.4, 567.8), (600.0, 800.0)] {
+            let (ix, iy) = lb.to_input(x, y);
+            let (sx, sy) = lb.to_source(ix, iy);
+            assert!((sx - x).abs() < 1e-3 && (sy - y).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn test_letterbox_into_frame() {
+        // 帧的输入张量按配置尺寸分配，非 640x384 的输入同样可以直接写入
+        let mut frame = RbtFrame::new(416, 416);
+        frame.set_state(RbtFrameStage::Pre);
+        let img = DynamicImage::ImageRgb8(RgbImage::from_pixel(1280, 720, Rgb([10, 20, 30])));
+        let lb = letterbox(&mut frame.pre_data(), &img);
+        assert_eq!(frame.pre_data().dim(), (1, 3, 416, 416));
+        assert_eq!(lb.pad(), (0, 91));
+        assert_eq!(frame.pre_data()[[0, 2, 208, 208]], 30.0);
+        assert_eq!(frame.pre_data()[[0, 0, 0, 0]], GRAY);
+    }
+
+    #[test]
+    fn test_letterbox_fill() {
+        // 左半红右半蓝的 8x4 图像，letterbox 到 4x4 后上下各填充 1 行
+        let img = DynamicImage::ImageRgb8(RgbImage::from_fn(8, 4, |x, _| {
+            if x < 4 {
+                Rgb([255, 0, 0])
+            } else {
+                Rgb([0, 0, 255])
+            }
+        }));
+        let mut input = nd::Array4::zeros((1, 3, 4, 4));
+        let lb = letterbox(&mut input, &img);
+        assert_eq!(lb.pad(), (0, 1));
+        for x in 0..4 {
+            for c in 0..3 {
+                assert_eq!(input[[0, c, 0, x]], GRAY);
+                assert_eq!(input[[0, c, 3, x]], GRAY);
+            }
+        }
+        assert_eq!(input[[0, 0, 1, 0]], 255.0);
+        assert_eq!(input[[0, 2, 1, 0]], 0.0);
+        assert_eq!(input[[0, 0, 2, 3]], 0.0);
+        assert_eq!(input[[0, 2, 2, 3]], 255.0);
+
+        // 检测框映射回原图
+        let bbox = lb.bbox_to_source(&BBox::new(0.0, 1.0, 2.0, 3.0));
+        assert_eq!(
+            (bbox.x1(), bbox.y1(), bbox.x2(), bbox.y2()),
+            (0.0, 0.0, 4.0, 4.0)
+        );
+        let point = lb.point_to_source(4.0, 3.0);
+        assert_eq!((point.x, point.y), (8.0, 4.0));
+    }
+
+    /// 读取稀疏存储的 output0 布局数据
+    fn load_raw_output(path: &str) -> nd::Array2<f32> {
+        let layout = YoloLayout::ARMOR;
+        let mut output = nd::Array2::zeros((layout.anchor_count, layout.row_len()));
+        let text = std::fs::read_to_string(path).unwrap();
+        for line in text
+            .lines()
+            .filter(|l| !l.starts_with('#') && !l.is_empty())
+        {
+            let (anchor, values) = line.split_once(':').unwrap();
+            let values = values
+                .split_whitespace()
+                .map(|v| v.parse::<f32>().unwrap())
+                .collect::<Vec<_>>();
+            output
+                .row_mut(anchor.parse().unwrap())
+                .assign(&nd::ArrayView1::from(&values));
+        }
+        output
+    }
+
+    /// 手工构造的合成输出，包含重复框、低置信度和己方装甲板等情形，并非模型真实推理结果
+    fn synthetic_output() -> nd::Array2<f32> {
+        load_raw_output(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/testdata/yolo_armor_output0_synthetic.txt"
+        ))
+    }
+
+    fn points(armor: &DetectedArmor) -> Vec<(f64, f64)> {
+        [
+            armor.center(),
+            armor.lt(),
+            armor.lb(),
+            armor.rb(),
+            armor.rt(),
+        ]
+        .iter()
+        .map(|p| (p.x, p.y))
+        .collect()
+    }
+
+    #[test]
+    fn test_armor_layout() {
+        let layout = YoloLayout::ARMOR;
+        assert_eq!(layout.row_len(), 48);
+        assert_eq!(layout.class_count(), armor_labels().len());
+        assert_eq!(layout.keypoint_offsets, [0, 40, 42, 44, 46]);
+        assert!(layout.check_output_shape(&[1, 48, 5040]).is_ok());
+        assert!(layout.check_output_shape(&[-1, 48, -1]).is_ok());
+        assert!(matches!(
+            layout.check_output_shape(&[1, 52, 5040]),
+            Err(RbtError::OutputShapeMismatch {
+                expected: (5040, 48),
+                actual: (5040, 52)
+            })
+        ));
+        assert!(layout.check_output_shape(&[48, 5040]).is_err());
+        // 三个检测头的步长分别为 8、16、32
+        assert_eq!(
+            layout.anchor_count,
+            [8, 16, 32]
+                .iter()
+                .map(|s| (640 / s) * (384 / s))
+                .sum::<usize>()
+        );
+        // 其他输入尺寸，例如常见的 416x416
+        assert_eq!(
+            YoloLayout::anchor_count((416, 416)),
+            52 * 52 + 26 * 26 + 13 * 13
+        );
+        let layout = YoloLayout::armor(36, (416, 416));
+        assert!(layout.check_output_shape(&[1, 48, 3549]).is_ok());
+        assert!(layout.check_output_shape(&[1, 48, 5040]).is_err());
+    }
+
+    #[test]
+    fn test_decode_synthetic_output() {
+        let decoder =
+            YoloDecoder::new(YoloLayout::ARMOR, armor_labels(), Nms::new(0.8, 0.7)).unwrap();
+        let lb = Letterbox::new((1280, 720), (640, 384));
+        let output = synthetic_output();
+
+        // 己方红色：丢弃置信度 0.55 的哨兵、被抑制的重复步兵和红色英雄
+        let armors = decoder
+            .decode(output.view(), &lb, Some(&ArmorColor::R))
+            .unwrap();
+        assert_eq!(armors.len(), 2);
+        let infantry = &armors[&ArmorId::Infantry3];
+        assert_eq!(infantry.len(), 1);
+        assert_eq!(
+            points(&infantry[0]),
+            [
+                (400.0, 276.0),
+                (344.0, 256.0),
+                (344.0, 296.0),
+                (456.0, 296.0),
+                (456.0, 256.0)
+            ]
+        );
+        let hero = &armors[&ArmorId::Hero1];
+        assert_eq!(hero.len(), 1);
+        assert_eq!(
+            points(&hero[0]),
+            [
+                (1040.0, 576.0),
+                (970.0, 556.0),
+                (970.0, 596.0),
+                (1110.0, 596.0),
+                (1110.0, 556.0)
+            ]
+        );
+
+        // 不过滤阵营时红色英雄与蓝色英雄归为同一组
+        let armors = decoder.decode(output.view(), &lb, None).unwrap();
+        assert_eq!(armors[&ArmorId::Hero1].len(), 2);
+        assert_eq!(armors.values().map(Vec::len).sum::<usize>(), 3);
+    }
+
+    #[test]
+    fn test_detections_keep_scores() {
+        let lb = Letterbox::new((1280, 720), (640, 384));
+        let output = synthetic_output();
+        let decoder =
+            YoloDecoder::new(YoloLayout::ARMOR, armor_labels(), Nms::new(0.8, 0.7)).unwrap();
+        let detections = decoder.detections(output.view(), &lb).unwrap();
+        assert_eq!(detections.len(), 3);
+        assert!(detections.windows(2).all(|w| w[0].score >= w[1].score));
+        // 检测框与关键点一样还原到原图，中心点位于检测框中心
+        for detection in &detections {
+            let center = detection.keypoints[0];
+            let bbox = detection.bbox;
+            assert!((center.x as f32 - (bbox.x1() + bbox.x2()) / 2.0).abs() < 1e-3);
+            assert!((center.y as f32 - (bbox.y1() + bbox.y2()) / 2.0).abs() < 1e-3);
+        }
+
+        // 降低阈值后低置信度的哨兵也被保留
+        let mut decoder = decoder;
+        decoder.set_nms(Nms::new(0.8, 0.7).with_score_threshold(0.5));
+        let detections = decoder.detections(output.view(), &lb).unwrap();
+        assert_eq!(detections.len(), 4);
+        assert!(detections.iter().any(|d| (d.score - 0.55).abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_decode_shape_mismatch() {
+        let decoder =
+            YoloDecoder::new(YoloLayout::ARMOR, armor_labels(), Nms::new(0.8, 0.7)).unwrap();
+        let output = nd::Array2::<f32>::zeros((8400, 48));
+        assert!(matches!(
+            decoder.decode(output.view(), &Letterbox::default(), None),
+            Err(RbtError::OutputShapeMismatch {
+                expected: (5040, 48),
+                actual: (8400, 48)
+            })
+        ));
+    }
+
+    #[test]
+    fn test_nms_class_aware() {
+        // 蓝 3 号与红 3 号重叠，另有一个与蓝 3 号高度重叠的重复框和一个低分框
+        let boxes = vec![
+            (BBox::new(0.0, 0.0, 10.0, 10.0), 3, 0.95, 0),
+            (BBox::new(1.0, 0.0, 11.0, 10.0), 3, 0.90, 1),
+            (BBox::new(1.0, 1.0, 11.0, 11.0), 21, 0.85, 2),
+            (BBox::new(50.0, 50.0, 60.0, 60.0), 3, 0.5, 3),
+        ];
+        let kept = Nms::new(0.8, 0.7).run(boxes);
+        assert_eq!(kept.iter().map(|b| b.3).collect::<Vec<_>>(), [0, 2]);
+    }
+
+    #[test]
+    fn test_soft_nms() {
+        // IoU = 0.8 的同类框：hard 直接丢弃，soft 按 exp(-0.64 / sigma) 衰减
+        let boxes = vec![
+            (BBox::new(0.0, 0.0, 10.0, 10.0), 3, 0.95, 0),
+            (BBox::new(0.0, 0.0, 10.0, 8.0), 3, 0.9, 1),
+        ];
+        assert!((iou(&boxes[0].0, &boxes[1].0) - 0.8).abs() < 1e-6);
+        assert_eq!(Nms::new(0.3, 0.7).run(boxes.clone()).len(), 1);
+
+        let kept = Nms::new(0.3, 0.7).with_soft(2.0).run(boxes.clone());
+        assert_eq!(kept.len(), 2);
+        assert!((kept[1].2 - 0.9 * (-0.32f32).exp()).abs() < 1e-6);
+
+        // 衰减到阈值以下后丢弃
+        assert_eq!(Nms::new(0.3, 0.7).with_soft(0.2).run(boxes).len(), 1);
+    }
+
+    #[test]
+    fn test_decode_custom_labels() {
+        // 重新训练的 2 类模型：红 5 号步兵和蓝方基地
+        let labels = YoloLabelTable::from_toml_str(
+            r#"labels = [{ color = "R", id = "Infantry5" }, { color = "B", id = "Base" }]"#,
+        )
+        .unwrap();
+        assert!(YoloDecoder::new(YoloLayout::ARMOR, labels.clone(), Nms::new(0.8, 0.7)).is_err());
+
+        let layout = YoloLayout::armor(labels.len(), (640, 384));
+        assert_eq!(layout.row_len(), 14);
+        let decoder = YoloDecoder::new(layout, labels, Nms::new(0.8, 0.7)).unwrap();
+        let mut output = nd::Array2::zeros((5040, 14));
+        output.row_mut(7).assign(&nd::arr1(&[
+            100.0, 50.0, 20.0, 10.0, 0.1, 0.9, 90.0, 45.0, 90.0, 55.0, 110.0, 55.0, 110.0, 45.0,
+        ]));
+        let armors = decoder
+            .decode(output.view(), &Letterbox::default(), Some(&ArmorColor::R))
+            .unwrap();
+        assert_eq!(
+            points(&armors[&ArmorId::Base][0]),
+            [
+                (100.0, 50.0),
+                (90.0, 45.0),
+                (90.0, 55.0),
+                (110.0, 55.0),
+                (110.0, 45.0)
+            ]
+        );
+    }
+}
@@ -19,7 +19,7 @@ use crate::rbt_base::rbt_algorithm::rbt_eskf::{ESKF, StrategyDynamicModel};
 use crate::rbt_base::rbt_geometry::rbt_cylindrical2::RbtCylindricalPoint2;
 use crate::rbt_infra::rbt_cfg::EstimatorCfg;
 use crate::rbt_mod::rbt_armor::tracked_armor::TrackedArmor;
-use crate::rbt_mod::rbt_solver::{RbtSolvedResult, RbtSolvedResults};
+use crate::rbt_mod::rbt_solver::{EnemyPrior, RbtSolvedResult, RbtSolvedResults};
 
 use rbt_enemy_dynamic_model::{
     Enemy, EnemyESKFState, EnemyId, EnemyModel, armor_switch_decision, handle_switch,
@@ -155,6 +155,15 @@ impl RbtEstimator {
         }
     }
 
+    pub fn state(&self) -> &EstimatorStateMachine {
+        &self.state
+    }
+
+    /// 当前跟踪的敌方单位状态，未跟踪时为 None
+    pub fn nominal_state(&self) -> Option<&EnemyESKFState> {
+        self.tracked_enemy.as_ref().map(|enemy| &enemy.nominal_state)
+    }
+
     pub fn update(
         &mut self,
         cfg: &EstimatorCfg,
@@ -268,8 +277,8 @@ impl RbtEstimator {
                     }
 
                     self.eskf.predict(&self.enemy_model, nominal_state, &input, &self.state);
-                    // 恢复状态增加过程噪声加快收敛
-                    self.eskf.set_r(na::SMatrix::<f64, 4, 4>::identity() * 0.5);
+                    // 中心测量噪声取自解算协方差，其余分量沿用加大的噪声
+                    self.eskf.set_r(measurement_noise(solved_enemy, 0.5));
                     self.eskf.predict(&self.enemy_model, nominal_state, &input, &self.state);
                     self.eskf.update(&self.enemy_model, nominal_state, &measurement, &self.state);
                     self.eskf.set_r(na::SMatrix::<f64, 4, 4>::identity() * 0.1); // 恢复默认噪声
@@ -295,8 +304,8 @@ impl RbtEstimator {
                     let measurement = enemy.get_eskf_measurement();
                     let nominal_state = enemy.get_mut_nominal_state();
 
-                    // 增加过程噪声以加快收敛
-                    self.eskf.set_r(na::SMatrix::<f64, 4, 4>::identity() * 0.5);
+                    // 增加过程噪声以加快收敛，中心测量噪声取自解算协方差
+                    self.eskf.set_r(measurement_noise(solved_enemy, 0.5));
                     self.eskf.predict(&self.enemy_model, nominal_state, &input, &self.state);
                     self.eskf
                         .update(&self.enemy_model, nominal_state, &measurement, &self.state);
@@ -307,3 +316,144 @@ impl RbtEstimator {
         }
     }
 }
+
+/// 构造测量噪声 R：(theta_d, rho) 块使用解算给出的协方差，armor_yaw 与 armor_height 取 `default`
+///
+/// 协方差含非有限值或对角元非正时整体退回 `default` 对角阵
+fn measurement_noise(solved_enemy: &RbtSolvedResult, default: f64) -> na::SMatrix<f64, 4, 4> {
+    let mut r = na::SMatrix::<f64, 4, 4>::identity() * default;
+    let center_cov = solved_enemy.measurement_covariance();
+    if center_cov.iter().all(|v| v.is_finite())
+        && center_cov[(0, 0)] > 0.0
+        && center_cov[(1, 1)] > 0.0
+    {
+        r.fixed_view_mut::<2, 2>(0, 0).copy_from(&center_cov);
+    }
+    r
+}
+
+/// 选中的击打目标，供控制线程预测使用
+#[derive(Debug, Clone)]
+pub struct AimTarget {
+    pub enemy_id: EnemyId,
+    pub state: EnemyESKFState,
+    pub fire: bool,
+    /// 状态对应的时刻
+    pub time_stamp: tokio::time::Instant,
+}
+
+/// 所有敌方单位估计器的管理池
+///
+/// 与 `RbtSolvedResults` 一样为每个兵种维护一个估计器，需要在视觉线程中长期持有
+#[derive(Debug, Clone)]
+pub struct RbtHandlerPoll {
+    inner: HashMap<EnemyId, RbtEstimator>,
+    time_stamp: Option<tokio::time::Instant>,
+}
+
+impl Default for RbtHandlerPoll {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RbtHandlerPoll {
+    pub fn new() -> Self {
+        let inner = RbtSolvedResults::default()
+            .keys()
+            .map(|enemy_id| (*enemy_id, RbtEstimator::new(*enemy_id)))
+            .collect();
+        Self {
+            inner,
+            time_stamp: None,
+        }
+    }
+
+    /// 用一帧的解算结果更新所有估计器，`time_stamp` 为该帧的时刻
+    pub fn update(
+        &mut self,
+        cfg: &EstimatorCfg,
+        enemys: &RbtSolvedResults,
+        time_stamp: tokio::time::Instant,
+    ) {
+        for (enemy_id, estimator) in self.inner.iter_mut() {
+            let solved_enemy = enemys.get(enemy_id).cloned().flatten();
+            estimator.update(cfg, &solved_enemy);
+        }
+        self.time_stamp = Some(time_stamp);
+    }
+
+    /// 各估计器当前的装甲板半径和跟踪装甲板 yaw，作为后续帧解算的先验
+    pub fn enemy_priors(&self) -> HashMap<EnemyId, EnemyPrior> {
+        let Some(time_stamp) = self.time_stamp else {
+            return HashMap::new();
+        };
+        self.inner
+            .iter()
+            .filter_map(|(enemy_id, estimator)| {
+                estimator.nominal_state().map(|state| {
+                    let prior = EnemyPrior {
+                        radius: state.armor_r,
+                        armor_yaw: state.armor_yaw,
+                        v_spin: state.v_spin,
+                        time_stamp,
+                    };
+                    (*enemy_id, prior)
+                })
+            })
+            .collect()
+    }
+
+    /// 选出处于跟踪状态且距离最近的目标
+    pub fn aim_target(&self) -> Option<AimTarget> {
+        let time_stamp = self.time_stamp?;
+        self.inner
+            .values()
+            .filter(|estimator| matches!(estimator.state(), EstimatorStateMachine::Track { .. }))
+            .filter_map(|estimator| {
+                estimator.nominal_state().map(|state| AimTarget {
+                    enemy_id: estimator.enemy_id,
+                    state: state.clone(),
+                    fire: estimator.fire,
+                    time_stamp,
+                })
+            })
+            .min_by(|a, b| a.state.distance.total_cmp(&b.state.distance))
+    }
+}
+
+impl Deref for RbtHandlerPoll {
+    type Target = HashMap<EnemyId, RbtEstimator>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_measurement_noise() {
+        let mut solved = RbtSolvedResult {
+            coord: RbtCylindricalPoint2::from_xy(na::Point2::new(0.0, 2000.0)),
+            radius: 250.0,
+            covariance: na::Matrix3::from_diagonal(&na::Vector3::new(400.0, 100.0, 2500.0)),
+            armors: vec![],
+        };
+        let r = measurement_noise(&solved, 0.5);
+        let center_cov = solved.measurement_covariance();
+        assert_eq!(r.fixed_view::<2, 2>(0, 0), center_cov);
+        assert_eq!(r[(2, 2)], 0.5);
+        assert_eq!(r[(3, 3)], 0.5);
+        assert_eq!(r[(0, 2)], 0.0);
+
+        // 退化的协方差不进入滤波器
+        solved.covariance[(0, 0)] = f64::NAN;
+        assert_eq!(
+            measurement_noise(&solved, 0.5),
+            na::SMatrix::<f64, 4, 4>::identity() * 0.5
+        );
+    }
+}
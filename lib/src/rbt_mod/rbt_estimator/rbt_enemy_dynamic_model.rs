@@ -15,9 +15,10 @@ use crate::rbt_base::rbt_algorithm::rbt_eskf::StrategyDynamicModel;
 use crate::rbt_base::rbt_geometry::rbt_cylindrical2::RbtCylindricalPoint2;
 use crate::rbt_mod::rbt_estimator::EstimatorStateMachine;
 use crate::rbt_mod::rbt_solver::{RbtSolvedResult, RbtSolvedResults, RbtSolver};
+use serde::Deserialize;
 use std::fmt::Display;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
 /// 描述敌方装甲板大或者小
 pub enum EnemyArmorType {
     Small,
@@ -27,26 +28,30 @@ pub enum EnemyArmorType {
 impl EnemyArmorType {
     pub fn from_enemy_id(enemy_id: &EnemyId) -> Self {
         match enemy_id {
-            EnemyId::Hero1 => EnemyArmorType::Large,
+            EnemyId::Hero1 | EnemyId::Base => EnemyArmorType::Large,
             _ => EnemyArmorType::Small,
         }
     }
 }
 
 /// 用于描述装甲板/敌方车辆的唯一标记型 ID
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, strum::Display)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, strum::Display, Deserialize)]
 pub enum EnemyId {
     Hero1,
     Engineer2,
     Infantry3,
     Infantry4,
+    Infantry5,
     Sentry7,
     Outpost8,
+    Base,
+    /// 编号未识别的敌方单位，灯条检测未接数字识别器时使用，独占一个估计器
+    Unknown,
     Invalid,
 }
 
 /// 描述敌方阵营
-#[derive(PartialEq, Eq, strum::Display)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, strum::Display, Deserialize)]
 pub enum EnemyFaction {
     R,
     B,
@@ -76,6 +81,14 @@ impl EnemyArmorLayout {
         EnemyArmorLayout::Symmetric4([rh; 4])
     }
 
+    /// 相邻装甲板的夹角 deg
+    pub fn armor_interval(&self) -> f64 {
+        match self {
+            EnemyArmorLayout::Symmetric4(_) => 90.0,
+            EnemyArmorLayout::Tripod3(_) => 120.0,
+        }
+    }
+
     pub fn from_enemy_id(enemy_id: &EnemyId) -> Self {
         match enemy_id {
             EnemyId::Outpost8 => EnemyArmorLayout::new_3(ArmorRH {
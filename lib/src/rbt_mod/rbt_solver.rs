@@ -1,18 +1,22 @@
 use crate::rbt_base::rbt_algorithm::rbt_ippe::ArmorPnpSolver;
+use crate::rbt_base::rbt_algorithm::rbt_pose_refine::PoseRefiner;
 use crate::rbt_base::rbt_geometry::{
     rbt_cylindrical2::RbtCylindricalPoint2,
-    rbt_line2::{RbtLine2, find_intersection},
+    rbt_line2::RbtLine2,
     rbt_pose3::{RbtPose3, RbtPoseCoordSys},
+    rbt_tf::{RbtTf, RbtTfFrame},
 };
-use crate::rbt_infra::rbt_cfg;
+use crate::rbt_infra::rbt_cfg::{CamCfg, PoseRefineCfg, SolverCfg};
 use crate::rbt_infra::rbt_err::{RbtError, RbtResult};
+use crate::rbt_mod::rbt_armor::ArmorType;
 use crate::rbt_mod::rbt_armor::detected_armor::DetectedArmor;
 use crate::rbt_mod::rbt_armor::solved_armor::SolvedArmor;
-use crate::rbt_mod::rbt_estimator::rbt_enemy_dynamic_model::EnemyId;
+use crate::rbt_mod::rbt_estimator::rbt_enemy_dynamic_model::{EnemyArmorLayout, EnemyId};
 use nalgebra::Vector2;
 use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
-use tracing::{debug, error, info, warn};
+use tokio::time::Instant;
+use tracing::{debug, info, warn};
 
 #[derive(Debug, Clone)]
 pub struct RbtSolver {
@@ -29,9 +33,29 @@ impl RbtSolver {
 #[derive(Debug, Clone)]
 pub struct RbtSolvedResult {
     pub coord: RbtCylindricalPoint2,
+    /// 联合解算得到的装甲板半径 mm
+    pub radius: f64,
+    /// 中心和半径 (x, y, r) 的协方差 mm²
+    pub covariance: na::Matrix3<f64>,
     pub armors: Vec<SolvedArmor>,
 }
 
+impl RbtSolvedResult {
+    /// 中心在 ESKF 测量量 (theta_d, rho) 下的协方差，由 (x, y) 协方差经一阶传播得到
+    pub fn measurement_covariance(&self) -> na::Matrix2<f64> {
+        let rho = self.coord.rho.max(f64::EPSILON);
+        let theta = self.coord.theta_d.to_radians();
+        let (sin, cos) = theta.sin_cos();
+        let jacobian = na::Matrix2::new(
+            -sin / rho * 180.0 / std::f64::consts::PI,
+            cos / rho * 180.0 / std::f64::consts::PI,
+            cos,
+            sin,
+        );
+        jacobian * self.covariance.fixed_view::<2, 2>(0, 0) * jacobian.transpose()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct RbtSolvedResults {
     inner: HashMap<EnemyId, Option<RbtSolvedResult>>,
@@ -39,13 +63,16 @@ pub struct RbtSolvedResults {
 
 impl Default for RbtSolvedResults {
     fn default() -> Self {
-        let mut result = HashMap::with_capacity(6);
+        let mut result = HashMap::with_capacity(8);
         result.insert(EnemyId::Hero1, None);
         result.insert(EnemyId::Engineer2, None);
         result.insert(EnemyId::Infantry3, None);
         result.insert(EnemyId::Infantry4, None);
+        result.insert(EnemyId::Infantry5, None);
         result.insert(EnemyId::Sentry7, None);
         result.insert(EnemyId::Outpost8, None);
+        result.insert(EnemyId::Base, None);
+        result.insert(EnemyId::Unknown, None);
         RbtSolvedResults { inner: result }
     }
 }
@@ -64,6 +91,35 @@ impl DerefMut for RbtSolvedResults {
     }
 }
 
+/// 估计器对单个敌方单位的先验，供后续帧的解算使用
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnemyPrior {
+    /// 装甲板半径 mm，作为中心解算的半径先验
+    pub radius: f64,
+    /// 跟踪装甲板的 yaw 角 deg，同 `EnemyESKFState::armor_yaw`，为装甲板指向车体中心的方向
+    pub armor_yaw: f64,
+    /// 陀螺角速度 deg/s
+    pub v_spin: f64,
+    /// 先验对应的时刻
+    pub time_stamp: Instant,
+}
+
+impl EnemyPrior {
+    /// 按陀螺角速度外推到 `time` 时刻
+    pub fn predict(&self, time: Instant) -> Self {
+        let dt = if time >= self.time_stamp {
+            (time - self.time_stamp).as_secs_f64()
+        } else {
+            -(self.time_stamp - time).as_secs_f64()
+        };
+        Self {
+            armor_yaw: self.armor_yaw + self.v_spin * dt,
+            time_stamp: time,
+            ..*self
+        }
+    }
+}
+
 pub enum DetectedEnemyArmor {
     One {
         armor: DetectedArmor,
@@ -75,13 +131,24 @@ pub enum DetectedEnemyArmor {
 }
 
 /// enemys_solver全流程
+///
+/// 检测到的角点按 `cam_cfg` 中的镜头畸变在 PnP 前去畸变；
+/// `priors` 为估计器外推到本帧的各兵种先验，缺省时半径使用 `cfg.default_armor_radius`
 pub fn enemys_solver(
     detector_result: HashMap<EnemyId, Vec<DetectedArmor>>,
-    cam_k: &na::Matrix3<f64>,
+    cam_cfg: &CamCfg,
+    tf: &RbtTf,
+    cfg: &SolverCfg,
+    refine_cfg: &PoseRefineCfg,
+    priors: &HashMap<EnemyId, EnemyPrior>,
     rec: &rr::RecordingStream,
 ) -> RbtResult<RbtSolvedResults> {
     // 0. 构建全单位解算结果，内部是一个HashMap
     let mut enemys = RbtSolvedResults::default();
+    let cam_k = &cam_cfg.cam_k();
+    // 位姿优化使用当前云台姿态下相机坐标系中的竖直方向
+    let up = tf.lookup(RbtTfFrame::World, RbtTfFrame::Camera).rotation * na::Vector3::z_axis();
+    let refiner = PoseRefiner::new(refine_cfg.clone()).with_gravity_up(up);
 
     // 1. 遍历消耗所有检测到的同 id 的装甲板集合，进行敌方单位求解
     for (enemy_id, enemy_armors) in detector_result.into_iter() {
@@ -95,44 +162,84 @@ pub fn enemys_solver(
         let mut enemy_solved_armors = Vec::with_capacity(detected_enemy_armors_num);
         for armor in enemy_armors.into_iter() {
             let armor_key_points_na = armor.corner_points().map(|p| p.into());
-            let pnp_solver = ArmorPnpSolver::new().ok_or(RbtError::StringError(
+            let pnp_solver = match armor.armor_type() {
+                ArmorType::Small => ArmorPnpSolver::new(),
+                ArmorType::Large => ArmorPnpSolver::large(),
+            }
+            .ok_or(RbtError::StringError(
                 "Failed to create ArmorPnpSolver Instant".to_string(),
-            ))?;
-            if let Some(camera_pose) = pnp_solver.solve(&armor_key_points_na, &cam_k) {
-                let solved_armor = SolvedArmor::new(armor, camera_pose, 0.0, 0.0, 0.0);
+            ))?
+            .with_distortion(*cam_cfg.distortion());
+            // 位姿优化使用针孔模型，输入去畸变后的角点
+            let ideal_key_points = pnp_solver.undistort(&armor_key_points_na, cam_k);
+            // IPPE 的两个候选解都保留，优化开启时分别优化
+            let refine = |pose: na::Isometry3<f64>| {
+                if !refine_cfg.enable {
+                    return pose;
+                }
+                let object_points = pnp_solver.object_points();
+                match refiner.refine(&pose, &object_points, &ideal_key_points, cam_k) {
+                    Some((refined, report)) => {
+                        debug!("PnP 位姿优化: {:?}", report);
+                        refined
+                    }
+                    None => pose,
+                }
+            };
+            if let Some(solutions) = pnp_solver.solve_candidates(&armor_key_points_na, cam_k) {
+                let mut solved_armor =
+                    SolvedArmor::new(armor, refine(solutions.best.pose), 0.0, 0.0, 0.0);
+                if let Some(alternative) = solutions.alternative {
+                    solved_armor.set_alternative(refine(alternative.pose), solutions.ambiguity());
+                }
                 enemy_solved_armors.push(solved_armor);
             } else {
-                error!("❌ PnP solving failed!");
-                todo!("Solve失败应该后续使用ESKF纯预测进行一次更新");
+                // 退化的角点（例如灯条检测给出的畸形四边形）会让 PnP 失败，跳过这块装甲板
+                warn!("{:?} 装甲板 PnP 解算失败，跳过", enemy_id);
+                continue;
             };
         }
-
-        // 1.3 将 pnp 结果转换为机体坐标系
-        for solved_armor in enemy_solved_armors.iter_mut() {
-            solved_armor
-                .pose_mut()
-                .coord_trans_mut(RbtPoseCoordSys::BaseXyz);
+        if enemy_solved_armors.is_empty() {
+            continue; // 所有装甲板都解算失败，该单位本帧无测量，估计器按纯预测处理
+        }
+        // 编号未识别的装甲板可能来自不同车辆，不能联合解算，只保留最靠近光轴的一块，
+        // 云台跟随目标后同一块装甲板会一直留在画面中央，跟踪保持稳定
+        if enemy_id == EnemyId::Unknown {
+            enemy_solved_armors = enemy_solved_armors
+                .into_iter()
+                .min_by(|a, b| off_axis_angle(a).total_cmp(&off_axis_angle(b)))
+                .into_iter()
+                .collect();
         }
 
-        // 1.4 根据装甲板的连线计算敌人中心坐标
-        let armors_line_2d = enemy_solved_armors
+        // 1.3 按相机距离给出每块装甲板的位置测量标准差，再将 pnp 结果转换为世界坐标系，不随己方云台转动
+        let armors_std = enemy_solved_armors
             .iter()
             .map(|solved_armor| {
-                let armor_pose = solved_armor.pose();
-                let [armor_x, armor_y] = [armor_pose.translation.x, armor_pose.translation.y];
-                let rot_mat = armor_pose.rotation.to_rotation_matrix().matrix().clone();
-                let [armor_2d_pose_a, armor_2d_pose_b] = [rot_mat.m13, rot_mat.m23];
-                RbtLine2 {
-                    point: na::Point2::new(armor_x, armor_y),
-                    direction: na::Vector2::new(armor_2d_pose_a, armor_2d_pose_b),
-                }
+                let distance_m = solved_armor.pose().translation.vector.norm() / 1000.0;
+                cfg.armor_position_std * distance_m.max(1.0)
             })
-            .collect::<Vec<RbtLine2>>();
-        let enemy_center_xy = solve_enemy_center(&armors_line_2d)
-            .ok_or(RbtError::StringError("Failed to solve enemy center".into()))?;
+            .collect::<Vec<f64>>();
+        for solved_armor in enemy_solved_armors.iter_mut() {
+            solved_armor.coord_trans_mut(RbtPoseCoordSys::WorldXyz, tf);
+        }
 
-        // 1.5 得到的base坐标系下敌人中心坐标
-        let enemy_base_cylindrical = RbtCylindricalPoint2::from_xy(enemy_center_xy);
+        // 1.4 以每块装甲板的法线为约束，联合求解敌人中心坐标和半径
+        let Some(enemy_center) = solve_armors_center(
+            &mut enemy_solved_armors,
+            &armors_std,
+            enemy_id,
+            priors.get(&enemy_id),
+            cfg,
+        ) else {
+            // 中心解算失败只影响该单位，本帧无测量，不中断其他单位的解算
+            warn!("{:?} 车体中心解算失败，跳过", enemy_id);
+            continue;
+        };
+        let enemy_center_xy = enemy_center.center;
+
+        // 1.5 得到的world坐标系下敌人中心坐标
+        let enemy_world_cylindrical = RbtCylindricalPoint2::from_xy(enemy_center_xy);
         // 根据该中心坐标，求解装甲板其他参数
         for solved_armor in enemy_solved_armors.iter_mut() {
             let armor_pose = solved_armor.pose();
@@ -158,15 +265,11 @@ pub fn enemys_solver(
             armor_pose.pose().armor_visualize(&rec, idx)?
         }
 
-        let image =
-            image::open("/home/flamingo/Project/robomaster/auto_aim_rust/imgs/test_resize.jpg")
-                .unwrap();
-        rec.log("world/image", &rerun::Image::from_image(image).unwrap())
-            .expect("failed to show img in rerun");
-
         // 1.7 写入该敌方单位输出结果
         let solved_result = RbtSolvedResult {
-            coord: enemy_base_cylindrical,
+            coord: enemy_world_cylindrical,
+            radius: enemy_center.radius,
+            covariance: enemy_center.covariance,
             armors: enemy_solved_armors,
         };
         enemys
@@ -179,32 +282,511 @@ pub fn enemys_solver(
     Ok(enemys)
 }
 
-fn solve_enemy_center(armors_line_2d: &[RbtLine2]) -> Option<na::Point2<f64>> {
-    let armors_line_num = armors_line_2d.len();
-    if armors_line_num == 0 {
+/// 相机坐标系下装甲板中心偏离光轴的角度
+fn off_axis_angle(armor: &SolvedArmor) -> f64 {
+    let position = armor.pose().translation.vector;
+    position.xy().norm().atan2(position.z)
+}
+
+/// 求解装甲板所属车体的中心
+///
+/// 看到多块装甲板时，对歧义度不低于 `ambiguity_threshold` 的装甲板枚举 IPPE 的两个候选位姿，
+/// 保留中心最小二乘残差最小的组合，并把选中的位姿写回装甲板。
+/// 只看到一块装甲板时中心约束无法区分两个候选位姿，改为选取朝向与 `prior` 中跟踪装甲板 yaw
+/// 最接近的候选，比较时对相邻装甲板的夹角取模，装甲板切换不影响选择
+fn solve_armors_center(
+    armors: &mut [SolvedArmor],
+    armors_std: &[f64],
+    enemy_id: EnemyId,
+    prior: Option<&EnemyPrior>,
+    cfg: &SolverCfg,
+) -> Option<EnemyCenterSolution> {
+    let radius_prior = prior.map_or(cfg.default_armor_radius, |prior| prior.radius);
+    let solve = |armors: &[SolvedArmor]| {
+        let constraints = armors
+            .iter()
+            .zip(armors_std)
+            .map(|(armor, std)| ArmorCenterConstraint::from_pose(armor.pose(), *std))
+            .collect::<Vec<_>>();
+        solve_enemy_center(&constraints, radius_prior, cfg.radius_prior_std)
+    };
+    let ambiguous = armors
+        .iter()
+        .enumerate()
+        .filter(|(_, armor)| {
+            armor.alternative().is_some() && armor.ambiguity() >= cfg.ambiguity_threshold
+        })
+        .map(|(idx, _)| idx)
+        .collect::<Vec<_>>();
+    if ambiguous.is_empty() {
+        return solve(armors);
+    }
+    if let [armor] = armors {
+        if let Some(prior) = prior {
+            let interval = EnemyArmorLayout::from_enemy_id(&enemy_id).armor_interval();
+            let deviation = |armor: &SolvedArmor| {
+                // 法线朝外，armor_yaw 指向车体中心，两者相差 180°
+                let normal = ArmorCenterConstraint::from_pose(armor.pose(), 0.0)
+                    .line
+                    .direction;
+                let yaw = (-normal.y).atan2(-normal.x).to_degrees() - prior.armor_yaw;
+                (yaw - interval * (yaw / interval).round()).abs()
+            };
+            let mut swapped = armor.clone();
+            swapped.swap_alternative();
+            if deviation(&swapped) < deviation(armor) {
+                debug!("根据跟踪装甲板的 yaw 先验切换了歧义装甲板的位姿");
+                *armor = swapped;
+            }
+        }
+        return solve(armors);
+    }
+
+    let swapped = |mask: u32| {
+        let mut candidate = armors.to_vec();
+        for (bit, idx) in ambiguous.iter().enumerate() {
+            if mask >> bit & 1 == 1 {
+                candidate[*idx].swap_alternative();
+            }
+        }
+        candidate
+    };
+    let (mask, solution) = (0..1u32 << ambiguous.len())
+        .filter_map(|mask| solve(&swapped(mask)).map(|solution| (mask, solution)))
+        .min_by(|a, b| a.1.cost.total_cmp(&b.1.cost))?;
+    if mask != 0 {
+        debug!("根据车体中心约束切换了歧义装甲板的位姿, mask = {:#b}", mask);
+        armors.clone_from_slice(&swapped(mask));
+    }
+    Some(solution)
+}
+
+/// 单块装甲板对敌方中心的约束：装甲板中心沿法线反向延长半径即为车体中心
+#[derive(Debug, Clone, Copy)]
+pub struct ArmorCenterConstraint {
+    /// `point` 为装甲板中心，`direction` 为装甲板朝外的法线，无需归一化
+    pub line: RbtLine2,
+    /// 装甲板位置的测量标准差 mm
+    pub std: f64,
+}
+
+impl ArmorCenterConstraint {
+    /// 由世界坐标系下的装甲板位姿构建，法线取装甲板 z 轴的水平分量
+    pub fn from_pose(armor_pose: &RbtPose3, std: f64) -> Self {
+        let [armor_x, armor_y] = [armor_pose.translation.x, armor_pose.translation.y];
+        let rot_mat = armor_pose.rotation.to_rotation_matrix().into_inner();
+        let [armor_2d_pose_a, armor_2d_pose_b] = [rot_mat.m13, rot_mat.m23];
+        Self {
+            line: RbtLine2 {
+                point: na::Point2::new(armor_x, armor_y),
+                direction: na::Vector2::new(armor_2d_pose_a, armor_2d_pose_b),
+            },
+            std,
+        }
+    }
+}
+
+/// 敌方中心的最小二乘解
+#[derive(Debug, Clone)]
+pub struct EnemyCenterSolution {
+    pub center: na::Point2<f64>,
+    pub radius: f64,
+    /// (x, y, r) 的协方差
+    pub covariance: na::Matrix3<f64>,
+    /// 含半径先验的加权残差平方和，用于比较不同的候选位姿组合
+    pub cost: f64,
+}
+
+/// 联合求解敌方中心和半径
+///
+/// 每块装甲板给出两个线性约束 c + r·n = p，按测量标准差加权；
+/// 半径先验 r = `radius_prior` 作为一条额外约束，只看到一块装甲板时由它确定半径，
+/// 两块装甲板法线接近平行时也由它避免方程病态。
+/// 协方差取信息矩阵的逆，法线长度退化的装甲板会被忽略。
+pub fn solve_enemy_center(
+    constraints: &[ArmorCenterConstraint],
+    radius_prior: f64,
+    radius_prior_std: f64,
+) -> Option<EnemyCenterSolution> {
+    let prior_weight = radius_prior_std.powi(-2);
+    let mut information = na::Matrix3::new(0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.0, prior_weight);
+    let mut rhs = na::Vector3::new(0.0, 0.0, prior_weight * radius_prior);
+    let mut used = 0;
+    for constraint in constraints {
+        let Some(normal) = constraint.line.direction.try_normalize(1e-6) else {
+            continue;
+        };
+        let weight = constraint.std.powi(-2);
+        let point = constraint.line.point;
+        // 雅可比两行分别为 [1, 0, n_x] 与 [0, 1, n_y]
+        for (row, value) in [
+            (na::Vector3::new(1.0, 0.0, normal.x), point.x),
+            (na::Vector3::new(0.0, 1.0, normal.y), point.y),
+        ] {
+            information += weight * row * row.transpose();
+            rhs += weight * value * row;
+        }
+        used += 1;
+    }
+    if used == 0 {
         warn!("未能成功解算出装甲板，跳过");
-        None
-    } else if armors_line_num == 1 {
-        Some(handle_single_armor(&armors_line_2d[0]))
-    } else if armors_line_num == 2 {
-        Some(handle_multi_armor(&armors_line_2d))
-    } else {
-        warn!("解算出两块以上的装甲板, 跳过");
-        None
-    }
-}
-
-/// 处理只看到一块装甲板的情况
-/// 使用估计出的半径进行中心求解
-fn handle_single_armor(armors_line_2d: &RbtLine2) -> na::Point2<f64> {
-    let r = 200.0;
-    dbg!(&armors_line_2d);
-    dbg!(armors_line_2d.point - armors_line_2d.direction * r);
-    armors_line_2d.point - armors_line_2d.direction * r
-}
-
-/// 处理看到两块装甲板的情况
-/// 使用反向延长线求解中心
-fn handle_multi_armor(armors_line_2d: &[RbtLine2]) -> na::Point2<f64> {
-    find_intersection(&armors_line_2d[0], &armors_line_2d[1])
+        return None;
+    }
+
+    let covariance = information.cholesky()?.inverse();
+    let solution = covariance * rhs;
+    if solution.z <= 0.0 {
+        warn!("解算出的装甲板半径 {} 非正，跳过", solution.z);
+        return None;
+    }
+    let (center, radius) = (na::Point2::new(solution.x, solution.y), solution.z);
+    let cost = prior_weight * (radius - radius_prior).powi(2)
+        + constraints
+            .iter()
+            .filter_map(|constraint| {
+                let normal = constraint.line.direction.try_normalize(1e-6)?;
+                let residual = center + normal * radius - constraint.line.point;
+                Some(residual.norm_squared() * constraint.std.powi(-2))
+            })
+            .sum::<f64>();
+    debug!(
+        "{} 块装甲板解算中心 ({}, {}), 半径 {}",
+        used, solution.x, solution.y, solution.z
+    );
+    Some(EnemyCenterSolution {
+        center,
+        radius,
+        covariance,
+        cost,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rbt_base::rbt_geometry::rbt_point2::RbtImgPoint2;
+    use crate::rbt_base::rbt_geometry::rbt_tf::RbtTfTree;
+    use crate::rbt_infra::rbt_cfg::RbtCfg;
+
+    const STD: f64 = 10.0;
+
+    /// 以 `center` 为中心、半径 `radius` 的车体在 `yaw_deg` 方向上的装甲板约束
+    fn armor_constraint(
+        center: na::Point2<f64>,
+        radius: f64,
+        yaw_deg: f64,
+    ) -> ArmorCenterConstraint {
+        let normal = na::Vector2::new(yaw_deg.to_radians().cos(), yaw_deg.to_radians().sin());
+        ArmorCenterConstraint {
+            line: RbtLine2 {
+                point: center + normal * radius,
+                direction: normal,
+            },
+            std: STD,
+        }
+    }
+
+    #[test]
+    fn test_single_armor_uses_radius_prior() {
+        let center = na::Point2::new(3000.0, 500.0);
+        let constraint = armor_constraint(center, 250.0, 160.0);
+        let solution = solve_enemy_center(&[constraint], 250.0, 50.0).unwrap();
+        assert!((solution.center - center).norm() < 1e-6);
+        assert!((solution.radius - 250.0).abs() < 1e-6);
+        // 沿法线方向的不确定度由半径先验决定，切向由测量决定
+        let normal = constraint.line.direction;
+        let tangent = na::Vector2::new(-normal.y, normal.x);
+        let xy_cov = solution.covariance.fixed_view::<2, 2>(0, 0).into_owned();
+        assert!(
+            ((normal.transpose() * xy_cov * normal)[0] - (50.0f64.powi(2) + STD * STD)).abs()
+                < 1e-6
+        );
+        assert!(((tangent.transpose() * xy_cov * tangent)[0] - STD * STD).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_multi_armor_solves_radius() {
+        let center = na::Point2::new(2500.0, -800.0);
+        // 先验半径偏差 80mm，多块装甲板时应以测量为主，只残留几毫米的偏差
+        for yaws in [&[150.0, 240.0][..], &[120.0, 210.0, 300.0][..]] {
+            let constraints = yaws
+                .iter()
+                .map(|yaw| armor_constraint(center, 220.0, *yaw))
+                .collect::<Vec<_>>();
+            let solution = solve_enemy_center(&constraints, 300.0, 50.0).unwrap();
+            assert!((solution.center - center).norm() < 5.0, "{:?}", solution);
+            assert!((solution.radius - 220.0).abs() < 5.0, "{:?}", solution);
+            assert!(solution.covariance[(2, 2)] < STD * STD);
+        }
+    }
+
+    #[test]
+    fn test_near_parallel_armors() {
+        let center = na::Point2::new(4000.0, 0.0);
+        let constraints = [
+            armor_constraint(center, 250.0, 180.0),
+            armor_constraint(center, 250.0, 180.5),
+        ];
+        let solution = solve_enemy_center(&constraints, 250.0, 50.0).unwrap();
+        assert!((solution.center - center).norm() < 1e-6);
+        assert!(solution.covariance.iter().all(|v| v.is_finite()));
+        assert!(solution.covariance[(2, 2)] <= 50.0f64.powi(2));
+        assert!(solve_enemy_center(&[], 250.0, 50.0).is_none());
+    }
+
+    #[test]
+    fn test_measurement_covariance() {
+        let solved = RbtSolvedResult {
+            coord: RbtCylindricalPoint2::from_xy(na::Point2::new(0.0, 2000.0)),
+            radius: 250.0,
+            covariance: na::Matrix3::from_diagonal(&na::Vector3::new(400.0, 100.0, 2500.0)),
+            armors: vec![],
+        };
+        assert!((solved.coord.theta_d - 90.0).abs() < 1e-9);
+        let cov = solved.measurement_covariance();
+        // 中心位于 y 轴上时：x 方向误差对应角度，y 方向误差对应距离
+        let theta_std_deg = (20.0f64 / 2000.0).to_degrees();
+        assert!((cov[(0, 0)] - theta_std_deg.powi(2)).abs() < 1e-9);
+        assert!((cov[(1, 1)] - 100.0).abs() < 1e-9);
+        assert!(cov[(0, 1)].abs() < 1e-9);
+    }
+
+    /// 世界坐标系下，z 轴水平朝向 `yaw_deg` 的装甲板
+    fn world_armor(center: na::Point2<f64>, radius: f64, yaw_deg: f64) -> na::Isometry3<f64> {
+        let normal = na::Vector3::new(yaw_deg.to_radians().cos(), yaw_deg.to_radians().sin(), 0.0);
+        let rotation = na::UnitQuaternion::rotation_between(&na::Vector3::z(), &normal).unwrap();
+        let position = center.coords + normal.xy() * radius;
+        na::Isometry3::from_parts(na::Translation3::new(position.x, position.y, 0.0), rotation)
+    }
+
+    #[test]
+    fn test_ambiguous_armor_disambiguated_by_center() {
+        let center = na::Point2::new(3000.0, 0.0);
+        let point = RbtImgPoint2::new_screen_pixel(0.0, 0.0);
+        let detected = DetectedArmor::new(point, point, point, point, point, 0);
+        let cfg = SolverCfg {
+            armor_position_std: STD,
+            radius_prior_std: 50.0,
+            default_armor_radius: 250.0,
+            ambiguity_threshold: 0.5,
+        };
+        let clear = SolvedArmor::new(
+            detected.clone(),
+            world_armor(center, 250.0, 150.0),
+            0.0,
+            0.0,
+            0.0,
+        );
+        // 第二块装甲板 IPPE 选错了分支，正确位姿留在候选解中
+        let truth = world_armor(center, 250.0, 180.0);
+        let mut wrong = truth;
+        wrong.rotation = world_armor(center, 250.0, 230.0).rotation;
+        let mut ambiguous = SolvedArmor::new(detected, wrong, 0.0, 0.0, 0.0);
+        ambiguous.set_alternative(truth, 0.9);
+
+        let mut armors = vec![clear.clone(), ambiguous.clone()];
+        let solution =
+            solve_armors_center(&mut armors, &[STD, STD], EnemyId::Infantry3, None, &cfg).unwrap();
+        assert!((solution.center - center).norm() < 1e-6, "{:?}", solution);
+        assert!(solution.cost < 1e-9);
+        assert_eq!(armors[1].pose().rotation, truth.rotation);
+
+        // 歧义度低于阈值时不切换
+        ambiguous.set_alternative(truth, 0.2);
+        let mut armors = vec![clear, ambiguous];
+        let solution =
+            solve_armors_center(&mut armors, &[STD, STD], EnemyId::Infantry3, None, &cfg).unwrap();
+        assert!(solution.cost > 1.0);
+        assert_eq!(armors[1].pose().rotation, wrong.rotation);
+    }
+
+    /// 静止的先验，yaw 为跟踪装甲板指向车体中心的方向
+    fn prior(radius: f64, armor_yaw: f64) -> EnemyPrior {
+        EnemyPrior {
+            radius,
+            armor_yaw,
+            v_spin: 0.0,
+            time_stamp: Instant::now(),
+        }
+    }
+
+    #[test]
+    fn test_single_armor_disambiguated_by_prior() {
+        let center = na::Point2::new(3000.0, 0.0);
+        let point = RbtImgPoint2::new_screen_pixel(0.0, 0.0);
+        let detected = DetectedArmor::new(point, point, point, point, point, 0);
+        let cfg = SolverCfg {
+            armor_position_std: STD,
+            radius_prior_std: 50.0,
+            default_armor_radius: 250.0,
+            ambiguity_threshold: 0.5,
+        };
+        // 正对己方的装甲板 IPPE 选错了分支，朝外法线偏到 230°
+        let truth = world_armor(center, 250.0, 180.0);
+        let mut wrong = truth;
+        wrong.rotation = world_armor(center, 250.0, 230.0).rotation;
+        let mut ambiguous = SolvedArmor::new(detected, wrong, 0.0, 0.0, 0.0);
+        ambiguous.set_alternative(truth, 0.9);
+
+        // 没有先验时保持 IPPE 的选择
+        let mut armors = vec![ambiguous.clone()];
+        solve_armors_center(&mut armors, &[STD], EnemyId::Infantry3, None, &cfg).unwrap();
+        assert_eq!(armors[0].pose().rotation, wrong.rotation);
+
+        // 跟踪装甲板 yaw 接近真值，或刚切换到相邻装甲板，都选中真值
+        for armor_yaw in [5.0, 95.0, -85.0] {
+            let prior = prior(250.0, armor_yaw);
+            let mut armors = vec![ambiguous.clone()];
+            solve_armors_center(&mut armors, &[STD], EnemyId::Infantry3, Some(&prior), &cfg)
+                .unwrap();
+            assert_eq!(armors[0].pose().rotation, truth.rotation, "{armor_yaw}");
+        }
+    }
+
+    #[test]
+    fn test_prior_predict() {
+        let mut spinning = prior(250.0, 10.0);
+        spinning.v_spin = 180.0;
+        let time = spinning.time_stamp + std::time::Duration::from_millis(100);
+        let predicted = spinning.predict(time);
+        assert!((predicted.armor_yaw - 28.0).abs() < 1e-9);
+        assert_eq!(predicted.time_stamp, time);
+        assert!((predicted.predict(spinning.time_stamp).armor_yaw - 10.0).abs() < 1e-9);
+    }
+
+    /// 相机坐标系下位于 (x, y, z)、正对相机的装甲板
+    fn facing_armor(x: f64, y: f64, z: f64) -> na::Isometry3<f64> {
+        let facing = na::Rotation3::from_matrix_unchecked(na::Matrix3::from_diagonal(
+            &na::Vector3::new(1.0, -1.0, -1.0),
+        ));
+        na::Isometry3::from_parts(
+            na::Translation3::new(x, y, z),
+            na::UnitQuaternion::from_rotation_matrix(&facing),
+        )
+    }
+
+    /// 相机坐标系下位姿为 `pose` 的装甲板在图像中的检测结果
+    fn project_armor(
+        pose: &na::Isometry3<f64>,
+        cam_k: &na::Matrix3<f64>,
+        armor_type: ArmorType,
+    ) -> DetectedArmor {
+        let pnp_solver = match armor_type {
+            ArmorType::Small => ArmorPnpSolver::new(),
+            ArmorType::Large => ArmorPnpSolver::large(),
+        };
+        let uvs = pnp_solver.unwrap().object_points().map(|p| {
+            let uv = cam_k * (pose * p).coords;
+            na::Point2::new(uv.x / uv.z, uv.y / uv.z)
+        });
+        let center = na::center(&uvs[0], &uvs[2]);
+        let [center, lt, lb, rb, rt] = [center, uvs[0], uvs[1], uvs[2], uvs[3]]
+            .map(|p| RbtImgPoint2::new_screen_pixel(p.x as f32, p.y as f32));
+        DetectedArmor::new(center, lt, lb, rb, rt, 0).with_armor_type(armor_type)
+    }
+
+    /// 以零关节角下的坐标变换解算，同时返回该变换；测试的是 IPPE 解，不做位姿优化
+    fn solve(detected: HashMap<EnemyId, Vec<DetectedArmor>>) -> (RbtSolvedResults, RbtTf) {
+        solve_with_priors(detected, &HashMap::new())
+    }
+
+    /// 同 `solve`，指定各单位的先验
+    fn solve_with_priors(
+        detected: HashMap<EnemyId, Vec<DetectedArmor>>,
+        priors: &HashMap<EnemyId, EnemyPrior>,
+    ) -> (RbtSolvedResults, RbtTf) {
+        let mut cfg = RbtCfg::from_toml().unwrap();
+        cfg.pose_refine_cfg.enable = false;
+        let tf = RbtTfTree::new(&cfg.tf_cfg).static_tf().clone();
+        let enemys = enemys_solver(
+            detected,
+            &cfg.cam_cfg,
+            &tf,
+            &cfg.solver_cfg,
+            &cfg.pose_refine_cfg,
+            priors,
+            &rr::RecordingStream::disabled(),
+        )
+        .unwrap();
+        (enemys, tf)
+    }
+
+    /// 解算出的装甲板世界坐标与相机坐标系下真值 `pose` 的距离
+    fn position_error(armor: &SolvedArmor, pose: &na::Isometry3<f64>, tf: &RbtTf) -> f64 {
+        let expected = tf.lookup(RbtTfFrame::Camera, RbtTfFrame::World) * pose;
+        (armor.pose().translation.vector - expected.translation.vector).norm()
+    }
+
+    #[test]
+    fn test_degenerate_armor_skipped() {
+        let cam_k = RbtCfg::from_toml().unwrap().cam_cfg.cam_k();
+        // 四个角点重合的畸形四边形无法解算，只跳过该装甲板，不影响同一帧的其他单位
+        let point = RbtImgPoint2::new_screen_pixel(640.0, 360.0);
+        let degenerate = DetectedArmor::new(point, point, point, point, point, 0);
+        let pose = facing_armor(0.0, 0.0, 3000.0);
+        let (enemys, _) = solve(HashMap::from([
+            (EnemyId::Hero1, vec![degenerate]),
+            (
+                EnemyId::Infantry3,
+                vec![project_armor(&pose, &cam_k, ArmorType::Small)],
+            ),
+        ]));
+        assert!(enemys[&EnemyId::Hero1].is_none());
+        let infantry = enemys[&EnemyId::Infantry3].as_ref().unwrap();
+        assert_eq!(infantry.armors.len(), 1);
+    }
+
+    #[test]
+    fn test_center_failure_skipped() {
+        let cam_k = RbtCfg::from_toml().unwrap().cam_cfg.cam_k();
+        // 非正的半径先验使单块装甲板的中心解算失败，只跳过该单位
+        let hero = facing_armor(-500.0, 0.0, 3000.0);
+        let infantry = facing_armor(500.0, 0.0, 3000.0);
+        let (enemys, _) = solve_with_priors(
+            HashMap::from([
+                (
+                    EnemyId::Hero1,
+                    vec![project_armor(&hero, &cam_k, ArmorType::Small)],
+                ),
+                (
+                    EnemyId::Infantry3,
+                    vec![project_armor(&infantry, &cam_k, ArmorType::Small)],
+                ),
+            ]),
+            &HashMap::from([(EnemyId::Hero1, prior(-250.0, 0.0))]),
+        );
+        assert!(enemys[&EnemyId::Hero1].is_none());
+        assert!(enemys[&EnemyId::Infantry3].is_some());
+    }
+
+    #[test]
+    fn test_large_armor_geometry() {
+        let cam_k = RbtCfg::from_toml().unwrap().cam_cfg.cam_k();
+        let pose = facing_armor(100.0, 0.0, 4000.0);
+        let (enemys, tf) = solve(HashMap::from([(
+            EnemyId::Hero1,
+            vec![project_armor(&pose, &cam_k, ArmorType::Large)],
+        )]));
+        let hero = enemys[&EnemyId::Hero1].as_ref().unwrap();
+        // 按小装甲板尺寸解算时距离会缩小到约 135/230
+        assert!(position_error(&hero.armors[0], &pose, &tf) < 5.0);
+    }
+
+    #[test]
+    fn test_unknown_armors_not_merged() {
+        let cam_k = RbtCfg::from_toml().unwrap().cam_cfg.cam_k();
+        // 两块编号未识别的装甲板来自不同车辆，只保留靠近光轴的一块
+        let near_axis = facing_armor(100.0, 0.0, 3000.0);
+        let off_axis = facing_armor(-600.0, 0.0, 2500.0);
+        let (enemys, tf) = solve(HashMap::from([(
+            EnemyId::Unknown,
+            vec![
+                project_armor(&off_axis, &cam_k, ArmorType::Small),
+                project_armor(&near_axis, &cam_k, ArmorType::Small),
+            ],
+        )]));
+        let unknown = enemys[&EnemyId::Unknown].as_ref().unwrap();
+        assert_eq!(unknown.armors.len(), 1);
+        assert!(position_error(&unknown.armors[0], &near_axis, &tf) < 5.0);
+    }
 }
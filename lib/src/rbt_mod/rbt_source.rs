@@ -0,0 +1,347 @@
+//! 图像源
+//!
+//! 流水线从 `FrameSource` 中逐帧读取带时间戳的图像，具体实现由 `SourceCfg` 选择：
+//! - `ImageSource`：单张图片重复输出，用于性能测试
+//! - `DirSource`：按文件名顺序读取目录下的图片
+//! - `VideoSource`：读取 MJPEG 编码的 AVI 录像
+//!
+//! 工业相机接入后同样实现 `FrameSource` 即可替换。
+
+use image::DynamicImage;
+use std::path::{Path, PathBuf};
+use tokio::time::Instant;
+use tracing::info;
+
+use crate::rbt_infra::rbt_cfg::{SourceCfg, SourceKind};
+use crate::rbt_infra::rbt_err::{RbtError, RbtResult};
+use crate::rbt_mod::rbt_source::rbt_avi::MjpegAviReader;
+
+pub mod rbt_avi;
+
+/// 目录图像源支持的图片格式
+const IMAGE_EXTENSIONS: [&str; 4] = ["jpg", "jpeg", "png", "bmp"];
+
+/// 带时间戳的图像
+pub struct SourceFrame {
+    image: DynamicImage,
+    time_stamp: Instant,
+    id: u64,
+}
+
+impl SourceFrame {
+    /// 以当前时刻为采集时刻
+    pub fn new(image: DynamicImage, id: u64) -> Self {
+        Self::with_time_stamp(image, id, Instant::now())
+    }
+
+    pub fn with_time_stamp(image: DynamicImage, id: u64, time_stamp: Instant) -> Self {
+        Self {
+            image,
+            time_stamp,
+            id,
+        }
+    }
+
+    pub fn image(&self) -> &DynamicImage {
+        &self.image
+    }
+
+    pub fn into_image(self) -> DynamicImage {
+        self.image
+    }
+
+    /// 图像采集时刻，用于查询该时刻的云台姿态
+    pub fn time_stamp(&self) -> Instant {
+        self.time_stamp
+    }
+
+    /// 从 1 开始的帧序号
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+/// 图像源
+pub trait FrameSource: Send {
+    /// 读取下一帧，图像源耗尽时返回 Ok(None)
+    fn next_frame(&mut self) -> RbtResult<Option<SourceFrame>>;
+}
+
+/// 播放计数，`repeat` 为 0 时无限循环
+#[derive(Debug, Clone, Copy)]
+struct Repeat {
+    total: u64,
+    done: u64,
+}
+
+impl Repeat {
+    fn new(total: u64) -> Self {
+        Self { total, done: 0 }
+    }
+
+    fn finished(&self) -> bool {
+        self.total != 0 && self.done >= self.total
+    }
+}
+
+/// 单张图片，重复输出 `repeat` 帧
+pub struct ImageSource {
+    image: DynamicImage,
+    repeat: Repeat,
+}
+
+impl ImageSource {
+    pub fn open(path: impl AsRef<Path>, repeat: u64) -> RbtResult<Self> {
+        Ok(Self::new(image::open(path)?, repeat))
+    }
+
+    pub fn new(image: DynamicImage, repeat: u64) -> Self {
+        Self {
+            image,
+            repeat: Repeat::new(repeat),
+        }
+    }
+}
+
+impl FrameSource for ImageSource {
+    fn next_frame(&mut self) -> RbtResult<Option<SourceFrame>> {
+        if self.repeat.finished() {
+            return Ok(None);
+        }
+        self.repeat.done += 1;
+        Ok(Some(SourceFrame::new(self.image.clone(), self.repeat.done)))
+    }
+}
+
+/// 图片目录，按文件名排序后依次输出，播放 `repeat` 遍
+pub struct DirSource {
+    paths: Vec<PathBuf>,
+    cursor: usize,
+    repeat: Repeat,
+    id: u64,
+}
+
+impl DirSource {
+    pub fn open(dir: impl AsRef<Path>, repeat: u64) -> RbtResult<Self> {
+        let mut paths = std::fs::read_dir(dir.as_ref())?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+            })
+            .collect::<Vec<_>>();
+        if paths.is_empty() {
+            return Err(RbtError::FrameSourceError(format!(
+                "目录 {} 中没有图片",
+                dir.as_ref().display()
+            )));
+        }
+        paths.sort();
+        Ok(Self {
+            paths,
+            cursor: 0,
+            repeat: Repeat::new(repeat),
+            id: 0,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+}
+
+impl FrameSource for DirSource {
+    fn next_frame(&mut self) -> RbtResult<Option<SourceFrame>> {
+        if self.cursor == self.paths.len() {
+            self.cursor = 0;
+            self.repeat.done += 1;
+        }
+        if self.repeat.finished() {
+            return Ok(None);
+        }
+        let image = image::open(&self.paths[self.cursor])?;
+        self.cursor += 1;
+        self.id += 1;
+        Ok(Some(SourceFrame::new(image, self.id)))
+    }
+}
+
+/// MJPEG 编码的 AVI 录像，播放 `repeat` 遍
+///
+/// 读取速度与录制帧率无关，时间戳按录制帧间隔从第一帧的读取时刻起依次递增，
+/// 估计器据此得到与录制时一致的帧间隔
+pub struct VideoSource {
+    reader: MjpegAviReader<std::io::BufReader<std::fs::File>>,
+    cursor: usize,
+    repeat: Repeat,
+    id: u64,
+    start: Option<Instant>,
+}
+
+impl VideoSource {
+    pub fn open(path: impl AsRef<Path>, repeat: u64) -> RbtResult<Self> {
+        let reader = MjpegAviReader::open(path)?;
+        if reader.frame_count() == 0 {
+            return Err(RbtError::FrameSourceError("录像中没有视频帧".to_string()));
+        }
+        Ok(Self {
+            reader,
+            cursor: 0,
+            repeat: Repeat::new(repeat),
+            id: 0,
+            start: None,
+        })
+    }
+
+    /// 录制时的帧间隔
+    pub fn frame_interval(&self) -> std::time::Duration {
+        self.reader.frame_interval()
+    }
+}
+
+impl FrameSource for VideoSource {
+    fn next_frame(&mut self) -> RbtResult<Option<SourceFrame>> {
+        if self.cursor == self.reader.frame_count() {
+            self.cursor = 0;
+            self.repeat.done += 1;
+        }
+        if self.repeat.finished() {
+            return Ok(None);
+        }
+        let image = self.reader.read_frame(self.cursor)?;
+        let start = *self.start.get_or_insert_with(Instant::now);
+        let time_stamp = start + self.frame_interval() * self.id as u32;
+        self.cursor += 1;
+        self.id += 1;
+        let frame = SourceFrame::with_time_stamp(image, self.id, time_stamp);
+        Ok(Some(frame))
+    }
+}
+
+/// 根据配置创建图像源
+pub fn source_from_cfg(cfg: &SourceCfg) -> RbtResult<Box<dyn FrameSource>> {
+    let path = cfg.path();
+    info!("Frame source: {:?} {}", cfg.kind, path.display());
+    Ok(match cfg.kind {
+        SourceKind::Image => Box::new(ImageSource::open(path, cfg.repeat)?),
+        SourceKind::Dir => Box::new(DirSource::open(path, cfg.repeat)?),
+        SourceKind::Video => Box::new(VideoSource::open(path, cfg.repeat)?),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    /// 纯色图片，用红色通道区分帧
+    fn solid(r: u8) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(32, 16, Rgb([r, 0, 0])))
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rbt_source_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn drain(source: &mut dyn FrameSource) -> Vec<SourceFrame> {
+        std::iter::from_fn(|| source.next_frame().unwrap()).collect()
+    }
+
+    #[test]
+    fn test_image_source_repeat() {
+        let mut source = ImageSource::new(solid(10), 3);
+        let frames = drain(&mut source);
+        assert_eq!(frames.iter().map(|f| f.id()).collect::<Vec<_>>(), [1, 2, 3]);
+        assert!(
+            frames
+                .windows(2)
+                .all(|w| w[0].time_stamp() <= w[1].time_stamp())
+        );
+        assert!(source.next_frame().unwrap().is_none());
+    }
+
+    #[test]
+    fn test_dir_source_order() {
+        let dir = temp_dir("dir");
+        for (name, r) in [("b.png", 20), ("a.png", 10), ("c.png", 30)] {
+            solid(r).save(dir.join(name)).unwrap();
+        }
+        std::fs::write(dir.join("notes.txt"), "not an image").unwrap();
+
+        let mut source = DirSource::open(&dir, 2).unwrap();
+        assert_eq!(source.len(), 3);
+        let reds = drain(&mut source)
+            .iter()
+            .map(|f| f.image().to_rgb8().get_pixel(0, 0).0[0])
+            .collect::<Vec<_>>();
+        assert_eq!(reds, [10, 20, 30, 10, 20, 30]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_empty_dir_rejected() {
+        let dir = temp_dir("empty");
+        assert!(matches!(
+            DirSource::open(&dir, 1),
+            Err(RbtError::FrameSourceError(_))
+        ));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_video_source() {
+        let dir = temp_dir("video");
+        let path = dir.join("record.avi");
+        let frames = [solid(0), solid(128), solid(255)];
+        std::fs::write(&path, rbt_avi::tests::mjpeg_avi(&frames, 50)).unwrap();
+
+        let mut source = VideoSource::open(&path, 1).unwrap();
+        assert_eq!(
+            source.frame_interval(),
+            std::time::Duration::from_millis(20)
+        );
+        let reds = drain(&mut source)
+            .iter()
+            .map(|f| f.image().to_rgb8().get_pixel(16, 8).0[0] as i32)
+            .collect::<Vec<_>>();
+        // JPEG 有损，允许少量误差
+        assert_eq!(reds.len(), 3);
+        for (red, expected) in reds.iter().zip([0, 128, 255]) {
+            assert!((red - expected).abs() <= 4, "{reds:?}");
+        }
+
+        // 时间戳按录制帧间隔递增，循环播放时继续递增
+        let mut source = VideoSource::open(&path, 2).unwrap();
+        let frames = drain(&mut source);
+        assert_eq!(frames.len(), 6);
+        for (idx, frame) in frames.iter().enumerate() {
+            assert_eq!(
+                frame.time_stamp() - frames[0].time_stamp(),
+                std::time::Duration::from_millis(20) * idx as u32
+            );
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_source_from_cfg() {
+        let cfg: SourceCfg = toml::from_str(concat!(
+            "kind = \"image\"\n\
+             path = \"",
+            env!("CARGO_MANIFEST_DIR"),
+            "/../imgs/test_resize.jpg\"\n\
+             repeat = 2",
+        ))
+        .unwrap();
+        let mut source = source_from_cfg(&cfg).unwrap();
+        assert_eq!(drain(source.as_mut()).len(), 2);
+    }
+}
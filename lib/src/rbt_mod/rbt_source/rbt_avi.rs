@@ -0,0 +1,275 @@
+//! MJPEG 编码的 AVI 录像读取
+//!
+//! 工业相机和 ffmpeg（`-c:v mjpeg`）都可以直接录制这种格式，每一帧都是独立的 JPEG，
+//! 不需要额外的解码库。打开时只扫描 RIFF 结构并记录每帧的偏移，读取时再按需解码。
+//!
+//! AVI 结构：
+//! ```text
+//! RIFF 'AVI '
+//! ├── LIST 'hdrl'
+//! │   ├── 'avih'            主文件头，包含帧间隔
+//! │   └── LIST 'strl' × N   每个流的 'strh' / 'strf'
+//! ├── LIST 'movi'
+//! │   └── '00dc' × M        视频帧数据，可能嵌套在 LIST 'rec ' 中
+//! └── 'idx1'                索引，不使用
+//! ```
+
+use image::{DynamicImage, ImageFormat};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::rbt_infra::rbt_err::{RbtError, RbtResult};
+
+type FourCC = [u8; 4];
+
+/// MJPEG AVI 读取器
+pub struct MjpegAviReader<R> {
+    reader: R,
+    frames: Vec<(u64, u32)>, // 每帧数据的偏移和长度
+    micros_per_frame: u32,
+}
+
+/// 扫描 RIFF 结构时的中间状态
+#[derive(Default)]
+struct AviLayout {
+    micros_per_frame: u32,
+    streams: usize,
+    video_stream: Option<usize>,
+    mjpeg: bool,
+    frames: Vec<(u64, u32)>,
+}
+
+impl MjpegAviReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> RbtResult<Self> {
+        Self::new(BufReader::new(File::open(path)?))
+    }
+}
+
+impl<R: Read + Seek> MjpegAviReader<R> {
+    pub fn new(mut reader: R) -> RbtResult<Self> {
+        reader.seek(SeekFrom::Start(0))?;
+        let riff = read_fourcc(&mut reader)?;
+        let riff_size = read_u32(&mut reader)? as u64;
+        let form = read_fourcc(&mut reader)?;
+        if &riff != b"RIFF" || &form != b"AVI " {
+            return Err(avi_error("不是 AVI 文件"));
+        }
+        let file_len = reader.seek(SeekFrom::End(0))?;
+
+        let mut layout = AviLayout::default();
+        // 录制中断的文件 RIFF 长度可能不准，以实际文件长度为准
+        layout.walk(&mut reader, 12, (8 + riff_size).min(file_len), false)?;
+        if layout.video_stream.is_none() {
+            return Err(avi_error("没有视频流"));
+        }
+        if !layout.mjpeg {
+            return Err(avi_error("只支持 MJPEG 编码"));
+        }
+        Ok(Self {
+            reader,
+            frames: layout.frames,
+            micros_per_frame: layout.micros_per_frame,
+        })
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// 录制时的帧间隔
+    pub fn frame_interval(&self) -> Duration {
+        Duration::from_micros(self.micros_per_frame as u64)
+    }
+
+    /// 读取并解码第 `idx` 帧
+    pub fn read_frame(&mut self, idx: usize) -> RbtResult<DynamicImage> {
+        let (offset, len) = *self
+            .frames
+            .get(idx)
+            .ok_or_else(|| avi_error(format!("帧序号 {} 超出范围", idx)))?;
+        let mut buf = vec![0u8; len as usize];
+        self.reader.seek(SeekFrom::Start(offset))?;
+        self.reader.read_exact(&mut buf)?;
+        Ok(image::load_from_memory_with_format(
+            &buf,
+            ImageFormat::Jpeg,
+        )?)
+    }
+}
+
+impl AviLayout {
+    /// 遍历 `[start, end)` 范围内的 chunk
+    fn walk<R: Read + Seek>(
+        &mut self,
+        reader: &mut R,
+        start: u64,
+        end: u64,
+        in_movi: bool,
+    ) -> RbtResult<()> {
+        let mut pos = start;
+        while pos + 8 <= end {
+            reader.seek(SeekFrom::Start(pos))?;
+            let id = read_fourcc(reader)?;
+            let size = read_u32(reader)? as u64;
+            let data = pos + 8;
+            let data_end = (data + size).min(end);
+
+            match &id {
+                b"LIST" => match &read_fourcc(reader)? {
+                    b"hdrl" | b"strl" => self.walk(reader, data + 4, data_end, false)?,
+                    b"movi" | b"rec " => self.walk(reader, data + 4, data_end, true)?,
+                    _ => {}
+                },
+                b"avih" => self.micros_per_frame = read_u32(reader)?,
+                b"strh" => {
+                    let fcc_type = read_fourcc(reader)?;
+                    let handler = read_fourcc(reader)?;
+                    if &fcc_type == b"vids" && self.video_stream.is_none() {
+                        self.video_stream = Some(self.streams);
+                        self.mjpeg = handler.eq_ignore_ascii_case(b"MJPG");
+                    }
+                    self.streams += 1;
+                }
+                b"strf" if self.video_stream == self.streams.checked_sub(1) => {
+                    // BITMAPINFOHEADER 的 biCompression 位于第 16 字节
+                    reader.seek(SeekFrom::Current(16))?;
+                    let compression = read_fourcc(reader)?;
+                    self.mjpeg |= compression.eq_ignore_ascii_case(b"MJPG");
+                }
+                // 截断的最后一帧不计入
+                _ if in_movi && size > 0 && data + size <= end && self.is_video_chunk(&id) => {
+                    self.frames.push((data, size as u32));
+                }
+                _ => {}
+            }
+            // chunk 按 2 字节对齐
+            pos = data + size + (size & 1);
+        }
+        Ok(())
+    }
+
+    /// 视频帧 chunk 的 id 为两位流序号加 'dc'（压缩）或 'db'（未压缩）
+    fn is_video_chunk(&self, id: &FourCC) -> bool {
+        let Some(stream) = self.video_stream else {
+            return false;
+        };
+        let prefix = format!("{:02}", stream);
+        &id[..2] == prefix.as_bytes() && (&id[2..] == b"dc" || &id[2..] == b"db")
+    }
+}
+
+fn avi_error(msg: impl Into<String>) -> RbtError {
+    RbtError::FrameSourceError(format!("AVI: {}", msg.into()))
+}
+
+fn read_fourcc<R: Read>(reader: &mut R) -> RbtResult<FourCC> {
+    let mut buf = [0u8; 4];
+    reader.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> RbtResult<u32> {
+    Ok(u32::from_le_bytes(read_fourcc(reader)?))
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+    use std::io::Cursor;
+
+    fn chunk(id: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut out = id.to_vec();
+        out.extend((data.len() as u32).to_le_bytes());
+        out.extend(data);
+        if data.len() % 2 == 1 {
+            out.push(0);
+        }
+        out
+    }
+
+    fn list(kind: &[u8; 4], children: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = kind.to_vec();
+        children.iter().for_each(|c| data.extend(c));
+        chunk(b"LIST", &data)
+    }
+
+    fn u32s(values: &[u32]) -> Vec<u8> {
+        values.iter().flat_map(|v| v.to_le_bytes()).collect()
+    }
+
+    /// 构造 MJPEG AVI，视频流前放一个音频流，movi 中混入音频帧和 JUNK
+    pub(crate) fn mjpeg_avi(frames: &[DynamicImage], fps: u32) -> Vec<u8> {
+        let (w, h) = (frames[0].width(), frames[0].height());
+        let n = frames.len() as u32;
+        // 帧间隔、码率、对齐、标志、帧数、初始帧、流数、缓冲区、宽、高，后接 16 字节保留
+        let mut avih = u32s(&[1_000_000 / fps, 0, 0, 0, n, 0, 2, 0, w, h]);
+        avih.extend([0; 16]);
+        let strh = |fcc_type: &[u8; 4], handler: &[u8; 4]| {
+            let mut data = fcc_type.to_vec();
+            data.extend(handler);
+            // 标志、优先级与语言、初始帧、scale、rate、起始、长度，其余为 0
+            data.extend(u32s(&[0, 0, 0, 1, fps, 0, n]));
+            data.extend([0; 20]);
+            chunk(b"strh", &data)
+        };
+        let audio = list(b"strl", &[strh(b"auds", &[0; 4]), chunk(b"strf", &[0; 18])]);
+        let mut bih = u32s(&[40, w, h]);
+        bih.extend(1u16.to_le_bytes());
+        bih.extend(24u16.to_le_bytes());
+        bih.extend(b"MJPG");
+        bih.extend(u32s(&[w * h * 3, 0, 0, 0, 0]));
+        let video = list(b"strl", &[strh(b"vids", b"MJPG"), chunk(b"strf", &bih)]);
+        let hdrl = list(b"hdrl", &[chunk(b"avih", &avih), audio, video]);
+
+        let mut movi = vec![chunk(b"JUNK", &[0; 7])];
+        for frame in frames {
+            let mut jpeg = Cursor::new(Vec::new());
+            frame.write_to(&mut jpeg, ImageFormat::Jpeg).unwrap();
+            movi.push(chunk(b"00wb", &[0; 3]));
+            movi.push(chunk(b"01dc", jpeg.get_ref()));
+        }
+        let movi = list(b"movi", &movi);
+
+        let mut body = b"AVI ".to_vec();
+        body.extend(hdrl);
+        body.extend(movi);
+        body.extend(chunk(b"idx1", &[]));
+        chunk(b"RIFF", &body)
+    }
+
+    #[test]
+    fn test_read_mjpeg_avi() {
+        let frames = (0..4)
+            .map(|i| DynamicImage::ImageRgb8(RgbImage::from_pixel(15, 9, Rgb([i * 60, 100, 0]))))
+            .collect::<Vec<_>>();
+        let mut reader = MjpegAviReader::new(Cursor::new(mjpeg_avi(&frames, 100))).unwrap();
+        assert_eq!(reader.frame_count(), 4);
+        assert_eq!(reader.frame_interval(), Duration::from_millis(10));
+        for i in 0..4 {
+            let image = reader.read_frame(i).unwrap().to_rgb8();
+            assert_eq!(image.dimensions(), (15, 9));
+            let red = image.get_pixel(7, 4).0[0] as i32;
+            assert!((red - i as i32 * 60).abs() <= 4, "frame {i}: {red}");
+        }
+        assert!(reader.read_frame(4).is_err());
+    }
+
+    #[test]
+    fn test_reject_non_avi() {
+        assert!(MjpegAviReader::new(Cursor::new(chunk(b"RIFF", b"WAVEfmt "))).is_err());
+        let mut raw = mjpeg_avi(&[DynamicImage::new_rgb8(4, 4)], 30);
+        // 把编码改为 H264
+        for i in 0..raw.len() - 4 {
+            if &raw[i..i + 4] == b"MJPG" {
+                raw[i..i + 4].copy_from_slice(b"H264");
+            }
+        }
+        assert!(matches!(
+            MjpegAviReader::new(Cursor::new(raw)),
+            Err(RbtError::FrameSourceError(_))
+        ));
+    }
+}
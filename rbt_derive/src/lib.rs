@@ -0,0 +1,246 @@
+//! 通讯帧派生宏
+//!
+//! - `#[derive(CommData)]`: 按字段声明顺序生成小端序的数据段编解码，自动计算 `FRAME_SIZE`，
+//!   并生成往返(round-trip)测试
+//! - `#[derive(CommField)]`: 为 `#[repr(u8)]` 的无字段枚举生成单字节编解码
+//!
+//! 生成的代码通过 `::lib` 路径引用通讯模块，只能在 `lib` 及依赖 `lib` 的 crate 中使用
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Fields, Ident, LitInt, parse_macro_input};
+
+/// 派生 `CommData`
+///
+/// ```ignore
+/// #[derive(CommData)]
+/// #[comm_frame(sof = 0x33, eof = 0xEE)]
+/// pub struct CtrlData {
+///     pub gimbal_yaw: f32,
+///     pub shot_mode: ShotMode,
+/// }
+/// ```
+#[proc_macro_derive(CommData, attributes(comm_frame))]
+pub fn derive_comm_data(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_comm_data(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// 派生 `CommField`，只支持 `#[repr(u8)]` 的无字段枚举
+#[proc_macro_derive(CommField)]
+pub fn derive_comm_field(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_comm_field(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn frame_mod() -> TokenStream2 {
+    quote!(::lib::rbt_mod::rbt_comm::rbt_comm_frame)
+}
+
+/// 解析 `#[comm_frame(sof = .., eof = ..)]`
+fn parse_frame_attr(input: &DeriveInput) -> syn::Result<(LitInt, LitInt)> {
+    let mut sof = None;
+    let mut eof = None;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("comm_frame")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("sof") {
+                sof = Some(meta.value()?.parse::<LitInt>()?);
+            } else if meta.path.is_ident("eof") {
+                eof = Some(meta.value()?.parse::<LitInt>()?);
+            } else {
+                return Err(meta.error("expected `sof` or `eof`"));
+            }
+            Ok(())
+        })?;
+    }
+    match (sof, eof) {
+        (Some(sof), Some(eof)) => Ok((sof, eof)),
+        _ => Err(syn::Error::new(
+            Span::call_site(),
+            "CommData requires #[comm_frame(sof = .., eof = ..)]",
+        )),
+    }
+}
+
+fn expand_comm_data(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (sof, eof) = parse_frame_attr(input)?;
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    name,
+                    "CommData can only be derived for structs with named fields",
+                ));
+            }
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "CommData can only be derived for structs",
+            ));
+        }
+    };
+    if fields.is_empty() {
+        return Err(syn::Error::new_spanned(name, "CommData frame must not be empty"));
+    }
+
+    let frame = frame_mod();
+    let idents: Vec<&Ident> = fields.iter().map(|f| f.ident.as_ref().unwrap()).collect();
+    let tys: Vec<&syn::Type> = fields.iter().map(|f| &f.ty).collect();
+    let seeds = (0..fields.len()).map(|idx| idx as u8);
+
+    let test_mod = format_ident!("__comm_data_{}", to_snake_case(&name.to_string()));
+
+    Ok(quote! {
+        impl #frame::CommData for #name {
+            const PAYLOAD_SIZE: usize = 0 #(+ <#tys as #frame::CommField>::SIZE)*;
+            const SOF: u8 = #sof;
+            const EOF: u8 = #eof;
+
+            fn serialize_payload(&self, payload: &mut [u8]) {
+                let mut offset = 0usize;
+                #(
+                    let size = <#tys as #frame::CommField>::SIZE;
+                    #frame::CommField::write_le(&self.#idents, &mut payload[offset..offset + size]);
+                    offset += size;
+                )*
+                debug_assert_eq!(offset, payload.len());
+            }
+
+            fn deserialize_payload(payload: &[u8]) -> ::lib::rbt_infra::rbt_err::RbtResult<Self> {
+                let mut offset = 0usize;
+                #(
+                    let size = <#tys as #frame::CommField>::SIZE;
+                    let #idents = <#tys as #frame::CommField>::read_le(&payload[offset..offset + size])?;
+                    offset += size;
+                )*
+                debug_assert_eq!(offset, payload.len());
+                Ok(Self { #(#idents),* })
+            }
+        }
+
+        #[cfg(test)]
+        mod #test_mod {
+            use super::*;
+            use #frame::{CommData, CommField, FRAME_HEADER_SIZE, FRAME_TAIL_SIZE};
+
+            fn sample(seed: u8) -> #name {
+                #name {
+                    #(#idents: <#tys as CommField>::sample(seed.wrapping_add(#seeds))),*
+                }
+            }
+
+            #[test]
+            fn test_frame_size() {
+                assert_eq!(
+                    <#name as CommData>::FRAME_SIZE,
+                    FRAME_HEADER_SIZE + <#name as CommData>::PAYLOAD_SIZE + FRAME_TAIL_SIZE
+                );
+            }
+
+            #[test]
+            fn test_round_trip() {
+                for seed in 0..16u8 {
+                    let mut buffer = [0u8; <#name as CommData>::FRAME_SIZE];
+                    sample(seed).serialize(seed, &mut buffer).unwrap();
+                    let (seq, data) = <#name as CommData>::deserialize(&buffer).unwrap();
+                    assert_eq!(seq, seed);
+
+                    let mut round_trip = [0u8; <#name as CommData>::FRAME_SIZE];
+                    data.serialize(seq, &mut round_trip).unwrap();
+                    assert_eq!(buffer, round_trip);
+                }
+            }
+        }
+    })
+}
+
+fn expand_comm_field(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let variants = match &input.data {
+        Data::Enum(data) => &data.variants,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "CommField can only be derived for enums",
+            ));
+        }
+    };
+
+    let mut is_repr_u8 = false;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("repr")) {
+        attr.parse_nested_meta(|meta| {
+            is_repr_u8 |= meta.path.is_ident("u8");
+            Ok(())
+        })?;
+    }
+    if !is_repr_u8 {
+        return Err(syn::Error::new_spanned(
+            name,
+            "CommField enums must be #[repr(u8)]",
+        ));
+    }
+    if let Some(variant) = variants.iter().find(|v| !matches!(v.fields, Fields::Unit)) {
+        return Err(syn::Error::new_spanned(
+            variant,
+            "CommField enums must only have unit variants",
+        ));
+    }
+    if variants.is_empty() {
+        return Err(syn::Error::new_spanned(name, "CommField enum must not be empty"));
+    }
+
+    let frame = frame_mod();
+    let idents: Vec<&Ident> = variants.iter().map(|v| &v.ident).collect();
+    let indices = 0..idents.len();
+    let variant_num = idents.len();
+    let name_str = name.to_string();
+
+    Ok(quote! {
+        impl #frame::CommField for #name {
+            const SIZE: usize = 1;
+
+            fn write_le(&self, bytes: &mut [u8]) {
+                bytes[0] = match self {
+                    #(Self::#idents => Self::#idents as u8),*
+                };
+            }
+
+            fn read_le(bytes: &[u8]) -> ::lib::rbt_infra::rbt_err::RbtResult<Self> {
+                match bytes[0] {
+                    #(value if value == Self::#idents as u8 => Ok(Self::#idents),)*
+                    value => Err(#frame::invalid_comm_field(#name_str, value)),
+                }
+            }
+
+            fn sample(seed: u8) -> Self {
+                match seed as usize % #variant_num {
+                    #(#indices => Self::#idents,)*
+                    _ => unreachable!(),
+                }
+            }
+        }
+    })
+}
+
+fn to_snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (idx, ch) in name.chars().enumerate() {
+        if ch.is_uppercase() {
+            if idx != 0 {
+                snake.push('_');
+            }
+            snake.extend(ch.to_lowercase());
+        } else {
+            snake.push(ch);
+        }
+    }
+    snake
+}
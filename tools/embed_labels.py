@@ -0,0 +1,31 @@
+# 将类别标签表写入 onnx 模型元数据，推理端启动时优先读取该字段
+# 用法: python tools/embed_labels.py model/armor/best_fp16_norm.onnx cfg/armor_labels.toml
+
+import sys
+import tomllib
+
+import onnx
+
+KEY = "rbt_labels"
+
+model_path, label_path = sys.argv[1], sys.argv[2]
+
+with open(label_path, "rb") as f:
+    text = f.read().decode("utf-8")
+labels = tomllib.loads(text)["labels"]
+
+model = onnx.load(model_path)
+
+# 输出形状为 [1, 4 + 类别数 + 8, anchor 数]，写入前先核对类别数
+dims = model.graph.output[0].type.tensor_type.shape.dim
+row_len = dims[1].dim_value
+if row_len and row_len != 4 + len(labels) + 8:
+    sys.exit(f"模型每个 anchor 输出 {row_len} 列，与 {len(labels)} 个标签不符")
+
+for prop in list(model.metadata_props):
+    if prop.key == KEY:
+        model.metadata_props.remove(prop)
+model.metadata_props.append(onnx.StringStringEntryProto(key=KEY, value=text))
+
+onnx.save(model, model_path)
+print(f"已写入 {len(labels)} 个标签到 {model_path}")
@@ -0,0 +1,242 @@
+# gen_distortion_fixture.py
+# 生成 lib/testdata/distortion_opencv.txt，供 rbt_distortion 的单元测试对照
+# 优先调用 cv2.projectPoints / cv2.fisheye.projectPoints 与
+# cv2.undistortPointsIter / cv2.fisheye.undistortPoints；
+# 未安装 OpenCV 时按 OpenCV 源码中的同一公式与迭代计算，文件头会记录实际使用的后端，
+# 此时生成的数值只是自洽参考，提交前应在装有 OpenCV 的环境中重新生成
+#
+# 用法：
+#   python tools/gen_distortion_fixture.py                   需要 cv2，写入测试数据
+#   python tools/gen_distortion_fixture.py --check           需要 cv2，比对已提交的测试数据
+#   python tools/gen_distortion_fixture.py --allow-fallback  没有 cv2 时用纯 Python 实现生成
+"""生成 rbt_distortion 单元测试使用的 OpenCV 投影与去畸变参考值"""
+import argparse
+import math
+import sys
+from pathlib import Path
+
+# 广角镜头的典型内参，1280x1024
+CAM_K = [1100.0, 0.0, 640.0, 0.0, 1100.0, 512.0, 0.0, 0.0, 1.0]
+# k1 k2 p1 p2 k3
+BROWN_CONRADY = [-0.32, 0.12, 0.0008, -0.0005, -0.02]
+# k1 k2 k3 k4
+FISHEYE = [0.05, -0.01, 0.003, -0.0005]
+
+# 相机坐标系下的点，覆盖图像中心到边角
+POINTS = [
+    (0.0, 0.0, 1000.0),
+    (120.0, -80.0, 2000.0),
+    (-900.0, 300.0, 3000.0),
+    (1500.0, 1100.0, 2600.0),
+    (-2200.0, -1700.0, 3200.0),
+    (700.0, -2000.0, 4500.0),
+]
+FISHEYE_POINTS = POINTS + [
+    (1500.0, 1150.0, 2200.0),
+    (-1500.0, 1150.0, 2400.0),
+]
+
+# 去畸变输入的像素点，独立于投影点，覆盖图像中心、中部和四角
+PIXELS = [
+    (640.0, 512.0),
+    (320.0, 256.0),
+    (960.0, 768.0),
+    (1200.0, 80.0),
+    (100.0, 900.0),
+    (0.0, 0.0),
+    (1279.0, 1023.0),
+]
+# 去畸变迭代的终止条件：(最大次数, 精度)，与 cv2.TermCriteria 的 COUNT + EPS 相同
+UNDISTORT_CRITERIA = (100, 1e-10)
+
+
+def project_brown_conrady(point):
+    fx, _, cx, _, fy, cy, _, _, _ = CAM_K
+    k1, k2, p1, p2, k3 = BROWN_CONRADY
+    x, y = point[0] / point[2], point[1] / point[2]
+    r2 = x * x + y * y
+    radial = 1.0 + k1 * r2 + k2 * r2 * r2 + k3 * r2 * r2 * r2
+    xd = x * radial + 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x)
+    yd = y * radial + p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y
+    return fx * xd + cx, fy * yd + cy
+
+
+def project_fisheye(point):
+    fx, _, cx, _, fy, cy, _, _, _ = CAM_K
+    k1, k2, k3, k4 = FISHEYE
+    x, y = point[0] / point[2], point[1] / point[2]
+    r = math.hypot(x, y)
+    theta = math.atan(r)
+    t2 = theta * theta
+    theta_d = theta * (1.0 + k1 * t2 + k2 * t2**2 + k3 * t2**3 + k4 * t2**4)
+    scale = theta_d / r if r > 1e-8 else 1.0
+    return fx * x * scale + cx, fy * y * scale + cy
+
+
+def undistort_brown_conrady(pixel):
+    """cv::undistortPoints 的不动点迭代，EPS 按重投影像素误差判断"""
+    fx, _, cx, _, fy, cy, _, _, _ = CAM_K
+    k1, k2, p1, p2, k3 = BROWN_CONRADY
+    max_count, eps = UNDISTORT_CRITERIA
+    x0, y0 = (pixel[0] - cx) / fx, (pixel[1] - cy) / fy
+    x, y = x0, y0
+    for _ in range(max_count):
+        r2 = x * x + y * y
+        icdist = 1.0 / (1.0 + k1 * r2 + k2 * r2 * r2 + k3 * r2 * r2 * r2)
+        delta_x = 2.0 * p1 * x * y + p2 * (r2 + 2.0 * x * x)
+        delta_y = p1 * (r2 + 2.0 * y * y) + 2.0 * p2 * x * y
+        x, y = (x0 - delta_x) * icdist, (y0 - delta_y) * icdist
+        u, v = project_brown_conrady((x, y, 1.0))
+        if math.hypot(u - pixel[0], v - pixel[1]) < eps:
+            break
+    return fx * x + cx, fy * y + cy
+
+
+def undistort_fisheye(pixel):
+    """cv::fisheye::undistortPoints 对入射角 θ 的牛顿迭代"""
+    fx, _, cx, _, fy, cy, _, _, _ = CAM_K
+    k1, k2, k3, k4 = FISHEYE
+    max_count, eps = UNDISTORT_CRITERIA
+    x, y = (pixel[0] - cx) / fx, (pixel[1] - cy) / fy
+    theta_d = min(max(math.hypot(x, y), -math.pi / 2.0), math.pi / 2.0)
+    scale = 1.0
+    if theta_d > 1e-8:
+        theta = theta_d
+        for _ in range(max_count):
+            t2 = theta * theta
+            value = theta * (1.0 + k1 * t2 + k2 * t2**2 + k3 * t2**3 + k4 * t2**4) - theta_d
+            derivative = 1.0 + 3.0 * k1 * t2 + 5.0 * k2 * t2**2 + 7.0 * k3 * t2**3 + 9.0 * k4 * t2**4
+            step = value / derivative
+            theta -= step
+            if abs(step) < eps:
+                break
+        scale = math.tan(theta) / theta_d
+    return fx * x * scale + cx, fy * y * scale + cy
+
+
+def undistort_opencv(pixels, fisheye):
+    """P 取 cam_k，输出同一内参下的理想针孔像素，与 undistort_points 一致"""
+    import numpy as np
+    import cv2
+
+    k = np.array(CAM_K, dtype=np.float64).reshape(3, 3)
+    pts = np.array(pixels, dtype=np.float64).reshape(-1, 1, 2)
+    max_count, eps = UNDISTORT_CRITERIA
+    criteria = (cv2.TERM_CRITERIA_COUNT | cv2.TERM_CRITERIA_EPS, max_count, eps)
+    if fisheye:
+        dist = np.array(FISHEYE, dtype=np.float64)
+        uvs = cv2.fisheye.undistortPoints(pts, k, dist, R=None, P=k, criteria=criteria)
+    else:
+        dist = np.array(BROWN_CONRADY, dtype=np.float64)
+        uvs = cv2.undistortPointsIter(pts, k, dist, None, k, criteria)
+    return [tuple(uv) for uv in uvs.reshape(-1, 2)]
+
+
+def project_opencv(points, fisheye):
+    import numpy as np
+    import cv2
+
+    k = np.array(CAM_K, dtype=np.float64).reshape(3, 3)
+    pts = np.array(points, dtype=np.float64).reshape(-1, 1, 3)
+    zero = np.zeros((3, 1), dtype=np.float64)
+    if fisheye:
+        dist = np.array(FISHEYE, dtype=np.float64)
+        uvs, _ = cv2.fisheye.projectPoints(pts, zero, zero, k, dist)
+    else:
+        dist = np.array(BROWN_CONRADY, dtype=np.float64)
+        uvs, _ = cv2.projectPoints(pts, zero, zero, k, dist)
+    return [tuple(uv) for uv in uvs.reshape(-1, 2)]
+
+
+def opencv_rows():
+    """用 OpenCV 计算全部参考值，未安装 cv2 时抛出 ImportError"""
+    import cv2
+
+    rows = (
+        project_opencv(POINTS, fisheye=False),
+        project_opencv(FISHEYE_POINTS, fisheye=True),
+        undistort_opencv(PIXELS, fisheye=False),
+        undistort_opencv(PIXELS, fisheye=True),
+    )
+    return rows, f"OpenCV {cv2.__version__}"
+
+
+def fallback_rows():
+    rows = (
+        [project_brown_conrady(p) for p in POINTS],
+        [project_fisheye(p) for p in FISHEYE_POINTS],
+        [undistort_brown_conrady(p) for p in PIXELS],
+        [undistort_fisheye(p) for p in PIXELS],
+    )
+    return rows, "OpenCV 公式的纯 Python 实现（未安装 cv2，需在装有 OpenCV 的环境中重新生成）"
+
+
+def fixture_lines(rows, backend):
+    brown, fisheye, brown_undistort, fisheye_undistort = rows
+    fmt = lambda values: " ".join(repr(float(v)) for v in values)
+    lines = [
+        "# tools/gen_distortion_fixture.py 生成，请勿手动修改",
+        f"# 后端：{backend}",
+        "# 每行为 `键: 数值`，投影行为相机坐标系点 X Y Z (mm) 与畸变后的像素 u v",
+        "# 去畸变行为畸变像素 u v 与同一内参下的理想针孔像素 u v",
+        f"cam_k: {fmt(CAM_K)}",
+        f"brown_conrady_coeffs: {fmt(BROWN_CONRADY)}",
+        f"fisheye_coeffs: {fmt(FISHEYE)}",
+    ]
+    lines += [f"brown_conrady: {fmt(p + uv)}" for p, uv in zip(POINTS, brown)]
+    lines += [f"fisheye: {fmt(p + uv)}" for p, uv in zip(FISHEYE_POINTS, fisheye)]
+    lines += [f"brown_conrady_undistort: {fmt(p + uv)}" for p, uv in zip(PIXELS, brown_undistort)]
+    lines += [f"fisheye_undistort: {fmt(p + uv)}" for p, uv in zip(PIXELS, fisheye_undistort)]
+    return lines
+
+
+def check(out, lines):
+    """逐行比对已提交的测试数据与 OpenCV 的结果，数值容差 1e-6"""
+    committed = [l for l in out.read_text(encoding="utf-8").splitlines() if not l.startswith("#")]
+    expected = [l for l in lines if not l.startswith("#")]
+    if len(committed) != len(expected):
+        print(f"{out} 有 {len(committed)} 行数据，OpenCV 生成 {len(expected)} 行")
+        return False
+    ok = True
+    for got, want in zip(committed, expected):
+        (got_key, got_values), (want_key, want_values) = (l.split(":", 1) for l in (got, want))
+        diff = max(
+            (abs(float(a) - float(b)) for a, b in zip(got_values.split(), want_values.split())),
+            default=0.0,
+        )
+        if got_key != want_key or len(got_values.split()) != len(want_values.split()) or diff > 1e-6:
+            print(f"不一致：\n  已提交 {got}\n  OpenCV {want}")
+            ok = False
+    return ok
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__)
+    parser.add_argument("--check", action="store_true", help="只与 OpenCV 比对已提交的测试数据，不写文件")
+    parser.add_argument(
+        "--allow-fallback",
+        action="store_true",
+        help="未安装 cv2 时用纯 Python 实现生成，结果不能作为 OpenCV 的参考值",
+    )
+    args = parser.parse_args()
+    out = Path(__file__).parent.parent / "lib" / "testdata" / "distortion_opencv.txt"
+
+    try:
+        rows, backend = opencv_rows()
+    except ImportError:
+        if not args.allow_fallback or args.check:
+            sys.exit("需要安装 OpenCV（pip install opencv-python numpy）")
+        rows, backend = fallback_rows()
+    lines = fixture_lines(rows, backend)
+
+    if args.check:
+        if not check(out, lines):
+            sys.exit(f"{out} 与 {backend} 不一致，请重新生成")
+        print(f"{out} 与 {backend} 一致")
+        return
+    out.write_text("\n".join(lines) + "\n", encoding="utf-8")
+    print(f"写入 {out}")
+
+
+if __name__ == "__main__":
+    main()